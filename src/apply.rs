@@ -0,0 +1,360 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+use toml::{Table, Value};
+
+use crate::config::GLOBAL_CONFIG;
+use crate::errors::config::ConfigError;
+use crate::errors::login::LoginManagerInstanceError;
+use crate::login::manager::{self, get_current_manager, get_last_autologin_user, is_autologin_enabled_in_config, SupportedManager};
+use crate::session::{Protocol, Session};
+
+/// A session declared in a desired-state file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesiredSession {
+    pub reg_name: String,
+    pub session: String,
+    /// The protocol of the session, detected from the installed desktop file if `None`.
+    pub protocol: Option<Protocol>,
+    /// The logout command of the session, left as is if `None`.
+    pub logout_command: Option<String>,
+}
+
+/// The state of molyuuctl declared in a desired-state file.
+///
+/// ```toml
+/// default = "plasma"
+/// manager = "sddm"
+///
+/// [autologin]
+/// enabled = true
+/// user = "molyuu"
+///
+/// [sessions.plasma]
+/// session = "plasma"
+/// protocol = "wayland"
+/// logout_command = "qdbus org.kde.Shutdown /Shutdown logout"
+/// ```
+///
+/// Every key is optional. Registered sessions that are not declared are kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DesiredState {
+    pub sessions: Vec<DesiredSession>,
+    pub default: Option<String>,
+    pub manager: Option<String>,
+    /// Whether Auto Login is enabled, and for which user.
+    pub autologin: Option<(bool, Option<String>)>,
+}
+
+impl DesiredState {
+    /// Read a desired-state file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, see `parse` for the other errors.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read desired state from {path}: {err}"))?;
+        Ok(Self::parse(contents.as_str())?)
+    }
+
+    /// Parse the contents of a desired-state file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidConfig` if the contents are not valid TOML, a key has the
+    /// wrong type, the manager or a protocol is not supported, or Auto Login is enabled without
+    /// a user.
+    pub fn parse(contents: &str) -> Result<Self, ConfigError> {
+        let value = contents.parse::<Table>()
+            .map_err(|err| ConfigError::InvalidConfig(err.message().to_string()))?;
+
+        let mut sessions = Vec::new();
+        if let Some(session_tables) = value.get("sessions") {
+            let session_tables = session_tables.as_table()
+                .ok_or_else(|| ConfigError::InvalidConfig(String::from("sessions must be a table of sessions")))?;
+            for (reg_name, session_table) in session_tables {
+                let session_str = |key: &str| -> Result<Option<String>, ConfigError> {
+                    match session_table.get(key) {
+                        None => Ok(None),
+                        Some(Value::String(value)) => Ok(Some(value.clone())),
+                        Some(_) => Err(ConfigError::InvalidConfig(format!("Session {reg_name}: {key} must be a string"))),
+                    }
+                };
+                let protocol = session_str("protocol")?
+                    .map(|protocol| protocol.parse::<Protocol>()
+                        .map_err(|_| ConfigError::InvalidConfig(format!("Session {reg_name} has an unsupported protocol: {protocol}"))))
+                    .transpose()?;
+                sessions.push(DesiredSession {
+                    reg_name: reg_name.clone(),
+                    session: session_str("session")?.unwrap_or_else(|| reg_name.clone()),
+                    protocol,
+                    logout_command: session_str("logout_command")?,
+                });
+            }
+        }
+
+        let top_level_str = |key: &str| -> Result<Option<String>, ConfigError> {
+            match value.get(key) {
+                None => Ok(None),
+                Some(Value::String(value)) => Ok(Some(value.clone())),
+                Some(_) => Err(ConfigError::InvalidConfig(format!("{key} must be a string"))),
+            }
+        };
+        let default = top_level_str("default")?;
+        let manager = top_level_str("manager")?.map(|manager| manager.to_lowercase());
+        if let Some(manager) = &manager {
+            if SupportedManager::from_name(manager).is_none() {
+                return Err(ConfigError::InvalidConfig(format!("Unsupported login manager: {manager}")));
+            }
+        }
+
+        let autologin = match value.get("autologin") {
+            None => None,
+            Some(autologin) => {
+                let enabled = autologin.get("enabled").and_then(|enabled| enabled.as_bool())
+                    .ok_or_else(|| ConfigError::InvalidConfig(String::from("autologin.enabled must be a boolean")))?;
+                let user = autologin.get("user").and_then(|user| user.as_str()).map(String::from);
+                if enabled && user.is_none() {
+                    return Err(ConfigError::InvalidConfig(String::from("autologin.user must be set if Auto Login is enabled")));
+                }
+                Some((enabled, user))
+            }
+        };
+
+        Ok(Self { sessions, default, manager, autologin })
+    }
+}
+
+/// A change that brings the system closer to the desired state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    RegisterSession(String),
+    UpdateSession(String),
+    SetDefault(String),
+    SetManager(String),
+    EnableAutoLogin(String),
+    DisableAutoLogin,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::RegisterSession(reg_name) => write!(f, "Register session {reg_name}"),
+            Change::UpdateSession(reg_name) => write!(f, "Update session {reg_name}"),
+            Change::SetDefault(reg_name) => write!(f, "Set default session to {reg_name}"),
+            Change::SetManager(manager) => write!(f, "Set login manager to {manager}"),
+            Change::EnableAutoLogin(user) => write!(f, "Enable Auto Login for {user}"),
+            Change::DisableAutoLogin => write!(f, "Disable Auto Login"),
+        }
+    }
+}
+
+/// Build the session a desired session resolves to, with the canonical session name and the
+/// detected protocol.
+fn resolve_session(desired: &DesiredSession) -> Result<Session, Box<dyn Error>> {
+    Session::new(desired.reg_name.clone(), desired.session.clone(), desired.logout_command.clone(), desired.protocol)
+}
+
+/// Compute the changes that bring the global configuration to the desired state, in the order
+/// they are applied: sessions, the default session, the login manager and Auto Login.
+///
+/// # Errors
+///
+/// Returns an error if a declared session is not installed in the system.
+pub fn plan(desired: &DesiredState) -> Result<Vec<Change>, Box<dyn Error>> {
+    let mut changes = Vec::new();
+    for desired_session in &desired.sessions {
+        let session = resolve_session(desired_session)?;
+        match Session::config_table(desired_session.reg_name.as_str()) {
+            Err(_) => changes.push(Change::RegisterSession(desired_session.reg_name.clone())),
+            Ok(table) => {
+                let table_str = |key: &str| table.get(key).and_then(|value| value.as_str());
+                let protocol = session.get_protocol().to_string();
+                if table_str("session") != Some(session.get_real_name())
+                    || table_str("protocol") != Some(protocol.as_str())
+                    || desired_session.logout_command.as_deref().is_some_and(|command| table_str("logout_command") != Some(command)) {
+                    changes.push(Change::UpdateSession(desired_session.reg_name.clone()));
+                }
+            }
+        }
+    }
+
+    if let Some(default) = &desired.default {
+        if Session::get_default_name()?.as_ref() != Some(default) {
+            changes.push(Change::SetDefault(default.clone()));
+        }
+    }
+
+    if let Some(manager) = &desired.manager {
+        let current_manager = GLOBAL_CONFIG.get_mut().unwrap().get("login").get("manager")
+            .and_then(|manager| manager.as_str())
+            .map(String::from);
+        if current_manager.as_ref() != Some(manager) {
+            changes.push(Change::SetManager(manager.clone()));
+        }
+    }
+
+    match &desired.autologin {
+        Some((true, Some(user))) if !is_autologin_enabled_in_config() || get_last_autologin_user().as_ref() != Some(user) => {
+            changes.push(Change::EnableAutoLogin(user.clone()));
+        }
+        Some((false, _)) if is_autologin_enabled_in_config() => changes.push(Change::DisableAutoLogin),
+        _ => {}
+    }
+    Ok(changes)
+}
+
+/// Apply the changes computed by `plan`.
+///
+/// Every change is checked before any of them is applied. The session changes are written to
+/// the global configuration at once, which is left unchanged if one of them fails. The config and
+/// unit state of the login manager cannot be rolled back, so they are only changed once the
+/// global configuration is saved.
+///
+/// # Errors
+///
+/// Returns an error if a change cannot be applied or the configuration cannot be saved.
+pub fn apply(desired: &DesiredState, changes: &[Change]) -> Result<(), Box<dyn Error>> {
+    validate_changes(desired, changes)?;
+    let (manager_changes, config_changes): (Vec<&Change>, Vec<&Change>) = changes.iter()
+        .partition(|change| changes_manager(change));
+
+    GLOBAL_CONFIG.get_mut().unwrap().begin_batch();
+    match apply_changes(desired, &config_changes) {
+        Ok(()) => GLOBAL_CONFIG.get_mut().unwrap().end_batch()?,
+        Err(err) => {
+            GLOBAL_CONFIG.get_mut().unwrap().abort_batch()?;
+            return Err(err);
+        }
+    }
+    apply_changes(desired, &manager_changes)
+}
+
+/// Whether a change writes the config or unit state of the login manager.
+fn changes_manager(change: &Change) -> bool {
+    matches!(change, Change::SetManager(_) | Change::EnableAutoLogin(_) | Change::DisableAutoLogin)
+}
+
+fn desired_session<'a>(desired: &'a DesiredState, reg_name: &str) -> Result<&'a DesiredSession, Box<dyn Error>> {
+    desired.sessions.iter()
+        .find(|desired_session| desired_session.reg_name == reg_name)
+        .ok_or_else(|| Box::from(format!("Session {reg_name} is not declared in the desired state")))
+}
+
+/// Check that every change can be applied, so that `apply` fails before changing anything.
+fn validate_changes(desired: &DesiredState, changes: &[Change]) -> Result<(), Box<dyn Error>> {
+    let mut registered = Vec::new();
+    for change in changes {
+        match change {
+            Change::RegisterSession(reg_name) => {
+                resolve_session(desired_session(desired, reg_name)?)?;
+                registered.push(reg_name.as_str());
+            }
+            Change::UpdateSession(reg_name) => {
+                resolve_session(desired_session(desired, reg_name)?)?;
+            }
+            Change::SetDefault(reg_name) => {
+                if !registered.contains(&reg_name.as_str()) {
+                    Session::config_table(reg_name.as_str())?;
+                }
+            }
+            Change::SetManager(manager_name) => {
+                if SupportedManager::from_name(manager_name.to_lowercase().as_str()).is_none() {
+                    return Err(Box::from(LoginManagerInstanceError::UnsupportedManager));
+                }
+            }
+            Change::EnableAutoLogin(_) | Change::DisableAutoLogin => {
+                if !changes.iter().any(|change| matches!(change, Change::SetManager(_))) {
+                    get_current_manager()?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_changes(desired: &DesiredState, changes: &[&Change]) -> Result<(), Box<dyn Error>> {
+    for change in changes {
+        match change {
+            Change::RegisterSession(reg_name) => resolve_session(desired_session(desired, reg_name)?)?.register()?,
+            Change::UpdateSession(reg_name) => {
+                resolve_session(desired_session(desired, reg_name)?)?.register_or_update()?;
+            }
+            Change::SetDefault(reg_name) => Session::from_config(Some(reg_name.as_str()))?.set_as_default()?,
+            Change::SetManager(manager_name) => manager::set_manager(manager_name.as_str(), false, false, true)?,
+            Change::EnableAutoLogin(user) => get_current_manager()?.set_auto_login(true, Some(user.as_str()), false)?,
+            Change::DisableAutoLogin => get_current_manager()?.set_auto_login(false, None, false)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static DESIRED_STATE: &str = r#"default = "gamma"
+
+[sessions.alpha]
+session = "molyuuctl-test-beta"
+protocol = "wayland"
+
+[sessions.gamma]
+session = "molyuuctl-test-alpha"
+"#;
+
+    #[test]
+    fn apply_converges_to_the_desired_state() {
+        let fixture = crate::testing::load_sessions_config();
+        let desired = DesiredState::parse(DESIRED_STATE).unwrap();
+        let changes = plan(&desired).unwrap();
+        assert_eq!(changes, vec![
+            Change::UpdateSession(String::from("alpha")),
+            Change::RegisterSession(String::from("gamma")),
+            Change::SetDefault(String::from("gamma")),
+        ]);
+
+        apply(&desired, &changes).unwrap();
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("gamma"));
+        assert_eq!(Session::from_config(Some("alpha")).unwrap().get_real_name(), "molyuuctl-test-beta");
+        assert!(fixture.read().contains("[session.gamma]"));
+        assert_eq!(plan(&desired).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn failed_apply_leaves_the_config_unchanged() {
+        let fixture = crate::testing::load_sessions_config();
+        let before = fixture.read();
+        let desired = DesiredState::parse(DESIRED_STATE).unwrap();
+        // Registering twice passes the checks, but the second one fails after the first is applied
+        let changes = vec![Change::RegisterSession(String::from("gamma")), Change::RegisterSession(String::from("gamma"))];
+        assert!(apply(&desired, &changes).is_err());
+        assert_eq!(fixture.read(), before);
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("alpha"));
+    }
+
+    #[test]
+    fn change_that_cannot_be_applied_fails_before_any_is_applied() {
+        let fixture = crate::testing::load_sessions_config();
+        let before = fixture.read();
+        let desired = DesiredState::parse(DESIRED_STATE).unwrap();
+
+        let changes = vec![Change::RegisterSession(String::from("gamma")), Change::SetManager(String::from("xdm"))];
+        assert!(apply(&desired, &changes).is_err());
+        let changes = vec![Change::SetDefault(String::from("gamma")), Change::DisableAutoLogin];
+        assert!(apply(&desired, &changes).is_err());
+        let changes = vec![Change::RegisterSession(String::from("delta"))];
+        let err = apply(&desired, &changes).unwrap_err();
+        assert_eq!(err.to_string(), "Session delta is not declared in the desired state");
+        assert_eq!(fixture.read(), before);
+    }
+
+    #[test]
+    fn parse_rejects_autologin_without_user() {
+        let err = DesiredState::parse("[autologin]\nenabled = true\n").unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidConfig(_)));
+        assert!(DesiredState::parse("manager = \"xdm\"\n").is_err());
+    }
+}
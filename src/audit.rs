@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::mem::MaybeUninit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use toml::Value;
+
+use crate::system::privilege;
+
+static AUDIT_LOG_DIRECTORY: &str = "/var/log/molyuuctl";
+static AUDIT_LOG_FILE: &str = "audit.log";
+
+/// Whether the `enabled` key of the `[audit]` section is set in a configuration.
+pub fn is_enabled(config: &Value) -> bool {
+    config.get("audit")
+        .and_then(|audit| audit.get("enabled"))
+        .and_then(|enabled| enabled.as_bool())
+        .unwrap_or(false)
+}
+
+/// Describe how a configuration changed, section by section.
+///
+/// Every key is listed under the table it belongs to, removed values are prefixed with `-` and
+/// added values with `+`, so a changed value shows up as a removal followed by an addition.
+///
+/// # Returns
+///
+/// Returns the lines of the diff, or nothing if the configurations are equal.
+pub fn diff(before: &Value, after: &Value) -> Vec<String> {
+    let mut before_keys = BTreeMap::new();
+    let mut after_keys = BTreeMap::new();
+    flatten(String::new(), before, &mut before_keys);
+    flatten(String::new(), after, &mut after_keys);
+    diff_keys(&before_keys, &after_keys)
+}
+
+/// Describe how the keys of a login manager config changed, section by section, like `diff`.
+///
+/// The contents are not parsed as a whole, so that this works for both INI and TOML configs.
+/// Every `key = value` line is listed under the last `[section]` header before it, comments and
+/// other lines are ignored.
+///
+/// # Returns
+///
+/// Returns the lines of the diff, or nothing if no key changed.
+pub fn diff_lines(before: &str, after: &str) -> Vec<String> {
+    diff_keys(&flatten_lines(before), &flatten_lines(after))
+}
+
+/// List the keys that differ between two flattened configurations, grouped by their section.
+fn diff_keys(before_keys: &BTreeMap<(String, String), String>, after_keys: &BTreeMap<(String, String), String>) -> Vec<String> {
+    // Group the changed keys by the section they belong to
+    let mut sections: BTreeMap<&String, Vec<String>> = BTreeMap::new();
+    let all_keys: BTreeSet<&(String, String)> = before_keys.keys().chain(after_keys.keys()).collect();
+    for key @ (section, name) in all_keys {
+        let old_value = before_keys.get(key);
+        let new_value = after_keys.get(key);
+        if old_value == new_value {
+            continue;
+        }
+        let lines = sections.entry(section).or_default();
+        if let Some(old_value) = old_value {
+            lines.push(format!("- {name} = {old_value}"));
+        }
+        if let Some(new_value) = new_value {
+            lines.push(format!("+ {name} = {new_value}"));
+        }
+    }
+
+    sections.into_iter()
+        .flat_map(|(section, lines)| {
+            let header = if section.is_empty() { String::from("[]") } else { format!("[{section}]") };
+            std::iter::once(header).chain(lines)
+        })
+        .collect()
+}
+
+/// Collect the non-table values of a configuration, keyed by their section and key name.
+fn flatten(section: String, value: &Value, keys: &mut BTreeMap<(String, String), String>) {
+    let Some(table) = value.as_table() else {
+        return;
+    };
+    for (name, value) in table {
+        match value {
+            Value::Table(_) => {
+                let subsection = if section.is_empty() { name.clone() } else { format!("{section}.{name}") };
+                flatten(subsection, value, keys);
+            }
+            _ => {
+                keys.insert((section.clone(), name.clone()), value.to_string());
+            }
+        }
+    }
+}
+
+/// Collect the `key = value` lines of a config file, keyed by their section and key name.
+fn flatten_lines(contents: &str) -> BTreeMap<(String, String), String> {
+    let mut keys = BTreeMap::new();
+    let mut section = String::new();
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = String::from(name.trim());
+        } else if let Some((name, value)) = line.split_once('=') {
+            keys.insert((section.clone(), String::from(name.trim())), String::from(value.trim()));
+        }
+    }
+    keys
+}
+
+/// The current time in UTC, formatted as RFC 3339.
+fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs()) as libc::time_t;
+    let mut time = MaybeUninit::<libc::tm>::uninit();
+    if unsafe { libc::gmtime_r(&seconds, time.as_mut_ptr()) }.is_null() {
+        return seconds.to_string();
+    }
+    let time = unsafe { time.assume_init() };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        time.tm_year + 1900, time.tm_mon + 1, time.tm_mday, time.tm_hour, time.tm_min, time.tm_sec)
+}
+
+/// Append a configuration change to the audit log, if `[audit].enabled` is set.
+///
+/// The entry records the time, the real user ID of the invoking user, the command line and the
+/// `diff` of the configuration. Nothing is written if the configuration did not change.
+///
+/// # Parameters
+///
+/// * `before`: The configuration before the change.
+/// * `after`: The configuration after the change. The change is recorded if either
+///   configuration enables auditing, so that turning it off is recorded as well.
+///
+/// # Errors
+///
+/// Returns an error if the audit log cannot be written.
+pub fn record(before: &Value, after: &Value) -> Result<(), Box<dyn Error>> {
+    append(changes(before, after))
+}
+
+/// Append a change of a login manager config to the audit log, if `[audit].enabled` is set in
+/// the global configuration.
+///
+/// The entry is the same as for `record`, with the `diff_lines` of the file after its path.
+///
+/// # Parameters
+///
+/// * `config`: The global configuration, which decides whether the change is recorded.
+/// * `path`: The path of the login manager config.
+/// * `before`: The contents of the file before the change, empty if it did not exist.
+/// * `after`: The contents written to the file.
+///
+/// # Errors
+///
+/// Returns an error if the audit log cannot be written.
+pub fn record_file(config: &Value, path: &str, before: &str, after: &str) -> Result<(), Box<dyn Error>> {
+    append(file_changes(config, path, before, after))
+}
+
+/// The lines `record` appends for a change of the global configuration, or nothing if neither
+/// configuration enables auditing.
+fn changes(before: &Value, after: &Value) -> Vec<String> {
+    if !is_enabled(before) && !is_enabled(after) {
+        return Vec::new();
+    }
+    diff(before, after)
+}
+
+/// The lines `record_file` appends for a change of a login manager config, or nothing if
+/// auditing is disabled in `config`.
+fn file_changes(config: &Value, path: &str, before: &str, after: &str) -> Vec<String> {
+    if !is_enabled(config) {
+        return Vec::new();
+    }
+    let changes = diff_lines(before, after);
+    if changes.is_empty() {
+        return changes;
+    }
+    std::iter::once(format!("file: {path}")).chain(changes).collect()
+}
+
+/// Append an entry with `changes` to the audit log, unless there are none.
+fn append(changes: Vec<String>) -> Result<(), Box<dyn Error>> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let command: Vec<String> = env::args().collect();
+    let mut entry = format!("{} uid={} command: {}\n", timestamp(), privilege::real_uid(), command.join(" "));
+    for line in changes {
+        entry.push_str(format!("  {line}\n").as_str());
+    }
+
+    unsafe {
+        privilege::exec(|| {
+            fs::create_dir_all(AUDIT_LOG_DIRECTORY)?;
+            let mut log = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(format!("{AUDIT_LOG_DIRECTORY}/{AUDIT_LOG_FILE}"))?;
+            log.write_all(entry.as_bytes())?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static BEFORE: &str = "[login.autologin]\nenable = false\nuser = \"alice\"\n\n[session]\ndefault = \"alpha\"\n\n[session.alpha]\nsession_name = \"plasma\"\nprotocol = \"wayland\"\n";
+
+    #[test]
+    fn session_register_is_shown_as_added_keys() {
+        let before = BEFORE.parse::<Value>().unwrap();
+        let after = format!("{BEFORE}\n[session.beta]\nsession_name = \"xfce\"\nprotocol = \"x11\"\n").parse::<Value>().unwrap();
+        assert_eq!(diff(&before, &after), [
+            "[session.beta]",
+            "+ protocol = \"x11\"",
+            "+ session_name = \"xfce\"",
+        ]);
+    }
+
+    #[test]
+    fn autologin_toggle_is_shown_as_a_changed_value() {
+        let before = BEFORE.parse::<Value>().unwrap();
+        let after = BEFORE.replace("enable = false", "enable = true").parse::<Value>().unwrap();
+        assert_eq!(diff(&before, &after), [
+            "[login.autologin]",
+            "- enable = false",
+            "+ enable = true",
+        ]);
+        assert!(diff(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn audit_is_disabled_unless_enabled() {
+        assert!(!is_enabled(&BEFORE.parse::<Value>().unwrap()));
+        assert!(!is_enabled(&"[audit]\nenabled = false\n".parse::<Value>().unwrap()));
+        assert!(is_enabled(&"[audit]\nenabled = true\n".parse::<Value>().unwrap()));
+        // Nothing is written, so the log directory is not needed
+        record(&BEFORE.parse::<Value>().unwrap(), &"[session]\n".parse::<Value>().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn turning_audit_off_is_recorded() {
+        let before = "[audit]\nenabled = true\n".parse::<Value>().unwrap();
+        let after = "[audit]\nenabled = false\n".parse::<Value>().unwrap();
+        assert_eq!(changes(&before, &after), ["[audit]", "- enabled = true", "+ enabled = false"]);
+        assert_eq!(changes(&after, &before), ["[audit]", "- enabled = false", "+ enabled = true"]);
+        assert!(changes(&after, &"[audit]\n".parse::<Value>().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn login_manager_config_change_is_shown_by_section() {
+        let config = "[audit]\nenabled = true\n".parse::<Value>().unwrap();
+        let before = "# Written by molyuuctl\n[General]\nNumlock=on\n\n[Autologin]\nUser=alice\n";
+        let after = "# Written by molyuuctl\n[General]\nNumlock=on\n\n[Autologin]\nUser=bob\nSession=molyuu-redirect-wayland\n";
+        assert_eq!(file_changes(&config, "/etc/sddm.conf.d/molyuuctl.conf", before, after), [
+            "file: /etc/sddm.conf.d/molyuuctl.conf",
+            "[Autologin]",
+            "+ Session = molyuu-redirect-wayland",
+            "- User = alice",
+            "+ User = bob",
+        ]);
+        assert!(file_changes(&config, "/etc/sddm.conf.d/molyuuctl.conf", after, after).is_empty());
+        assert!(file_changes(&"[audit]\n".parse::<Value>().unwrap(), "/etc/sddm.conf.d/molyuuctl.conf", before, after).is_empty());
+    }
+}
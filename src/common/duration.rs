@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::time::Duration;
+
+/// Parse a human-friendly duration like `500ms`, `5s` or `2m`.
+///
+/// A number without a unit is taken as seconds. Surrounding whitespace is ignored.
+///
+/// # Errors
+///
+/// Returns an error if the number is missing or not a non-negative integer, or the unit is not
+/// one of `ms`, `s` and `m`.
+pub fn parse_duration(value: &str) -> Result<Duration, Box<dyn Error>> {
+    let value = value.trim();
+    let unit_start = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+    if number.is_empty() {
+        return Err(Box::from(format!("Invalid duration: {value:?} (Expected e.g. 500ms, 5s or 2m)")));
+    }
+    let number: u64 = number.parse()
+        .map_err(|err| format!("Invalid duration: {value:?} ({err})"))?;
+
+    let duration = match unit.trim() {
+        "ms" => Some(Duration::from_millis(number)),
+        "" | "s" => Some(Duration::from_secs(number)),
+        "m" => number.checked_mul(60).map(Duration::from_secs),
+        unit => return Err(Box::from(format!("Invalid duration unit: {unit:?} (Supported: ms, s, m)"))),
+    };
+    duration.ok_or_else(|| Box::from(format!("Duration is too long: {value:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_durations_are_parsed() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("5s").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("10").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration(" 3 s ").unwrap(), Duration::from_secs(3));
+        assert_eq!(parse_duration("0ms").unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        for value in ["", "s", "-5s", "1.5s", "5h", "5 sec", "99999999999999999999s", "307445734561825861m"] {
+            assert!(parse_duration(value).is_err(), "{value:?} should be rejected");
+        }
+    }
+}
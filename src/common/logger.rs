@@ -1,15 +1,92 @@
+use std::env;
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
 use colored::Colorize;
-use log::{Level, LevelFilter, Metadata, Record, SetLoggerError};
+use log::{warn, Level, LevelFilter, Metadata, Record, SetLoggerError};
 use supports_color::Stream;
 
+use crate::config::GLOBAL_CONFIG;
+
+static JOURNAL_STREAM_ENV: &str = "JOURNAL_STREAM";
+static NO_COLOR_ENV: &str = "NO_COLOR";
+
+// The format is chosen after the logger is installed, once the configuration is loaded
+static FORMAT: AtomicU8 = AtomicU8::new(LogFormat::Human as u8);
+
+/// How log records are printed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LogFormat {
+    /// `level: message`, with the level colored if the terminal supports it.
+    Human,
+    /// `level: message` without color.
+    Plain,
+    /// `<priority>message` with the `sd-daemon(3)` priority prefix, so journald classifies the
+    /// level itself.
+    Journal,
+}
+
+impl LogFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "human" => Some(Self::Human),
+            "plain" => Some(Self::Plain),
+            "journal" => Some(Self::Journal),
+            _ => None,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            value if value == Self::Plain as u8 => Self::Plain,
+            value if value == Self::Journal as u8 => Self::Journal,
+            _ => Self::Human,
+        }
+    }
+}
+
+/// When log levels are colored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorChoice {
+    Always,
+    /// Color if stdout supports it and `NO_COLOR` is not set.
+    Auto,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "always" => Some(Self::Always),
+            "auto" => Some(Self::Auto),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+
+    /// Resolve the choice for a stream, given whether the stream supports color.
+    pub fn resolve(self, supported: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => supported && env::var_os(NO_COLOR_ENV).is_none_or(|no_color| no_color.is_empty()),
+        }
+    }
+}
+
 struct SimpleLogger {
-    colored: bool,
+    // Shared with `set_color`, so that `--color` can override the detection after `init`
+    colored: &'static AtomicBool,
 }
 
+static COLORED: AtomicBool = AtomicBool::new(false);
+
 impl SimpleLogger {
     pub fn new() -> Self {
+        COLORED.store(ColorChoice::Auto.resolve(supports_color::on(Stream::Stdout).is_some()), Ordering::Relaxed);
         Self {
-            colored: supports_color::on(Stream::Stdout).is_some()
+            colored: &COLORED
         }
     }
 }
@@ -20,28 +97,162 @@ impl log::Log for SimpleLogger {
     }
 
     fn log(&self, record: &Record) {
-        let level_str = {
-            let mut lowercase_str = record.level().as_str().to_lowercase();
-            if self.colored {
-                match record.level() {
-                    Level::Info => lowercase_str = lowercase_str.green().bold().to_string(),
-                    Level::Error => lowercase_str = lowercase_str.red().bold().to_string(),
-                    Level::Warn => lowercase_str = lowercase_str.yellow().bold().to_string(),
-                    _ => {}
-                };
-            }
-            lowercase_str
-        };
-
-        if self.enabled(record.metadata()) {
-            println!("{}: {}", level_str, record.args());
+        if !self.enabled(record.metadata()) {
+            return;
         }
+
+        let format = LogFormat::from_u8(FORMAT.load(Ordering::Relaxed));
+        println!("{}", format_line(record.level(), record.args(), format, self.colored.load(Ordering::Relaxed)));
     }
 
     fn flush(&self) {}
 }
 
+/// Format a log line in `format`, coloring the level only if `colored` is set and the format is
+/// `LogFormat::Human`.
+fn format_line(level: Level, message: &Arguments, format: LogFormat, colored: bool) -> String {
+    if format == LogFormat::Journal {
+        return format!("<{}>{}", journal_priority(level), message);
+    }
+
+    let level_str = {
+        let mut lowercase_str = level.as_str().to_lowercase();
+        if colored && format == LogFormat::Human {
+            match level {
+                Level::Info => lowercase_str = lowercase_str.green().bold().to_string(),
+                Level::Error => lowercase_str = lowercase_str.red().bold().to_string(),
+                Level::Warn => lowercase_str = lowercase_str.yellow().bold().to_string(),
+                _ => {}
+            };
+        }
+        lowercase_str
+    };
+    format!("{}: {}", level_str, message)
+}
+
+/// Map a log level to the syslog priority journald expects in a `<priority>` prefix.
+pub fn journal_priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Check whether stdout is connected to the journal, like `sd_journal_stream_fd(3)` callers do.
+///
+/// systemd sets `JOURNAL_STREAM` to the device and inode of the stream it connected, which is
+/// compared with stdout since the variable is inherited by processes whose output is redirected.
+fn is_journal_stream() -> bool {
+    env::var(JOURNAL_STREAM_ENV).is_ok_and(|journal_stream| is_stdout_stream(journal_stream.as_str()))
+}
+
+/// Check whether a `JOURNAL_STREAM` value of `<device>:<inode>` names stdout.
+fn is_stdout_stream(journal_stream: &str) -> bool {
+    let Some((device, inode)) = journal_stream.split_once(':') else {
+        return false;
+    };
+
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(libc::STDOUT_FILENO, &mut stat) } < 0 {
+        return false;
+    }
+    device.parse::<u64>().is_ok_and(|device| device == stat.st_dev)
+        && inode.parse::<u64>().is_ok_and(|inode| inode == stat.st_ino)
+}
+
+/// Override whether log levels are colored, e.g. from `--color`.
+///
+/// `ColorChoice::Always` also forces color on when `NO_COLOR` is set.
+pub fn set_color(choice: ColorChoice) {
+    let colored = choice.resolve(supports_color::on(Stream::Stdout).is_some());
+    colored::control::set_override(colored);
+    COLORED.store(colored, Ordering::Relaxed);
+}
+
+pub fn set_format(format: LogFormat) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+/// Select the log format from the `format` key of the `[logging]` section in the global
+/// configuration.
+///
+/// If it is not set, `LogFormat::Journal` is used when stdout is connected to the journal and
+/// `LogFormat::Human` otherwise.
+pub fn apply_config() {
+    let configured = GLOBAL_CONFIG.get_mut()
+        .and_then(|config| config.try_get("logging"))
+        .and_then(|logging| logging.get("format"))
+        .and_then(|format| format.as_str())
+        .map(String::from);
+
+    match configured.as_deref().map(|name| (name, LogFormat::from_name(name))) {
+        Some((_, Some(format))) => set_format(format),
+        Some((name, None)) => warn!("Unknown log format: {} (Supported: human, plain, journal)", name),
+        None => {}
+    }
+}
+
 pub fn init() -> Result<(), SetLoggerError> {
+    if is_journal_stream() {
+        set_format(LogFormat::Journal);
+    }
     log::set_boxed_logger(Box::from(SimpleLogger::new()))
         .map(|()| log::set_max_level(LevelFilter::Info))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_map_to_sd_daemon_priorities() {
+        assert_eq!(journal_priority(Level::Error), 3);
+        assert_eq!(journal_priority(Level::Warn), 4);
+        assert_eq!(journal_priority(Level::Info), 6);
+        assert_eq!(journal_priority(Level::Debug), 7);
+        assert_eq!(journal_priority(Level::Trace), 7);
+    }
+
+    #[test]
+    fn format_names_are_parsed() {
+        for format in [LogFormat::Human, LogFormat::Plain, LogFormat::Journal] {
+            assert_eq!(LogFormat::from_u8(format as u8), format);
+        }
+        assert_eq!(LogFormat::from_name("journal"), Some(LogFormat::Journal));
+        assert_eq!(LogFormat::from_name("plain"), Some(LogFormat::Plain));
+        assert_eq!(LogFormat::from_name("Journal"), None);
+    }
+
+    #[test]
+    fn journal_stream_must_match_stdout() {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        assert_eq!(unsafe { libc::fstat(libc::STDOUT_FILENO, &mut stat) }, 0);
+
+        assert!(is_stdout_stream(format!("{}:{}", stat.st_dev, stat.st_ino).as_str()));
+        // Inherited by a process whose stdout is redirected elsewhere
+        assert!(!is_stdout_stream(format!("{}:{}", stat.st_dev, stat.st_ino + 1).as_str()));
+        assert!(!is_stdout_stream("malformed"));
+    }
+
+    #[test]
+    fn never_suppresses_ansi_codes_on_a_terminal() {
+        // A stream that supports color, like a TTY
+        let colored = ColorChoice::Never.resolve(true);
+        assert!(!colored);
+        colored::control::set_override(true);
+        assert_eq!(format_line(Level::Error, &format_args!("Session failed"), LogFormat::Human, colored), "error: Session failed");
+
+        let line = format_line(Level::Error, &format_args!("Session failed"), LogFormat::Human, ColorChoice::Always.resolve(false));
+        assert!(line.contains('\x1b'));
+        assert!(line.ends_with(": Session failed"));
+        colored::control::unset_override();
+    }
+
+    #[test]
+    fn plain_and_journal_formats_are_never_colored() {
+        assert_eq!(format_line(Level::Warn, &format_args!("Ignoring vt"), LogFormat::Plain, true), "warn: Ignoring vt");
+        assert_eq!(format_line(Level::Warn, &format_args!("Ignoring vt"), LogFormat::Journal, true), "<4>Ignoring vt");
+    }
+}
@@ -1,8 +1,66 @@
+/// Run a block that can use `?` and return its `Result`.
+///
+/// An optional `finally` block runs after the action, whether it succeeded, failed or returned
+/// early, before the result is yielded. It does not run if the action panics or calls `exit`.
+///
+/// ```
+/// use molyuuctl::attempt;
+///
+/// let mut cleaned_up = false;
+/// let result = attempt! {{
+///     Err("failed")?;
+///     Ok(())
+/// } finally {
+///     cleaned_up = true;
+/// }};
+/// assert!(result.is_err() && cleaned_up);
+/// ```
+// Exported for the binary only, it is not part of the library API
+#[doc(hidden)]
+#[macro_export]
 macro_rules! attempt {
     ($action:block) => {{
         let process = || -> Result<(), Box<dyn std::error::Error>> { $action };
         process()
     }};
+    ($action:block finally $cleanup:block) => {{
+        let process = || -> Result<(), Box<dyn std::error::Error>> { $action };
+        let result = process();
+        $cleanup
+        result
+    }};
 }
 
-pub(crate) use attempt;
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    fn fail() -> Result<(), Box<dyn Error>> {
+        Err(Box::from("failed"))
+    }
+
+    #[test]
+    fn finally_runs_after_success() {
+        let mut cleanups = 0;
+        let result = attempt! {{
+            Ok(())
+        } finally {
+            cleanups += 1;
+        }};
+        assert!(result.is_ok());
+        assert_eq!(cleanups, 1);
+    }
+
+    #[test]
+    fn finally_runs_after_an_early_return() {
+        let mut cleanups = 0;
+        let result = attempt! {{
+            fail()?;
+            unreachable!("the error returns early")
+        } finally {
+            cleanups += 1;
+        }};
+        assert_eq!(result.unwrap_err().to_string(), "failed");
+        assert_eq!(cleanups, 1);
+    }
+}
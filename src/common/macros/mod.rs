@@ -1,5 +1,3 @@
-pub(crate) use attempt::attempt;
-
 mod attempt;
 pub mod toml_macros;
 
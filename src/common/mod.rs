@@ -1,3 +1,5 @@
 pub mod structs;
 pub mod macros;
 pub mod logger;
+pub mod shell_words;
+pub mod duration;
@@ -0,0 +1,133 @@
+use std::error::Error;
+
+/// Split a command line into words the way a POSIX shell would, without expanding anything.
+///
+/// Words are separated by unquoted whitespace. Single quotes preserve everything literally,
+/// double quotes preserve everything except backslash escapes of `"`, `\`, `$` and `` ` ``, and
+/// a backslash outside quotes escapes the next character.
+///
+/// # Errors
+///
+/// Returns an error if a quote is not terminated or the command ends with a lone backslash.
+pub fn split(command: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Distinguishes an empty quoted word ('') from no word at all
+    let mut in_word = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(Box::from(format!("Unterminated single quote in command: {command}"))),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(Box::from(format!("Unterminated double quote in command: {command}"))),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(Box::from(format!("Unterminated double quote in command: {command}"))),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err(Box::from(format!("Trailing backslash in command: {command}"))),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Quote a word so that `split` and a POSIX shell read it back as a single word.
+///
+/// Words made only of characters a shell does not treat specially are returned as they are,
+/// anything else is wrapped in single quotes.
+pub fn quote(word: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(is_plain) {
+        return String::from(word);
+    }
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(command: &str) -> Vec<String> {
+        split(command).unwrap()
+    }
+
+    #[test]
+    fn unquoted_words_are_split_on_whitespace() {
+        assert_eq!(words("  startplasma-wayland --x11  -v\t"), ["startplasma-wayland", "--x11", "-v"]);
+        assert!(words("").is_empty());
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes_group_words() {
+        assert_eq!(words(r#"sway -c '/home/a b/config' --my-next-gpu-wont-work"#), ["sway", "-c", "/home/a b/config", "--my-next-gpu-wont-work"]);
+        assert_eq!(words(r#"env "A=1 2" B='$HOME'"#), ["env", "A=1 2", "B=$HOME"]);
+        assert_eq!(words(r#"a''b "" ''"#), ["ab", "", ""]);
+    }
+
+    #[test]
+    fn backslashes_escape() {
+        assert_eq!(words(r"a\ b c\\d"), ["a b", r"c\d"]);
+        assert_eq!(words(r#""a\"b\$c\n""#), [r#"a"b$c\n"#]);
+        assert_eq!(words(r"'a\b'"), [r"a\b"]);
+    }
+
+    #[test]
+    fn unterminated_input_is_an_error() {
+        assert!(split("sway 'oops").is_err());
+        assert!(split("sway \"oops").is_err());
+        assert!(split("sway \"oops\\").is_err());
+        assert!(split("sway \\").is_err());
+    }
+
+    #[test]
+    fn quoted_words_split_back_to_themselves() {
+        assert_eq!(quote("--xwayland"), "--xwayland");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote(""), "''");
+        for word in ["--xwayland", "a b", "", "it's", r#"$HOME "x" \y"#] {
+            assert_eq!(words(quote(word).as_str()), [word]);
+        }
+    }
+}
@@ -27,6 +27,6 @@ impl<T> Cell<T> {
     }
 
     pub fn get_mut(&self) -> Option<&mut T> {
-        unsafe { Some(self.inner.as_ptr().as_mut().unwrap().as_mut().unwrap()) }
+        unsafe { self.inner.as_ptr().as_mut().unwrap().as_mut() }
     }
 }
\ No newline at end of file
@@ -0,0 +1,195 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// A line-oriented INI editor.
+///
+/// Unlike a full parse and reserialize, only the lines of the keys that are set or deleted are
+/// touched, so comments, blank lines, ordering and unrelated keys of the file are preserved. This
+/// keeps keys that molyuuctl does not own intact in the login manager config across any number of
+/// Auto Login toggles.
+#[derive(Debug, Default, Clone)]
+pub struct IniDocument {
+    lines: Vec<String>,
+}
+
+impl IniDocument {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads an INI document from a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::from(fs::read_to_string(path)?.as_str()))
+    }
+
+    /// Writes the INI document to a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Sets `key` to `value` in `section`.
+    ///
+    /// An existing key is rewritten in place. Otherwise the key is appended to the end of the
+    /// section, and the section is appended to the end of the document if it does not exist yet.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) -> &mut Self {
+        let line = format!("{key}={value}");
+        match self.section_range(section) {
+            Some(range) => {
+                if let Some(index) = self.find_key(range.clone(), key) {
+                    self.lines[index] = line;
+                } else {
+                    // Insert after the last non-blank line to keep blank separators between sections
+                    let position = range.clone().rev()
+                        .find(|index| !self.lines[*index].trim().is_empty())
+                        .map_or(range.start, |index| index + 1);
+                    self.lines.insert(position, line);
+                }
+            }
+            None => {
+                if self.lines.last().is_some_and(|last| !last.trim().is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{section}]"));
+                self.lines.push(line);
+            }
+        }
+        self
+    }
+
+    /// Deletes `key` from `section`, if present.
+    pub fn delete(&mut self, section: &str, key: &str) -> &mut Self {
+        if let Some(range) = self.section_range(section) {
+            if let Some(index) = self.find_key(range, key) {
+                self.lines.remove(index);
+            }
+        }
+        self
+    }
+
+    /// Returns the value of `key` in `section`, if present.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let index = self.find_key(self.section_range(section)?, key)?;
+        self.lines[index].split_once('=').map(|(_, value)| value.trim())
+    }
+
+    /// Returns the names of the sections in the order they appear in the document.
+    pub fn sections(&self) -> Vec<String> {
+        self.lines.iter()
+            .filter_map(|line| Self::section_name(line))
+            .map(String::from)
+            .collect()
+    }
+
+    /// Returns the range of lines belonging to the body of `section`, excluding its header.
+    ///
+    /// Headers are matched the way the login managers read them, so a header padded with spaces
+    /// like `[ Autologin ]` is found instead of being shadowed by a duplicate section.
+    fn section_range(&self, section: &str) -> Option<Range<usize>> {
+        let start = self.lines.iter().position(|line| Self::section_name(line) == Some(section))? + 1;
+        let end = self.lines[start..].iter()
+            .position(|line| Self::section_name(line).is_some())
+            .map_or(self.lines.len(), |offset| start + offset);
+        Some(start..end)
+    }
+
+    /// Returns the name of the section if `line` is a section header.
+    fn section_name(line: &str) -> Option<&str> {
+        let (name, _) = line.trim_start().strip_prefix('[')?.split_once(']')?;
+        Some(name.trim())
+    }
+
+    fn find_key(&self, range: Range<usize>, key: &str) -> Option<usize> {
+        range.into_iter().find(|index| {
+            let line = self.lines[*index].trim_start();
+            if line.starts_with('#') || line.starts_with(';') {
+                return false;
+            }
+            line.split_once('=').is_some_and(|(name, _)| name.trim() == key)
+        })
+    }
+}
+
+impl From<&str> for IniDocument {
+    fn from(contents: &str) -> Self {
+        Self {
+            lines: contents.lines().map(String::from).collect(),
+        }
+    }
+}
+
+impl Display for IniDocument {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SHARED_CONFIG: &str = "# Shared with other tools\n[General]\n; Keep the numlock on\nNumlock=on\n\n[Autologin]\n# Set by molyuuctl\nUser=alice\nRelogin=false\n";
+
+    #[test]
+    fn unchanged_document_round_trips() {
+        assert_eq!(IniDocument::from(SHARED_CONFIG).to_string(), SHARED_CONFIG);
+    }
+
+    #[test]
+    fn set_rewrites_only_the_owned_key() {
+        let mut document = IniDocument::from(SHARED_CONFIG);
+        document.set("Autologin", "User", "bob").set("Autologin", "Session", "molyuu-redirect-wayland");
+        assert_eq!(document.to_string(), "# Shared with other tools\n[General]\n; Keep the numlock on\nNumlock=on\n\n[Autologin]\n# Set by molyuuctl\nUser=bob\nRelogin=false\nSession=molyuu-redirect-wayland\n");
+    }
+
+    #[test]
+    fn set_appends_a_missing_section() {
+        let mut document = IniDocument::from("# Comment\n[General]\nNumlock=on\n");
+        document.set("Autologin", "User", "alice");
+        assert_eq!(document.to_string(), "# Comment\n[General]\nNumlock=on\n\n[Autologin]\nUser=alice\n");
+    }
+
+    #[test]
+    fn toggling_a_key_keeps_comments() {
+        let mut document = IniDocument::from(SHARED_CONFIG);
+        document.set("Autologin", "Session", "molyuu-redirect-wayland");
+        document.delete("Autologin", "Session");
+        assert_eq!(document.to_string(), SHARED_CONFIG);
+    }
+
+    #[test]
+    fn commented_key_is_not_rewritten() {
+        let mut document = IniDocument::from("[Autologin]\n# User=root\n");
+        document.set("Autologin", "User", "alice");
+        assert_eq!(document.to_string(), "[Autologin]\n# User=root\nUser=alice\n");
+    }
+
+    #[test]
+    fn keys_and_sections_are_read() {
+        let document = IniDocument::from(SHARED_CONFIG);
+        assert_eq!(document.sections(), ["General", "Autologin"]);
+        assert_eq!(document.get("Autologin", "User"), Some("alice"));
+        assert_eq!(document.get("Autologin", "Session"), None);
+        assert_eq!(document.get("Missing", "User"), None);
+    }
+
+    #[test]
+    fn padded_section_header_is_matched() {
+        let mut document = IniDocument::from("[ Autologin ]\nUser=alice\n");
+        document.delete("Autologin", "User");
+        assert_eq!(document.to_string(), "[ Autologin ]\n");
+    }
+}
@@ -1 +1,2 @@
 pub mod cell;
+pub mod ini_document;
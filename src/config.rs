@@ -1,14 +1,37 @@
-use std::fs;
+use std::env;
+use std::error::Error;
+use std::ffi::{CString, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
 
 use lazy_static::lazy_static;
-use toml::Value;
+use log::warn;
+use toml::{Table, Value};
 
+use crate::audit;
+use crate::common::shell_words;
 use crate::common::structs::cell::Cell;
+use crate::errors::config::ConfigError;
+use crate::errors::system::LockError;
+use crate::session::Session;
+use crate::system::lock::{self, Lock, LOCK_RUN_DIR};
 use crate::system::privilege;
 
 static DEFAULT_CONFIG: &'static str = "config.toml";
 pub static DEFAULT_CONFIG_DIRECTORY: &'static str = "/etc/molyuuctl";
-
+static CONFIG_LOCK: &str = "molyuuctl-config-lock";
+// Only root can write to it, unlike the world-writable /tmp where anybody could plant the lock file
+static CONFIG_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+static DEFAULT_EDITOR: &str = "vi";
+// Lines added to the file being edited to show why it was rejected, stripped before validating
+static EDIT_ERROR_PREFIX: &str = "# molyuuctl: ";
+static EDIT_DIRECTORY_TEMPLATE: &str = "molyuuctl-edit-XXXXXX";
+static EDIT_FILE_NAME: &str = "config.toml";
 
 lazy_static! {
     pub static ref GLOBAL_CONFIG: Cell<Configuration> = Cell::default();
@@ -17,39 +40,645 @@ lazy_static! {
 pub struct Configuration {
     path: String,
     value: Cell<Value>,
+    /// Whether the configuration lock is held for the whole command, see `set_locked`.
+    locked: bool,
+    /// Modification time of the file when it was last loaded or saved.
+    modified: Option<SystemTime>,
+    /// Whether `[audit].enabled` is set in the file as it was last loaded or saved.
+    audit_enabled: bool,
+    force: bool,
+    /// The file `save_config` writes to instead of `path`, see `set_output_path`.
+    output_path: Option<String>,
+    /// Whether `save_config` is deferred until `end_batch`.
+    batch: bool,
+    /// Whether `save_config` was called since `begin_batch`.
+    dirty: bool,
 }
 
 impl Configuration {
-    fn new(config_path: Option<&str>) -> Self {
+    fn new(config_path: Option<&str>) -> Result<Self, ConfigError> {
         let file_path = if config_path.is_some() {
             config_path.unwrap().to_string()
         } else {
             format!("{}/{}", DEFAULT_CONFIG_DIRECTORY, DEFAULT_CONFIG)
         };
 
-        let contents = fs::read_to_string(file_path.as_str()).unwrap();
-        let value = contents.parse::<Value>().unwrap();
+        let modified = Self::modified_time(file_path.as_str());
+        let value = Self::read(file_path.as_str())?;
 
-        Self {
+        Ok(Self {
             path: file_path,
+            audit_enabled: audit::is_enabled(&value),
             value: Cell::new(value),
+            locked: false,
+            modified,
+            force: false,
+            output_path: None,
+            batch: false,
+            dirty: false,
+        })
+    }
+
+    fn read(file_path: &str) -> Result<Value, ConfigError> {
+        let contents = fs::read_to_string(file_path).map_err(|err| ConfigError::from_io(err, file_path))?;
+        contents.parse::<Value>()
+            .map_err(|err| ConfigError::InvalidConfig(err.message().to_string()))
+    }
+
+    fn modified_time(file_path: &str) -> Option<SystemTime> {
+        fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    pub fn init(config_path: Option<&str>) -> Result<(), ConfigError> {
+        GLOBAL_CONFIG.init(Self::new(config_path)?).unwrap();
+        Ok(())
+    }
+
+    /// Re-read the configuration from disk, replacing the in-memory copy.
+    ///
+    /// This picks up changes made by another process or by hand since the configuration was
+    /// loaded. Any unsaved in-memory changes are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is no longer valid TOML. The in-memory
+    /// configuration is kept unchanged in that case.
+    pub fn reload(&mut self) -> Result<(), ConfigError> {
+        let modified = Self::modified_time(self.path.as_str());
+        let value = Self::read(self.path.as_str())?;
+        self.audit_enabled = audit::is_enabled(&value);
+        self.value.init(value).unwrap();
+        self.modified = modified;
+        Ok(())
+    }
+
+    /// Acquire the lock that serializes config modifications between molyuuctl instances.
+    ///
+    /// The lock must be taken before the configuration is loaded and held until it is saved,
+    /// so that concurrent read-modify-write cycles do not lose each other's changes. It is
+    /// released when the returned `Lock` is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::ConfigLocked` if another instance still holds the lock after
+    /// `CONFIG_LOCK_TIMEOUT`.
+    pub fn lock() -> Result<Lock, Box<dyn Error>> {
+        let mut lock = Self::config_lock()?;
+        lock.lock_timeout(CONFIG_LOCK_TIMEOUT)
+            .map_err(Self::lock_failed)?;
+        Ok(lock)
+    }
+
+    /// Acquire the configuration lock shared, to load the configuration for reading only.
+    ///
+    /// Any number of readers can hold it at the same time, but not while a command that modifies
+    /// the configuration holds it with `lock`, so the file is never read while it is written.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::ConfigLocked` if another instance still holds the lock exclusively
+    /// after `CONFIG_LOCK_TIMEOUT`.
+    pub fn lock_shared() -> Result<Lock, Box<dyn Error>> {
+        let mut lock = Self::config_lock()?;
+        lock.lock_shared_timeout(CONFIG_LOCK_TIMEOUT)
+            .map_err(Self::lock_failed)?;
+        Ok(lock)
+    }
+
+    /// Report a configuration lock that is still held after the timeout as
+    /// `ConfigError::ConfigLocked`, other errors are passed through unchanged.
+    fn lock_failed(err: Box<dyn Error>) -> Box<dyn Error> {
+        if err.downcast_ref::<LockError>() == Some(&LockError::FileIsLocked) {
+            return Box::from(ConfigError::ConfigLocked(format!("{LOCK_RUN_DIR}/{CONFIG_LOCK}.lock")));
         }
+        err
     }
 
-    pub fn init(config_path: Option<&str>) {
-        GLOBAL_CONFIG.init(Self::new(config_path)).unwrap();
+    /// Create the configuration lock in `LOCK_RUN_DIR`, creating the directory and the lock file
+    /// with root permissions if they do not exist yet. The lock file is kept on release.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or the lock file cannot be created.
+    fn config_lock() -> Result<Lock, Box<dyn Error>> {
+        let path = format!("{LOCK_RUN_DIR}/{CONFIG_LOCK}.lock");
+        if !Path::new(path.as_str()).exists() {
+            lock::create_run_dir()?;
+            unsafe {
+                privilege::exec(|| {
+                    OpenOptions::new().write(true).create(true).truncate(false).mode(0o644).open(path.as_str())?;
+                    Ok(())
+                })?;
+            }
+        }
+        let mut lock = Lock::new_in(LOCK_RUN_DIR, CONFIG_LOCK, None);
+        lock.set_keep_file(true);
+        Ok(lock)
+    }
+
+    /// Run `f` on the configuration re-read from disk while holding the configuration lock.
+    ///
+    /// This is for commands that run without the lock, like `session start`, to make their few
+    /// changes without holding the lock for as long as they run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock cannot be acquired or the configuration cannot be re-read.
+    /// Errors returned by `f` are passed through.
+    pub fn with_lock<T, F>(f: F) -> Result<T, Box<dyn Error>>
+        where F: FnOnce() -> Result<T, Box<dyn Error>>
+    {
+        // Taking the lock again would wait for this process, and the loaded configuration is current
+        if GLOBAL_CONFIG.get_mut().unwrap().locked {
+            return f();
+        }
+        let _lock = Self::lock()?;
+        GLOBAL_CONFIG.get_mut().unwrap().reload()?;
+        f()
+    }
+
+    /// Record that the configuration lock was taken before loading and is held until exit, so
+    /// that `with_lock` runs its closure right away instead of waiting for the lock.
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    pub fn get_path(&self) -> &str {
+        self.path.as_str()
     }
 
     pub fn get(&mut self, config_name: &str) -> &mut Value {
         &mut self.value.get_mut().unwrap()[config_name]
     }
 
-    pub fn save_config(&mut self) {
+    /// Retrieve a copy of a top-level table of the configuration for read-only callers.
+    ///
+    /// Unlike `get`, the copy does not alias the global configuration, so it is unaffected by
+    /// later changes to it.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the section is missing or is not a table.
+    pub fn get_section_snapshot(&self, name: &str) -> Option<Table> {
+        self.value.get_mut().unwrap().get(name).and_then(|section| section.as_table()).cloned()
+    }
+
+    /// Retrieve a top-level section of the configuration without panicking if it is missing.
+    pub fn try_get(&mut self, config_name: &str) -> Option<&mut Value> {
+        self.value.get_mut().unwrap().get_mut(config_name)
+    }
+
+    /// Allow `save_config` to overwrite the file even if it changed on disk since it was loaded.
+    pub fn set_force(&mut self, force: bool) {
+        self.force = force;
+    }
+
+    /// Make `save_config` write to another file, leaving the loaded one untouched.
+    ///
+    /// This builds a configuration offline, e.g. to ship in an image, from the one on the system.
+    /// The output file is written with the permissions of the invoking user and without checking
+    /// it for changes on disk, since it is not the file the configuration was loaded from.
+    pub fn set_output_path(&mut self, output_path: Option<&str>) {
+        self.output_path = output_path.map(String::from);
+    }
+
+    /// Write the in-memory configuration back to its file, or to the output file set with
+    /// `set_output_path`.
+    ///
+    /// Unless forced with `set_force`, the file is only written if it was not modified since it
+    /// was loaded, so that edits made by hand or by another process are not silently clobbered.
+    /// The change is recorded in the audit log if `[audit].enabled` is set, see `audit::record`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::ChangedOnDisk` if the file changed on disk, or an error if it
+    /// cannot be written.
+    pub fn save_config(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.batch {
+            self.dirty = true;
+            return Ok(());
+        }
+        let value = self.value.get_mut().unwrap();
+        let contents = toml::to_string(value)?;
+        let audit_enabled = audit::is_enabled(value);
+        self.write(contents, audit_enabled)
+    }
+
+    /// Record a change of a login manager config in the audit log if `[audit].enabled` is set,
+    /// see `audit::record_file`.
+    ///
+    /// The file is written already, so a failure to audit the change is only logged.
+    pub fn record_file_change(&self, path: &str, before: &str, after: &str) {
+        if let Err(_err) = audit::record_file(self.value.get_mut().unwrap(), path, before, after) {
+            warn!("Failed to record config change in the audit log: {}", _err);
+        }
+    }
+
+    /// Defer `save_config` until `end_batch`, so that a series of changes is written at once.
+    pub fn begin_batch(&mut self) {
+        self.batch = true;
+        self.dirty = false;
+    }
+
+    /// Stop deferring `save_config` and write the configuration if it changed since
+    /// `begin_batch`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `save_config`.
+    pub fn end_batch(&mut self) -> Result<(), Box<dyn Error>> {
+        self.batch = false;
+        if std::mem::take(&mut self.dirty) {
+            self.save_config()?;
+        }
+        Ok(())
+    }
+
+    /// Write the changes deferred by a batch that was neither ended nor aborted, e.g. because of
+    /// an early return, so that they are not lost on exit.
+    ///
+    /// Does nothing outside of a batch, since `save_config` writes right away then.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if there were deferred changes and they were written.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `save_config`.
+    pub fn flush_open_batch(&mut self) -> Result<bool, Box<dyn Error>> {
+        if !self.batch {
+            return Ok(false);
+        }
+        self.batch = false;
+        if !std::mem::take(&mut self.dirty) {
+            return Ok(false);
+        }
+        self.save_config()?;
+        Ok(true)
+    }
+
+    /// Stop deferring `save_config` and discard the changes made since `begin_batch` by
+    /// re-reading the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `reload`.
+    pub fn abort_batch(&mut self) -> Result<(), ConfigError> {
+        self.batch = false;
+        self.dirty = false;
+        self.reload()
+    }
+
+    /// Write `contents` to the config file, or to the output path if one is set.
+    ///
+    /// `audit_enabled` tells whether `contents` enable auditing. The change is only recorded in
+    /// the audit log if auditing is enabled before or after it, see `audit::record`.
+    fn write(&mut self, contents: String, audit_enabled: bool) -> Result<(), Box<dyn Error>> {
+        if let Some(output_path) = &self.output_path {
+            fs::write(output_path, contents)
+                .map_err(|err| format!("Failed to write configuration to {output_path}: {err}"))?;
+            return Ok(());
+        }
+        if !self.force && Self::modified_time(self.path.as_str()) != self.modified {
+            return Err(Box::new(ConfigError::ChangedOnDisk(self.path.clone())));
+        }
+
+        // Reading the previous file back is only needed to record the change in the audit log
+        let audited = self.audit_enabled || audit_enabled;
+        let before = audited.then(|| Self::read(self.path.as_str()).ok()).flatten();
+        let after = audited.then(|| contents.parse::<Value>().ok()).flatten();
+
         unsafe {
             privilege::exec(|| {
-                fs::write(&self.path, toml::to_string(self.value.get_mut().unwrap()).unwrap())?;
+                fs::write(&self.path, contents)?;
                 Ok(())
-            }).unwrap();
+            })?;
+        }
+        self.modified = Self::modified_time(self.path.as_str());
+        self.audit_enabled = audit_enabled;
+
+        // The change is saved already, a failure to audit it must not look like a failure to save
+        if let (Some(before), Some(after)) = (before, after) {
+            if let Err(_err) = audit::record(&before, &after) {
+                warn!("Failed to record config change in the audit log: {}", _err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the contents of a config file are valid TOML with the structure molyuuctl needs.
+    ///
+    /// Besides the required sections, every registered session must pass
+    /// `Session::validate_table`, the rules `session register` enforces, and the default session
+    /// must be registered.
+    ///
+    /// # Returns
+    ///
+    /// Returns the parsed configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidConfig` describing the first problem found.
+    pub fn validate_contents(contents: &str) -> Result<Value, ConfigError> {
+        let value = contents.parse::<Value>()
+            .map_err(|err| ConfigError::InvalidConfig(err.message().to_string()))?;
+        for section in ["login", "session"] {
+            if !value.get(section).is_some_and(|section| section.is_table()) {
+                return Err(ConfigError::InvalidConfig(format!("Section [{section}] is missing")));
+            }
+        }
+        if !value["login"].get("autologin").is_some_and(|section| section.is_table()) {
+            return Err(ConfigError::InvalidConfig(String::from("Section [login.autologin] is missing")));
+        }
+
+        let session_info = value["session"].as_table().unwrap();
+        for (name, session) in session_info.iter().filter(|(_, session)| session.is_table()) {
+            if let Err(err) = Session::validate_table(name, session.as_table().unwrap()) {
+                return Err(ConfigError::InvalidConfig(format!("Session {}: {err}", name.escape_debug())));
+            }
         }
+        for default_key in ["default", "default_x11", "default_wayland"] {
+            if let Some(default) = session_info.get(default_key) {
+                if !default.as_str().is_some_and(|default| session_info.get(default).is_some_and(|session| session.is_table())) {
+                    return Err(ConfigError::InvalidConfig(format!("Default session is not registered: {default}")));
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    /// Edit the config file in `$EDITOR` (`vi` if it is not set) and save the result if it is valid.
+    ///
+    /// The configuration is edited in a temporary copy. If the edited copy does not pass
+    /// `validate_contents`, the editor is opened again with the problem shown at the top of the
+    /// file, until the copy is valid or left unchanged.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if the edited configuration was saved, or `Ok(false)` if it was not
+    /// changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the editor cannot be run or exits with non-zero status, or if the file
+    /// cannot be read or written.
+    pub fn edit(&mut self) -> Result<bool, Box<dyn Error>> {
+        let original = fs::read_to_string(self.path.as_str())?;
+        let editor = env::var("EDITOR").ok()
+            .filter(|editor| !editor.is_empty())
+            .unwrap_or_else(|| String::from(DEFAULT_EDITOR));
+        let editor = shell_words::split(editor.as_str())?;
+        let (program, args) = editor.split_first().ok_or("EDITOR is empty")?;
+
+        // The editor gets a file in a directory only the current user can enter, so a file planted
+        // in the shared temp directory, e.g. a symlink, cannot be written through when run as root
+        let temp_dir = create_private_temp_dir()?;
+        let temp_path = temp_dir.join(EDIT_FILE_NAME);
+        OpenOptions::new().write(true).create_new(true).mode(0o600).open(&temp_path)?
+            .write_all(original.as_bytes())?;
+        let result = (|| -> Result<bool, Box<dyn Error>> {
+            loop {
+                let status = Command::new(program).args(args).arg(&temp_path).status()
+                    .map_err(|err| format!("Failed to run editor {program}: {err}"))?;
+                if !status.success() {
+                    return Err(Box::from(format!("Editor {program} exited with {status}, config is left unchanged")));
+                }
+
+                let edited: String = fs::read_to_string(&temp_path)?
+                    .lines()
+                    .filter(|line| !line.starts_with(EDIT_ERROR_PREFIX))
+                    .map(|line| format!("{line}\n"))
+                    .collect();
+                if edited.trim_end() == original.trim_end() {
+                    return Ok(false);
+                }
+
+                match Self::validate_contents(edited.as_str()) {
+                    Ok(value) => {
+                        self.write(edited, audit::is_enabled(&value))?;
+                        self.value.init(value).unwrap();
+                        return Ok(true);
+                    }
+                    Err(err) => {
+                        let message: String = err.to_string().lines()
+                            .map(|line| format!("{EDIT_ERROR_PREFIX}{line}\n"))
+                            .collect();
+                        fs::write(&temp_path, format!("{message}{edited}"))?;
+                    }
+                }
+            }
+        })();
+        let _ = fs::remove_dir_all(&temp_dir);
+        result
+    }
+}
+
+/// Create a new directory with a random name in the temp directory, accessible only by the
+/// current user.
+fn create_private_temp_dir() -> io::Result<PathBuf> {
+    let template = env::temp_dir().join(EDIT_DIRECTORY_TEMPLATE);
+    let mut template = CString::new(template.into_os_string().into_vec())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+        .into_bytes_with_nul();
+    // mkdtemp replaces the trailing XXXXXX and creates the directory with mode 0700
+    if unsafe { libc::mkdtemp(template.as_mut_ptr() as *mut libc::c_char) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    template.pop();
+    Ok(PathBuf::from(OsString::from_vec(template)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Instant;
+
+    use toml::Table;
+
+    use super::*;
+    use crate::errors::{MolyuuError, EXIT_LOCKED};
+    use crate::testing;
+
+    #[test]
+    fn with_lock_saves_on_top_of_changes_made_on_disk() {
+        let fixture = testing::load_config("[session]\n");
+        fs::write(&fixture.path, "[session]\ndefault = \"alpha\"\n").unwrap();
+
+        Configuration::with_lock(|| {
+            let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+            session_info.insert(String::from("oneshot_session"), Value::String(String::from("beta")));
+            GLOBAL_CONFIG.get_mut().unwrap().save_config()
+        }).unwrap();
+
+        let saved = fixture.read().parse::<Table>().unwrap();
+        assert_eq!(saved["session"]["default"].as_str(), Some("alpha"));
+        assert_eq!(saved["session"]["oneshot_session"].as_str(), Some("beta"));
+    }
+
+    #[test]
+    fn with_lock_does_not_wait_for_a_lock_held_by_the_command() {
+        let _fixture = testing::load_config("[session]\n");
+        let _lock = Configuration::lock().unwrap();
+        GLOBAL_CONFIG.get_mut().unwrap().set_locked(true);
+
+        let start = Instant::now();
+        assert_eq!(Configuration::with_lock(|| Ok(1)).unwrap(), 1);
+        assert!(start.elapsed() < CONFIG_LOCK_TIMEOUT);
+    }
+
+    #[test]
+    fn config_lock_timeout_exits_as_locked() {
+        let _fixture = testing::load_config("[session]\n");
+        let _lock = Configuration::lock().unwrap();
+
+        let err = Configuration::config_lock().unwrap().lock_timeout(Duration::from_millis(200))
+            .map_err(Configuration::lock_failed).unwrap_err();
+        assert!(matches!(err.downcast_ref::<ConfigError>(), Some(ConfigError::ConfigLocked(_))));
+        assert_eq!(MolyuuError::from(err).exit_code(), EXIT_LOCKED);
+    }
+
+    #[test]
+    fn save_refuses_to_overwrite_changes_made_on_disk() {
+        let fixture = testing::load_config("[session]\n");
+        let config = GLOBAL_CONFIG.get_mut().unwrap();
+        fs::write(&fixture.path, "[session]\ndefault = \"alpha\"\n").unwrap();
+        // Make sure the change is visible even with a coarse timestamp resolution
+        let file = fs::File::options().write(true).open(&fixture.path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+
+        let err = config.save_config().unwrap_err();
+        assert!(matches!(err.downcast_ref::<ConfigError>(), Some(ConfigError::ChangedOnDisk(_))));
+        assert!(fixture.read().contains("alpha"));
+
+        config.set_force(true);
+        config.save_config().unwrap();
+        assert!(!fixture.read().contains("alpha"));
+
+        // Saving again is fine, the file was last written by this process
+        config.set_force(false);
+        config.save_config().unwrap();
+    }
+
+    #[test]
+    fn reload_picks_up_changes_on_disk() {
+        let fixture = testing::load_config("[session]\n");
+        let config = GLOBAL_CONFIG.get_mut().unwrap();
+        fs::write(&fixture.path, "[session]\ndefault = \"alpha\"\n").unwrap();
+        assert!(config.get("session").get("default").is_none());
+
+        config.reload().unwrap();
+        assert_eq!(config.get("session")["default"].as_str(), Some("alpha"));
+    }
+
+    #[test]
+    fn reload_keeps_in_memory_copy_when_file_is_invalid() {
+        let fixture = testing::load_config("[session]\ndefault = \"alpha\"\n");
+        let config = GLOBAL_CONFIG.get_mut().unwrap();
+        fs::write(&fixture.path, "[session\n").unwrap();
+
+        assert!(matches!(config.reload(), Err(ConfigError::InvalidConfig(_))));
+        assert_eq!(config.get("session")["default"].as_str(), Some("alpha"));
+    }
+
+    #[test]
+    fn open_batch_is_flushed() {
+        let fixture = testing::load_config("[session]\n");
+        let before = fixture.read();
+        let config = GLOBAL_CONFIG.get_mut().unwrap();
+        config.begin_batch();
+        config.get("session").as_table_mut().unwrap().insert(String::from("default"), Value::String(String::from("alpha")));
+        config.save_config().unwrap();
+        assert_eq!(fixture.read(), before);
+
+        assert!(config.flush_open_batch().unwrap());
+        assert!(fixture.read().contains("default = \"alpha\""));
+
+        // Outside of a batch changes are saved right away and there is nothing to flush
+        config.get("session").as_table_mut().unwrap().insert(String::from("default"), Value::String(String::from("beta")));
+        config.save_config().unwrap();
+        assert!(!config.flush_open_batch().unwrap());
+        assert!(fixture.read().contains("default = \"beta\""));
+
+        // A batch without changes is closed without writing
+        config.begin_batch();
+        assert!(!config.flush_open_batch().unwrap());
+    }
+
+    #[test]
+    fn output_path_receives_the_change_and_the_loaded_config_is_untouched() {
+        let fixture = testing::load_sessions_config();
+        let before = fixture.read();
+        let output_path = fixture.dir.path().join("image-config.toml");
+        GLOBAL_CONFIG.get_mut().unwrap().set_output_path(Some(output_path.to_str().unwrap()));
+
+        Session::from_config(Some("beta")).unwrap().set_as_default().unwrap();
+        assert_eq!(fixture.read(), before);
+        let output = fs::read_to_string(&output_path).unwrap().parse::<Table>().unwrap();
+        assert_eq!(output["session"]["default"].as_str(), Some("beta"));
+        assert!(output["session"].get("alpha").is_some());
+    }
+
+    #[test]
+    fn private_temp_dir_is_new_and_only_accessible_by_the_owner() {
+        let first = create_private_temp_dir().unwrap();
+        let second = create_private_temp_dir().unwrap();
+        assert_ne!(first, second);
+        assert!(first.file_name().unwrap().as_bytes().starts_with(b"molyuuctl-edit-"));
+        assert_eq!(fs::metadata(&first).unwrap().permissions().mode() & 0o777, 0o700);
+        fs::remove_dir(first).unwrap();
+        fs::remove_dir(second).unwrap();
+    }
+
+    #[test]
+    fn section_snapshot_is_unaffected_by_later_mutation() {
+        let _fixture = testing::load_sessions_config();
+        let snapshot = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap();
+        GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap().insert(String::from("default"), Value::String(String::from("beta")));
+
+        assert_eq!(snapshot.get("default").and_then(|default| default.as_str()), Some("alpha"));
+        assert_eq!(GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap()["default"].as_str(), Some("beta"));
+        assert!(GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("missing").is_none());
+    }
+
+    #[test]
+    fn valid_contents_are_accepted() {
+        let value = Configuration::validate_contents(testing::SESSIONS_CONFIG).unwrap();
+        assert_eq!(value["session"]["default"].as_str(), Some("alpha"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn contents_that_are_not_toml_are_rejected() {
+        let err = Configuration::validate_contents("[session\ndefault = \"alpha\"\n").unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidConfig(_)));
+    }
+
+    #[test]
+    fn contents_with_a_wrong_typed_section_are_rejected() {
+        let err = Configuration::validate_contents("login = \"autologin\"\n[session]\n").unwrap_err();
+        assert_eq!(err, ConfigError::InvalidConfig(String::from("Section [login] is missing")));
+
+        let contents = testing::SESSIONS_CONFIG.replace("protocol = \"wayland\"", "protocol = 1");
+        let err = Configuration::validate_contents(contents.as_str()).unwrap_err();
+        assert_eq!(err, ConfigError::InvalidConfig(String::from("Session alpha: Unsupported protocol: 1")));
+    }
+
+    #[test]
+    fn contents_with_names_that_register_rejects_are_rejected() {
+        let contents = format!("{}\n[session.\"../x\"]\nsession = \"x\"\n", testing::SESSIONS_CONFIG);
+        let err = Configuration::validate_contents(contents.as_str()).unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidConfig(message) if message.starts_with("Session ../x: ")));
+
+        let contents = testing::SESSIONS_CONFIG.replace("\"molyuuctl-test-alpha\"", "\"../../etc/foo\"");
+        let err = Configuration::validate_contents(contents.as_str()).unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidConfig(message) if message.starts_with("Session alpha: ")));
+    }
+
+    #[test]
+    fn contents_with_an_unregistered_default_session_are_rejected() {
+        let contents = testing::SESSIONS_CONFIG.replace("default = \"alpha\"", "default = \"gamma\"");
+        let err = Configuration::validate_contents(contents.as_str()).unwrap_err();
+        assert_eq!(err, ConfigError::InvalidConfig(String::from("Default session is not registered: \"gamma\"")));
+    }
+}
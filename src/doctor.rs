@@ -0,0 +1,212 @@
+use std::error::Error;
+use std::fs;
+
+use crate::config::{Configuration, GLOBAL_CONFIG};
+use crate::login::manager::get_current_manager;
+use crate::session::Session;
+use crate::system::SYSTEMCTL;
+
+type Check = fn() -> Result<(), Box<dyn Error>>;
+
+static CHECKS: [(&str, Check); 9] = [
+    ("Config parses", check_config),
+    ("Login manager is set", check_manager_set),
+    ("Login manager is installed", check_manager_installed),
+    ("Login manager unit is loadable", check_manager_loadable),
+    ("systemd starts the login manager", check_display_manager_alias),
+    ("Default session resolves", check_default_session),
+    ("Registered sessions resolve", check_registered_sessions),
+    ("Redirect session exists", check_redirect_session),
+    ("No stale session lock", check_stale_lock),
+];
+
+/// Run all health checks and print a pass/fail line for each of them.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if every check passed.
+///
+/// # Errors
+///
+/// Returns an error describing how many checks failed if any check did not pass.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let mut failed = 0;
+    for (name, check) in CHECKS.iter() {
+        match check() {
+            Ok(()) => println!("[PASS] {}", name),
+            Err(err) => {
+                println!("[FAIL] {}: {}", name, err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(Box::from(format!("{} of {} checks failed", failed, CHECKS.len())));
+    }
+    Ok(())
+}
+
+/// Dry-run the start of a registered session and print a pass/fail line for each step.
+///
+/// See `Session::dry_run` for the steps. The session is never launched.
+///
+/// # Errors
+///
+/// Returns an error if the session is not registered, or describing how many steps failed if any
+/// step did not pass.
+pub fn test_session(reg_name: &str) -> Result<(), Box<dyn Error>> {
+    let steps = Session::from_config(Some(reg_name))?.dry_run();
+    let mut failed = 0;
+    for (name, result) in steps.iter() {
+        match result {
+            Ok(detail) => println!("[PASS] {}: {}", name, detail),
+            Err(err) => {
+                println!("[FAIL] {}: {}", name, err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(Box::from(format!("{} of {} steps failed", failed, steps.len())));
+    }
+    Ok(())
+}
+
+/// Check that the config file on disk parses and contains the required sections.
+pub fn check_config() -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(GLOBAL_CONFIG.get_mut().unwrap().get_path())?;
+    Configuration::validate_contents(contents.as_str())?;
+    Ok(())
+}
+
+/// Check that a supported login manager is set.
+pub fn check_manager_set() -> Result<(), Box<dyn Error>> {
+    get_current_manager()?;
+    Ok(())
+}
+
+/// Check that the unit file of the current login manager is installed.
+pub fn check_manager_installed() -> Result<(), Box<dyn Error>> {
+    let unit = format!("{}.service", get_current_manager()?.get_metadata().systemd_unit);
+    SYSTEMCTL.lock().unwrap().get_unit_file_state(unit.as_str())?;
+    Ok(())
+}
+
+/// Check that systemd is able to load the unit of the current login manager.
+pub fn check_manager_loadable() -> Result<(), Box<dyn Error>> {
+    let unit = format!("{}.service", get_current_manager()?.get_metadata().systemd_unit);
+    let state = SYSTEMCTL.lock().unwrap().get_unit_load_state(unit.as_str())?;
+    if state != "loaded" {
+        return Err(Box::from(format!("Unit {} is {}", unit, state)));
+    }
+    Ok(())
+}
+
+/// Check that `display-manager.service` points to the unit of the current login manager.
+pub fn check_display_manager_alias() -> Result<(), Box<dyn Error>> {
+    if get_current_manager()?.check_display_manager_alias()?.is_none() {
+        return Err(Box::from("display-manager.service is not enabled"));
+    }
+    Ok(())
+}
+
+/// Check that the default session is set and its desktop file exists.
+pub fn check_default_session() -> Result<(), Box<dyn Error>> {
+    Session::get_default_session()?.validate()
+}
+
+/// Check that every registered session resolves to a desktop file in the system.
+pub fn check_registered_sessions() -> Result<(), Box<dyn Error>> {
+    let broken: Vec<String> = Session::validate_all()
+        .into_iter()
+        .filter(|(_, result)| result.is_err())
+        .map(|(name, _)| name)
+        .collect();
+    if !broken.is_empty() {
+        return Err(Box::from(format!("Sessions cannot be resolved: {}", broken.join(", "))));
+    }
+    Ok(())
+}
+
+/// Check that the molyuu-redirect session used by auto login exists, if auto login is enabled.
+pub fn check_redirect_session() -> Result<(), Box<dyn Error>> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    let autologin_enabled = login_info.get("autologin")
+        .and_then(|autologin| autologin.get("enable"))
+        .and_then(|enable| enable.as_bool())
+        .unwrap_or(false);
+    if !autologin_enabled {
+        return Ok(());
+    }
+
+    get_current_manager()?.check_redirect_session()
+}
+
+/// Check that no session startup lock is left behind by a session that is no longer running.
+pub fn check_stale_lock() -> Result<(), Box<dyn Error>> {
+    if Session::has_stale_startup_lock(Session::current_seat().as_str())? {
+        return Err(Box::from("Session startup lock exists but is not held by any process"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{load_config, load_sessions_config, SESSIONS_CONFIG};
+
+    #[test]
+    fn config_check_reads_the_file_on_disk() {
+        let fixture = load_sessions_config();
+        assert!(check_config().is_ok());
+        fs::write(&fixture.path, "[session]\n").unwrap();
+        assert!(check_config().is_err());
+    }
+
+    #[test]
+    fn manager_check_fails_without_a_manager() {
+        let _fixture = load_sessions_config();
+        assert!(check_manager_set().is_err());
+    }
+
+    #[test]
+    fn default_session_check_needs_the_desktop_file() {
+        let fixture = load_sessions_config();
+        assert!(check_default_session().is_ok());
+        fs::remove_file(fixture.dir.path().join("wayland-sessions/molyuuctl-test-alpha.desktop")).unwrap();
+        assert!(check_default_session().is_err());
+    }
+
+    #[test]
+    fn registered_sessions_check_names_the_broken_sessions() {
+        let fixture = load_sessions_config();
+        assert!(check_registered_sessions().is_ok());
+        fs::remove_file(fixture.dir.path().join("wayland-sessions/molyuuctl-test-beta.desktop")).unwrap();
+        let err = check_registered_sessions().unwrap_err().to_string();
+        assert!(err.ends_with(": beta"), "{err}");
+    }
+
+    #[test]
+    fn redirect_session_check_passes_without_auto_login() {
+        let _fixture = load_config(SESSIONS_CONFIG);
+        assert!(check_redirect_session().is_ok());
+    }
+
+    #[test]
+    fn redirect_session_check_needs_a_manager_with_auto_login() {
+        let _fixture = load_config(SESSIONS_CONFIG.replace("[login.autologin]\n", "[login.autologin]\nenable = true\n").as_str());
+        assert!(check_redirect_session().is_err());
+    }
+
+    #[test]
+    fn session_test_fails_with_the_number_of_failed_steps() {
+        let fixture = load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "true");
+        test_session("alpha").unwrap();
+
+        fixture.install_session("wayland-sessions", "molyuuctl-test-beta", "molyuuctl-test-missing-program");
+        assert_eq!(test_session("beta").unwrap_err().to_string(), "1 of 5 steps failed");
+    }
+}
@@ -0,0 +1,50 @@
+use std::io;
+
+use crate::errors::generator::generate_error_enum;
+
+generate_error_enum!(ConfigError, {
+    ConfigNotFound(String): "Config file is not found: {}",
+    PermissionDenied(String): "Permission denied while accessing config: {}",
+    InvalidConfig(String): "Config is invalid: {}",
+    IoError(String): "Failed to access config: {}",
+    ConfigLocked(String): "Failed to lock configuration, is another molyuuctl running? ({})",
+    ChangedOnDisk(String): "Config file changed on disk since it was loaded, run the command again or pass --force-write to overwrite it: {}",
+});
+
+impl ConfigError {
+    /// Classify an error accessing a config file, keeping the path of the file as the payload.
+    ///
+    /// # Parameters
+    ///
+    /// * `err`: The error returned while accessing the file.
+    /// * `path`: The path of the config file.
+    pub fn from_io(err: io::Error, path: &str) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::ConfigNotFound(String::from(path)),
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied(String::from(path)),
+            _ => Self::IoError(format!("{path}: {err}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_convert_by_kind() {
+        let err = ConfigError::from_io(io::Error::new(io::ErrorKind::NotFound, "missing"), "/etc/molyuuctl/config.toml");
+        assert_eq!(err, ConfigError::ConfigNotFound(String::from("/etc/molyuuctl/config.toml")));
+        let err = ConfigError::from_io(io::Error::new(io::ErrorKind::PermissionDenied, "denied"), "/etc/molyuuctl/config.toml");
+        assert_eq!(err, ConfigError::PermissionDenied(String::from("/etc/molyuuctl/config.toml")));
+        let err = ConfigError::from_io(io::Error::new(io::ErrorKind::InvalidData, "garbled"), "/etc/molyuuctl/config.toml");
+        assert_eq!(err, ConfigError::IoError(String::from("/etc/molyuuctl/config.toml: garbled")));
+    }
+
+    #[test]
+    fn missing_config_is_not_found() {
+        let path = "/nonexistent/molyuuctl/config.toml";
+        let err = std::fs::read_to_string(path).map_err(|err| ConfigError::from_io(err, path)).unwrap_err();
+        assert_eq!(err, ConfigError::ConfigNotFound(String::from(path)));
+    }
+}
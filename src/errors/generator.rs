@@ -1,8 +1,8 @@
 macro_rules! generate_error_enum {
-    ($enum_name:ident, {$($variant:ident: $description:expr),+,}) => {
+    ($enum_name:ident, {$($variant:ident $(($payload:ty))?: $description:literal),+,}) => {
         #[derive(Debug, Eq, PartialEq)]
         pub enum $enum_name {
-            $($variant,)+
+            $($variant $(($payload))?,)+
         }
 
         impl std::fmt::Display for $enum_name {
@@ -14,15 +14,58 @@ macro_rules! generate_error_enum {
         impl std::error::Error for $enum_name {}
 
         impl $enum_name {
-            pub fn get_description(&self) -> &'static str {
+            pub fn get_description(&self) -> String {
                 match self {
                     $(
-                        $enum_name::$variant => $description,
+                        $crate::errors::generator::generate_error_enum!(@pattern $enum_name, $variant, payload $(, $payload)?) =>
+                            $crate::errors::generator::generate_error_enum!(@describe $description, payload $(, $payload)?),
                     )+
                 }
             }
         }
     };
+
+    // Variants without payload match by name and use the description as is
+    (@pattern $enum_name:ident, $variant:ident, $binding:ident) => {
+        $enum_name::$variant
+    };
+    (@describe $description:literal, $binding:ident) => {
+        String::from($description)
+    };
+
+    // Variants with payload bind it and interpolate it into the description
+    (@pattern $enum_name:ident, $variant:ident, $binding:ident, $payload:ty) => {
+        $enum_name::$variant($binding)
+    };
+    (@describe $description:literal, $binding:ident, $payload:ty) => {
+        format!($description, $binding)
+    };
 }
 
-pub(crate) use generate_error_enum;
\ No newline at end of file
+pub(crate) use generate_error_enum;
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::session::SessionInstanceError;
+    use crate::errors::system::LockError;
+
+    generate_error_enum!(TestError, {
+        Plain: "Plain error",
+        WithPayload(u32): "Error with payload {}",
+    });
+
+    #[test]
+    fn description_interpolates_the_payload() {
+        assert_eq!(TestError::Plain.to_string(), "Plain error");
+        assert_eq!(TestError::WithPayload(42).to_string(), "Error with payload 42");
+        assert_eq!(TestError::WithPayload(42).get_description(), TestError::WithPayload(42).to_string());
+    }
+
+    #[test]
+    fn migrated_errors_carry_their_payload() {
+        assert_eq!(LockError::from(libc::EIO).to_string(), format!("Unknown Error (errno {})", libc::EIO));
+        assert_eq!(LockError::from(libc::EWOULDBLOCK), LockError::FileIsLocked);
+        assert_eq!(SessionInstanceError::SessionNotFoundInSystem(String::from("plasma")).to_string(),
+            "Specific session is not found in system: plasma");
+    }
+}
@@ -3,6 +3,12 @@ use crate::errors::generator::generate_error_enum;
 generate_error_enum!(LoginManagerInstanceError, {
    UnknownCurrentManager: "Default Manager is unsupported or it is not set.",
    UnsupportedManager: "Specific Manager is unsupported.",
+   ManagerNotInstalled(String): "Login manager is not installed: {}",
    ManagerAlreadyDefault: "Specific manager is already current login manager.",
    InvalidParameters: "Provided parameters are invalid.",
+   RedirectSessionNotFound: "Molyuu redirect session is not installed in system.",
+   AutoLoginUserNotSet: "Auto Login user is not set, please specify one with --user.",
+   AutoLoginUserNotFound(String): "Auto Login user does not exist: {}, pass --force if the account will be created later.",
+   DisplayManagerMismatch(String): "systemd starts a different login manager at boot: {}",
+   ReservedOption(String): "Option is managed by Auto Login and cannot be set: {}",
 });
\ No newline at end of file
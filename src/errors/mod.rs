@@ -1,4 +1,163 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+use crate::errors::config::ConfigError;
+use crate::errors::login::LoginManagerInstanceError;
+use crate::errors::session::SessionInstanceError;
+use crate::errors::system::{LockError, PrivilegeError, UserError};
+
 mod generator;
+pub mod config;
 pub mod session;
 pub mod login;
-pub mod system;
\ No newline at end of file
+pub mod system;
+
+pub static EXIT_FAILURE: i32 = 1;
+pub static EXIT_CONFIG: i32 = 2;
+pub static EXIT_NOT_FOUND: i32 = 3;
+pub static EXIT_PERMISSION: i32 = 4;
+pub static EXIT_LOGIN_MANAGER: i32 = 5;
+pub static EXIT_LOCKED: i32 = 6;
+
+/// Any error molyuuctl reports, classified by the module it comes from.
+///
+/// Errors are passed around as `Box<dyn Error>` and only converted with `MolyuuError::from` at the
+/// top level, to pick the process exit code.
+#[derive(Debug)]
+pub enum MolyuuError {
+    Config(ConfigError),
+    Session(SessionInstanceError),
+    Login(LoginManagerInstanceError),
+    Lock(LockError),
+    Privilege(PrivilegeError),
+    User(UserError),
+    Io(io::Error),
+    Other(Box<dyn Error>),
+}
+
+impl MolyuuError {
+    /// The process exit code for the class of this error.
+    ///
+    /// | Code | Class |
+    /// |------|-------|
+    /// | 1 | Any other failure |
+    /// | 2 | The configuration is missing or invalid |
+    /// | 3 | A session, its command or a user is not found |
+    /// | 4 | Permission denied |
+    /// | 5 | The login manager is not set or not supported |
+    /// | 6 | Another molyuuctl holds a lock |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MolyuuError::Config(ConfigError::PermissionDenied(_)) => EXIT_PERMISSION,
+            MolyuuError::Config(ConfigError::ConfigLocked(_)) => EXIT_LOCKED,
+            MolyuuError::Config(_) => EXIT_CONFIG,
+            MolyuuError::Session(
+                SessionInstanceError::DefaultSessionNotSet
+                | SessionInstanceError::OneshotSessionNotSet
+                | SessionInstanceError::PreviousDefaultNotSet
+                | SessionInstanceError::SessionNotFoundInConfig
+                | SessionInstanceError::SessionNotFoundInSystem(_)
+                | SessionInstanceError::CommandNotFound(_)
+                | SessionInstanceError::KeyNotSet(_)
+            ) => EXIT_NOT_FOUND,
+            MolyuuError::Session(_) => EXIT_FAILURE,
+            MolyuuError::Login(_) => EXIT_LOGIN_MANAGER,
+            MolyuuError::Lock(LockError::FileIsLocked) => EXIT_LOCKED,
+            MolyuuError::Lock(_) => EXIT_FAILURE,
+            MolyuuError::Privilege(_) => EXIT_PERMISSION,
+            MolyuuError::User(UserError::UserNotFound(_)) => EXIT_NOT_FOUND,
+            MolyuuError::User(_) => EXIT_FAILURE,
+            MolyuuError::Io(err) if err.kind() == io::ErrorKind::PermissionDenied => EXIT_PERMISSION,
+            MolyuuError::Io(_) | MolyuuError::Other(_) => EXIT_FAILURE,
+        }
+    }
+}
+
+impl From<Box<dyn Error>> for MolyuuError {
+    fn from(err: Box<dyn Error>) -> Self {
+        let err = match err.downcast::<ConfigError>() {
+            Ok(err) => return MolyuuError::Config(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<SessionInstanceError>() {
+            Ok(err) => return MolyuuError::Session(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<LoginManagerInstanceError>() {
+            Ok(err) => return MolyuuError::Login(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<LockError>() {
+            Ok(err) => return MolyuuError::Lock(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<PrivilegeError>() {
+            Ok(err) => return MolyuuError::Privilege(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<UserError>() {
+            Ok(err) => return MolyuuError::User(*err),
+            Err(err) => err,
+        };
+        match err.downcast::<io::Error>() {
+            Ok(err) => MolyuuError::Io(*err),
+            Err(err) => MolyuuError::Other(err),
+        }
+    }
+}
+
+impl From<ConfigError> for MolyuuError {
+    fn from(err: ConfigError) -> Self {
+        MolyuuError::Config(err)
+    }
+}
+
+impl Display for MolyuuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MolyuuError::Config(err) => write!(f, "{err}"),
+            MolyuuError::Session(err) => write!(f, "{err}"),
+            MolyuuError::Login(err) => write!(f, "{err}"),
+            MolyuuError::Lock(err) => write!(f, "{err}"),
+            MolyuuError::Privilege(err) => write!(f, "{err}"),
+            MolyuuError::User(err) => write!(f, "{err}"),
+            MolyuuError::Io(err) => write!(f, "{err}"),
+            MolyuuError::Other(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for MolyuuError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exit_code(err: impl Into<Box<dyn Error>>) -> i32 {
+        MolyuuError::from(err.into()).exit_code()
+    }
+
+    #[test]
+    fn error_classes_map_to_exit_codes() {
+        assert_eq!(exit_code(ConfigError::InvalidConfig(String::from("line 1"))), EXIT_CONFIG);
+        assert_eq!(exit_code(ConfigError::PermissionDenied(String::from("denied"))), EXIT_PERMISSION);
+        assert_eq!(exit_code(SessionInstanceError::SessionNotFoundInConfig), EXIT_NOT_FOUND);
+        assert_eq!(exit_code(SessionInstanceError::SessionExists), EXIT_FAILURE);
+        assert_eq!(exit_code(LoginManagerInstanceError::UnsupportedManager), EXIT_LOGIN_MANAGER);
+        assert_eq!(exit_code(LockError::FileIsLocked), EXIT_LOCKED);
+        assert_eq!(exit_code(ConfigError::ConfigLocked(String::from("/run/molyuuctl/molyuuctl-config-lock.lock"))), EXIT_LOCKED);
+        assert_eq!(exit_code(PrivilegeError::RootRequired(String::from("login set"))), EXIT_PERMISSION);
+        assert_eq!(exit_code(UserError::UserNotFound(String::from("nobody-here"))), EXIT_NOT_FOUND);
+        assert_eq!(exit_code(io::Error::new(io::ErrorKind::PermissionDenied, "denied")), EXIT_PERMISSION);
+        assert_eq!(exit_code(io::Error::new(io::ErrorKind::NotFound, "missing")), EXIT_FAILURE);
+        assert_eq!(exit_code("Some other failure"), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn classified_error_keeps_its_message() {
+        let err = MolyuuError::from(Box::<dyn Error>::from(SessionInstanceError::SessionExists));
+        assert!(matches!(err, MolyuuError::Session(SessionInstanceError::SessionExists)));
+        assert_eq!(err.to_string(), SessionInstanceError::SessionExists.to_string());
+    }
+}
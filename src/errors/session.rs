@@ -1,12 +1,68 @@
+use std::io;
+
 use crate::errors::generator::generate_error_enum;
 
 generate_error_enum!(SessionInstanceError,
     {
         DefaultSessionNotSet: "Default session is not set or no session is specified.",
+        OneshotSessionNotSet: "Oneshot session is not set, set one with session set-oneshot.",
+        PreviousDefaultNotSet: "Previous default session is not recorded, swap one in with session swap-default.",
         SessionNotFoundInConfig: "Specific session is not found in config.",
-        SessionNotFoundInSystem: "Specific session is not found in system.",
+        SessionNotFoundInSystem(String): "Specific session is not found in system: {}",
         UnknownProtocol: "Session Protocol is unknown or not supported.",
         LogoutCommandNotSet: "Logout command is not set",
+        LogoutCommandFailed: "Logout command exited with non-zero status",
         SessionExists: "Specific session already exists",
+        InvalidSessionName(String): "Session name must be the name of a desktop file, not \".\" or \"..\", and without path separators or control characters: {}",
+        InvalidRegisterName(String): "Session register name must be 1 to 64 characters, not \".\" or \"..\", and without path separators or control characters: {}",
+        ReservedRegisterName(String): "Session register name is reserved for a [session] setting: {}",
+        SessionIoError(String): "Failed to access session: {}",
+        PrerequisiteFailed(String): "Session prerequisite is not active: {}",
+        InvalidFallback(String): "Session cannot fall back to itself: {}",
+        SessionNotRunning(String): "Specific session is not running: {}",
+        MalformedDesktopFile(String): "Session desktop file is malformed: {}",
+        InvalidSeat(String): "Seat name is invalid: {}",
+        CommandNotFound(String): "Session command is not found or not executable: {}",
+        StartNotConfirmed(String): "Session start is not confirmed: {}",
+        InvalidVt(String): "Virtual terminal must be a positive number: {}",
+        InvalidResourceLimit(String): "Session resource limit is malformed: {}",
+        ResourceLimitsUnsupported(String): "Session resource limits need the systemd-run launcher, set [session].launcher = \"systemd-run\": {}",
+        KeyNotSet(String): "Session key is not set: {}",
+        StructuralKey(String): "Session key is needed to start the session, pass --force to change it: {}",
+    }
+);
+
+impl SessionInstanceError {
+    /// Classify an error accessing a file of a session, keeping the session name as the payload
+    /// like the other variants do.
+    ///
+    /// # Parameters
+    ///
+    /// * `err`: The error returned while accessing the file.
+    /// * `session_name`: The name of the session the file belongs to.
+    pub fn from_io(err: io::Error, session_name: &str) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::SessionNotFoundInSystem(String::from(session_name)),
+            _ => Self::SessionIoError(format!("{session_name}: {err}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_convert_by_kind() {
+        let err = SessionInstanceError::from_io(io::Error::new(io::ErrorKind::NotFound, "missing"), "plasma");
+        assert_eq!(err, SessionInstanceError::SessionNotFoundInSystem(String::from("plasma")));
+        let err = SessionInstanceError::from_io(io::Error::new(io::ErrorKind::PermissionDenied, "denied"), "plasma");
+        assert_eq!(err, SessionInstanceError::SessionIoError(String::from("plasma: denied")));
+    }
+
+    #[test]
+    fn converted_error_can_be_downcast() {
+        let err: Box<dyn std::error::Error> = Box::from(SessionInstanceError::from_io(io::Error::from(io::ErrorKind::NotFound), "plasma"));
+        assert!(matches!(err.downcast_ref::<SessionInstanceError>(), Some(SessionInstanceError::SessionNotFoundInSystem(_))));
     }
-);
\ No newline at end of file
+}
@@ -9,7 +9,8 @@ generate_error_enum!(LockError, {
     InvalidOperation: "Operation is invalid.",
     NoMemoryForLock: "The kernel ran out of memory for allocating lock records.",
     FileIsLocked: "The file is locked and the LOCK_NB flag was selected.",
-    UnknownError: "Unknown Error",
+    Timeout(String): "Timed out while probing lock: {}",
+    UnknownError(c_int): "Unknown Error (errno {})",
 });
 
 impl LockError {
@@ -22,8 +23,19 @@ impl LockError {
             libc::EWOULDBLOCK => Self::FileIsLocked,
             _ => {
                 warn!("{}", format!("LockError: Unknown error {errno}"));
-                Self::UnknownError
+                Self::UnknownError(errno)
             }
         }
     }
-}
\ No newline at end of file
+}
+generate_error_enum!(UserError, {
+    UserNotFound(String): "User is not found: {}",
+    LookupFailed(String): "Failed to look up user: {}",
+    InvalidUserName(String): "Invalid user name: {}",
+});
+
+generate_error_enum!(PrivilegeError, {
+    GrantFailed(String): "Failed to gain root permissions: {}",
+    ReturnFailed(String): "Failed to return root permissions: {}",
+    RootRequired(String): "Root permissions are required: {}",
+});
@@ -0,0 +1,77 @@
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+// Off unless `--explain` is given, so the decisions are not traced at all otherwise
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref RECORDS: Mutex<Vec<TraceRecord>> = Mutex::new(Vec::new());
+}
+
+/// The decision a trace record explains.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stage {
+    /// Whether the oneshot session is started.
+    Oneshot,
+    /// Which key the default session is read from.
+    Default,
+    /// Which protocol a session or the redirect session uses.
+    Protocol,
+    /// What is written to the config of the login manager for Auto Login.
+    Redirect,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Oneshot => "oneshot",
+            Stage::Default => "default",
+            Stage::Protocol => "protocol",
+            Stage::Redirect => "redirect",
+        }
+    }
+}
+
+/// A decision taken while selecting the session to start or the session to redirect to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub stage: Stage,
+    pub decision: String,
+}
+
+impl Display for TraceRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "explain[{}]: {}", self.stage.as_str(), self.decision)
+    }
+}
+
+/// Start tracing decisions, e.g. for `--explain`.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Record a decision and print it to stderr, if tracing is enabled.
+///
+/// The decision is only built if tracing is enabled, so callers can describe it freely.
+pub fn record<F>(stage: Stage, decision: F)
+    where F: FnOnce() -> String
+{
+    if !is_enabled() {
+        return;
+    }
+    let record = TraceRecord { stage, decision: decision() };
+    eprintln!("{}", record);
+    RECORDS.lock().unwrap().push(record);
+}
+
+/// Take the decisions recorded so far, for callers that inspect them instead of reading stderr.
+pub fn take() -> Vec<TraceRecord> {
+    std::mem::take(&mut *RECORDS.lock().unwrap())
+}
@@ -0,0 +1,24 @@
+//! MolyuuOS System Controller.
+//!
+//! The library behind the `molyuuctl` binary. It can be embedded to manage registered sessions
+//! (`session`), the login manager and Auto Login (`login`) and the global configuration
+//! (`config`) without shelling out to the command line, or reconcile all of them at once with a
+//! desired-state file (`apply`).
+//!
+//! The global configuration must be initialized with `config::Configuration::init` before any
+//! session or login manager operation.
+
+pub mod config;
+pub mod session;
+pub mod login;
+pub mod errors;
+pub mod system;
+pub mod common;
+pub mod doctor;
+pub mod audit;
+pub mod apply;
+pub mod explain;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,185 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ini::Ini;
+
+use crate::common::structs::ini_document::IniDocument;
+use crate::login::manager::{ManagerMetadata, SupportedManager};
+use crate::system::privilege;
+
+/// Where a login manager reads Auto Login from, besides the file molyuuctl writes.
+struct ConfigLocation {
+    manager: &'static str,
+    /// The main config file of the login manager.
+    main_config: &'static str,
+    /// The directory of drop-in config files, read in alphabetical order.
+    drop_in_directory: &'static str,
+    /// The keys that configure Auto Login.
+    autologin_keys: &'static [&'static str],
+    /// Whether a section can hold Auto Login keys.
+    is_autologin_section: fn(&str) -> bool,
+}
+
+static CONFIG_LOCATIONS: [ConfigLocation; 2] = [
+    ConfigLocation {
+        manager: "lightdm",
+        main_config: "/etc/lightdm/lightdm.conf",
+        drop_in_directory: "/etc/lightdm/lightdm.conf.d",
+        autologin_keys: &["autologin-user", "autologin-session"],
+        is_autologin_section: |section| section.starts_with("Seat:") || section == "SeatDefaults",
+    },
+    ConfigLocation {
+        manager: "sddm",
+        main_config: "/etc/sddm.conf",
+        drop_in_directory: "/etc/sddm.conf.d",
+        autologin_keys: &["User", "Session"],
+        is_autologin_section: |section| section == "Autologin",
+    },
+];
+
+/// An Auto Login key in a login manager config that molyuuctl does not own, e.g. one left behind
+/// by another tool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictingKey {
+    pub manager: String,
+    pub path: PathBuf,
+    pub section: String,
+    pub key: String,
+    pub value: String,
+}
+
+impl Display for ConflictingKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): [{}] {}={}", self.path.display(), self.manager, self.section, self.key, self.value)
+    }
+}
+
+impl ConfigLocation {
+    /// List the config files of the login manager, without the one molyuuctl writes.
+    fn config_files(&self) -> Vec<PathBuf> {
+        let owned_path = SupportedManager::from_name(self.manager)
+            .map(|manager| PathBuf::from(ManagerMetadata::build_for_supported_manager(manager).config_path));
+
+        let mut files = vec![PathBuf::from(self.main_config)];
+        if let Ok(entries) = fs::read_dir(self.drop_in_directory) {
+            let mut drop_ins: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|extension| extension == "conf"))
+                .collect();
+            drop_ins.sort();
+            files.extend(drop_ins);
+        }
+        files.retain(|path| path.is_file() && Some(path) != owned_path.as_ref());
+        files
+    }
+
+    fn scan_file(&self, path: &Path) -> Result<Vec<ConflictingKey>, Box<dyn Error>> {
+        let file = Ini::load_from_file(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let mut conflicts = Vec::new();
+        for (section, properties) in file.iter() {
+            let Some(section) = section.filter(|section| (self.is_autologin_section)(section.trim())) else {
+                continue;
+            };
+            for (key, value) in properties.iter() {
+                if self.autologin_keys.contains(&key.trim()) && !value.trim().is_empty() {
+                    conflicts.push(ConflictingKey {
+                        manager: String::from(self.manager),
+                        path: path.to_path_buf(),
+                        section: String::from(section.trim()),
+                        key: String::from(key.trim()),
+                        value: String::from(value.trim()),
+                    });
+                }
+            }
+        }
+        Ok(conflicts)
+    }
+}
+
+/// Find Auto Login keys in the configs of all supported INI-based login managers that molyuuctl
+/// does not own.
+///
+/// The main config file and the drop-in directory of LightDM and SDDM are scanned, except the
+/// file molyuuctl writes to. Such keys are left behind by other tools and take effect alongside,
+/// or instead of, the Auto Login molyuuctl configures, e.g. after switching login managers.
+///
+/// # Errors
+///
+/// Returns an error if one of the files cannot be parsed.
+pub fn find() -> Result<Vec<ConflictingKey>, Box<dyn Error>> {
+    let mut conflicts = Vec::new();
+    for location in &CONFIG_LOCATIONS {
+        for path in location.config_files() {
+            conflicts.extend(location.scan_file(path.as_path())?);
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Remove conflicting Auto Login keys found by `find` from their files.
+///
+/// Only the lines of the keys are removed, the rest of each file is kept as it is.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or written.
+pub fn remove(conflicts: &[ConflictingKey]) -> Result<(), Box<dyn Error>> {
+    let mut paths: Vec<&PathBuf> = conflicts.iter().map(|conflict| &conflict.path).collect();
+    paths.dedup();
+    for path in paths {
+        let mut document = IniDocument::load_from_file(path)?;
+        for conflict in conflicts.iter().filter(|conflict| &conflict.path == path) {
+            document.delete(conflict.section.as_str(), conflict.key.as_str());
+        }
+        unsafe {
+            privilege::exec(|| {
+                document.write_to_file(path)?;
+                Ok(())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    static FOREIGN_SDDM_CONFIG: &str = "[Theme]\nCurrent=breeze\n\n[Autologin]\nUser=guest\nSession=plasma\nRelogin=true\n";
+
+    #[test]
+    fn planted_autologin_stanza_is_reported() {
+        let dir = TempDir::new();
+        let path = dir.write("sddm.conf.d/10-other-tool.conf", FOREIGN_SDDM_CONFIG);
+        let conflicts = CONFIG_LOCATIONS[1].scan_file(path.as_path()).unwrap();
+        let keys: Vec<(&str, &str)> = conflicts.iter().map(|conflict| (conflict.key.as_str(), conflict.value.as_str())).collect();
+        assert_eq!(keys, [("User", "guest"), ("Session", "plasma")]);
+        assert_eq!(conflicts[0].to_string(), format!("{} (sddm): [Autologin] User=guest", path.display()));
+    }
+
+    #[test]
+    fn autologin_keys_outside_autologin_sections_are_ignored() {
+        let dir = TempDir::new();
+        let path = dir.write("lightdm.conf", "[LightDM]\nautologin-user=guest\n\n[Seat:*]\nautologin-user=\n");
+        assert_eq!(CONFIG_LOCATIONS[0].scan_file(path.as_path()).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn remove_deletes_only_the_conflicting_lines() {
+        let dir = TempDir::new();
+        let path = dir.write("sddm.conf.d/10-other-tool.conf", FOREIGN_SDDM_CONFIG);
+        let conflicts = CONFIG_LOCATIONS[1].scan_file(path.as_path()).unwrap();
+        remove(&conflicts).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("User=guest"));
+        assert!(!contents.contains("Session=plasma"));
+        assert!(contents.contains("Current=breeze"));
+        assert!(contents.contains("Relogin=true"));
+        assert_eq!(CONFIG_LOCATIONS[1].scan_file(path.as_path()).unwrap(), Vec::new());
+    }
+}
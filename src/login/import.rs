@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::fs;
+
+use ini::Ini;
+use log::info;
+
+use crate::login::manager::get_current_manager;
+use crate::session::Session;
+
+static ACCOUNTSSERVICE_USERS_PATH: &str = "/var/lib/AccountsService/users";
+
+/// Where Auto Login settings are imported from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImportSource {
+    /// The per-user files of AccountsService in `/var/lib/AccountsService/users`.
+    AccountsService,
+}
+
+impl ImportSource {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "accountsservice" => Some(Self::AccountsService),
+            _ => None,
+        }
+    }
+}
+
+/// Auto Login settings read from another tool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedAutoLogin {
+    pub user: String,
+    /// The name of the session desktop file the user logs in to, if the source knows it.
+    pub session: Option<String>,
+}
+
+/// Parse the AccountsService file of a user.
+///
+/// The session is taken from the `Session` key of the `[User]` section, or from `XSession` if
+/// `Session` is not set.
+///
+/// # Returns
+///
+/// Returns `None` if the user is marked with `SystemAccount=true`, which is never logged in.
+///
+/// # Errors
+///
+/// Returns an error if the file is not valid INI.
+pub fn parse_accountsservice(user: &str, contents: &str) -> Result<Option<ImportedAutoLogin>, Box<dyn Error>> {
+    let file = Ini::load_from_str(contents)?;
+    let Some(user_section) = file.section(Some("User")) else {
+        return Ok(Some(ImportedAutoLogin { user: String::from(user), session: None }));
+    };
+    if user_section.get("SystemAccount").is_some_and(|system| system.trim().eq_ignore_ascii_case("true")) {
+        return Ok(None);
+    }
+
+    let session = ["Session", "XSession"].iter()
+        .filter_map(|key| user_section.get(key))
+        .map(str::trim)
+        .find(|session| !session.is_empty())
+        .map(String::from);
+    Ok(Some(ImportedAutoLogin { user: String::from(user), session }))
+}
+
+/// Read the Auto Login settings of a user from another tool.
+///
+/// # Parameters
+///
+/// * `source`: The tool to read from.
+/// * `user`: The user to import, or `None` to pick the only user the source knows about.
+///
+/// # Errors
+///
+/// Returns an error if the settings cannot be read, if `user` is a system account, or if `user`
+/// is `None` and the source knows about no user or more than one.
+pub fn read(source: ImportSource, user: Option<&str>) -> Result<ImportedAutoLogin, Box<dyn Error>> {
+    match source {
+        ImportSource::AccountsService => {
+            if let Some(user) = user {
+                let contents = fs::read_to_string(format!("{ACCOUNTSSERVICE_USERS_PATH}/{user}"))
+                    .map_err(|err| format!("Failed to read AccountsService settings of {user}: {err}"))?;
+                return parse_accountsservice(user, contents.as_str())?
+                    .ok_or_else(|| Box::from(format!("{user} is a system account in AccountsService")));
+            }
+
+            let mut candidates = Vec::new();
+            for entry in fs::read_dir(ACCOUNTSSERVICE_USERS_PATH)? {
+                let entry = entry?;
+                let user = entry.file_name().to_string_lossy().into_owned();
+                if let Some(imported) = parse_accountsservice(user.as_str(), fs::read_to_string(entry.path())?.as_str())? {
+                    candidates.push(imported);
+                }
+            }
+            match candidates.len() {
+                1 => Ok(candidates.remove(0)),
+                0 => Err(Box::from("AccountsService knows about no user to import")),
+                _ => {
+                    let users: Vec<&str> = candidates.iter().map(|imported| imported.user.as_str()).collect();
+                    Err(Box::from(format!("AccountsService knows about several users, pick one with --user: {}", users.join(", "))))
+                }
+            }
+        }
+    }
+}
+
+/// Enable Auto Login with imported settings.
+///
+/// The imported session becomes the default session. It is registered under its own name first
+/// if no registered session uses its desktop file yet.
+///
+/// # Errors
+///
+/// Returns an error if the session cannot be found or registered, or Auto Login cannot be enabled.
+pub fn apply(imported: &ImportedAutoLogin) -> Result<(), Box<dyn Error>> {
+    if let Some(session_name) = &imported.session {
+        let registered = Session::list_registered().into_iter().find(|reg_name| {
+            Session::config_table(reg_name.as_str()).ok()
+                .and_then(|table| table.get("session").and_then(|session| session.as_str()).map(String::from))
+                .is_some_and(|session| session.eq_ignore_ascii_case(session_name))
+        });
+        let session = match registered {
+            Some(reg_name) => Session::from_config(Some(reg_name.as_str()))?,
+            None => {
+                info!("Registering imported session {}", session_name);
+                let mut session = Session::new(session_name.clone(), session_name.clone(), None, None)?;
+                session.register()?;
+                session
+            }
+        };
+        session.set_as_default()?;
+    }
+
+    get_current_manager()?.set_auto_login(true, Some(imported.user.as_str()), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accountsservice_session_is_parsed() {
+        let contents = "[User]\nLanguage=en_US.UTF-8\nSession=plasma\nXSession=plasmax11\nIcon=/home/alice/.face\nSystemAccount=false\n";
+        assert_eq!(parse_accountsservice("alice", contents).unwrap(), Some(ImportedAutoLogin {
+            user: String::from("alice"),
+            session: Some(String::from("plasma")),
+        }));
+    }
+
+    #[test]
+    fn xsession_is_used_without_a_session() {
+        let contents = "[User]\nSession=\nXSession=xfce\n";
+        assert_eq!(parse_accountsservice("alice", contents).unwrap().unwrap().session.as_deref(), Some("xfce"));
+        assert_eq!(parse_accountsservice("alice", "[User]\nLanguage=de_DE.UTF-8\n").unwrap().unwrap().session, None);
+        assert_eq!(parse_accountsservice("alice", "").unwrap().unwrap().session, None);
+    }
+
+    #[test]
+    fn system_accounts_are_not_imported() {
+        assert_eq!(parse_accountsservice("gdm", "[User]\nSystemAccount=true\n").unwrap(), None);
+        assert_eq!(ImportSource::from_name("accountsservice"), Some(ImportSource::AccountsService));
+        assert_eq!(ImportSource::from_name("lightdm"), None);
+    }
+}
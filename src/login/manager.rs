@@ -1,34 +1,80 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
 use ini::Ini;
-use toml::Value;
+use log::{info, warn};
+use toml::{Table, Value};
+use toml_edit::DocumentMut;
 
 use crate::common::macros::toml_macros;
+use crate::common::structs::ini_document::IniDocument;
 use crate::config::GLOBAL_CONFIG;
 use crate::errors::login::LoginManagerInstanceError;
-use crate::errors::session::SessionInstanceError;
 use crate::session::Protocol;
 use crate::session::Session;
-use crate::system::{privilege, SYSTEMCTL};
+use crate::errors::system::UserError;
+use crate::explain::{self, Stage};
+use crate::system::{init, privilege, SYSTEMCTL};
+use crate::system::init::InitSystem;
+use crate::system::user::UserInfo;
 
 pub static MOLYUU_REDIRECT_SESSION_PREFIX: &'static str = "molyuu-redirect";
 static LIGHTDM_CUSTOM_CONFIG_PATH: &'static str = "/etc/lightdm/lightdm.conf.d/10-molyuud-session.conf";
 static SDDM_CUSTOM_CONFIG_PATH: &'static str = "/etc/sddm.conf.d/molyuuctl.conf";
+// greetd reads a single config file, molyuuctl only manages its [initial_session] table
+static GREETD_CONFIG_PATH: &str = "/etc/greetd/config.toml";
+static GREETD_AUTOLOGIN_COMMAND: &str = "molyuuctl session start";
+static LIGHTDM_DEFAULT_SEAT: &str = "*";
+static MANAGER_CONFIG_FILE_MODE: u32 = 0o644;
+static MANAGER_CONFIG_DIRECTORY_MODE: u32 = 0o755;
+/// Name patterns of the units of login managers, including the ones molyuuctl does not support.
+static MANAGER_UNIT_PATTERNS: [&str; 2] = ["*dm.service", "greetd.service"];
 
 pub type ConfigList = Option<HashMap<String, HashMap<String, (String, String)>>>;
+/// The values of the session and user keys of an Auto Login section.
+type AutoLoginKeys = (Option<String>, Option<String>);
 
 pub enum SupportedManager {
     LightDM,
     SDDM,
+    Greetd,
+}
+
+impl SupportedManager {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lightdm" => Some(Self::LightDM),
+            "sddm" => Some(Self::SDDM),
+            "greetd" => Some(Self::Greetd),
+            _ => None,
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![Self::LightDM, Self::SDDM, Self::Greetd]
+    }
+}
+
+/// The format of the config file of a login manager.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ManagerConfigFormat {
+    #[default]
+    Ini,
+    Toml,
 }
 
 #[derive(Debug, Clone)]
 pub struct ManagerMetadata {
     pub systemd_unit: String,
     pub config_path: String,
+    pub config_format: ManagerConfigFormat,
+    /// The command the login manager starts for Auto Login, written to the session key instead
+    /// of the name of a molyuu-redirect session. Used by login managers that start a command
+    /// rather than a session desktop file, like greetd.
+    pub autologin_command: Option<String>,
     pub autologin_section_name: String,
     pub autologin_session_key_name: String,
     pub autologin_user_key_name: String,
@@ -42,7 +88,9 @@ impl ManagerMetadata {
                 Self {
                     systemd_unit: "lightdm".to_string(),
                     config_path: LIGHTDM_CUSTOM_CONFIG_PATH.to_string(),
-                    autologin_section_name: "Seat:*".to_string(),
+                    config_format: ManagerConfigFormat::Ini,
+                    autologin_command: None,
+                    autologin_section_name: format!("Seat:{}", get_autologin_seat()),
                     autologin_session_key_name: "autologin-session".to_string(),
                     autologin_user_key_name: "autologin-user".to_string(),
                     autologin_related_other_configs: None,
@@ -60,24 +108,45 @@ impl ManagerMetadata {
                 Self {
                     systemd_unit: "sddm".to_string(),
                     config_path: SDDM_CUSTOM_CONFIG_PATH.to_string(),
+                    config_format: ManagerConfigFormat::Ini,
+                    autologin_command: None,
                     autologin_section_name: "Autologin".to_string(),
                     autologin_session_key_name: "Session".to_string(),
                     autologin_user_key_name: "User".to_string(),
                     autologin_related_other_configs: Some(other_configs),
                 }
             }
+            SupportedManager::Greetd => {
+                Self {
+                    systemd_unit: "greetd".to_string(),
+                    config_path: GREETD_CONFIG_PATH.to_string(),
+                    config_format: ManagerConfigFormat::Toml,
+                    autologin_command: Some(GREETD_AUTOLOGIN_COMMAND.to_string()),
+                    autologin_section_name: "initial_session".to_string(),
+                    autologin_session_key_name: "command".to_string(),
+                    autologin_user_key_name: "user".to_string(),
+                    autologin_related_other_configs: None,
+                }
+            }
         }
     }
 }
 
 pub struct ManagerBuilder(ManagerMetadata);
 
-#[allow(dead_code)]
+impl Default for ManagerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ManagerBuilder {
     pub fn new() -> Self {
         Self(ManagerMetadata {
             systemd_unit: "".to_string(),
             config_path: "".to_string(),
+            config_format: ManagerConfigFormat::Ini,
+            autologin_command: None,
             autologin_section_name: "".to_string(),
             autologin_session_key_name: "".to_string(),
             autologin_user_key_name: "".to_string(),
@@ -100,6 +169,16 @@ impl ManagerBuilder {
         self
     }
 
+    pub fn config_format(mut self, config_format: ManagerConfigFormat) -> Self {
+        self.0.config_format = config_format;
+        self
+    }
+
+    pub fn autologin_command(mut self, command: Option<&str>) -> Self {
+        self.0.autologin_command = command.map(String::from);
+        self
+    }
+
     pub fn autologin_section(mut self, section_name: &str) -> Self {
         self.0.autologin_section_name = section_name.to_string();
         self
@@ -137,12 +216,12 @@ impl ManagerBuilder {
     /// # Examples
     ///
     /// ```
-    /// use crate::{ConfigList, YourStruct};
+    /// use molyuuctl::login::manager::ManagerBuilder;
     /// use std::collections::HashMap;
     ///
-    /// let mut your_struct = YourStruct::new();
+    /// let mut builder = ManagerBuilder::new();
     ///
-    /// let mut config_list = ConfigList::new();
+    /// let mut config_list = HashMap::new();
     ///
     /// let mut section1 = HashMap::new();
     /// section1.insert("ConfigKeyName1".to_string(), ("DisabledValue1".to_string(), "EnabledValue1".to_string()));
@@ -158,7 +237,7 @@ impl ManagerBuilder {
     /// config_list.insert("SectionName2".to_string(), section2);
     /// // Add more sections as needed...
     ///
-    /// your_struct = your_struct.other_related_configs(config_list);
+    /// builder = builder.other_related_configs(Some(config_list));
     /// ```
     pub fn other_related_configs(mut self, config_list: ConfigList) -> Self {
         self.0.autologin_related_other_configs = config_list;
@@ -170,8 +249,49 @@ impl ManagerBuilder {
     }
 }
 
+/// Auto Login state, comparing the molyuuctl config with the config of the login manager.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AutoLoginState {
+    Enabled,
+    Disabled,
+    /// The molyuuctl config and the config of the login manager disagree.
+    Drift,
+}
+
+impl AutoLoginState {
+    /// The process exit code reported by `login autologin status` for this state.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AutoLoginState::Enabled => 0,
+            AutoLoginState::Disabled => 10,
+            AutoLoginState::Drift => 11,
+        }
+    }
+}
+
+/// Which side wins when reconciling the molyuuctl config with the config of the login manager.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReconcileDirection {
+    /// Update the molyuuctl config to match the config of the login manager.
+    FromManager,
+    /// Rewrite the config of the login manager to match the molyuuctl config.
+    ToManager,
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoLoginStatus {
+    pub state: AutoLoginState,
+    /// Whether the molyuuctl config has Auto Login enabled.
+    pub config_enabled: bool,
+    /// Whether the config of the login manager has Auto Login enabled.
+    pub manager_enabled: bool,
+    pub user: Option<String>,
+    pub session: Option<String>,
+}
+
 pub struct Manager {
     autologin: bool,
+    redirect_prefix: String,
     session_type: Option<Protocol>,
     login_user: Option<String>,
     metadata: ManagerMetadata,
@@ -203,46 +323,59 @@ impl Manager {
     /// Manager, such as failure to load the configuration file, invalid configuration
     /// parameters, or errors encountered while retrieving session information.
     pub fn new(metadata: ManagerMetadata) -> Result<Self, Box<dyn Error>> {
-        // Determine the session protocol
-        let session_type = {
-            let oneshot_session = Session::get_oneshot_session()?;
-            if let Some(oneshot_session) = oneshot_session {
-                Some(oneshot_session.get_protocol())
-            } else {
-                let default_session = Session::get_default_session();
-                if let Ok(default_session_inner) = default_session {
-                    Some(default_session_inner.get_protocol())
-                } else {
-                    let err = default_session.err().unwrap();
-                    let err_inner = err.downcast_ref::<SessionInstanceError>();
-                    if err_inner.is_some() && *err_inner.unwrap() == SessionInstanceError::DefaultSessionNotSet {
-                        None
-                    } else {
-                        return Err(err);
-                    }
-                }
-            }
+        // Determine the session protocol, an explicitly configured redirect protocol wins over detection
+        let session_type = if let Some(protocol) = get_redirect_protocol_override() {
+            explain::record(Stage::Protocol, || format!("Redirect protocol is forced to {protocol} by [login.autologin].protocol"));
+            Some(protocol)
+        } else if let Some(oneshot_name) = Session::get_oneshot_name() {
+            // Only the protocol is needed, so a missing desktop file does not fail the selection
+            let protocol = Session::protocol_of(oneshot_name.as_str())?;
+            explain::record(Stage::Protocol, || format!("Redirect protocol is {protocol} from oneshot session {oneshot_name}"));
+            Some(protocol)
+        } else if let Some(default_name) = Session::get_default_name()? {
+            let protocol = Session::protocol_of(default_name.as_str())?;
+            explain::record(Stage::Protocol, || format!("Redirect protocol is {protocol} from default session {default_name}"));
+            Some(protocol)
+        } else {
+            // Without a default for both, redirect to a protocol that has a default of its own
+            let protocol = [Protocol::Wayland, Protocol::X11].into_iter()
+                .find(|&protocol| Session::get_default_name_for(Some(protocol)).is_ok_and(|name| name.is_some()));
+            explain::record(Stage::Protocol, || match protocol {
+                Some(protocol) => format!("Redirect protocol is {protocol}, the only protocol with a default session"),
+                None => String::from("No default session is set, the redirect protocol is unknown"),
+            });
+            protocol
         };
 
+        let redirect_prefix = get_redirect_session_prefix();
+
         // Check if the configuration file exists
         if Path::new(&metadata.config_path).exists() {
-            // Load the configuration file
-            let config = Ini::load_from_file(&metadata.config_path)?;
             // Check for the autologin section in the configuration
-            if let Some(autologin_section) = config.section(Some(&metadata.autologin_section_name)) {
-                let autologin_session = autologin_section.get(&metadata.autologin_session_key_name);
-                let autologin_user = autologin_section.get(&metadata.autologin_user_key_name);
+            if let Some((autologin_session, mut autologin_user)) = Self::read_autologin_section(&metadata)? {
+                let autologin_session = autologin_session.as_ref();
+                // A half-written section with a user but no session does not log anyone in, treat
+                // it as disabled so the next save_config does not write the user back on its own
+                if autologin_session.is_none() {
+                    if let Some(user) = autologin_user.take() {
+                        warn!("Ignoring Auto Login user {} in {}, it has no session", user, metadata.config_path);
+                    }
+                }
                 // Initialize the Manager instance with autologin information if available
                 return Ok(Self {
                     autologin: if let Some(autologin_session) = autologin_session {
                         // Determine if autologin is enabled based on the session
-                        autologin_session == &format!("{MOLYUU_REDIRECT_SESSION_PREFIX}-wayland") ||
-                            autologin_session == &format!("{MOLYUU_REDIRECT_SESSION_PREFIX}-x11")
+                        match &metadata.autologin_command {
+                            Some(command) => autologin_session == command,
+                            None => autologin_session == &format!("{redirect_prefix}-{}", Protocol::Wayland) ||
+                                autologin_session == &format!("{redirect_prefix}-{}", Protocol::X11),
+                        }
                     } else {
                         false
                     },
+                    redirect_prefix,
                     session_type,
-                    login_user: autologin_user.map(|user| String::from(user)),
+                    login_user: autologin_user,
                     metadata: metadata.clone(),
                 });
             }
@@ -251,15 +384,63 @@ impl Manager {
         // Initialize the Manager instance with default values
         Ok(Self {
             autologin: false,
+            redirect_prefix,
             session_type,
             login_user: None,
             metadata: metadata.clone(),
         })
     }
 
-    pub fn set_auto_login(&mut self, enabled: bool, user: Option<&str>) -> Result<(), Box<dyn Error>> {
-        if let Err(_err) = Session::get_default_session() {
-            return Err(Box::from(format!("Cannot change Auto Login status, Reason: {}", _err)));
+    /// Read the session and user keys of the Auto Login section from the config of the login
+    /// manager.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the config has no Auto Login section.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config cannot be read or parsed.
+    fn read_autologin_section(metadata: &ManagerMetadata) -> Result<Option<AutoLoginKeys>, Box<dyn Error>> {
+        match metadata.config_format {
+            ManagerConfigFormat::Ini => {
+                let config = Ini::load_from_file(&metadata.config_path)?;
+                Ok(config.section(Some(&metadata.autologin_section_name)).map(|autologin_section| (
+                    autologin_section.get(&metadata.autologin_session_key_name).map(String::from),
+                    autologin_section.get(&metadata.autologin_user_key_name).map(String::from),
+                )))
+            }
+            ManagerConfigFormat::Toml => {
+                let config = fs::read_to_string(&metadata.config_path)?.parse::<Table>()?;
+                Ok(config.get(&metadata.autologin_section_name).map(|autologin_section| (
+                    autologin_section.get(&metadata.autologin_session_key_name).and_then(|session| session.as_str()).map(String::from),
+                    autologin_section.get(&metadata.autologin_user_key_name).and_then(|user| user.as_str()).map(String::from),
+                )))
+            }
+        }
+    }
+
+    /// Enable or disable Auto Login and write it to the config of the login manager.
+    ///
+    /// # Parameters
+    ///
+    /// * `enabled`: Whether Auto Login is enabled.
+    /// * `user`: The user to log in when enabling, or `None` to reuse the last Auto Login user.
+    /// * `force`: Enable Auto Login even if the user does not exist, for an account that will be
+    ///   created later.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LoginManagerInstanceError::AutoLoginUserNotFound` if the user does not exist and
+    /// `force` is not set, `LoginManagerInstanceError::AutoLoginUserNotSet` if no user is given or
+    /// remembered, or an error if the default session is not set when enabling or a config cannot
+    /// be written.
+    pub fn set_auto_login(&mut self, enabled: bool, user: Option<&str>, force: bool) -> Result<(), Box<dyn Error>> {
+        // Disabling does not need the default session, it is disabled when the default is removed
+        if enabled {
+            if let Err(_err) = Session::get_default_session_for(self.session_type) {
+                return Err(Box::from(format!("Cannot change Auto Login status, Reason: {}", _err)));
+            }
         }
 
         match (enabled, user) {
@@ -267,6 +448,15 @@ impl Manager {
                 self.login_user = Some(String::from(login_user));
                 self.autologin = enabled;
             }
+            (true, None) => {
+                // Reuse the last Auto Login user, which is kept in the global config even when
+                // Auto Login is disabled
+                let login_user = get_last_autologin_user()
+                    .or_else(|| self.login_user.clone())
+                    .ok_or(LoginManagerInstanceError::AutoLoginUserNotSet)?;
+                self.login_user = Some(login_user);
+                self.autologin = enabled;
+            }
             (false, None) => {
                 self.login_user = None;
                 self.autologin = enabled;
@@ -276,22 +466,203 @@ impl Manager {
             }
         }
 
+        // Refuse to enable Auto Login if it would redirect to a session that is not installed, or
+        // log in a user that does not exist and would silently fail at boot
+        if self.autologin {
+            self.check_redirect_session()?;
+            if !force {
+                check_autologin_user(self.login_user.as_deref().unwrap())?;
+            }
+        }
+
         self.save_config()?;
         Ok(())
     }
 
+    /// Check that the molyuu-redirect session used for the current session protocol is installed.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the redirect session desktop file exists, or if no session protocol is
+    /// determined yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LoginManagerInstanceError::RedirectSessionNotFound` if the desktop file is missing.
+    pub fn check_redirect_session(&self) -> Result<(), Box<dyn Error>> {
+        // A login manager that starts a command does not go through a redirect session
+        if self.metadata.autologin_command.is_some() {
+            return Ok(());
+        }
+        if let Some((redirect_session, protocol)) = self.get_redirect_session() {
+            if !Session::exists_in_system(redirect_session.as_str(), protocol) {
+                return Err(Box::from(LoginManagerInstanceError::RedirectSessionNotFound));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_redirect_session(&self) -> Option<(String, Protocol)> {
+        self.session_type.map(|protocol| (format!("{}-{}", self.redirect_prefix, protocol), protocol))
+    }
+
     pub fn set_as_default_manager(&self) -> Result<(), Box<dyn Error>> {
         let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table_mut().unwrap();
         toml_macros::change_or_insert!(login_info, "manager", Value::String(String::from(self.metadata.systemd_unit.as_str())));
-        GLOBAL_CONFIG.get_mut().unwrap().save_config();
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
         Ok(())
     }
 
+    /// Reconcile the enabled state of login manager units with the current manager.
+    ///
+    /// This function enables the unit of the current manager and disables the units of all other
+    /// supported managers that are enabled, so systemd starts the same manager that molyuuctl
+    /// is configured for.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if enabling or disabling unit files via systemd fails.
+    pub fn sync_unit_state(&self) -> Result<(), Box<dyn Error>> {
+        let current_unit = format!("{}.service", self.metadata.systemd_unit);
+        let mut systemctl = SYSTEMCTL.lock().unwrap();
+        // Reading the unit file states needs no root permissions, only changing them does
+        let stale_units = get_units_to_disable(current_unit.as_str(), |unit| systemctl.get_unit_file_state(unit).ok());
+        unsafe {
+            privilege::exec(|| {
+                for unit in &stale_units {
+                    systemctl.disable_unit_files(&[unit.as_str()])?;
+                }
+                systemctl.enable_unit_files(&[current_unit.as_str()], true)?;
+                systemctl.reload()
+            })
+        }
+    }
+
+    /// Check that systemd starts this login manager at boot.
+    ///
+    /// # Returns
+    ///
+    /// Returns the unit `display-manager.service` points to, or `None` if no login manager is
+    /// enabled in systemd.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LoginManagerInstanceError::DisplayManagerMismatch` if `display-manager.service`
+    /// points to the unit of another login manager, or an error if the alias cannot be read.
+    pub fn check_display_manager_alias(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let display_manager = SYSTEMCTL.lock().unwrap().get_default_display_manager()?;
+        if let Some(unit) = &display_manager {
+            if *unit != format!("{}.service", self.metadata.systemd_unit) {
+                return Err(Box::from(LoginManagerInstanceError::DisplayManagerMismatch(unit.clone())));
+            }
+        }
+        Ok(display_manager)
+    }
+
+    /// Make the init system start this login manager at boot, e.g. by re-pointing the
+    /// `display-manager.service` alias of systemd to its unit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the init system is not supported or the alias cannot be updated.
+    pub fn update_system_alias(&self) -> Result<(), Box<dyn Error>> {
+        self.update_system_alias_with(init::from_config()?.as_mut())
+    }
+
+    /// Make `init_system` start this login manager at boot, see `update_system_alias`.
+    fn update_system_alias_with(&self, init_system: &mut dyn InitSystem) -> Result<(), Box<dyn Error>> {
+        unsafe {
+            privilege::exec(|| init_system.set_boot_manager(self.metadata.systemd_unit.as_str()))
+        }
+    }
+
     pub fn login_now(&self) -> Result<(), Box<dyn Error>> {
         self.save_config()?;
-        SYSTEMCTL.lock().unwrap().reset_failed_unit(format!("{}.service", self.metadata.systemd_unit).as_str())?;
-        SYSTEMCTL.lock().unwrap().restart_unit(format!("{}.service", self.metadata.systemd_unit).as_str())?;
-        Ok(())
+        self.restart()
+    }
+
+    /// Restart the service of the login manager so that it picks up its configuration.
+    ///
+    /// The service is restarted through the init system selected by `init::from_config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the init system is not supported, or resetting or restarting the
+    /// service fails.
+    pub fn restart(&self) -> Result<(), Box<dyn Error>> {
+        self.restart_with(init::from_config()?.as_mut())
+    }
+
+    /// Restart the service of the login manager through `init_system`, see `restart`.
+    fn restart_with(&self, init_system: &mut dyn InitSystem) -> Result<(), Box<dyn Error>> {
+        init_system.reset_manager(self.metadata.systemd_unit.as_str())?;
+        init_system.restart_manager(self.metadata.systemd_unit.as_str())
+    }
+
+    /// Retrieve the Auto Login status of the login manager.
+    ///
+    /// The state is read from both the molyuuctl config and the config of the login manager, so
+    /// that drift between them (e.g. the manager config lost its session key) is detected.
+    ///
+    /// The session is the one the redirect session starts on the next login, see
+    /// `Session::get_next_start_name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the default session cannot be read from the global configuration.
+    pub fn get_auto_login_status(&self) -> Result<AutoLoginStatus, Box<dyn Error>> {
+        let config_enabled = is_autologin_enabled_in_config();
+        let manager_enabled = self.is_autologin_enabled();
+        let state = match (config_enabled, manager_enabled) {
+            (true, true) => AutoLoginState::Enabled,
+            (false, false) => AutoLoginState::Disabled,
+            _ => AutoLoginState::Drift,
+        };
+
+        Ok(AutoLoginStatus {
+            state,
+            config_enabled,
+            manager_enabled,
+            user: self.autologin_user().map(String::from).or_else(get_last_autologin_user),
+            session: Session::get_next_start_name(self.session_type)?,
+        })
+    }
+
+    /// Bring the Auto Login settings of molyuuctl and the login manager back in sync.
+    ///
+    /// # Parameters
+    ///
+    /// * `direction`: Which config is taken as the source of truth.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either config cannot be written, or if the molyuuctl config enables Auto
+    /// Login without a user when reconciling towards the login manager.
+    pub fn reconcile(&mut self, direction: ReconcileDirection) -> Result<(), Box<dyn Error>> {
+        match direction {
+            ReconcileDirection::FromManager => self.update_global_config(),
+            ReconcileDirection::ToManager => self.set_auto_login(is_autologin_enabled_in_config(), None, false),
+        }
+    }
+
+    /// Whether Auto Login is enabled in the config of the login manager, as loaded by `Manager::new`
+    /// or last set with `set_auto_login`.
+    pub fn is_autologin_enabled(&self) -> bool {
+        self.autologin
+    }
+
+    /// The user the login manager logs in automatically, as loaded by `Manager::new` or last set
+    /// with `set_auto_login`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the config of the login manager has no Auto Login user.
+    pub fn autologin_user(&self) -> Option<&str> {
+        self.login_user.as_deref()
+    }
+
+    pub fn get_metadata(&self) -> &ManagerMetadata {
+        &self.metadata
     }
 
     pub fn update_metadata(&mut self, metadata: ManagerMetadata) -> Result<(), Box<dyn Error>> {
@@ -304,7 +675,8 @@ impl Manager {
     /// This function updates or creates the configuration file with the current settings. It manages
     /// the configuration for autologin and login user, ensuring that the autologin session and user
     /// are correctly set based on the current state. After updating the configuration file, it also
-    /// updates the program global configuration accordingly.
+    /// updates the program global configuration accordingly. Like the global configuration, the
+    /// change to the configuration file is recorded in the audit log if `[audit].enabled` is set.
     ///
     /// # Returns
     ///
@@ -318,86 +690,619 @@ impl Manager {
     /// configuration, such as failure to load or create the configuration file, inability to write
     /// to the file, or errors encountered while updating global configuration.
     pub fn save_config(&self) -> Result<(), Box<dyn Error>> {
-        // Load existing configuration or create a new one
-        let mut config = if Path::new(self.metadata.config_path.as_str()).exists() {
-            Ini::load_from_file(self.metadata.config_path.as_str())?
-        } else {
-            if !Path::new(self.metadata.config_path.as_str()).parent().unwrap().exists() {
-                unsafe {
-                    privilege::exec(|| {
-                        fs::create_dir_all(Path::new(self.metadata.config_path.as_str()).parent().unwrap())?;
-                        Ok(())
-                    })?;
-                }
-            }
-            Ini::new()
-        };
+        if self.metadata.config_format == ManagerConfigFormat::Toml {
+            self.save_toml_config()?;
+            return self.update_global_config();
+        }
+
+        let mut config = self.load_manager_config()?;
 
         // Configure autologin session based on the current state
-        let mut autologin_section = &mut config.with_section(Some(self.metadata.autologin_section_name.as_str()));
+        let autologin_section = self.metadata.autologin_section_name.as_str();
         if self.autologin && self.login_user.is_some() && self.session_type.is_some() {
             // Set login user
-            autologin_section = autologin_section.set(self.metadata.autologin_user_key_name.as_str(), self.login_user.clone().unwrap());
+            config.set(autologin_section, self.metadata.autologin_user_key_name.as_str(), self.login_user.clone().unwrap().as_str());
 
-            match self.session_type {
-                Some(Protocol::X11) => {
-                    autologin_section.set(self.metadata.autologin_session_key_name.as_str(), format!("{MOLYUU_REDIRECT_SESSION_PREFIX}-x11"));
-                }
-                Some(Protocol::Wayland) => {
-                    autologin_section.set(self.metadata.autologin_session_key_name.as_str(), format!("{MOLYUU_REDIRECT_SESSION_PREFIX}-wayland"));
-                }
-                None => {}
-            };
+            if let Some((redirect_session, _)) = self.get_redirect_session() {
+                config.set(autologin_section, self.metadata.autologin_session_key_name.as_str(), redirect_session.as_str());
+                explain::record(Stage::Redirect, || format!("Writing {} = {} to [{}] in {}", self.metadata.autologin_session_key_name, redirect_session, autologin_section, self.metadata.config_path));
+            }
+
+            // Warn loudly since Auto Login silently fails at boot without the redirect session
+            if let Err(_err) = self.check_redirect_session() {
+                warn!("{}", _err);
+                warn!("Auto Login will not work until the molyuu-redirect session is installed");
+            }
 
             // Update other related configs
             if let Some(config_map) = &self.metadata.autologin_related_other_configs {
                 for (section_name, map) in config_map {
-                    let mut section = &mut config.with_section(Some(section_name.as_str()));
                     for (k, v) in map {
-                        section = section.set(k.as_str(), v.1.clone());
+                        config.set(section_name.as_str(), k.as_str(), v.1.as_str());
                     }
                 }
             }
         } else {
-            autologin_section.delete(&self.metadata.autologin_session_key_name.as_str());
+            config.delete(autologin_section, self.metadata.autologin_session_key_name.as_str());
 
             // Update other related configs
             if let Some(config_map) = &self.metadata.autologin_related_other_configs {
                 for (section_name, map) in config_map {
-                    let mut section = &mut config.with_section(Some(section_name.as_str()));
                     for (k, v) in map {
-                        section = section.set(k.as_str(), v.0.clone());
+                        config.set(section_name.as_str(), k.as_str(), v.0.as_str());
                     }
                 }
             }
         }
 
+        // Auto Login may have moved to another LightDM seat with `--seat`
+        self.remove_autologin_from_other_seats(&mut config);
+
+        // Apply the options set with `login set-option`, which do not depend on Auto Login
+        for (section_name, key, value) in get_manager_options(self.metadata.systemd_unit.as_str()) {
+            config.set(section_name.as_str(), key.as_str(), value.as_str());
+        }
+
         // Write configuration to file
+        self.write_manager_config(&config)?;
+
+        // Update program global configuration
+        self.update_global_config()?;
+
+        Ok(())
+    }
+
+    /// Remove the Auto Login keys molyuuctl wrote to the other seat sections of the config, like
+    /// `[Seat:seat0]` of LightDM, so that a login manager does not keep logging in on the seat
+    /// Auto Login was configured for before.
+    ///
+    /// Only sections that log in to a redirect session are changed, so Auto Login configured by
+    /// hand for other seats is kept. Login managers without seat sections are left alone.
+    fn remove_autologin_from_other_seats(&self, config: &mut IniDocument) {
+        let autologin_section = self.metadata.autologin_section_name.as_str();
+        let Some((seat_prefix, _)) = autologin_section.split_once(':') else {
+            return;
+        };
+        let session_key = self.metadata.autologin_session_key_name.as_str();
+        let user_key = self.metadata.autologin_user_key_name.as_str();
+        let redirect_sessions = [Protocol::Wayland, Protocol::X11].map(|protocol| format!("{}-{protocol}", self.redirect_prefix));
+
+        for section in config.sections() {
+            if section == autologin_section || section.split_once(':').is_none_or(|(prefix, _)| prefix != seat_prefix) {
+                continue;
+            }
+            if config.get(section.as_str(), session_key).is_some_and(|session| redirect_sessions.iter().any(|redirect| redirect == session)) {
+                config.delete(section.as_str(), session_key).delete(section.as_str(), user_key);
+            }
+        }
+    }
+
+    /// Write Auto Login and the options set with `login set-option` to a TOML config, like the
+    /// one of greetd.
+    ///
+    /// Auto Login is written as a table with the command of `autologin_command` and the user, e.g.
+    /// `[initial_session]` with `command = "molyuuctl session start"` and `user = "molyuu"`. The
+    /// whole table is removed when Auto Login is disabled, since greetd requires both keys. Only
+    /// the keys molyuuctl writes are changed, other tables like `[terminal]` and
+    /// `[default_session]` and the comments in the file are kept as they are.
+    fn save_toml_config(&self) -> Result<(), Box<dyn Error>> {
+        let mut config = self.load_toml_manager_config()?;
+
+        let autologin_section = self.metadata.autologin_section_name.as_str();
+        match (&self.login_user, &self.metadata.autologin_command) {
+            (Some(login_user), Some(command)) if self.autologin => {
+                let autologin_table = self.toml_section_mut(&mut config, autologin_section)?;
+                autologin_table[self.metadata.autologin_session_key_name.as_str()] = toml_edit::value(command.as_str());
+                autologin_table[self.metadata.autologin_user_key_name.as_str()] = toml_edit::value(login_user.as_str());
+                explain::record(Stage::Redirect, || format!("Writing {} = \"{}\" to [{}] in {}", self.metadata.autologin_session_key_name, command, autologin_section, self.metadata.config_path));
+            }
+            _ => {
+                config.remove(autologin_section);
+            }
+        }
+
+        // Apply the options set with `login set-option`, which do not depend on Auto Login
+        for (section_name, key, value) in get_manager_options(self.metadata.systemd_unit.as_str()) {
+            self.toml_section_mut(&mut config, section_name.as_str())?[key.as_str()] = toml_edit::value(value);
+        }
+
+        self.write_toml_manager_config(&config)
+    }
+
+    /// Retrieve a table of a TOML config of the login manager, adding it if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the section exists but is not a table.
+    fn toml_section_mut<'a>(&self, config: &'a mut DocumentMut, section_name: &str) -> Result<&'a mut toml_edit::Table, Box<dyn Error>> {
+        config.entry(section_name)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| Box::from(format!("[{section_name}] is not a table in {}", self.metadata.config_path)))
+    }
+
+    fn load_toml_manager_config(&self) -> Result<DocumentMut, Box<dyn Error>> {
+        if Path::new(self.metadata.config_path.as_str()).exists() {
+            return Ok(fs::read_to_string(self.metadata.config_path.as_str())?.parse::<DocumentMut>()?);
+        }
+        self.create_manager_config_dir()?;
+        Ok(DocumentMut::new())
+    }
+
+    fn write_toml_manager_config(&self, config: &DocumentMut) -> Result<(), Box<dyn Error>> {
+        self.write_config_file(config.to_string())
+    }
+
+    /// Load the config file of the login manager, or start a new one if it does not exist yet.
+    fn load_manager_config(&self) -> Result<IniDocument, Box<dyn Error>> {
+        if Path::new(self.metadata.config_path.as_str()).exists() {
+            return Ok(IniDocument::load_from_file(self.metadata.config_path.as_str())?);
+        }
+        self.create_manager_config_dir()?;
+        Ok(IniDocument::new())
+    }
+
+    fn create_manager_config_dir(&self) -> Result<(), Box<dyn Error>> {
+        if !Path::new(self.metadata.config_path.as_str()).parent().unwrap().exists() {
+            unsafe {
+                privilege::exec(|| {
+                    let config_dir = Path::new(self.metadata.config_path.as_str()).parent().unwrap();
+                    fs::create_dir_all(config_dir)?;
+                    // Do not rely on the umask of root for a directory read by the login manager
+                    fs::set_permissions(config_dir, fs::Permissions::from_mode(MANAGER_CONFIG_DIRECTORY_MODE))?;
+                    Ok(())
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_manager_config(&self, config: &IniDocument) -> Result<(), Box<dyn Error>> {
+        self.write_config_file(config.to_string())
+    }
+
+    /// Write the config file of the login manager and record the change in the audit log.
+    fn write_config_file(&self, contents: String) -> Result<(), Box<dyn Error>> {
+        // Only needed to record the change in the audit log
+        let before = fs::read_to_string(&self.metadata.config_path).unwrap_or_default();
+
         unsafe {
             privilege::exec(|| {
-                config.write_to_file(&self.metadata.config_path)?;
+                fs::write(&self.metadata.config_path, contents.as_str())?;
+                fs::set_permissions(&self.metadata.config_path, fs::Permissions::from_mode(MANAGER_CONFIG_FILE_MODE))?;
                 Ok(())
             })?;
         }
+        GLOBAL_CONFIG.get_mut().unwrap().record_file_change(self.metadata.config_path.as_str(), before.as_str(), contents.as_str());
+        Ok(())
+    }
 
-        // Update program global configuration
-        self.update_global_config()?;
+    /// Set a key in the config of the login manager that does not depend on Auto Login, like
+    /// `Numlock` in the `General` section of SDDM.
+    ///
+    /// The option is recorded in the `[login.options.<manager>]` section of the global
+    /// configuration, so it is written again whenever the config of the login manager is saved.
+    ///
+    /// # Parameters
+    ///
+    /// * `section`: The section of the key in the config of the login manager.
+    /// * `key`: The key to set.
+    /// * `value`: The value to set, or `None` to remove the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LoginManagerInstanceError::ReservedOption` if the key is managed by Auto Login, or
+    /// an error if either config cannot be written.
+    pub fn set_option(&self, section: &str, key: &str, value: Option<&str>) -> Result<(), Box<dyn Error>> {
+        if self.is_reserved_option(section, key) {
+            return Err(Box::from(LoginManagerInstanceError::ReservedOption(format!("[{section}] {key}"))));
+        }
 
-        Ok(())
+        let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table_mut().unwrap();
+        let options = login_info.entry("options")
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or("[login.options] is not a table")?;
+        let manager_options = options.entry(self.metadata.systemd_unit.as_str())
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or("Options of the login manager are not a table")?;
+        match value {
+            Some(value) => {
+                let section_options = manager_options.entry(section)
+                    .or_insert_with(|| Value::Table(Table::new()))
+                    .as_table_mut()
+                    .ok_or("Options of the section are not a table")?;
+                toml_macros::change_or_insert!(section_options, key, Value::String(String::from(value)));
+            }
+            None => {
+                if let Some(section_options) = manager_options.get_mut(section).and_then(|section_options| section_options.as_table_mut()) {
+                    section_options.remove(key);
+                    if section_options.is_empty() {
+                        manager_options.remove(section);
+                    }
+                }
+                // Do not leave empty tables behind once the last option is removed
+                if manager_options.is_empty() {
+                    options.remove(self.metadata.systemd_unit.as_str());
+                }
+                if options.is_empty() {
+                    login_info.remove("options");
+                }
+            }
+        }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+
+        match value {
+            Some(_) => self.save_config(),
+            None if self.metadata.config_format == ManagerConfigFormat::Toml => {
+                let mut config = self.load_toml_manager_config()?;
+                if let Some(section_table) = config.get_mut(section).and_then(|section_table| section_table.as_table_mut()) {
+                    section_table.remove(key);
+                }
+                self.write_toml_manager_config(&config)
+            }
+            None => {
+                let mut config = self.load_manager_config()?;
+                config.delete(section, key);
+                self.write_manager_config(&config)
+            }
+        }
+    }
+
+    /// Whether a key of the login manager config is written by molyuuctl for Auto Login.
+    fn is_reserved_option(&self, section: &str, key: &str) -> bool {
+        if section == self.metadata.autologin_section_name
+            && (key == self.metadata.autologin_session_key_name || key == self.metadata.autologin_user_key_name) {
+            return true;
+        }
+        self.metadata.autologin_related_other_configs.as_ref()
+            .and_then(|config_map| config_map.get(section))
+            .is_some_and(|map| map.contains_key(key))
     }
 
     pub fn update_global_config(&self) -> Result<(), Box<dyn Error>> {
         let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table_mut().unwrap();
         let autologin_info = login_info.get_mut("autologin").unwrap().as_table_mut().unwrap();
         toml_macros::change_or_insert!(autologin_info, "enable", Value::Boolean(self.autologin));
+        // A single-boot Auto Login is consumed once Auto Login is disabled, whatever disabled it
+        if !self.autologin {
+            autologin_info.remove("once");
+        }
         if self.login_user.is_some() {
             toml_macros::change_or_insert!(autologin_info, "user", Value::String(self.login_user.clone().unwrap()));
         }
-        GLOBAL_CONFIG.get_mut().unwrap().save_config();
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
         Ok(())
     }
 }
 
+/// Retrieve the options recorded with `Manager::set_option` for a login manager.
+///
+/// # Parameters
+///
+/// * `manager`: The systemd unit name of the login manager, e.g. `sddm`.
+///
+/// # Returns
+///
+/// Returns the section, key and value of every option.
+pub fn get_manager_options(manager: &str) -> Vec<(String, String, String)> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    let Some(manager_options) = login_info.get("options")
+        .and_then(|options| options.get(manager))
+        .and_then(|manager_options| manager_options.as_table()) else {
+        return Vec::new();
+    };
+    manager_options.iter()
+        .filter_map(|(section_name, section_options)| section_options.as_table().map(|section_options| (section_name, section_options)))
+        .flat_map(|(section_name, section_options)| section_options.iter()
+            .filter_map(|(key, value)| value.as_str().map(|value| (section_name.clone(), key.clone(), String::from(value)))))
+        .collect()
+}
+
+/// Check that an Auto Login user exists in the user database.
+///
+/// # Errors
+///
+/// Returns `LoginManagerInstanceError::AutoLoginUserNotFound` if there is no such user, or an
+/// error if the user database cannot be read.
+pub fn check_autologin_user(user: &str) -> Result<(), Box<dyn Error>> {
+    check_autologin_user_with(user, UserInfo::lookup)
+}
+
+/// Check that an Auto Login user exists, looking it up with `lookup`.
+fn check_autologin_user_with<T, L>(user: &str, lookup: L) -> Result<(), Box<dyn Error>>
+    where L: FnOnce(&str) -> Result<T, Box<dyn Error>>
+{
+    match lookup(user) {
+        Ok(_) => Ok(()),
+        Err(err) if matches!(err.downcast_ref::<UserError>(), Some(UserError::UserNotFound(_))) => {
+            Err(Box::from(LoginManagerInstanceError::AutoLoginUserNotFound(String::from(user))))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Retrieve the last user that Auto Login was enabled for.
+///
+/// The user is read from the `user` key of the `[login.autologin]` section in the global
+/// configuration, which is preserved when Auto Login gets disabled.
+pub fn get_last_autologin_user() -> Option<String> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    login_info.get("autologin")
+        .and_then(|autologin| autologin.get("user"))
+        .and_then(|user| user.as_str())
+        .map(String::from)
+}
+
+/// Retrieve the LightDM seat that Auto Login is configured for.
+///
+/// The seat is read from the `seat` key of the `[login.autologin]` section in the global
+/// configuration and defaults to `LIGHTDM_DEFAULT_SEAT`, which matches all seats.
+pub fn get_autologin_seat() -> String {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    login_info.get("autologin")
+        .and_then(|autologin| autologin.get("seat"))
+        .and_then(|seat| seat.as_str())
+        .unwrap_or(LIGHTDM_DEFAULT_SEAT)
+        .to_string()
+}
+
+/// Persist the LightDM seat that Auto Login is configured for.
+///
+/// The seat only affects LightDM, whose Auto Login section is `[Seat:<seat>]`. It must be set
+/// before the current manager is retrieved to take effect.
+///
+/// # Parameters
+///
+/// * `seat`: The name of the seat, e.g. `seat0`, or `*` for all seats.
+pub fn set_autologin_seat(seat: &str) -> Result<(), Box<dyn Error>> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table_mut().unwrap();
+    let autologin_info = login_info.get_mut("autologin").unwrap().as_table_mut().unwrap();
+    toml_macros::change_or_insert!(autologin_info, "seat", Value::String(String::from(seat)));
+    GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+    Ok(())
+}
+
+/// Retrieve the protocol of the redirect session that Auto Login is forced to use.
+///
+/// The protocol is read from the `protocol` key of the `[login.autologin]` section in the global
+/// configuration. If it is not set, the protocol is detected from the oneshot or default session.
+pub fn get_redirect_protocol_override() -> Option<Protocol> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    login_info.get("autologin")
+        .and_then(|autologin| autologin.get("protocol"))
+        .and_then(|protocol| protocol.as_str())
+        .and_then(|protocol| protocol.parse().ok())
+}
+
+/// Set the protocol of the redirect session that Auto Login is forced to use.
+///
+/// Like the seat, it must be set before the current manager is retrieved to take effect, and it
+/// is stored together with the rest of the Auto Login settings by `set_auto_login`.
+///
+/// # Parameters
+///
+/// * `protocol`: The protocol to force, or `None` to detect it from the oneshot or default
+///   session again.
+pub fn set_redirect_protocol_override(protocol: Option<Protocol>) {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table_mut().unwrap();
+    let autologin_info = login_info.get_mut("autologin").unwrap().as_table_mut().unwrap();
+    match protocol {
+        Some(protocol) => toml_macros::change_or_insert!(autologin_info, "protocol", Value::String(protocol.to_string())),
+        None => {
+            autologin_info.remove("protocol");
+        }
+    }
+}
+
+/// Enable Auto Login for the current login manager, e.g. for `login autologin enable`.
+///
+/// The seat and the redirect protocol override are set before the manager is retrieved, but only
+/// stored together with the rest of the Auto Login settings, so nothing is stored if Auto Login
+/// cannot be enabled.
+///
+/// # Parameters
+///
+/// * `user`: The user to log in, or `None` to reuse the last Auto Login user.
+/// * `force`: Enable Auto Login even if the user does not exist.
+/// * `once`: Whether Auto Login is disabled again after the next login.
+/// * `seat`: The LightDM seat to use, or `None` to keep the stored one.
+/// * `protocol`: The redirect protocol override to store, `Some(None)` to detect the protocol
+///   again, or `None` to keep the stored one.
+/// * `immediate`: Whether to log in right away once Auto Login is enabled, see
+///   `login_with_auto_login`.
+///
+/// # Errors
+///
+/// Returns the same errors as `get_current_manager`, `set_auto_login` and
+/// `login_with_auto_login`, or an error if the global configuration cannot be written.
+pub fn enable_auto_login(user: Option<&str>, force: bool, once: bool, seat: Option<&str>, protocol: Option<Option<Protocol>>, immediate: bool) -> Result<(), Box<dyn Error>> {
+    let manager = enable_auto_login_with(get_current_manager, user, force, once, seat, protocol)?;
+    if immediate {
+        login_with_auto_login(&manager, init::from_config()?.as_mut())?;
+    }
+    Ok(())
+}
+
+/// Enable Auto Login for the manager returned by `get_manager`, see `enable_auto_login`.
+///
+/// # Returns
+///
+/// Returns the manager Auto Login was enabled for.
+fn enable_auto_login_with<F>(get_manager: F, user: Option<&str>, force: bool, once: bool, seat: Option<&str>, protocol: Option<Option<Protocol>>) -> Result<Manager, Box<dyn Error>>
+    where F: FnOnce() -> Result<Manager, Box<dyn Error>> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table_mut().unwrap();
+    let autologin_info = login_info.get_mut("autologin").unwrap().as_table_mut().unwrap();
+    let stored_seat = autologin_info.get("seat").cloned();
+    let stored_protocol = autologin_info.get("protocol").cloned();
+    if let Some(seat) = seat {
+        toml_macros::change_or_insert!(autologin_info, "seat", Value::String(String::from(seat)));
+    }
+    if let Some(protocol) = protocol {
+        set_redirect_protocol_override(protocol);
+    }
+
+    let result = get_manager().and_then(|mut manager| manager.set_auto_login(true, user, force).map(|()| manager));
+    if result.is_err() {
+        // Auto Login is checked before anything is saved, forget the seat and protocol again
+        for (key, stored) in [("seat", stored_seat), ("protocol", stored_protocol)] {
+            match stored {
+                Some(stored) => toml_macros::change_or_insert!(autologin_info, key, stored),
+                None => {
+                    autologin_info.remove(key);
+                }
+            }
+        }
+    }
+    let manager = result?;
+    set_autologin_once(once)?;
+    Ok(manager)
+}
+
+/// Restart the login manager Auto Login was just enabled for, so that it logs in right away.
+///
+/// The config of the login manager is re-read from disk first, so the login manager is only
+/// restarted once Auto Login is saved there. If the restart fails, Auto Login stays enabled and
+/// takes effect on the next `login now` or boot.
+///
+/// # Errors
+///
+/// Returns an error if Auto Login is not enabled in the saved config, or the login manager cannot
+/// be restarted through `init_system`.
+fn login_with_auto_login(manager: &Manager, init_system: &mut dyn InitSystem) -> Result<(), Box<dyn Error>> {
+    let saved = Manager::new(manager.get_metadata().clone())?;
+    if !saved.is_autologin_enabled() || !is_autologin_enabled_in_config() {
+        return Err(Box::from(format!("Auto Login is not enabled in the config of {}, not logging in", manager.metadata.systemd_unit)));
+    }
+    manager.restart_with(init_system)
+        .map_err(|err| format!("Auto Login is enabled, but failed to restart {} to log in: {err}, run login now to retry", manager.metadata.systemd_unit))?;
+    Ok(())
+}
+
+/// Check whether Auto Login is only enabled for the next boot.
+///
+/// This is read from the `once` key of the `[login.autologin]` section in the global
+/// configuration, see `set_autologin_once`.
+pub fn is_autologin_once() -> bool {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    login_info.get("autologin")
+        .and_then(|autologin| autologin.get("once"))
+        .and_then(|once| once.as_bool())
+        .unwrap_or(false)
+}
+
+/// Persist whether Auto Login is only enabled for the next boot.
+///
+/// A single-boot Auto Login is disabled by `consume_autologin_once` when the session it logs in
+/// to starts, like a oneshot session.
+///
+/// # Parameters
+///
+/// * `once`: Whether Auto Login is disabled again after the next login.
+pub fn set_autologin_once(once: bool) -> Result<(), Box<dyn Error>> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table_mut().unwrap();
+    let autologin_info = login_info.get_mut("autologin").unwrap().as_table_mut().unwrap();
+    if once {
+        toml_macros::change_or_insert!(autologin_info, "once", Value::Boolean(true));
+    } else {
+        autologin_info.remove("once");
+    }
+    GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+    Ok(())
+}
+
+/// Disable a single-boot Auto Login after it logged in, see `set_autologin_once`.
+///
+/// # Returns
+///
+/// Returns `true` if a single-boot Auto Login was disabled.
+///
+/// # Errors
+///
+/// Returns an error if Auto Login cannot be disabled.
+pub fn consume_autologin_once() -> Result<bool, Box<dyn Error>> {
+    if !is_autologin_once() {
+        return Ok(false);
+    }
+    match get_current_manager_if_set()? {
+        Some(mut manager) => manager.set_auto_login(false, None, false)?,
+        None => set_autologin_once(false)?,
+    }
+    info!("Auto Login was enabled for one login only, disabled it");
+    Ok(true)
+}
+
+/// Write the changes a batch left pending in the global configuration before exiting, see
+/// `Configuration::flush_open_batch`, and sync the config of the current login manager with them.
+///
+/// No login manager is set on a fresh install, in which case only the global configuration is
+/// written, so that the exit path itself does not fail.
+///
+/// # Errors
+///
+/// Returns an error if either config cannot be saved, or the set login manager cannot be built.
+pub fn flush_config_on_exit() -> Result<(), Box<dyn Error>> {
+    if !GLOBAL_CONFIG.get_mut().unwrap().flush_open_batch()? {
+        return Ok(());
+    }
+    match get_current_manager() {
+        Ok(manager) => manager.save_config(),
+        Err(err) if err.downcast_ref::<LoginManagerInstanceError>() == Some(&LoginManagerInstanceError::UnknownCurrentManager) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Check whether Auto Login is enabled in the `[login.autologin]` section of the global configuration.
+pub fn is_autologin_enabled_in_config() -> bool {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    login_info.get("autologin")
+        .and_then(|autologin| autologin.get("enable"))
+        .and_then(|enable| enable.as_bool())
+        .unwrap_or(false)
+}
+
+/// Retrieve the prefix of the redirect sessions that Auto Login logs in to.
+///
+/// The prefix is read from the `redirect_prefix` key of the `[login]` section in the global
+/// configuration and defaults to `MOLYUU_REDIRECT_SESSION_PREFIX` if it is not set.
+pub fn get_redirect_session_prefix() -> String {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    login_info.get("redirect_prefix")
+        .and_then(|prefix| prefix.as_str())
+        .unwrap_or(MOLYUU_REDIRECT_SESSION_PREFIX)
+        .to_string()
+}
+
+/// Restart the service of a login manager that is not necessarily the current one, e.g. to
+/// bring up the new manager while migrating to it before switching with `set_manager`.
+///
+/// Neither the configured manager nor the config of `manager_name` is changed.
+///
+/// # Errors
+///
+/// Returns `LoginManagerInstanceError::UnsupportedManager` if `manager_name` is not supported,
+/// `LoginManagerInstanceError::ManagerNotInstalled` if its service is not installed, or an error
+/// if resetting or restarting the service fails.
+pub fn restart_manager(manager_name: &str) -> Result<(), Box<dyn Error>> {
+    restart_manager_with(manager_name, init::from_config()?.as_mut())
+}
+
+/// Restart the service of the login manager `manager_name` through `init_system`, see
+/// `restart_manager`.
+fn restart_manager_with(manager_name: &str, init_system: &mut dyn InitSystem) -> Result<(), Box<dyn Error>> {
+    let supported_manager = match SupportedManager::from_name(manager_name.to_lowercase().as_str()) {
+        Some(supported_manager) => supported_manager,
+        None => return Err(Box::from(LoginManagerInstanceError::UnsupportedManager)),
+    };
+    let unit = ManagerMetadata::build_for_supported_manager(supported_manager).systemd_unit;
+    if !init_system.unit_installed(unit.as_str())? {
+        return Err(Box::from(LoginManagerInstanceError::ManagerNotInstalled(String::from(manager_name))));
+    }
+    init_system.reset_manager(unit.as_str())?;
+    init_system.restart_manager(unit.as_str())
+}
+
 pub fn get_current_manager() -> Result<Manager, Box<dyn Error>> {
     let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
     let current_manager = login_info.get("manager");
@@ -410,49 +1315,778 @@ pub fn get_current_manager() -> Result<Manager, Box<dyn Error>> {
             "sddm" => {
                 return Ok(ManagerBuilder::new().use_manager(SupportedManager::SDDM).build()?);
             }
+            "greetd" => {
+                return Ok(ManagerBuilder::new().use_manager(SupportedManager::Greetd).build()?);
+            }
             _ => {}
         }
     }
     Err(Box::from(LoginManagerInstanceError::UnknownCurrentManager))
 }
 
-pub fn set_manager(new_manager: &str) -> Result<(), Box<dyn Error>> {
-    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table_mut().unwrap();
-    let current_manager = login_info.get("manager");
-    if current_manager.is_some() {
-        let manager = String::from(current_manager.unwrap().as_str().unwrap());
+/// Find the units of login managers that systemd has loaded, e.g. for `login list-managers`.
+///
+/// The units are matched by name against `MANAGER_UNIT_PATTERNS` rather than taken from
+/// `SupportedManager`, so that login managers molyuuctl does not support, like GDM, are found too.
+///
+/// # Returns
+///
+/// Returns the name and the active state of every unit, sorted by name.
+///
+/// # Errors
+///
+/// Returns an error if the units cannot be listed.
+pub fn find_manager_units() -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut systemctl = SYSTEMCTL.lock().unwrap();
+    let mut units = Vec::new();
+    for pattern in MANAGER_UNIT_PATTERNS {
+        units.extend(systemctl.list_units_by_pattern(pattern)?);
+    }
+    units.sort();
+    units.dedup();
+    Ok(units)
+}
 
-        if manager == new_manager.to_lowercase() {
-            return Err(Box::from(LoginManagerInstanceError::ManagerAlreadyDefault));
-        }
+/// Check whether a unit found by `find_manager_units` belongs to a supported login manager.
+pub fn is_supported_manager_unit(unit: &str) -> bool {
+    unit.strip_suffix(".service")
+        .and_then(SupportedManager::from_name)
+        .is_some()
+}
 
-        match new_manager {
-            "lightdm" => {
-                let mut manager = get_current_manager()?;
-                manager.update_metadata(ManagerMetadata::build_for_supported_manager(SupportedManager::LightDM))?;
-                manager.save_config()?;
-                manager.set_as_default_manager()?;
-            }
-            "sddm" => {
-                let mut manager = get_current_manager()?;
-                manager.update_metadata(ManagerMetadata::build_for_supported_manager(SupportedManager::SDDM))?;
-                manager.save_config()?;
-                manager.set_as_default_manager()?;
-            }
-            _ => {
-                return Err(Box::from(LoginManagerInstanceError::UnsupportedManager));
+/// Retrieve the current login manager for callers that only keep its config in sync.
+///
+/// # Returns
+///
+/// Returns `None` with a warning if no login manager is set yet, so that session management
+/// works before a manager is ever set.
+///
+/// # Errors
+///
+/// Returns an error if the set login manager is unsupported or cannot be built.
+pub fn get_current_manager_if_set() -> Result<Option<Manager>, Box<dyn Error>> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    if login_info.get("manager").is_none() {
+        warn!("Login Manager is not set, skipped updating its config");
+        return Ok(None);
+    }
+    Ok(Some(get_current_manager()?))
+}
+
+/// Retrieve the units of the other supported login managers that are enabled, which
+/// `Manager::sync_unit_state` disables so systemd starts `current_unit` at boot.
+///
+/// # Parameters
+///
+/// * `current_unit`: The unit of the login manager molyuuctl is set to, e.g. `lightdm.service`.
+/// * `unit_file_state`: Returns the unit file state of a unit, or `None` if it is not installed.
+fn get_units_to_disable(current_unit: &str, mut unit_file_state: impl FnMut(&str) -> Option<String>) -> Vec<String> {
+    SupportedManager::all().into_iter()
+        .map(|manager| format!("{}.service", ManagerMetadata::build_for_supported_manager(manager).systemd_unit))
+        .filter(|unit| unit != current_unit)
+        .filter(|unit| unit_file_state(unit.as_str()).as_deref() == Some("enabled"))
+        .collect()
+}
+
+/// Switch the login manager used by molyuuctl.
+///
+/// # Parameters
+///
+/// * `new_manager`: The name of the login manager to switch to.
+/// * `resync`: If `true`, the manager config and unit state are re-synced even if `new_manager` is
+///   already the current manager, which fixes drift between molyuuctl and the system.
+/// * `restart`: If `true`, the unit of the new manager is restarted after switching. Otherwise
+///   the change is only staged and takes effect on the next `login now` or reboot.
+/// * `system_alias`: If `true`, the init system is set to start the new manager at boot, see
+///   `Manager::update_system_alias`. Re-syncing does this anyway.
+///
+/// # Errors
+///
+/// Returns `LoginManagerInstanceError::ManagerAlreadyDefault` if `new_manager` is already the
+/// current manager and `resync` is `false`, `LoginManagerInstanceError::UnsupportedManager` if
+/// `new_manager` is not supported, or any error encountered while saving the configuration.
+pub fn set_manager(new_manager: &str, resync: bool, restart: bool, system_alias: bool) -> Result<(), Box<dyn Error>> {
+    let supported_manager = match SupportedManager::from_name(new_manager.to_lowercase().as_str()) {
+        Some(supported_manager) => supported_manager,
+        None => return Err(Box::from(LoginManagerInstanceError::UnsupportedManager)),
+    };
+    set_manager_with(ManagerMetadata::build_for_supported_manager(supported_manager), resync, restart, system_alias, init::from_config()?.as_mut())
+}
+
+/// Switch the login manager used by molyuuctl to the one described by `metadata`, restarting its
+/// service through `init_system`, see `set_manager`.
+fn set_manager_with(metadata: ManagerMetadata, resync: bool, restart: bool, system_alias: bool, init_system: &mut dyn InitSystem) -> Result<(), Box<dyn Error>> {
+    let login_info = GLOBAL_CONFIG.get_mut().unwrap().get("login").as_table().unwrap();
+    let current_manager = login_info.get("manager").and_then(|manager| manager.as_str()).map(String::from);
+    let manager = match current_manager {
+        Some(manager_name) if manager_name == metadata.systemd_unit => {
+            if !resync {
+                return Err(Box::from(LoginManagerInstanceError::ManagerAlreadyDefault));
             }
+            Manager::new(metadata)?
         }
-    } else {
-        let manager = ManagerBuilder::new().use_manager(match new_manager {
-            "lightdm" => SupportedManager::LightDM,
-            "sddm" => SupportedManager::SDDM,
-            _ => {
-                return Err(Box::from(LoginManagerInstanceError::UnsupportedManager));
-            }
-        }).build()?;
-        manager.save_config()?;
-        manager.set_as_default_manager()?;
+        Some(_) => {
+            let mut manager = get_current_manager()?;
+            manager.update_metadata(metadata)?;
+            manager
+        }
+        None => Manager::new(metadata)?,
+    };
+
+    manager.save_config()?;
+    manager.set_as_default_manager()?;
+    if resync {
+        manager.sync_unit_state()?;
+    } else if system_alias {
+        manager.update_system_alias_with(init_system)?;
+    }
+    if restart {
+        manager.restart_with(init_system)?;
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing;
+
+    fn sddm_manager(fixture: &testing::ConfigFixture) -> Manager {
+        let config_path = fixture.dir.path().join("sddm.conf");
+        ManagerBuilder::new()
+            .use_manager(SupportedManager::SDDM)
+            .use_config(config_path.to_str().unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn manager_that_is_not_installed_is_not_restarted() {
+        let mut init_system = testing::RecordingInit::default();
+        let err = restart_manager_with("sddm", &mut init_system).unwrap_err();
+        assert_eq!(err.downcast_ref::<LoginManagerInstanceError>(), Some(&LoginManagerInstanceError::ManagerNotInstalled(String::from("sddm"))));
+        assert_eq!(init_system.calls, ["installed sddm"]);
+
+        init_system.installed = true;
+        init_system.calls.clear();
+        restart_manager_with("sddm", &mut init_system).unwrap();
+        assert_eq!(init_system.calls, ["installed sddm", "reset sddm", "restart sddm"]);
+    }
+
+    #[test]
+    fn config_is_flushed_on_exit_without_a_manager() {
+        let fixture = testing::load_sessions_config();
+        GLOBAL_CONFIG.get_mut().unwrap().begin_batch();
+        Session::from_config(Some("beta")).unwrap().set_as_default().unwrap();
+
+        flush_config_on_exit().unwrap();
+        assert!(fixture.read().contains("default = \"beta\""));
+        // Nothing is pending anymore
+        flush_config_on_exit().unwrap();
+    }
+
+    #[test]
+    fn auto_login_is_not_enabled_without_the_redirect_session() {
+        let fixture = testing::load_sessions_config();
+        let mut manager = sddm_manager(&fixture);
+
+        let err = manager.set_auto_login(true, Some("root"), false).unwrap_err();
+        assert_eq!(err.downcast_ref::<LoginManagerInstanceError>(), Some(&LoginManagerInstanceError::RedirectSessionNotFound));
+        assert!(!fixture.dir.path().join("sddm.conf").exists());
+
+        fixture.install_session("wayland-sessions", "molyuu-redirect-wayland", "/bin/true");
+        manager.set_auto_login(true, Some("root"), false).unwrap();
+        assert!(sddm_manager(&fixture).autologin);
+    }
+
+    #[test]
+    fn custom_redirect_prefix_is_written_and_detected() {
+        let fixture = testing::load_config(format!("[login]\nredirect_prefix = \"custom-redirect\"\n\n{}", testing::SESSIONS_CONFIG).as_str());
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+        fixture.install_session("wayland-sessions", "custom-redirect-wayland", "/bin/true");
+        let mut manager = sddm_manager(&fixture);
+
+        manager.set_auto_login(true, Some("root"), false).unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("Autologin"), "Session"), Some("custom-redirect-wayland"));
+        assert!(sddm_manager(&fixture).autologin);
+
+        // A session with the default prefix is not one of ours under a custom prefix
+        fs::write(fixture.dir.path().join("sddm.conf"), "[Autologin]\nSession=molyuu-redirect-wayland\nUser=root\n").unwrap();
+        assert!(!sddm_manager(&fixture).autologin);
+    }
+
+    #[test]
+    fn set_manager_refuses_the_current_manager_without_resync() {
+        let fixture = testing::load_config(format!("[login]\nmanager = \"sddm\"\n\n{}", testing::SESSIONS_CONFIG).as_str());
+        let before = fixture.read();
+
+        let err = set_manager("SDDM", false, false, true).unwrap_err();
+        assert_eq!(err.downcast_ref::<LoginManagerInstanceError>(), Some(&LoginManagerInstanceError::ManagerAlreadyDefault));
+        assert_eq!(fixture.read(), before);
+    }
+
+    #[test]
+    fn auto_login_user_is_required_the_first_time() {
+        let fixture = testing::load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuu-redirect-wayland", "/bin/true");
+        let mut manager = sddm_manager(&fixture);
+
+        let err = manager.set_auto_login(true, None, false).unwrap_err();
+        assert_eq!(err.downcast_ref::<LoginManagerInstanceError>(), Some(&LoginManagerInstanceError::AutoLoginUserNotSet));
+        assert!(GLOBAL_CONFIG.get_mut().unwrap().get("login")["autologin"].get("enable").is_none());
+    }
+
+    #[test]
+    fn last_auto_login_user_is_reused() {
+        let fixture = testing::load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuu-redirect-wayland", "/bin/true");
+        let mut manager = sddm_manager(&fixture);
+
+        manager.set_auto_login(true, Some("root"), false).unwrap();
+        manager.set_auto_login(false, None, false).unwrap();
+        assert_eq!(get_last_autologin_user().as_deref(), Some("root"));
+
+        // The user is kept in the molyuuctl config even if the SDDM config has none
+        fs::write(fixture.dir.path().join("sddm.conf"), "[Autologin]\n").unwrap();
+        let mut manager = sddm_manager(&fixture);
+        assert_eq!(manager.login_user.as_deref(), None);
+        manager.set_auto_login(true, None, false).unwrap();
+        assert_eq!(manager.login_user.as_deref(), Some("root"));
+        assert_eq!(GLOBAL_CONFIG.get_mut().unwrap().get("login")["autologin"]["enable"].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn manager_config_is_written_with_explicit_modes() {
+        let fixture = testing::load_sessions_config();
+        let config_path = fixture.dir.path().join("sddm.conf.d/molyuuctl.conf");
+        let mut manager = ManagerBuilder::new()
+            .use_manager(SupportedManager::SDDM)
+            .use_config(config_path.to_str().unwrap())
+            .build()
+            .unwrap();
+
+        manager.set_auto_login(false, None, false).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(config_path.parent().unwrap()), 0o755);
+        assert_eq!(mode(&config_path), 0o644);
+
+        // A world-writable config left behind by another tool is fixed on the next save
+        fs::set_permissions(&config_path, fs::Permissions::from_mode(0o666)).unwrap();
+        manager.set_auto_login(false, None, false).unwrap();
+        assert_eq!(mode(&config_path), 0o644);
+    }
+
+    #[test]
+    fn plain_set_manager_does_not_touch_the_init_system() {
+        let fixture = testing::load_sessions_config();
+        let metadata = sddm_manager(&fixture).get_metadata().clone();
+        let mut init_system = testing::RecordingInit::default();
+
+        set_manager_with(metadata, false, false, false, &mut init_system).unwrap();
+        assert!(init_system.calls.is_empty());
+        assert!(fixture.read().contains("manager = \"sddm\""));
+        assert!(fixture.dir.path().join("sddm.conf").exists());
+    }
+
+    #[test]
+    fn resync_disables_the_unit_of_a_drifted_manager() {
+        // molyuuctl is set to lightdm, but systemd starts sddm at boot
+        let state = |unit: &str| match unit {
+            "sddm.service" => Some(String::from("enabled")),
+            "lightdm.service" => Some(String::from("disabled")),
+            _ => None,
+        };
+        assert_eq!(get_units_to_disable("lightdm.service", state), ["sddm.service"]);
+        assert!(get_units_to_disable("sddm.service", state).is_empty());
+        assert!(get_units_to_disable("lightdm.service", |_| None).is_empty());
+    }
+
+    #[test]
+    fn set_manager_restarts_only_on_request() {
+        let fixture = testing::load_sessions_config();
+        let metadata = sddm_manager(&fixture).get_metadata().clone();
+        let mut init_system = testing::RecordingInit::default();
+
+        set_manager_with(metadata, false, true, false, &mut init_system).unwrap();
+        assert_eq!(init_system.calls, ["reset sddm", "restart sddm"]);
+    }
+
+    fn load_autologin_config(enable: bool) -> testing::ConfigFixture {
+        let fixture = testing::load_config(testing::SESSIONS_CONFIG
+            .replace("[login.autologin]\n", format!("[login.autologin]\nenable = {enable}\nuser = \"root\"\n").as_str()).as_str());
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+        fixture.install_session("wayland-sessions", "molyuu-redirect-wayland", "/bin/true");
+        fixture
+    }
+
+    #[test]
+    fn auto_login_status_is_enabled_in_both_configs() {
+        let fixture = load_autologin_config(false);
+        sddm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+
+        let status = sddm_manager(&fixture).get_auto_login_status().unwrap();
+        assert_eq!(status.state, AutoLoginState::Enabled);
+        assert_eq!(status.state.exit_code(), 0);
+        assert_eq!(status.user.as_deref(), Some("root"));
+        assert_eq!(status.session.as_deref(), Some("alpha"));
+    }
+
+    #[test]
+    fn auto_login_status_reports_the_session_started_next() {
+        let fixture = load_autologin_config(false);
+        sddm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        session_info.insert(String::from("default_wayland"), Value::String(String::from("beta")));
+        assert_eq!(sddm_manager(&fixture).get_auto_login_status().unwrap().session.as_deref(), Some("beta"));
+
+        // A pending one-shot session is started before the default session
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        session_info.insert(String::from("oneshot_session"), Value::String(String::from("alpha")));
+        session_info.insert(String::from("oneshot_started"), Value::Boolean(false));
+        assert_eq!(sddm_manager(&fixture).get_auto_login_status().unwrap().session.as_deref(), Some("alpha"));
+    }
+
+    #[test]
+    fn auto_login_status_is_disabled_in_both_configs() {
+        let fixture = load_autologin_config(false);
+
+        let status = sddm_manager(&fixture).get_auto_login_status().unwrap();
+        assert_eq!(status.state, AutoLoginState::Disabled);
+        assert_eq!(status.state.exit_code(), 10);
+        // The last user is reported even though Auto Login is disabled
+        assert_eq!(status.user.as_deref(), Some("root"));
+    }
+
+    #[test]
+    fn auto_login_status_detects_drift_in_the_molyuuctl_config() {
+        // The molyuuctl config enables Auto Login, but the SDDM config lost its session key
+        let fixture = load_autologin_config(true);
+        fixture.dir.write("sddm.conf", "[Autologin]\nUser=root\n");
+        let status = sddm_manager(&fixture).get_auto_login_status().unwrap();
+        assert_eq!(status.state, AutoLoginState::Drift);
+        assert_eq!(status.state.exit_code(), 11);
+        assert!(status.config_enabled && !status.manager_enabled);
+    }
+
+    #[test]
+    fn auto_login_status_detects_drift_in_the_manager_config() {
+        // The SDDM config enables Auto Login behind the back of molyuuctl
+        let fixture = load_autologin_config(false);
+        fixture.dir.write("sddm.conf", "[Autologin]\nSession=molyuu-redirect-wayland\nUser=root\n");
+        let status = sddm_manager(&fixture).get_auto_login_status().unwrap();
+        assert_eq!(status.state, AutoLoginState::Drift);
+        assert!(!status.config_enabled && status.manager_enabled);
+    }
+
+    #[test]
+    fn reconcile_from_manager_updates_the_molyuuctl_config() {
+        let fixture = load_autologin_config(false);
+        fixture.dir.write("sddm.conf", "[Autologin]\nSession=molyuu-redirect-wayland\nUser=root\n");
+
+        sddm_manager(&fixture).reconcile(ReconcileDirection::FromManager).unwrap();
+        assert!(is_autologin_enabled_in_config());
+        assert_eq!(sddm_manager(&fixture).get_auto_login_status().unwrap().state, AutoLoginState::Enabled);
+    }
+
+    #[test]
+    fn reconcile_to_manager_rewrites_the_manager_config() {
+        let fixture = load_autologin_config(true);
+        fixture.dir.write("sddm.conf", "# Kept\n[Autologin]\nUser=root\n");
+
+        sddm_manager(&fixture).reconcile(ReconcileDirection::ToManager).unwrap();
+        let config = fs::read_to_string(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert!(config.starts_with("# Kept\n"));
+        assert!(config.contains("Session=molyuu-redirect-wayland\n"));
+        assert_eq!(sddm_manager(&fixture).get_auto_login_status().unwrap().state, AutoLoginState::Enabled);
+    }
+
+    fn lightdm_manager(fixture: &testing::ConfigFixture) -> Manager {
+        let config_path = fixture.dir.path().join("lightdm.conf");
+        ManagerBuilder::new()
+            .use_manager(SupportedManager::LightDM)
+            .use_config(config_path.to_str().unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn lightdm_auto_login_is_written_for_the_chosen_seat() {
+        let fixture = load_autologin_config(false);
+        set_autologin_seat("seat0").unwrap();
+        assert!(fixture.read().contains("seat = \"seat0\""));
+
+        lightdm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("lightdm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("Seat:seat0"), "autologin-user"), Some("root"));
+        assert_eq!(config.get_from(Some("Seat:seat0"), "autologin-session"), Some("molyuu-redirect-wayland"));
+        assert!(config.section(Some("Seat:*")).is_none());
+    }
+
+    #[test]
+    fn lightdm_auto_login_is_removed_from_the_previous_seat() {
+        let fixture = load_autologin_config(false);
+        fixture.dir.write("lightdm.conf", "[Seat:seat1]\nautologin-user=guest\nautologin-session=xfce\n");
+        lightdm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+
+        set_autologin_seat("seat0").unwrap();
+        lightdm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("lightdm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("Seat:seat0"), "autologin-user"), Some("root"));
+        assert_eq!(config.get_from(Some("Seat:*"), "autologin-user"), None);
+        assert_eq!(config.get_from(Some("Seat:*"), "autologin-session"), None);
+        // Auto Login set up by hand for another seat is kept
+        assert_eq!(config.get_from(Some("Seat:seat1"), "autologin-user"), Some("guest"));
+        assert_eq!(config.get_from(Some("Seat:seat1"), "autologin-session"), Some("xfce"));
+    }
+
+    #[test]
+    fn lightdm_auto_login_defaults_to_all_seats() {
+        let fixture = load_autologin_config(false);
+
+        lightdm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("lightdm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("Seat:*"), "autologin-user"), Some("root"));
+    }
+
+    #[test]
+    fn seat_is_not_stored_when_auto_login_cannot_be_enabled() {
+        let fixture = testing::load_config(testing::SESSIONS_CONFIG);
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+
+        // The redirect session is not installed
+        let result = enable_auto_login_with(|| Ok(lightdm_manager(&fixture)), Some("root"), false, false, Some("seat1"), None);
+        assert!(result.is_err());
+        assert_eq!(get_autologin_seat(), LIGHTDM_DEFAULT_SEAT);
+        assert!(!fixture.read().contains("seat1"));
+
+        fixture.install_session("wayland-sessions", "molyuu-redirect-wayland", "/bin/true");
+        enable_auto_login_with(|| Ok(lightdm_manager(&fixture)), Some("root"), false, false, Some("seat1"), None).unwrap();
+        assert_eq!(get_autologin_seat(), "seat1");
+        assert!(fixture.read().contains("seat = \"seat1\""));
+        let config = Ini::load_from_file(fixture.dir.path().join("lightdm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("Seat:seat1"), "autologin-user"), Some("root"));
+    }
+
+    /// An init system that records whether Auto Login was saved when the login manager restarted.
+    struct SaveCheckingInit {
+        config_path: PathBuf,
+        fail_restart: bool,
+        saved_at_restart: Option<bool>,
+    }
+
+    impl InitSystem for SaveCheckingInit {
+        fn restart_manager(&mut self, _service: &str) -> Result<(), Box<dyn Error>> {
+            let config = Ini::load_from_file(&self.config_path).unwrap();
+            self.saved_at_restart = Some(config.get_from(Some("Autologin"), "User") == Some("root") && is_autologin_enabled_in_config());
+            if self.fail_restart {
+                return Err(Box::from("restart failed"));
+            }
+            Ok(())
+        }
+
+        fn reset_manager(&mut self, _service: &str) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn unit_active(&mut self, _service: &str) -> Result<bool, Box<dyn Error>> {
+            Ok(true)
+        }
+
+        fn unit_installed(&mut self, _service: &str) -> Result<bool, Box<dyn Error>> {
+            Ok(true)
+        }
+
+        fn set_boot_manager(&mut self, _service: &str) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn immediate_login_restarts_after_auto_login_is_saved() {
+        let fixture = load_autologin_config(false);
+        let mut init_system = SaveCheckingInit { config_path: fixture.dir.path().join("sddm.conf"), fail_restart: false, saved_at_restart: None };
+
+        let manager = enable_auto_login_with(|| Ok(sddm_manager(&fixture)), Some("root"), false, false, None, None).unwrap();
+        login_with_auto_login(&manager, &mut init_system).unwrap();
+        assert_eq!(init_system.saved_at_restart, Some(true));
+    }
+
+    #[test]
+    fn failed_immediate_login_keeps_auto_login_enabled() {
+        let fixture = load_autologin_config(false);
+        let mut init_system = SaveCheckingInit { config_path: fixture.dir.path().join("sddm.conf"), fail_restart: true, saved_at_restart: None };
+
+        let manager = enable_auto_login_with(|| Ok(sddm_manager(&fixture)), Some("root"), false, false, None, None).unwrap();
+        let err = login_with_auto_login(&manager, &mut init_system).unwrap_err();
+        assert!(err.to_string().starts_with("Auto Login is enabled, but failed to restart sddm"));
+        assert!(is_autologin_enabled_in_config());
+        assert!(sddm_manager(&fixture).is_autologin_enabled());
+    }
+
+    #[test]
+    fn redirect_protocol_override_wins_over_detection() {
+        let fixture = load_autologin_config(false);
+        fixture.install_session("xsessions", "molyuu-redirect-x11", "/bin/true");
+        assert_eq!(sddm_manager(&fixture).get_redirect_session(), Some((String::from("molyuu-redirect-wayland"), Protocol::Wayland)));
+
+        set_redirect_protocol_override(Some(Protocol::X11));
+        assert_eq!(get_redirect_protocol_override(), Some(Protocol::X11));
+        let mut manager = sddm_manager(&fixture);
+        manager.set_auto_login(true, Some("root"), false).unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("Autologin"), "Session"), Some("molyuu-redirect-x11"));
+
+        set_redirect_protocol_override(None);
+        assert_eq!(sddm_manager(&fixture).get_redirect_session(), Some((String::from("molyuu-redirect-wayland"), Protocol::Wayland)));
+    }
+
+    #[test]
+    fn protocol_override_is_not_stored_when_auto_login_cannot_be_enabled() {
+        let fixture = load_autologin_config(false);
+
+        // There is no X11 redirect session to log in to
+        let result = enable_auto_login_with(|| Ok(sddm_manager(&fixture)), Some("root"), false, false, None, Some(Some(Protocol::X11)));
+        assert!(result.is_err());
+        assert_eq!(get_redirect_protocol_override(), None);
+        assert!(!fixture.read().contains("x11"));
+        assert!(!is_autologin_enabled_in_config());
+
+        enable_auto_login_with(|| Ok(sddm_manager(&fixture)), Some("root"), false, false, None, Some(Some(Protocol::Wayland))).unwrap();
+        assert_eq!(get_redirect_protocol_override(), Some(Protocol::Wayland));
+        assert!(fixture.read().contains("protocol = \"wayland\""));
+    }
+
+    #[test]
+    fn auto_login_is_disabled_without_a_default_session() {
+        let fixture = testing::load_config(testing::SESSIONS_CONFIG.replace("default = \"alpha\"\n", "").as_str());
+        let mut manager = sddm_manager(&fixture);
+
+        manager.set_auto_login(false, None, false).unwrap();
+        assert!(!manager.autologin);
+        assert!(fixture.dir.path().join("sddm.conf").exists());
+    }
+
+    #[test]
+    fn auto_login_is_not_enabled_without_a_default_session() {
+        let fixture = testing::load_config(testing::SESSIONS_CONFIG.replace("default = \"alpha\"\n", "").as_str());
+        fixture.install_session("wayland-sessions", "molyuu-redirect-wayland", "/bin/true");
+
+        assert!(sddm_manager(&fixture).set_auto_login(true, Some("root"), false).is_err());
+    }
+
+    #[test]
+    fn only_units_of_supported_managers_are_supported() {
+        assert!(is_supported_manager_unit("sddm.service"));
+        assert!(is_supported_manager_unit("lightdm.service"));
+        assert!(is_supported_manager_unit("greetd.service"));
+        assert!(!is_supported_manager_unit("gdm.service"));
+        assert!(!is_supported_manager_unit("sddm"));
+    }
+
+    #[test]
+    fn manager_option_survives_auto_login_toggles() {
+        let fixture = load_autologin_config(false);
+        sddm_manager(&fixture).set_option("General", "Numlock", Some("on")).unwrap();
+        assert!(fixture.read().contains("[login.options.sddm.General]"));
+
+        sddm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+        sddm_manager(&fixture).set_auto_login(false, None, false).unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("General"), "Numlock"), Some("on"));
+
+        sddm_manager(&fixture).set_option("General", "Numlock", None).unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert!(config.get_from(Some("General"), "Numlock").is_none());
+        assert!(!fixture.read().contains("[login.options"));
+    }
+
+    #[test]
+    fn auto_login_keys_cannot_be_set_as_options() {
+        let fixture = load_autologin_config(false);
+        let err = sddm_manager(&fixture).set_option("Autologin", "User", Some("root")).unwrap_err();
+        assert!(matches!(err.downcast_ref::<LoginManagerInstanceError>(), Some(LoginManagerInstanceError::ReservedOption(_))));
+        assert!(get_manager_options("sddm").is_empty());
+    }
+
+    #[test]
+    fn unrelated_keys_survive_repeated_auto_login_toggles() {
+        let fixture = load_autologin_config(false);
+        let contents = "# Managed in part by molyuuctl\n[Theme]\nCurrent=breeze\n\n[ Autologin ]\nRelogin=true\n";
+        fixture.dir.write("sddm.conf", contents);
+
+        for _ in 0..2 {
+            sddm_manager(&fixture).set_auto_login(false, None, false).unwrap();
+            sddm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+        }
+        let written = fs::read_to_string(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert!(written.starts_with(contents));
+        assert_eq!(written.matches("Autologin").count(), 1);
+        let config = Ini::load_from_file(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("Autologin"), "User"), Some("root"));
+    }
+
+    #[test]
+    fn accessors_match_the_manager_config() {
+        let fixture = load_autologin_config(false);
+        fixture.dir.write("sddm.conf", "[Autologin]\nUser=alice\nSession=molyuu-redirect-wayland\n");
+        let manager = sddm_manager(&fixture);
+        assert!(manager.is_autologin_enabled());
+        assert_eq!(manager.autologin_user(), Some("alice"));
+
+        fixture.dir.write("sddm.conf", "[Autologin]\nUser=alice\nSession=plasma\n");
+        assert!(!sddm_manager(&fixture).is_autologin_enabled());
+
+        fixture.dir.write("sddm.conf", "[General]\nNumlock=on\n");
+        let manager = sddm_manager(&fixture);
+        assert!(!manager.is_autologin_enabled());
+        assert_eq!(manager.autologin_user(), None);
+    }
+
+    #[test]
+    fn restart_resets_the_service_before_restarting_it() {
+        let fixture = testing::load_sessions_config();
+        let manager = sddm_manager(&fixture);
+        let mut init_system = testing::RecordingInit::default();
+
+        manager.restart_with(&mut init_system).unwrap();
+        assert_eq!(init_system.calls, ["reset sddm", "restart sddm"]);
+    }
+
+    #[test]
+    fn missing_autologin_user_is_reported() {
+        let err = check_autologin_user_with("alice", |user| -> Result<(), Box<dyn Error>> {
+            Err(Box::from(UserError::UserNotFound(String::from(user))))
+        }).unwrap_err();
+        assert_eq!(err.downcast_ref::<LoginManagerInstanceError>(), Some(&LoginManagerInstanceError::AutoLoginUserNotFound(String::from("alice"))));
+
+        // Other lookup failures are passed on as they are
+        let err = check_autologin_user_with("alice", |_user| -> Result<(), Box<dyn Error>> {
+            Err(Box::from(UserError::LookupFailed(String::from("alice"))))
+        }).unwrap_err();
+        assert_eq!(err.downcast_ref::<UserError>(), Some(&UserError::LookupFailed(String::from("alice"))));
+
+        check_autologin_user_with("alice", |_user| -> Result<(), Box<dyn Error>> { Ok(()) }).unwrap();
+    }
+
+    #[test]
+    fn auto_login_for_a_missing_user_needs_force() {
+        let fixture = load_autologin_config(false);
+        let err = sddm_manager(&fixture).set_auto_login(true, Some("molyuuctl-test-nobody"), false).unwrap_err();
+        assert_eq!(err.downcast_ref::<LoginManagerInstanceError>(), Some(&LoginManagerInstanceError::AutoLoginUserNotFound(String::from("molyuuctl-test-nobody"))));
+        assert!(!fixture.dir.path().join("sddm.conf").exists());
+
+        sddm_manager(&fixture).set_auto_login(true, Some("molyuuctl-test-nobody"), true).unwrap();
+        assert_eq!(sddm_manager(&fixture).autologin_user(), Some("molyuuctl-test-nobody"));
+    }
+
+    #[test]
+    fn autologin_section_without_a_session_is_disabled() {
+        let fixture = load_autologin_config(false);
+        fixture.dir.write("sddm.conf", "[Autologin]\nUser=alice\nRelogin=false\n");
+        let manager = sddm_manager(&fixture);
+        assert!(!manager.is_autologin_enabled());
+        assert_eq!(manager.autologin_user(), None);
+
+        // The user is not written back on its own by the next save
+        manager.save_config().unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert!(config.get_from(Some("Autologin"), "Session").is_none());
+        assert_eq!(config.get_from(Some("Autologin"), "Relogin"), Some("false"));
+        assert_eq!(sddm_manager(&fixture).get_auto_login_status().unwrap().state, AutoLoginState::Disabled);
+    }
+
+    #[test]
+    fn redirect_protocol_comes_from_the_only_protocol_default() {
+        let contents = testing::SESSIONS_CONFIG.replace("default = \"alpha\"\n", "default_x11 = \"gamma\"\n")
+            + "\n[session.gamma]\nsession = \"molyuuctl-test-gamma\"\nprotocol = \"x11\"\n";
+        let fixture = testing::load_config(contents.as_str());
+        fixture.install_session("xsessions", "molyuuctl-test-gamma", "/bin/true");
+        fixture.install_session("xsessions", "molyuu-redirect-x11", "/bin/true");
+
+        sddm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+        let config = Ini::load_from_file(fixture.dir.path().join("sddm.conf")).unwrap();
+        assert_eq!(config.get_from(Some("Autologin"), "Session"), Some("molyuu-redirect-x11"));
+    }
+
+    #[test]
+    fn specified_manager_is_restarted_instead_of_the_set_one() {
+        let fixture = testing::load_config(testing::SESSIONS_CONFIG.replace("[login.autologin]\n", "[login]\nmanager = \"sddm\"\n\n[login.autologin]\n").as_str());
+        let contents = fixture.read();
+        let mut init_system = testing::RecordingInit { installed: true, ..Default::default() };
+
+        restart_manager_with("LightDM", &mut init_system).unwrap();
+        assert_eq!(init_system.calls, ["installed lightdm", "reset lightdm", "restart lightdm"]);
+        assert_eq!(fixture.read(), contents);
+
+        let err = restart_manager_with("gdm", &mut init_system).unwrap_err();
+        assert_eq!(err.downcast_ref::<LoginManagerInstanceError>(), Some(&LoginManagerInstanceError::UnsupportedManager));
+    }
+
+    #[test]
+    fn greetd_config_keeps_comments_and_other_tables() {
+        let fixture = testing::load_sessions_config();
+        let config_path = fixture.dir.write("greetd.toml", "# Managed by hand\n[terminal]\n# The VT to run on\nvt = 1\n\n[default_session]\ncommand = \"agreety --cmd /bin/sh\"\nuser = \"greeter\"\n");
+        let mut manager = ManagerBuilder::new()
+            .use_manager(SupportedManager::Greetd)
+            .use_config(config_path.to_str().unwrap())
+            .build()
+            .unwrap();
+
+        manager.set_auto_login(true, Some("root"), false).unwrap();
+        let enabled = fs::read_to_string(&config_path).unwrap();
+        assert!(enabled.starts_with("# Managed by hand\n[terminal]\n# The VT to run on\nvt = 1\n"));
+        let initial_session = enabled.parse::<Table>().unwrap()["initial_session"].clone();
+        assert_eq!(initial_session["user"].as_str(), Some("root"));
+        assert!(initial_session["command"].is_str());
+
+        manager.set_auto_login(false, None, false).unwrap();
+        let disabled = fs::read_to_string(&config_path).unwrap();
+        assert!(!disabled.contains("initial_session"));
+        assert!(disabled.contains("# The VT to run on\n"));
+        assert!(disabled.contains("user = \"greeter\""));
+    }
+
+    #[test]
+    fn autologin_once_is_consumed_by_one_login_cycle() {
+        let _fixture = load_autologin_config(true);
+        set_autologin_once(true).unwrap();
+        assert!(is_autologin_once());
+
+        assert!(consume_autologin_once().unwrap());
+        assert!(!is_autologin_once());
+        // The next login is a normal one
+        assert!(!consume_autologin_once().unwrap());
+    }
+
+    #[test]
+    fn disabling_autologin_consumes_autologin_once() {
+        let fixture = load_autologin_config(false);
+        sddm_manager(&fixture).set_auto_login(true, Some("root"), false).unwrap();
+        set_autologin_once(true).unwrap();
+
+        sddm_manager(&fixture).set_auto_login(false, None, false).unwrap();
+        assert!(!is_autologin_once());
+        assert!(!is_autologin_enabled_in_config());
+        assert!(!fixture.read().contains("once"));
+    }
+
+    #[test]
+    fn set_manager_points_the_system_alias_to_the_new_manager() {
+        let fixture = testing::load_sessions_config();
+        let mut init_system = testing::RecordingInit::default();
+        set_manager_with(lightdm_manager(&fixture).get_metadata().clone(), false, false, true, &mut init_system).unwrap();
+        assert_eq!(init_system.calls, ["boot lightdm"]);
+        assert!(fixture.read().contains("manager = \"lightdm\""));
+
+        drop(fixture);
+
+        // --no-system-alias leaves the alias alone
+        let fixture = testing::load_sessions_config();
+        let mut init_system = testing::RecordingInit::default();
+        set_manager_with(sddm_manager(&fixture).get_metadata().clone(), false, false, false, &mut init_system).unwrap();
+        assert!(!init_system.calls.iter().any(|call| call.starts_with("boot")));
+    }
+}
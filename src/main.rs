@@ -1,27 +1,54 @@
 extern crate core;
 
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::string::String;
+use std::time::Duration;
 
-use clap::{arg, Command};
-use log::error;
+use clap::{arg, ArgMatches, Command};
+use log::{error, info};
 
-use crate::common::macros::attempt;
-use crate::login::manager::get_current_manager;
-use crate::session::Protocol;
-use crate::session::Session;
-
-mod config;
-mod session;
-mod login;
-mod errors;
-mod system;
-mod common;
+use molyuuctl::{apply, attempt, common, config, doctor, explain, login};
+use molyuuctl::common::duration::parse_duration;
+use molyuuctl::common::logger::ColorChoice;
+use molyuuctl::config::GLOBAL_CONFIG;
+use molyuuctl::errors::MolyuuError;
+use molyuuctl::errors::session::SessionInstanceError;
+use molyuuctl::login::import::ImportSource;
+use molyuuctl::login::manager::{get_current_manager, AutoLoginState, ReconcileDirection};
+use molyuuctl::session::export::{self, ExportFormat};
+use molyuuctl::session::keys::{self, KeyType};
+use molyuuctl::session::list::{format_sessions, OutputFormat, SessionSummary};
+use molyuuctl::session::Protocol;
+use molyuuctl::session::Session;
+use molyuuctl::session::StartOptions;
+use molyuuctl::session::watch::WatchPolicy;
+use molyuuctl::system::{lock, SYSTEMCTL};
+#[cfg(feature = "tui")]
+use molyuuctl::tui;
 
 fn cli() -> Command {
-    Command::new("MolyuuOS System Controller")
+    let cli = Command::new("MolyuuOS System Controller")
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(arg!(--"force-write" "Overwrite the config file even if it changed on disk since it was loaded")
+            .id("force_write")
+            .global(true))
+        .arg(arg!(--"output-file" <FILE> "Write the changed configuration to this file instead of the loaded one, e.g. to build a config for an image")
+            .id("output_file")
+            .global(true))
+        .arg(arg!(--explain "Explain how the session to start and the Auto Login redirect protocol are selected")
+            .global(true))
+        .arg(arg!(--color <WHEN> "Color the log levels (auto respects NO_COLOR and whether stdout supports color)")
+            .value_parser(["always", "auto", "never"])
+            .default_value("auto")
+            .global(true))
+        .arg(arg!(--"wait-for-bus" <TIMEOUT> "Wait up to this long for the system bus before talking to systemd, e.g. when run early at boot")
+            .value_parser(|value: &str| parse_duration(value).map_err(|err| err.to_string()))
+            .global(true))
         .subcommand(Command::new("session")
             .about("Sessions settings")
             .subcommand_required(true)
@@ -37,12 +64,57 @@ fn cli() -> Command {
                     .value_parser(["auto", "wayland", "x11"])
                     .default_value("auto")
                     .default_missing_value("auto"))
-                .arg(arg!(-l --logout <LOGOUT_COMMAND> "Session logout command")))
+                .arg(arg!(--wayland "Shortcut for --protocol wayland")
+                    .conflicts_with_all(["protocol", "x11"]))
+                .arg(arg!(--x11 "Shortcut for --protocol x11")
+                    .conflicts_with("protocol"))
+                .arg(arg!(-l --logout <LOGOUT_COMMAND> "Session logout command"))
+                .arg(arg!(--default "Set the session as default after registering"))
+                .arg(arg!(--oneshot "Start the session once on next start after registering"))
+                .arg(arg!(--"validate-command" "Refuse to register the session if the program it launches is not installed"))
+                .arg(arg!(--update "Update the session if it is already registered instead of failing (Keeps the logout command if -l is not set)")))
             .subcommand(Command::new("set-default")
                 .about("Set default session")
                 .arg_required_else_help(true)
                 .arg(arg!([register_name] "Register name")
-                    .required(true)))
+                    .required(true))
+                .arg(arg!(-p --protocol <PROTOCOL_TYPE> "Only set the default session of this protocol, used before the default session when starting it")
+                    .value_parser(["x11", "wayland"])))
+            .subcommand(Command::new("swap-default")
+                .about("Set default session, remembering the replaced one to revert to")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required_unless_present("revert"))
+                .arg(arg!(--revert "Restore the default session replaced by the last swap")
+                    .conflicts_with("register_name")))
+            .subcommand(Command::new("list")
+                .about("List registered sessions")
+                .arg(arg!(-o --output <FORMAT> "Output format (table falls back to plain if stdout is not a terminal)")
+                    .value_parser(["table", "plain", "json"])
+                    .default_value("table")))
+            .subcommand(Command::new("names")
+                .about("Print the names of registered sessions, sorted, for use in menus")
+                .arg(arg!(--protocol "Annotate each name with its protocol"))
+                .arg(arg!(--json "Print a JSON array instead of one name per line")))
+            .subcommand(Command::new("default")
+                .about("Print default session"))
+            .subcommand(Command::new("stats")
+                .about("Summarize registered sessions, the default and oneshot session and Auto Login")
+                .arg(arg!(--json "Print a JSON object instead of one value per line")))
+            .subcommand(Command::new("export")
+                .about("Print the settings of registered sessions, to be imported with session import")
+                .arg(arg!([register_names] ... "Sessions to export (Default: all registered sessions)"))
+                .arg(arg!(-f --format <FORMAT> "Output format")
+                    .value_parser(["toml", "json"])
+                    .default_value("toml")))
+            .subcommand(Command::new("import")
+                .about("Register sessions from a file written by session export")
+                .arg_required_else_help(true)
+                .arg(arg!([file] "File to import")
+                    .required(true))
+                .arg(arg!(-f --format <FORMAT> "Input format (Default: detected from the file extension, toml otherwise)")
+                    .value_parser(["toml", "json"]))
+                .arg(arg!(--replace "Replace sessions that are already registered")))
             .subcommand(Command::new("set-logout-command")
                 .about("Set logout command for specific session")
                 .arg_required_else_help(true)
@@ -50,6 +122,70 @@ fn cli() -> Command {
                     .required(true))
                 .arg(arg!([logout_command] "Logout commnad")
                     .required(true)))
+            .subcommand(Command::new("set-requires")
+                .about("Set systemd units that must be active before starting a session")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!([units] ... "Units to start in order (Remove all prerequisites if empty)")))
+            .subcommand(Command::new("set-action")
+                .about("Launch the Exec of a desktop action instead of the main Exec of a session")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!([action] "Desktop action identifier (Launch the main Exec if empty)")))
+            .subcommand(Command::new("set-fallback")
+                .about("Start another session when a session exits with an error")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!([fallback] "Register name of the fallback session (Remove the fallback if empty)")))
+            .subcommand(Command::new("set-vt")
+                .about("Run an X11 session on a specific virtual terminal")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!([vt] "Number of the virtual terminal (Remove the VT if empty)")))
+            .subcommand(Command::new("set-display-server-args")
+                .about("Append arguments to the command of a Wayland session, e.g. to enable Xwayland")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!([args] ... "Arguments in order, after -- if they start with - (Remove all arguments if empty)")))
+            .subcommand(Command::new("set-limits")
+                .about("Limit the resources of a session (Requires [session].launcher = \"systemd-run\")")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!(--"memory-max" <SIZE> "MemoryMax of the session scope, e.g. 4G, 50% or infinity (Remove the limit if empty)"))
+                .arg(arg!(--"cpu-quota" <PERCENTAGE> "CPUQuota of the session scope, e.g. 200% for two CPUs (Remove the limit if empty)")))
+            .subcommand(Command::new("set-key")
+                .about("Set any key of a session, including keys without a dedicated command")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!([key] "Key")
+                    .required(true))
+                .arg(arg!([value] "Value")
+                    .required(true))
+                .arg(arg!(-t --type <TYPE> "Type of the value (Default: bool for true/false, int for numbers, string otherwise)")
+                    .value_parser(["string", "bool", "int"]))
+                .arg(arg!(--force "Allow changing the session and protocol keys")))
+            .subcommand(Command::new("get-key")
+                .about("Print any key of a session")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!([key] "Key")
+                    .required(true)))
+            .subcommand(Command::new("unset-key")
+                .about("Remove any key of a session")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Register name")
+                    .required(true))
+                .arg(arg!([key] "Key")
+                    .required(true))
+                .arg(arg!(--force "Allow removing the session and protocol keys")))
             .subcommand(Command::new("rename")
                 .about("Rename a session")
                 .arg_required_else_help(true)
@@ -62,62 +198,260 @@ fn cli() -> Command {
                 .arg_required_else_help(true)
                 .arg(arg!([register_name] "Session register name")
                     .required(true)))
+            .subcommand(Command::new("prune")
+                .about("Remove registered sessions whose desktop file is no longer installed")
+                .arg(arg!(-n --"dry-run" "Only list the sessions that would be removed")))
             .subcommand(Command::new("start")
                 .about("Start a session")
                 .arg(arg!([register_name] "Session register name")
                     .default_value("default")
-                    .default_missing_value("default")))
+                    .default_missing_value("default"))
+                .arg(arg!(--replace "Terminate the running session and start a new one"))
+                .arg(arg!(--wait "Wait for the session to exit (Default)")
+                    .overrides_with("no-wait"))
+                .arg(arg!(--"no-wait" "Return as soon as the session is launched")
+                    .overrides_with("wait"))
+                .arg(arg!(-u --user <USERNAME> "Run the session as this user (Requires root permissions)"))
+                .arg(arg!(--seat <SEAT> "Start the session on this seat (Default: $XDG_SEAT or seat0)"))
+                .arg(arg!(--select "Ask which session to start if the default session is not set (Requires a terminal)"))
+                .arg(arg!(-y --yes "Start a session that asks for confirmation without asking"))
+                .arg(arg!(--log <FILE> "Write the output of the session to this file, keeping the previous one as FILE.old (Default: <[session].log_output>/<register_name>.log)")
+                    .value_parser(clap::value_parser!(PathBuf)))
+                .arg(arg!(--"quiet-session" "Do not log which session is launched and how (Warnings and errors are still logged)"))
+                .arg(arg!(-p --protocol <PROTOCOL_TYPE> "Start the default session of this protocol, falling back to the default session")
+                    .value_parser(["x11", "wayland"])))
+            .subcommand(Command::new("watch")
+                .about("Start a session and restart it whenever it exits with an error, e.g. for kiosks")
+                .arg(arg!([register_name] "Session register name")
+                    .default_value("default"))
+                .arg(arg!(--"max-retries" <COUNT> "Give up after restarting the session this many times within the window")
+                    .value_parser(clap::value_parser!(u32))
+                    .default_value("5"))
+                .arg(arg!(--window <DURATION> "Period restarts are counted in, e.g. 30s or 5m")
+                    .value_parser(|value: &str| parse_duration(value).map_err(|err| err.to_string()))
+                    .default_value("60s"))
+                .arg(arg!(--backoff <DURATION> "Delay before the first restart, doubled for every further restart within the window (At most 30s)")
+                    .value_parser(|value: &str| parse_duration(value).map_err(|err| err.to_string()))
+                    .default_value("1s"))
+                .arg(arg!(--"restart-always" "Restart the session after a normal logout as well"))
+                .arg(arg!(--replace "Terminate the running session and start a new one"))
+                .arg(arg!(--seat <SEAT> "Start the session on this seat (Default: $XDG_SEAT or seat0)"))
+                .arg(arg!(-y --yes "Start a session that asks for confirmation without asking"))
+                .arg(arg!(--log <FILE> "Write the output of the session to this file, keeping the previous one as FILE.old (Default: <[session].log_output>/<register_name>.log)")
+                    .value_parser(clap::value_parser!(PathBuf))))
             .subcommand(Command::new("logout")
                 .about("Logout specific session (Logout oneshot session if no session specific")
-                .arg(arg!([register_name] "Session register name")))
+                .arg(arg!([register_name] "Session register name"))
+                .arg(arg!(--oneshot "Logout the oneshot session")
+                    .conflicts_with("register_name"))
+                .arg(arg!(--force "Kill the process group of the session if the logout command is not set, fails or hangs"))
+                .arg(arg!(--grace <DURATION> "How long to wait before killing the session with --force, e.g. 500ms, 5s or 2m (Default: [session].logout_grace_period or 5s)")
+                    .requires("force")
+                    .value_parser(|value: &str| parse_duration(value).map_err(|err| err.to_string())))
+                .arg(arg!(--seat <SEAT> "Seat of the session (Default: $XDG_SEAT or seat0)")))
+            .subcommand(Command::new("test")
+                .about("Check that a session would start, without starting it")
+                .arg_required_else_help(true)
+                .arg(arg!([register_name] "Session register name")
+                    .required(true)))
             .subcommand(Command::new("set-oneshot")
                 .about("Set a session to start oneshot while login with set login manager next time")
                 .arg_required_else_help(true)
                 .arg(arg!([register_name] "Session register name")
-                    .required(true))))
+                    .required(true))
+                .arg(arg!(--"no-save-manager" "Do not update the Login Manager config until the next login now"))))
         .subcommand(Command::new("login")
             .about("Login settings")
             .subcommand_required(true)
             .arg_required_else_help(true)
             .subcommand(Command::new("set-manager")
-                .about("Set Login Manager (Currently supported: lightdm, sddm)")
+                .about("Set Login Manager (Currently supported: lightdm, sddm, greetd)")
                 .arg_required_else_help(true)
                 .arg(arg!([manager_name] "Login Manager Name")
                     .required(true)
-                    .value_parser(["lightdm", "sddm"])))
+                    .value_parser(["lightdm", "sddm", "greetd"]))
+                .arg(arg!(--resync "Re-sync config and unit state even if the manager is already set"))
+                .arg(arg!(--restart "Restart the new Login Manager after switching"))
+                .arg(arg!(--"no-restart" "Only stage the change without restarting the Login Manager (default)")
+                    .overrides_with("restart"))
+                .arg(arg!(--"no-system-alias" "Do not point display-manager.service to the new Login Manager")))
+            .subcommand(Command::new("set-option")
+                .about("Set a key in the Login Manager config that does not depend on Auto Login")
+                .arg_required_else_help(true)
+                .arg(arg!([section] "Section of the key")
+                    .required(true))
+                .arg(arg!([key] "Key to set")
+                    .required(true))
+                .arg(arg!([value] "Value to set (Remove the key if empty)")))
             .subcommand(Command::new("autologin")
                 .about("Config Auto Login")
                 .subcommand_required(true)
                 .arg_required_else_help(true)
                 .subcommand(Command::new("enable")
                     .about("Enable Auto Login")
-                    .arg_required_else_help(true)
-                    .arg(arg!(-u --user <USERNAME> "User that login as")
-                        .required(true)))
+                    .arg(arg!(-u --user <USERNAME> "User that login as (Reuse last user if not specified)"))
+                    .arg(arg!(--force "Enable Auto Login even if the user does not exist yet"))
+                    .arg(arg!(--once "Disable Auto Login again after it logged in once"))
+                    .arg(arg!(--immediate "Log in right away by restarting the Login Manager once Auto Login is enabled"))
+                    .arg(arg!(--seat <SEAT> "LightDM seat to configure, remembered for later commands (Default: *)"))
+                    .arg(arg!(--"session-protocol" <PROTOCOL_TYPE> "Force the protocol of the redirect session, remembered for later commands (auto: detect from session)")
+                        .value_parser(["auto", "wayland", "x11"])))
                 .subcommand(Command::new("disable")
-                    .about("Disable Auto Login")))
+                    .about("Disable Auto Login")
+                    .arg(arg!(--seat <SEAT> "LightDM seat to configure, remembered for later commands (Default: *)")))
+                .subcommand(Command::new("status")
+                    .about("Show Auto Login status (Exit code: 0 enabled, 10 disabled, 11 config drift)")))
+            .subcommand(Command::new("reconcile")
+                .about("Sync Auto Login settings between molyuuctl and the Login Manager config")
+                .arg(arg!(-d --direction <DIRECTION> "Which config to update")
+                    .value_parser(["from-manager", "to-manager"])
+                    .default_value("from-manager")))
+            .subcommand(Command::new("list-managers")
+                .about("List the Login Manager units systemd has loaded, including unsupported ones"))
+            .subcommand(Command::new("import-autologin")
+                .about("Enable Auto Login with the user and session configured in another tool")
+                .arg(arg!(--from <SOURCE> "Tool to import from")
+                    .value_parser(["accountsservice"])
+                    .default_value("accountsservice"))
+                .arg(arg!(-u --user <USERNAME> "User to import (Default: the only user the tool knows about)")))
+            .subcommand(Command::new("status")
+                .about("Show the Login Manager molyuuctl is set to and the one systemd starts at boot (Exit code 5 if they differ)"))
+            .subcommand(Command::new("clean-conflicts")
+                .about("Report Auto Login keys in Login Manager configs that molyuuctl does not own")
+                .arg(arg!(--remove "Remove the reported keys")))
             .subcommand(Command::new("now")
-                .about("Login via set Login Manager now")))
+                .about("Login via set Login Manager now")
+                .arg(arg!(--manager <MANAGER_NAME> "Only restart this Login Manager, without changing the set one or its config")
+                    .value_parser(["lightdm", "sddm", "greetd"]))))
+        .subcommand(Command::new("config")
+            .about("Configuration settings")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(Command::new("reload")
+                .about("Re-read the configuration from disk and check that it is valid"))
+            .subcommand(Command::new("edit")
+                .about("Edit the configuration in $EDITOR and save it only if it is valid")))
+        .subcommand(Command::new("doctor")
+            .about("Check whether the system is configured correctly"))
+        .subcommand(Command::new("apply")
+            .about("Register sessions and set the default session, Login Manager and Auto Login declared in a file")
+            .arg_required_else_help(true)
+            .arg(arg!([file] "Desired state file")
+                .required(true))
+            .arg(arg!(-n --"dry-run" "Only list the changes that would be applied")));
+
+    #[cfg(feature = "tui")]
+    let cli = cli.mut_subcommand("session", |session| session
+        .subcommand(Command::new("tui")
+            .about("Pick a session to start in an interactive menu")));
+
+    cli
 }
 
 extern "C" fn cleanup(sig: libc::c_int) {
-    println!("Received SIGNAL: {}", sig);
-    println!("Clean up before exit ...");
+    // Only async-signal-safe calls are allowed in here. A running session is terminated instead,
+    // and its lock released once the session exited, see `lock::termination_signal`
+    if !lock::release_on_signal(sig) {
+        unsafe {
+            libc::_exit(128 + sig);
+        }
+    }
+}
+
+/// Whether a command may modify the configuration and therefore needs the config lock.
+///
+/// Read-only commands only hold the lock shared while the configuration is loaded, and so do
+/// long-running ones like `session start`, which would otherwise block every other command while
+/// the session runs. Those take the lock only around their own changes, see
+/// `Configuration::with_lock`.
+fn modifies_config(matches: &ArgMatches) -> bool {
+    match matches.subcommand() {
+        Some(("session", sub_m)) => !matches!(sub_m.subcommand_name(),
+            Some("list" | "names" | "default" | "stats" | "export" | "test" | "get-key" | "start" | "watch" | "tui" | "logout")),
+        Some(("login", sub_m)) => match sub_m.subcommand() {
+            Some(("autologin", autologin_sub_m)) => autologin_sub_m.subcommand_name() != Some("status"),
+            Some(("list-managers", _)) => false,
+            Some(("status", _)) => false,
+            _ => true,
+        },
+        Some(("config", sub_m)) => sub_m.subcommand_name() != Some("reload"),
+        Some(("doctor", _)) => false,
+        _ => true,
+    }
+}
 
-    println!("Done! Goodbye!");
-    exit(0);
+/// The protocol given to `session register`, or `None` to detect it from the session.
+///
+/// `--wayland` and `--x11` are shortcuts for `--protocol`, which defaults to `auto`.
+///
+/// # Errors
+///
+/// Returns an error if the protocol is not supported.
+fn register_protocol(register_m: &ArgMatches) -> Result<Option<Protocol>, Box<dyn Error>> {
+    if register_m.get_flag("wayland") {
+        return Ok(Some(Protocol::Wayland));
+    }
+    if register_m.get_flag("x11") {
+        return Ok(Some(Protocol::X11));
+    }
+    let protocol = register_m.get_one::<String>("protocol").expect("defaulted");
+    if protocol.eq_ignore_ascii_case("auto") {
+        return Ok(None);
+    }
+    Ok(Some(protocol.parse::<Protocol>()?))
+}
+
+/// Whether `login set-manager` should restart the new Login Manager.
+///
+/// `--restart` and `--no-restart` override each other, so the last one given wins, e.g. when
+/// `--no-restart` is appended to an alias that passes `--restart`. Without either flag the change
+/// is only staged.
+fn set_manager_restart(set_manager_m: &ArgMatches) -> bool {
+    set_manager_m.get_flag("restart") && !set_manager_m.get_flag("no-restart")
 }
 
 fn main() {
     common::logger::init().unwrap();
 
     unsafe {
-        libc::signal(libc::SIGINT, cleanup as libc::sighandler_t);
-        libc::signal(libc::SIGTERM, cleanup as libc::sighandler_t);
+        libc::signal(libc::SIGINT, cleanup as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, cleanup as *const () as libc::sighandler_t);
     }
 
     let matches = cli().get_matches();
-    config::Configuration::init(None);
+    common::logger::set_color(ColorChoice::from_name(matches.get_one::<String>("color").expect("default"))
+        .expect("validated by clap"));
+    if matches.get_flag("explain") {
+        explain::enable();
+    }
+    if let Some(timeout) = matches.get_one::<Duration>("wait-for-bus") {
+        SYSTEMCTL.lock().unwrap().set_bus_wait(Some(*timeout));
+    }
+    let modifies_config = modifies_config(&matches);
+    let config_lock = if modifies_config {
+        config::Configuration::lock()
+    } else {
+        config::Configuration::lock_shared()
+    };
+    let config_lock = match config_lock {
+        Ok(lock) => lock,
+        Err(_err) => {
+            let _err = MolyuuError::from(_err);
+            error!("{}", _err);
+            exit(_err.exit_code());
+        }
+    };
+    if let Err(_err) = config::Configuration::init(None) {
+        let _err = MolyuuError::from(_err);
+        error!("{}", _err);
+        exit(_err.exit_code());
+    }
+    // Read-only commands only keep a writer from changing the file while it is loaded
+    let config_lock = modifies_config.then_some(config_lock);
+    config::GLOBAL_CONFIG.get_mut().unwrap().set_locked(config_lock.is_some());
+    config::GLOBAL_CONFIG.get_mut().unwrap().set_force(matches.get_flag("force_write"));
+    config::GLOBAL_CONFIG.get_mut().unwrap().set_output_path(matches.get_one::<String>("output_file").map(String::as_str));
+    common::logger::apply_config();
 
     let status = attempt! {{
         match matches.subcommand() {
@@ -126,36 +460,178 @@ fn main() {
                     Some(("register", session_sub_m)) => {
                         let reg_name = session_sub_m.get_one::<String>("name").expect("required");
                         let session_name = session_sub_m.get_one::<String>("session").expect("required");
-                        let protocol_str = session_sub_m.get_one::<String>("protocol").expect("required");
                         let logout_command = session_sub_m.get_one::<String>("logout");
-                        let protocol = {
-                            let protocol_str_lower = protocol_str.to_lowercase();
-                            match protocol_str_lower.as_str() {
-                                "x11" => {
-                                    Some(Protocol::X11)
-                                }
-                                "wayland" => {
-                                    Some(Protocol::Wayland)
+                        let protocol = register_protocol(session_sub_m)?;
+                        let mut session = Session::new(reg_name.clone(), session_name.clone(), logout_command.cloned(), protocol)?;
+                        if session_sub_m.get_flag("validate-command") {
+                            session.validate_command()?;
+                        }
+                        if session_sub_m.get_flag("update") {
+                            if !session.register_or_update()? {
+                                info!("Updated session {}", reg_name);
+                            }
+                        } else {
+                            session.register()?;
+                        }
+                        session.promote(session_sub_m.get_flag("default"), session_sub_m.get_flag("oneshot"))?;
+                    }
+                    Some(("set-default", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let protocol = session_sub_m.get_one::<String>("protocol")
+                            .map(|protocol| protocol.parse::<Protocol>())
+                            .transpose()?;
+                        Session::from_config(Some(register_name.as_str()))?.set_as_default_for(protocol)?
+                    }
+                    Some(("swap-default", session_sub_m)) => {
+                        if session_sub_m.get_flag("revert") {
+                            let restored = Session::revert_default()?;
+                            info!("Default session is reverted to {}", restored);
+                        } else {
+                            let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                            match Session::from_config(Some(register_name.as_str()))?.swap_default()? {
+                                Some(previous_default) => info!("Default session is {} instead of {}, revert with --revert", register_name, previous_default),
+                                None => info!("Default session is {}", register_name),
+                            }
+                        }
+                    }
+                    Some(("list", session_sub_m)) => {
+                        let format = OutputFormat::from_name(session_sub_m.get_one::<String>("output").expect("default"))
+                            .expect("validated by clap")
+                            .for_output(io::stdout().is_terminal());
+                        print!("{}", format_sessions(&SessionSummary::collect()?, format));
+                    }
+                    Some(("names", session_sub_m)) => {
+                        let with_protocol = session_sub_m.get_flag("protocol");
+                        let sessions: Vec<(String, &str)> = Session::list_registered().into_iter()
+                            .map(|name| {
+                                let protocol = Session::get_registered_protocol(name.as_str())
+                                    .map_or("unknown", |protocol| protocol.as_str());
+                                (name, protocol)
+                            })
+                            .collect();
+                        if session_sub_m.get_flag("json") {
+                            let json = if with_protocol {
+                                serde_json::json!(sessions.iter()
+                                    .map(|(name, protocol)| serde_json::json!({"name": name, "protocol": protocol}))
+                                    .collect::<Vec<_>>())
+                            } else {
+                                serde_json::json!(sessions.iter().map(|(name, _)| name).collect::<Vec<_>>())
+                            };
+                            println!("{}", json);
+                        } else {
+                            for (name, protocol) in sessions {
+                                if with_protocol {
+                                    println!("{}\t{}", name, protocol);
+                                } else {
+                                    println!("{}", name);
                                 }
-                                "auto" => None,
-                                _ => panic!("Unknown protocol")
                             }
+                        }
+                    }
+                    Some(("stats", session_sub_m)) => {
+                        let stats = Session::stats()?;
+                        if session_sub_m.get_flag("json") {
+                            println!("{}", stats.to_json());
+                        } else {
+                            print!("{}", stats);
+                        }
+                    }
+                    Some(("export", session_sub_m)) => {
+                        let register_names: Vec<String> = session_sub_m.get_many::<String>("register_names")
+                            .map(|names| names.cloned().collect())
+                            .unwrap_or_default();
+                        let format = ExportFormat::from_name(session_sub_m.get_one::<String>("format").expect("default"))
+                            .expect("validated by clap");
+                        print!("{}", export::export(&register_names, format)?);
+                    }
+                    Some(("import", session_sub_m)) => {
+                        let file = session_sub_m.get_one::<String>("file").expect("required");
+                        let format = match session_sub_m.get_one::<String>("format") {
+                            Some(format) => ExportFormat::from_name(format).expect("validated by clap"),
+                            None => ExportFormat::from_path(Path::new(file)).unwrap_or_default(),
                         };
-                        Session::new(reg_name.clone(), session_name.clone(), logout_command.cloned(), protocol)?.register()?
+                        let contents = fs::read_to_string(file)
+                            .map_err(|err| format!("Failed to read sessions from {file}: {err}"))?;
+                        let imported = export::import(export::parse(contents.as_str(), format)?, session_sub_m.get_flag("replace"))?;
+                        info!("Imported sessions: {}", imported.join(", "));
                     }
-                    Some(("set-default", session_sub_m)) => {
+                    Some(("default", _)) => {
+                        match Session::get_default_name()? {
+                            Some(name) => println!("{}", name),
+                            None => return Err(Box::from(SessionInstanceError::DefaultSessionNotSet)),
+                        }
+                    }
+                    Some(("test", session_sub_m)) => {
                         let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
-                        Session::from_config(Some(register_name.as_str()))?.set_as_default()?
+                        doctor::test_session(register_name.as_str())?
                     }
                     Some(("set-oneshot", session_sub_m)) => {
                         let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
-                        Session::from_config(Some(register_name.as_str()))?.set_start_oneshot()?;
+                        let save_manager = !session_sub_m.get_flag("no-save-manager");
+                        Session::from_config(Some(register_name.as_str()))?.set_start_oneshot(save_manager)?;
                     }
                     Some(("set-logout-command", session_sub_m)) => {
                         let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
                         let logout_command = session_sub_m.get_one::<String>("logout_command").expect("required");
                         Session::from_config(Some(register_name.as_str()))?.set_logout_command(logout_command.as_str())?
                     }
+                    Some(("set-requires", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let units = session_sub_m.get_many::<String>("units").unwrap_or_default().cloned().collect();
+                        Session::from_config(Some(register_name.as_str()))?.set_requires(units)?
+                    }
+                    Some(("set-action", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let action = session_sub_m.get_one::<String>("action");
+                        Session::from_config(Some(register_name.as_str()))?.set_action(action.map(|action| action.as_str()))?
+                    }
+                    Some(("set-fallback", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let fallback = session_sub_m.get_one::<String>("fallback");
+                        Session::from_config(Some(register_name.as_str()))?.set_fallback(fallback.map(|fallback| fallback.as_str()))?
+                    }
+                    Some(("set-vt", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let vt = session_sub_m.get_one::<String>("vt");
+                        Session::from_config(Some(register_name.as_str()))?.set_vt(vt.map(|vt| vt.as_str()))?
+                    }
+                    Some(("set-display-server-args", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let args = session_sub_m.get_many::<String>("args").unwrap_or_default().cloned().collect();
+                        Session::from_config(Some(register_name.as_str()))?.set_display_server_args(args)?
+                    }
+                    Some(("set-limits", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let mut session = Session::from_config(Some(register_name.as_str()))?;
+                        let mut limits = session.get_resource_limits().clone();
+                        if let Some(memory_max) = session_sub_m.get_one::<String>("memory-max") {
+                            limits.memory_max = Some(memory_max.clone()).filter(|memory_max| !memory_max.is_empty());
+                        }
+                        if let Some(cpu_quota) = session_sub_m.get_one::<String>("cpu-quota") {
+                            limits.cpu_quota = Some(cpu_quota.clone()).filter(|cpu_quota| !cpu_quota.is_empty());
+                        }
+                        session.set_resource_limits(limits)?
+                    }
+                    Some(("set-key", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let key = session_sub_m.get_one::<String>("key").expect("required");
+                        let value = session_sub_m.get_one::<String>("value").expect("required");
+                        let key_type = match session_sub_m.get_one::<String>("type") {
+                            Some(key_type) => KeyType::from_name(key_type).expect("validated by clap"),
+                            None => KeyType::infer(value),
+                        };
+                        Session::set_key(register_name, key, key_type.parse(value)?, session_sub_m.get_flag("force"))?
+                    }
+                    Some(("get-key", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let key = session_sub_m.get_one::<String>("key").expect("required");
+                        println!("{}", keys::format_value(&Session::get_key(register_name, key)?));
+                    }
+                    Some(("unset-key", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let key = session_sub_m.get_one::<String>("key").expect("required");
+                        Session::unset_key(register_name, key, session_sub_m.get_flag("force"))?
+                    }
                     Some(("rename", session_sub_m)) => {
                         let original_name = session_sub_m.get_one::<String>("original_name").expect("required");
                         let new_name = session_sub_m.get_one::<String>("new_name").expect("required");
@@ -165,22 +641,84 @@ fn main() {
                         let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
                         Session::from_config(Some(register_name.as_str()))?.remove()?
                     }
+                    Some(("prune", session_sub_m)) => {
+                        let dry_run = session_sub_m.get_flag("dry-run");
+                        for name in Session::prune(dry_run)? {
+                            if dry_run {
+                                println!("Would remove {name}");
+                            } else {
+                                println!("Removed {name}");
+                            }
+                        }
+                    }
                     Some(("start", session_sub_m)) => {
                         let register_name = session_sub_m.get_one::<String>("register_name").expect("required");
+                        let options = StartOptions {
+                            replace: session_sub_m.get_flag("replace"),
+                            user: session_sub_m.get_one::<String>("user").cloned(),
+                            detach: session_sub_m.get_flag("no-wait"),
+                            seat: session_sub_m.get_one::<String>("seat").cloned(),
+                            assume_yes: session_sub_m.get_flag("yes"),
+                            log_output: session_sub_m.get_one::<PathBuf>("log").cloned(),
+                            quiet_session: session_sub_m.get_flag("quiet-session"),
+                            protocol: session_sub_m.get_one::<String>("protocol")
+                                .map(|protocol| protocol.parse::<Protocol>())
+                                .transpose()?,
+                        };
                         if register_name.as_str() == "default" {
-                            Session::start_oneshot_or_default_session()?
+                            match Session::start_oneshot_or_default_session(&options) {
+                                Err(err) if session_sub_m.get_flag("select") && io::stdin().is_terminal()
+                                    && err.downcast_ref::<SessionInstanceError>() == Some(&SessionInstanceError::DefaultSessionNotSet) => {
+                                    let selected = Session::select_registered(&mut io::stdin().lock(), &mut io::stderr())?;
+                                    Session::from_config(Some(selected.as_str()))?.start(&options)?
+                                }
+                                result => result?,
+                            }
                         } else {
-                            Session::from_config(Some(register_name.as_str()))?.start()?
+                            Session::from_config(Some(register_name.as_str()))?.start(&options)?
                         }
                     }
+                    Some(("watch", session_sub_m)) => {
+                        let register_name = session_sub_m.get_one::<String>("register_name").expect("default");
+                        let options = StartOptions {
+                            replace: session_sub_m.get_flag("replace"),
+                            seat: session_sub_m.get_one::<String>("seat").cloned(),
+                            assume_yes: session_sub_m.get_flag("yes"),
+                            log_output: session_sub_m.get_one::<PathBuf>("log").cloned(),
+                            ..StartOptions::default()
+                        };
+                        let policy = WatchPolicy {
+                            max_retries: *session_sub_m.get_one::<u32>("max-retries").expect("default"),
+                            window: *session_sub_m.get_one::<Duration>("window").expect("default"),
+                            backoff: *session_sub_m.get_one::<Duration>("backoff").expect("default"),
+                            restart_always: session_sub_m.get_flag("restart-always"),
+                            ..WatchPolicy::default()
+                        };
+                        let session = if register_name.as_str() == "default" {
+                            Session::get_default_session()?
+                        } else {
+                            Session::from_config(Some(register_name.as_str()))?
+                        };
+                        session.watch(&options, policy)?
+                    }
+                    #[cfg(feature = "tui")]
+                    Some(("tui", _)) => tui::run()?,
                     Some(("logout", session_sub_m)) => {
                         let register_name = session_sub_m.get_one::<String>("register_name");
-                        if let Some(name) = register_name {
-                            Session::from_config(Some(name.as_str()))?.logout()?
-                        } else if let Some(session) = Session::get_running_session()? {
-                                session.logout()?
+                        let seat = session_sub_m.get_one::<String>("seat").cloned().unwrap_or_else(Session::current_seat);
+                        let session = if let Some(name) = register_name {
+                            Session::from_config(Some(name.as_str()))?
+                        } else if session_sub_m.get_flag("oneshot") {
+                            Session::get_last_oneshot_session()?
+                        } else if let Some(session) = Session::get_running_session(seat.as_str())? {
+                            session
                         } else {
                             return Err(Box::from("No session is specific and running session!"));
+                        };
+                        if session_sub_m.get_flag("force") {
+                            session.force_logout(seat.as_str(), session_sub_m.get_one::<Duration>("grace").copied())?
+                        } else {
+                            session.logout()?
                         }
                     }
                     _ => {}
@@ -190,30 +728,230 @@ fn main() {
                 match sub_m.subcommand() {
                     Some(("set-manager", login_sub_m)) => {
                         let manager_name = login_sub_m.get_one::<String>("manager_name").expect("required");
-                        login::manager::set_manager(manager_name.to_lowercase().as_str())?;
+                        let resync = login_sub_m.get_flag("resync");
+                        let restart = set_manager_restart(login_sub_m);
+                        let system_alias = !login_sub_m.get_flag("no-system-alias");
+                        login::manager::set_manager(manager_name.to_lowercase().as_str(), resync, restart, system_alias)?;
+                    }
+                    Some(("set-option", login_sub_m)) => {
+                        let section = login_sub_m.get_one::<String>("section").expect("required");
+                        let key = login_sub_m.get_one::<String>("key").expect("required");
+                        let value = login_sub_m.get_one::<String>("value");
+                        get_current_manager()?.set_option(section.as_str(), key.as_str(), value.map(|value| value.as_str()))?;
                     }
                     Some(("autologin", login_sub_m)) => {
                         match login_sub_m.subcommand() {
                             Some(("enable", autologin_enable_sub_m)) => {
-                                let username = autologin_enable_sub_m.get_one::<String>("user").expect("required");
-                                get_current_manager()?.set_auto_login(true, Some(username.as_str()))?;
+                                let username = autologin_enable_sub_m.get_one::<String>("user");
+                                let seat = autologin_enable_sub_m.get_one::<String>("seat");
+                                let protocol = match autologin_enable_sub_m.get_one::<String>("session-protocol") {
+                                    Some(protocol_str) if protocol_str == "auto" => Some(None),
+                                    Some(protocol_str) => Some(Some(protocol_str.parse::<Protocol>()?)),
+                                    None => None,
+                                };
+                                login::manager::enable_auto_login(username.map(|user| user.as_str()), autologin_enable_sub_m.get_flag("force"), autologin_enable_sub_m.get_flag("once"), seat.map(|seat| seat.as_str()), protocol, autologin_enable_sub_m.get_flag("immediate"))?;
                             }
-                            Some(("disable", _)) => {
-                                get_current_manager()?.set_auto_login(false, None)?;
+                            Some(("disable", autologin_disable_sub_m)) => {
+                                if let Some(seat) = autologin_disable_sub_m.get_one::<String>("seat") {
+                                    login::manager::set_autologin_seat(seat.as_str())?;
+                                }
+                                get_current_manager()?.set_auto_login(false, None, false)?;
+                            }
+                            Some(("status", _)) => {
+                                let status = get_current_manager()?.get_auto_login_status()?;
+                                match status.state {
+                                    AutoLoginState::Enabled => println!("enabled"),
+                                    AutoLoginState::Disabled => println!("disabled"),
+                                    AutoLoginState::Drift => println!("drift (molyuuctl: {}, login manager: {})",
+                                        if status.config_enabled { "enabled" } else { "disabled" },
+                                        if status.manager_enabled { "enabled" } else { "disabled" }),
+                                }
+                                println!("user: {}", status.user.as_deref().unwrap_or("none"));
+                                println!("session: {}", status.session.as_deref().unwrap_or("none"));
+                                if status.state != AutoLoginState::Enabled {
+                                    exit(status.state.exit_code());
+                                }
                             }
                             _ => {}
                         }
                     }
-                    Some(("now", _)) => get_current_manager()?.login_now()?,
+                    Some(("list-managers", _)) => {
+                        for (unit, active_state) in login::manager::find_manager_units()? {
+                            let support = if login::manager::is_supported_manager_unit(unit.as_str()) { "supported" } else { "unsupported" };
+                            println!("{unit}: {active_state} ({support})");
+                        }
+                    }
+                    Some(("status", _)) => {
+                        let manager = get_current_manager()?;
+                        println!("manager: {}", manager.get_metadata().systemd_unit);
+                        let display_manager = SYSTEMCTL.lock().unwrap().get_default_display_manager()?;
+                        println!("display-manager.service: {}", display_manager.as_deref().unwrap_or("none"));
+                        manager.check_display_manager_alias()?;
+                    }
+                    Some(("reconcile", login_sub_m)) => {
+                        let direction = match login_sub_m.get_one::<String>("direction").expect("default").as_str() {
+                            "to-manager" => ReconcileDirection::ToManager,
+                            _ => ReconcileDirection::FromManager,
+                        };
+                        get_current_manager()?.reconcile(direction)?;
+                    }
+                    Some(("import-autologin", login_sub_m)) => {
+                        let source = ImportSource::from_name(login_sub_m.get_one::<String>("from").expect("default"))
+                            .expect("validated by clap");
+                        let imported = login::import::read(source, login_sub_m.get_one::<String>("user").map(|user| user.as_str()))?;
+                        login::import::apply(&imported)?;
+                        info!("Imported Auto Login for {} (session: {})", imported.user, imported.session.as_deref().unwrap_or("default"));
+                    }
+                    Some(("clean-conflicts", login_sub_m)) => {
+                        let conflicts = login::conflicts::find()?;
+                        if conflicts.is_empty() {
+                            info!("No conflicting Auto Login keys found");
+                        }
+                        for conflict in &conflicts {
+                            println!("{}", conflict);
+                        }
+                        if login_sub_m.get_flag("remove") && !conflicts.is_empty() {
+                            login::conflicts::remove(&conflicts)?;
+                            info!("Removed {} conflicting Auto Login keys", conflicts.len());
+                        }
+                    }
+                    Some(("now", login_sub_m)) => match login_sub_m.get_one::<String>("manager") {
+                        Some(manager_name) => login::manager::restart_manager(manager_name.as_str())?,
+                        None => get_current_manager()?.login_now()?,
+                    },
+                    _ => {}
+                }
+            }
+            Some(("config", sub_m)) => {
+                match sub_m.subcommand() {
+                    Some(("reload", _)) => {
+                        let config = GLOBAL_CONFIG.get_mut().unwrap();
+                        config.reload()?;
+                        info!("Reloaded configuration from {}", config.get_path());
+                    }
+                    Some(("edit", _)) => {
+                        let config = GLOBAL_CONFIG.get_mut().unwrap();
+                        if config.edit()? {
+                            info!("Saved configuration to {}", config.get_path());
+                        } else {
+                            info!("Configuration is not changed");
+                        }
+                    }
                     _ => {}
                 }
             }
+            Some(("apply", sub_m)) => {
+                let desired = apply::DesiredState::load(sub_m.get_one::<String>("file").expect("required"))?;
+                let changes = apply::plan(&desired)?;
+                if changes.is_empty() {
+                    info!("System already matches the desired state");
+                } else if sub_m.get_flag("dry-run") {
+                    for change in &changes {
+                        println!("{}", change);
+                    }
+                } else {
+                    apply::apply(&desired, &changes)?;
+                    for change in &changes {
+                        info!("{}", change);
+                    }
+                }
+            }
+            Some(("doctor", _)) => doctor::run()?,
             _ => {}
         }
         Ok(())
+    } finally {
+        // Write changes a batch left pending, then let other instances in
+        if let Err(_err) = login::manager::flush_config_on_exit() {
+            error!("Failed to write pending configuration changes: {}", _err);
+        }
+        // exit() skips destructors, release the config lock first
+        drop(config_lock);
     }};
 
     if let Err(_err) = status {
+        let _err = MolyuuError::from(_err);
         error!("{}", _err);
+        exit(_err.exit_code());
+    }
+    // Exit like the signal that ended the session would have
+    if let Some(sig) = lock::termination_signal() {
+        exit(128 + sig);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register_matches(args: &[&str]) -> Result<ArgMatches, clap::Error> {
+        let matches = cli().try_get_matches_from(["molyuuctl", "session", "register", "-n", "alpha", "-s", "plasma"].iter().chain(args))?;
+        let (_, session_m) = matches.subcommand().unwrap();
+        Ok(session_m.subcommand().unwrap().1.clone())
+    }
+
+    #[test]
+    fn register_protocol_flags_map_to_protocol() {
+        assert_eq!(register_protocol(&register_matches(&[]).unwrap()).unwrap(), None);
+        assert_eq!(register_protocol(&register_matches(&["--wayland"]).unwrap()).unwrap(), Some(Protocol::Wayland));
+        assert_eq!(register_protocol(&register_matches(&["--x11"]).unwrap()).unwrap(), Some(Protocol::X11));
+        assert_eq!(register_protocol(&register_matches(&["-p", "x11"]).unwrap()).unwrap(), Some(Protocol::X11));
+        assert_eq!(register_protocol(&register_matches(&["-p", "auto"]).unwrap()).unwrap(), None);
+    }
+
+    #[test]
+    fn register_protocol_flags_conflict() {
+        for args in [&["--wayland", "--x11"][..], &["--wayland", "-p", "x11"], &["--x11", "-p", "wayland"]] {
+            let err = register_matches(args).unwrap_err();
+            assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn set_manager_restarts_only_when_the_last_flag_asks_for_it() {
+        for (args, restart) in [
+            (&[][..], false),
+            (&["--restart"], true),
+            (&["--no-restart"], false),
+            (&["--restart", "--no-restart"], false),
+            (&["--no-restart", "--restart"], true),
+        ] {
+            let matches = cli().get_matches_from(["molyuuctl", "login", "set-manager", "sddm"].iter().chain(args));
+            let (_, login_m) = matches.subcommand().unwrap();
+            let (_, set_manager_m) = login_m.subcommand().unwrap();
+            assert_eq!(set_manager_restart(set_manager_m), restart, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn cli_is_consistent() {
+        cli().debug_assert();
+    }
+
+    #[test]
+    fn force_write_is_accepted_after_subcommands() {
+        let matches = cli().get_matches_from(["molyuuctl", "--force-write", "session", "list"]);
+        assert!(matches.get_flag("force_write"));
+
+        let matches = cli().get_matches_from(["molyuuctl", "session", "list", "--force-write"]);
+        assert!(matches.get_flag("force_write"));
+
+        let matches = cli().get_matches_from(["molyuuctl", "session", "list"]);
+        assert!(!matches.get_flag("force_write"));
+    }
+
+    #[test]
+    fn force_write_is_independent_of_subcommand_force() {
+        let matches = cli().get_matches_from(["molyuuctl", "--force-write", "session", "logout"]);
+        assert!(matches.get_flag("force_write"));
+        let (_, session_m) = matches.subcommand().unwrap();
+        let (_, logout_m) = session_m.subcommand().unwrap();
+        assert!(!logout_m.get_flag("force"));
+
+        let matches = cli().get_matches_from(["molyuuctl", "session", "logout", "--force"]);
+        assert!(!matches.get_flag("force_write"));
+        let (_, session_m) = matches.subcommand().unwrap();
+        let (_, logout_m) = session_m.subcommand().unwrap();
+        assert!(logout_m.get_flag("force"));
     }
 }
@@ -0,0 +1,209 @@
+use std::error::Error;
+use std::path::Path;
+
+use log::warn;
+use toml::{Table, Value};
+
+use crate::config::GLOBAL_CONFIG;
+use crate::errors::config::ConfigError;
+use crate::errors::session::SessionInstanceError;
+use crate::session::Session;
+
+/// The format sessions are exported to and imported from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// A `[<reg_name>]` table per session, as in the `[session]` section of the configuration.
+    #[default]
+    Toml,
+    /// An object with a member per session, for web tooling.
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// Detect the format of a file from its extension.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the extension is neither `.toml` nor `.json`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension().and_then(|extension| extension.to_str()).and_then(Self::from_name)
+    }
+}
+
+/// Serialize the tables of registered sessions.
+///
+/// Every key of a session table is exported as stored, including nested tables like the
+/// environment and hooks, so `import` restores the sessions as they were.
+///
+/// # Parameters
+///
+/// * `reg_names`: The sessions to export, or an empty slice to export all registered sessions.
+/// * `format`: The format to serialize to.
+///
+/// # Errors
+///
+/// Returns `SessionInstanceError::SessionNotFoundInConfig` if one of the sessions is not
+/// registered, or an error if the tables cannot be serialized.
+pub fn export(reg_names: &[String], format: ExportFormat) -> Result<String, Box<dyn Error>> {
+    let reg_names = if reg_names.is_empty() { Session::list_registered() } else { reg_names.to_vec() };
+    let mut sessions = Table::new();
+    for reg_name in reg_names {
+        let session_table = Session::config_table(reg_name.as_str())?;
+        sessions.insert(reg_name, Value::Table(session_table));
+    }
+
+    match format {
+        ExportFormat::Toml => Ok(toml::to_string(&sessions)?),
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&sessions)? + "\n"),
+    }
+}
+
+/// Parse sessions serialized by `export`.
+///
+/// # Errors
+///
+/// Returns `ConfigError::InvalidConfig` if the contents cannot be parsed, contain a value TOML
+/// cannot represent (like a JSON `null`), or a session is not a table with a `session` key.
+pub fn parse(contents: &str, format: ExportFormat) -> Result<Table, ConfigError> {
+    let sessions = match format {
+        ExportFormat::Toml => contents.parse::<Table>()
+            .map_err(|err| ConfigError::InvalidConfig(err.message().to_string()))?,
+        ExportFormat::Json => {
+            let json = serde_json::from_str::<serde_json::Value>(contents)
+                .map_err(|err| ConfigError::InvalidConfig(err.to_string()))?;
+            Table::try_from(json)
+                .map_err(|err| ConfigError::InvalidConfig(format!("Sessions cannot be represented in TOML: {err}")))?
+        }
+    };
+
+    for (reg_name, session_table) in &sessions {
+        let has_session = session_table.as_table()
+            .and_then(|session_table| session_table.get("session"))
+            .is_some_and(|session| session.is_str());
+        if !has_session {
+            return Err(ConfigError::InvalidConfig(format!("Session {reg_name} must be a table with a session key")));
+        }
+    }
+    Ok(sessions)
+}
+
+/// Register sessions parsed by `parse`.
+///
+/// All sessions are checked before any of them is registered, and the global configuration is
+/// saved once.
+///
+/// # Parameters
+///
+/// * `sessions`: The tables of the sessions to register, by register name.
+/// * `replace`: Whether to replace sessions that are already registered.
+///
+/// # Returns
+///
+/// Returns the register names of the imported sessions.
+///
+/// # Errors
+///
+/// Returns `SessionInstanceError::InvalidRegisterName` or `SessionInstanceError::ReservedRegisterName`
+/// if a register name is not valid, `SessionInstanceError::SessionExists` if a session is already registered and
+/// `replace` is not set, `ConfigError::InvalidConfig` if a register name is taken by a key of the
+/// `[session]` section like `default`, or an error if the configuration cannot be saved.
+pub fn import(sessions: Table, replace: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    for reg_name in sessions.keys() {
+        Session::validate_reg_name(reg_name.as_str())?;
+    }
+    let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+    if let Some(reg_name) = sessions.keys().find(|reg_name| session_info.get(reg_name.as_str()).is_some_and(|value| !value.is_table())) {
+        return Err(Box::from(ConfigError::InvalidConfig(format!("{reg_name} is reserved in the [session] section and cannot be a register name"))));
+    }
+    if !replace {
+        if let Some(reg_name) = sessions.keys().find(|reg_name| Session::config_table(reg_name.as_str()).is_ok()) {
+            warn!("Session {} is already registered, pass --replace to replace it", reg_name);
+            return Err(Box::from(SessionInstanceError::SessionExists));
+        }
+    }
+
+    let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+    let mut imported = Vec::new();
+    for (reg_name, session_table) in sessions {
+        session_info.insert(reg_name.clone(), session_table);
+        imported.push(reg_name);
+    }
+    GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    static NESTED_SESSION_CONFIG: &str = r#"[login.autologin]
+
+[session]
+default = "alpha"
+
+[session.alpha]
+session = "molyuuctl-test-alpha"
+protocol = "wayland"
+
+[session.alpha.env]
+QT_QPA_PLATFORM = "wayland"
+
+[session.alpha.hooks]
+pre_start = ["echo start"]
+"#;
+
+    fn round_trip(format: ExportFormat) {
+        let fixture = testing::load_config(NESTED_SESSION_CONFIG);
+        let exported = export(&[], format).unwrap();
+        let original = Session::config_table("alpha").unwrap();
+
+        GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap().remove("alpha");
+        let imported = import(parse(exported.as_str(), format).unwrap(), false).unwrap();
+        assert_eq!(imported, [String::from("alpha")]);
+        assert_eq!(Session::config_table("alpha").unwrap(), original);
+        assert!(fixture.read().contains("QT_QPA_PLATFORM"));
+    }
+
+    #[test]
+    fn toml_export_round_trips() {
+        round_trip(ExportFormat::Toml);
+    }
+
+    #[test]
+    fn json_export_round_trips_nested_tables() {
+        round_trip(ExportFormat::Json);
+    }
+
+    #[test]
+    fn format_is_detected_from_the_extension() {
+        assert_eq!(ExportFormat::from_path(Path::new("sessions.json")), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::from_path(Path::new("sessions.toml")), Some(ExportFormat::Toml));
+        assert_eq!(ExportFormat::from_path(Path::new("sessions.yaml")), None);
+    }
+
+    #[test]
+    fn parse_rejects_sessions_without_a_session_key() {
+        assert!(parse("{\"alpha\": {\"protocol\": \"wayland\"}}", ExportFormat::Json).is_err());
+        assert!(parse("{\"alpha\": {\"session\": null}}", ExportFormat::Json).is_err());
+    }
+
+    #[test]
+    fn import_refuses_existing_sessions_without_replace() {
+        let _fixture = testing::load_config(NESTED_SESSION_CONFIG);
+        let sessions = parse("[alpha]\nsession = \"molyuuctl-test-beta\"\n", ExportFormat::Toml).unwrap();
+        let err = import(sessions.clone(), false).unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::SessionExists));
+
+        import(sessions, true).unwrap();
+        assert_eq!(Session::config_table("alpha").unwrap()["session"].as_str(), Some("molyuuctl-test-beta"));
+    }
+}
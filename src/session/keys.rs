@@ -0,0 +1,90 @@
+use std::error::Error;
+
+use toml::Value;
+
+/// The type of a custom key stored in the table of a session.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyType {
+    String,
+    Bool,
+    Int,
+}
+
+impl KeyType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(Self::String),
+            "bool" => Some(Self::Bool),
+            "int" => Some(Self::Int),
+            _ => None,
+        }
+    }
+
+    /// Infer the type of a value given on the command line.
+    ///
+    /// `true` and `false` are booleans, anything that parses as a 64-bit integer is an integer and
+    /// everything else is a string.
+    pub fn infer(value: &str) -> Self {
+        if value == "true" || value == "false" {
+            Self::Bool
+        } else if value.parse::<i64>().is_ok() {
+            Self::Int
+        } else {
+            Self::String
+        }
+    }
+
+    /// Convert a value given on the command line to a TOML value of this type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` is not a valid boolean or integer for those types.
+    pub fn parse(&self, value: &str) -> Result<Value, Box<dyn Error>> {
+        match self {
+            Self::String => Ok(Value::String(String::from(value))),
+            Self::Bool => value.parse::<bool>()
+                .map(Value::Boolean)
+                .map_err(|_| Box::from(format!("Not a boolean (true or false): {value}"))),
+            Self::Int => value.parse::<i64>()
+                .map(Value::Integer)
+                .map_err(|_| Box::from(format!("Not an integer: {value}"))),
+        }
+    }
+}
+
+/// Print a value read from the table of a session, without quotes around strings.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infer_picks_bool_int_then_string() {
+        assert_eq!(KeyType::infer("true"), KeyType::Bool);
+        assert_eq!(KeyType::infer("-7"), KeyType::Int);
+        assert_eq!(KeyType::infer("True"), KeyType::String);
+        assert_eq!(KeyType::infer("1.5"), KeyType::String);
+    }
+
+    #[test]
+    fn parse_checks_the_value_against_the_type() {
+        assert_eq!(KeyType::Bool.parse("false").unwrap(), Value::Boolean(false));
+        assert_eq!(KeyType::Int.parse("12").unwrap(), Value::Integer(12));
+        assert_eq!(KeyType::String.parse("12").unwrap(), Value::String(String::from("12")));
+        assert!(KeyType::Bool.parse("yes").is_err());
+        assert!(KeyType::Int.parse("twelve").is_err());
+        assert_eq!(KeyType::from_name("float"), None);
+    }
+
+    #[test]
+    fn format_value_drops_string_quotes() {
+        assert_eq!(format_value(&Value::String(String::from("kde"))), "kde");
+        assert_eq!(format_value(&Value::Boolean(true)), "true");
+    }
+}
@@ -0,0 +1,294 @@
+use std::error::Error;
+use std::process::Command;
+
+use toml::{Table, Value};
+
+use crate::common::shell_words;
+use crate::config::GLOBAL_CONFIG;
+use crate::errors::session::SessionInstanceError;
+
+static MEMORY_MAX_KEY: &str = "memory_max";
+static CPU_QUOTA_KEY: &str = "cpu_quota";
+
+/// How the command of a session is launched.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Launcher {
+    /// Run the command through `bash -c`.
+    #[default]
+    Shell,
+    /// Split the command into words and execute it directly, without an intermediate shell.
+    Exec,
+    /// Run the command in a transient systemd scope, so it is tracked in its own cgroup.
+    SystemdRun,
+}
+
+impl Launcher {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "shell" => Some(Self::Shell),
+            "exec" => Some(Self::Exec),
+            "systemd-run" => Some(Self::SystemdRun),
+            _ => None,
+        }
+    }
+
+    /// Retrieve the launcher from the `launcher` key of the `[session]` section in the global
+    /// configuration, defaulting to `Launcher::Shell`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configured launcher is not supported.
+    pub fn from_config() -> Result<Self, Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+        match session_info.get("launcher").and_then(|launcher| launcher.as_str()) {
+            None => Ok(Self::default()),
+            Some(name) => Self::from_name(name)
+                .ok_or_else(|| Box::from(format!("Unknown session launcher: {name} (Supported: shell, exec, systemd-run)"))),
+        }
+    }
+
+    /// Check that the launcher is able to apply the resource limits of a session.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::ResourceLimitsUnsupported` if `limits` is not empty and
+    /// the launcher is not `Launcher::SystemdRun`.
+    pub fn check_limits(&self, limits: &ResourceLimits, reg_name: &str) -> Result<(), SessionInstanceError> {
+        if !limits.is_empty() && *self != Launcher::SystemdRun {
+            return Err(SessionInstanceError::ResourceLimitsUnsupported(String::from(reg_name)));
+        }
+        Ok(())
+    }
+
+    /// Compose the process that runs a session command.
+    ///
+    /// # Parameters
+    ///
+    /// * `command`: The `Exec` command of the session desktop file.
+    /// * `reg_name`: The register name of the session, used to describe the systemd scope.
+    /// * `limits`: The resource limits of the session, set as properties of the systemd scope.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::ResourceLimitsUnsupported` if `limits` is not empty and
+    /// the launcher is not `Launcher::SystemdRun`, or an error if the command cannot be split into
+    /// words or is empty.
+    pub fn build_command(&self, command: &str, reg_name: &str, limits: &ResourceLimits) -> Result<Command, Box<dyn Error>> {
+        self.check_limits(limits, reg_name)?;
+
+        match self {
+            Launcher::Shell => {
+                let mut process = Command::new("/bin/bash");
+                process.arg("-c").arg(command);
+                Ok(process)
+            }
+            Launcher::Exec => {
+                let words = shell_words::split(command)?;
+                let (program, args) = words.split_first().ok_or("Session command is empty")?;
+                let mut process = Command::new(program);
+                process.args(args);
+                Ok(process)
+            }
+            Launcher::SystemdRun => {
+                let words = shell_words::split(command)?;
+                if words.is_empty() {
+                    return Err(Box::from("Session command is empty"));
+                }
+                let mut process = Command::new("systemd-run");
+                process.arg("--scope")
+                    .arg("--quiet")
+                    .arg(format!("--description=molyuuctl session {reg_name}"))
+                    .args(limits.properties())
+                    .arg("--")
+                    .args(words);
+                Ok(process)
+            }
+        }
+    }
+}
+
+/// Resource limits of a session, set as properties of the systemd scope it runs in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// `MemoryMax=`, a size in bytes with an optional `K`, `M`, `G`, `T`, `P` or `E` suffix, a
+    /// percentage of the physical memory or `infinity`.
+    pub memory_max: Option<String>,
+    /// `CPUQuota=`, a percentage of the time of one CPU, e.g. `200%` for two CPUs.
+    pub cpu_quota: Option<String>,
+}
+
+impl ResourceLimits {
+    /// Read the `memory_max` and `cpu_quota` keys of a session table.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidResourceLimit` if one of them is malformed.
+    pub fn from_table(session_table: &Table) -> Result<Self, SessionInstanceError> {
+        let read = |key: &str| -> Result<Option<String>, SessionInstanceError> {
+            let value = match session_table.get(key) {
+                None => return Ok(None),
+                Some(Value::String(value)) => value.clone(),
+                Some(Value::Integer(value)) => value.to_string(),
+                Some(value) => return Err(SessionInstanceError::InvalidResourceLimit(format!("{key} = {value}"))),
+            };
+            Self::validate(key, value.as_str())?;
+            Ok(Some(value))
+        };
+
+        Ok(Self {
+            memory_max: read(MEMORY_MAX_KEY)?,
+            cpu_quota: read(CPU_QUOTA_KEY)?,
+        })
+    }
+
+    /// Write the limits to a session table, removing the keys of unset limits.
+    pub fn write_to(&self, session_table: &mut Table) {
+        for (key, value) in [(MEMORY_MAX_KEY, &self.memory_max), (CPU_QUOTA_KEY, &self.cpu_quota)] {
+            match value {
+                Some(value) => {
+                    session_table.insert(String::from(key), Value::String(value.clone()));
+                }
+                None => {
+                    session_table.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Check that the value of the `memory_max` or `cpu_quota` key is well-formed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidResourceLimit` if the value is malformed or `key` is
+    /// not a resource limit.
+    pub fn validate(key: &str, value: &str) -> Result<(), SessionInstanceError> {
+        let valid = match key {
+            _ if key == MEMORY_MAX_KEY => {
+                if value == "infinity" {
+                    true
+                } else if let Some(percentage) = value.strip_suffix('%') {
+                    percentage.parse::<f64>().is_ok_and(|percentage| percentage > 0.0 && percentage <= 100.0)
+                } else {
+                    let bytes = value.strip_suffix(['K', 'M', 'G', 'T', 'P', 'E']).unwrap_or(value);
+                    bytes.parse::<u64>().is_ok_and(|bytes| bytes > 0)
+                }
+            }
+            _ if key == CPU_QUOTA_KEY => value.strip_suffix('%')
+                .is_some_and(|percentage| percentage.parse::<u32>().is_ok_and(|percentage| percentage > 0)),
+            _ => false,
+        };
+        if !valid {
+            return Err(SessionInstanceError::InvalidResourceLimit(format!("{key} = {value}")));
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory_max.is_none() && self.cpu_quota.is_none()
+    }
+
+    /// The `--property` arguments of `systemd-run` that apply the limits.
+    pub fn properties(&self) -> Vec<String> {
+        let mut properties = Vec::new();
+        if let Some(memory_max) = &self.memory_max {
+            properties.push(format!("--property=MemoryMax={memory_max}"));
+        }
+        if let Some(cpu_quota) = &self.cpu_quota {
+            properties.push(format!("--property=CPUQuota={cpu_quota}"));
+        }
+        properties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+    use crate::testing;
+
+    fn invocation(launcher: Launcher, command: &str) -> (String, Vec<String>) {
+        let process = launcher.build_command(command, "alpha", &ResourceLimits::default()).unwrap();
+        let args = process.get_args().map(|arg: &OsStr| arg.to_string_lossy().to_string()).collect();
+        (process.get_program().to_string_lossy().to_string(), args)
+    }
+
+    #[test]
+    fn shell_launcher_runs_the_command_through_bash() {
+        assert_eq!(invocation(Launcher::Shell, "sway --unsupported-gpu 'x y'"),
+            (String::from("/bin/bash"), vec![String::from("-c"), String::from("sway --unsupported-gpu 'x y'")]));
+    }
+
+    #[test]
+    fn exec_launcher_runs_the_split_command() {
+        assert_eq!(invocation(Launcher::Exec, "sway --unsupported-gpu 'x y'"),
+            (String::from("sway"), vec![String::from("--unsupported-gpu"), String::from("x y")]));
+        assert!(Launcher::Exec.build_command("  ", "alpha", &ResourceLimits::default()).is_err());
+    }
+
+    #[test]
+    fn systemd_run_launcher_runs_the_command_in_a_scope() {
+        assert_eq!(invocation(Launcher::SystemdRun, "sway 'x y'"),
+            (String::from("systemd-run"), ["--scope", "--quiet", "--description=molyuuctl session alpha", "--", "sway", "x y"].map(String::from).to_vec()));
+        assert!(Launcher::SystemdRun.build_command("", "alpha", &ResourceLimits::default()).is_err());
+    }
+
+    #[test]
+    fn launcher_is_read_from_config() {
+        let fixture = testing::load_config("[session]\nlauncher = \"exec\"\n");
+        assert_eq!(Launcher::from_config().unwrap(), Launcher::Exec);
+        drop(fixture);
+
+        let fixture = testing::load_config("[session]\n");
+        assert_eq!(Launcher::from_config().unwrap(), Launcher::Shell);
+        drop(fixture);
+
+        let _fixture = testing::load_config("[session]\nlauncher = \"runit\"\n");
+        assert!(Launcher::from_config().is_err());
+    }
+
+    #[test]
+    fn resource_limits_become_scope_properties() {
+        let limits = ResourceLimits { memory_max: Some(String::from("4G")), cpu_quota: Some(String::from("200%")) };
+        let process = Launcher::SystemdRun.build_command("sway", "alpha", &limits).unwrap();
+        let args: Vec<String> = process.get_args().map(|arg: &OsStr| arg.to_string_lossy().to_string()).collect();
+        assert_eq!(args, ["--scope", "--quiet", "--description=molyuuctl session alpha", "--property=MemoryMax=4G", "--property=CPUQuota=200%", "--", "sway"].map(String::from).to_vec());
+    }
+
+    #[test]
+    fn resource_limits_need_systemd_run() {
+        let limits = ResourceLimits { memory_max: None, cpu_quota: Some(String::from("50%")) };
+        let err = Launcher::Shell.build_command("sway", "alpha", &limits).unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::ResourceLimitsUnsupported(String::from("alpha"))));
+        assert!(Launcher::Exec.check_limits(&ResourceLimits::default(), "alpha").is_ok());
+    }
+
+    #[test]
+    fn resource_limit_values_are_validated() {
+        for value in ["infinity", "50%", "512M", "1073741824"] {
+            assert!(ResourceLimits::validate(MEMORY_MAX_KEY, value).is_ok(), "{value}");
+        }
+        for value in ["0", "150%", "4GB", "-1", ""] {
+            assert!(ResourceLimits::validate(MEMORY_MAX_KEY, value).is_err(), "{value}");
+        }
+        assert!(ResourceLimits::validate(CPU_QUOTA_KEY, "200%").is_ok());
+        assert!(ResourceLimits::validate(CPU_QUOTA_KEY, "200").is_err());
+        assert!(ResourceLimits::validate(CPU_QUOTA_KEY, "0%").is_err());
+        assert!(ResourceLimits::validate("tasks_max", "10").is_err());
+    }
+
+    #[test]
+    fn resource_limits_round_trip_through_a_session_table() {
+        let table: Table = "memory_max = 1024\ncpu_quota = \"25%\"\n".parse().unwrap();
+        let limits = ResourceLimits::from_table(&table).unwrap();
+        assert_eq!(limits, ResourceLimits { memory_max: Some(String::from("1024")), cpu_quota: Some(String::from("25%")) });
+
+        let mut table = table;
+        ResourceLimits { memory_max: None, cpu_quota: Some(String::from("50%")) }.write_to(&mut table);
+        assert!(!table.contains_key(MEMORY_MAX_KEY));
+        assert_eq!(table.get(CPU_QUOTA_KEY), Some(&Value::String(String::from("50%"))));
+
+        let table: Table = "cpu_quota = true\n".parse().unwrap();
+        assert!(ResourceLimits::from_table(&table).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::session::{Protocol, Session};
+
+/// Output format of `session list`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns with headers, for humans.
+    Table,
+    /// Register names only, one per line, for scripts.
+    Plain,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "table" => Some(Self::Table),
+            "plain" => Some(Self::Plain),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// The format to print in, degrading `Table` to `Plain` if the output is not a terminal.
+    pub fn for_output(self, is_terminal: bool) -> Self {
+        match self {
+            Self::Table if !is_terminal => Self::Plain,
+            format => format,
+        }
+    }
+}
+
+/// What `session list` shows about a registered session.
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub name: String,
+    pub session: String,
+    pub protocol: Option<Protocol>,
+    pub default: bool,
+}
+
+impl SessionSummary {
+    /// Collect the summaries of all registered sessions, sorted by register name.
+    ///
+    /// Sessions are read from the configuration as stored, so a session whose desktop file is
+    /// missing is still listed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the default session cannot be read from the global configuration.
+    pub fn collect() -> Result<Vec<Self>, Box<dyn Error>> {
+        let default_name = Session::get_default_name()?;
+        Ok(Session::list_registered().into_iter()
+            .map(|name| {
+                let session = Session::config_table(name.as_str()).ok()
+                    .and_then(|table| table.get("session").and_then(|session| session.as_str()).map(String::from))
+                    .unwrap_or_default();
+                Self {
+                    protocol: Session::get_registered_protocol(name.as_str()),
+                    default: default_name.as_deref() == Some(name.as_str()),
+                    session,
+                    name,
+                }
+            })
+            .collect())
+    }
+}
+
+/// What `session stats` shows about the registered sessions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionStats {
+    pub total: usize,
+    pub x11: usize,
+    pub wayland: usize,
+    /// Sessions whose protocol is not stored and cannot be detected.
+    pub unknown: usize,
+    pub default: Option<String>,
+    /// The oneshot session, if it is set and not started yet.
+    pub oneshot: Option<String>,
+    /// Whether Auto Login is enabled in the molyuuctl config.
+    pub autologin: bool,
+}
+
+impl SessionStats {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "total": self.total,
+            "x11": self.x11,
+            "wayland": self.wayland,
+            "unknown": self.unknown,
+            "default": self.default,
+            "oneshot": self.oneshot,
+            "autologin": self.autologin,
+        })
+    }
+}
+
+impl Display for SessionStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "sessions: {}", self.total)?;
+        writeln!(f, "x11: {}", self.x11)?;
+        writeln!(f, "wayland: {}", self.wayland)?;
+        writeln!(f, "unknown: {}", self.unknown)?;
+        writeln!(f, "default: {}", self.default.as_deref().unwrap_or("none"))?;
+        writeln!(f, "oneshot: {}", self.oneshot.as_deref().unwrap_or("none"))?;
+        writeln!(f, "autologin: {}", if self.autologin { "enabled" } else { "disabled" })
+    }
+}
+
+/// Format session summaries for `session list`.
+///
+/// # Parameters
+///
+/// * `sessions`: The sessions to format.
+/// * `format`: The output format.
+///
+/// # Returns
+///
+/// Returns the formatted output, terminated by a newline if it is not empty.
+pub fn format_sessions(sessions: &[SessionSummary], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Plain => sessions.iter()
+            .map(|summary| format!("{}\n", summary.name))
+            .collect(),
+        OutputFormat::Json => {
+            let json: Vec<_> = sessions.iter()
+                .map(|summary| serde_json::json!({
+                    "name": summary.name,
+                    "session": summary.session,
+                    "protocol": summary.protocol.map(|protocol| protocol.as_str()),
+                    "default": summary.default,
+                }))
+                .collect();
+            format!("{}\n", serde_json::Value::from(json))
+        }
+        OutputFormat::Table => {
+            if sessions.is_empty() {
+                return String::new();
+            }
+
+            let rows: Vec<[String; 4]> = sessions.iter()
+                .map(|summary| [
+                    summary.name.clone(),
+                    summary.session.clone(),
+                    summary.protocol.map_or(String::from("unknown"), |protocol| protocol.to_string()),
+                    String::from(if summary.default { "*" } else { "" }),
+                ])
+                .collect();
+            let header = [
+                String::from("NAME"),
+                String::from("SESSION"),
+                String::from("PROTOCOL"),
+                String::from("DEFAULT"),
+            ];
+
+            let mut widths = [0usize; 4];
+            for row in std::iter::once(&header).chain(rows.iter()) {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.len());
+                }
+            }
+
+            std::iter::once(&header).chain(rows.iter())
+                .map(|row| {
+                    let line = row.iter().zip(widths)
+                        .map(|(cell, width)| format!("{cell:<width$}"))
+                        .collect::<Vec<_>>()
+                        .join("  ");
+                    format!("{}\n", line.trim_end())
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sessions() -> Vec<SessionSummary> {
+        vec![
+            SessionSummary { name: String::from("alpha"), session: String::from("plasma"), protocol: Some(Protocol::Wayland), default: true },
+            SessionSummary { name: String::from("gnome-classic"), session: String::from("gnome-xorg"), protocol: None, default: false },
+        ]
+    }
+
+    #[test]
+    fn plain_format_lists_names() {
+        assert_eq!(format_sessions(&sessions(), OutputFormat::Plain), "alpha\ngnome-classic\n");
+    }
+
+    #[test]
+    fn table_format_aligns_columns() {
+        assert_eq!(format_sessions(&sessions(), OutputFormat::Table),
+            "NAME           SESSION     PROTOCOL  DEFAULT\n\
+             alpha          plasma      wayland   *\n\
+             gnome-classic  gnome-xorg  unknown\n");
+        assert_eq!(format_sessions(&[], OutputFormat::Table), "");
+    }
+
+    #[test]
+    fn json_format_has_every_field() {
+        let json: serde_json::Value = serde_json::from_str(format_sessions(&sessions(), OutputFormat::Json).as_str()).unwrap();
+        assert_eq!(json, serde_json::json!([
+            {"name": "alpha", "session": "plasma", "protocol": "wayland", "default": true},
+            {"name": "gnome-classic", "session": "gnome-xorg", "protocol": null, "default": false},
+        ]));
+    }
+
+    #[test]
+    fn table_degrades_to_plain_without_a_terminal() {
+        assert_eq!(OutputFormat::Table.for_output(true), OutputFormat::Table);
+        assert_eq!(OutputFormat::Table.for_output(false), OutputFormat::Plain);
+        assert_eq!(OutputFormat::Json.for_output(false), OutputFormat::Json);
+    }
+
+    #[test]
+    fn summaries_are_collected_from_config() {
+        let _fixture = crate::testing::load_sessions_config();
+        let summaries = SessionSummary::collect().unwrap();
+        let names: Vec<_> = summaries.iter().map(|summary| (summary.name.as_str(), summary.session.as_str(), summary.default)).collect();
+        assert_eq!(names, [("alpha", "molyuuctl-test-alpha", true), ("beta", "molyuuctl-test-beta", false)]);
+    }
+
+    #[test]
+    fn stats_count_sessions_by_protocol() {
+        let contents = crate::testing::SESSIONS_CONFIG
+            .replace("[login.autologin]\n", "[login.autologin]\nenable = true\n")
+            .replace("default = \"alpha\"\n", "default = \"alpha\"\noneshot_session = \"gamma\"\noneshot_started = false\n")
+            + "\n[session.gamma]\nsession = \"molyuuctl-test-gamma\"\nprotocol = \"x11\"\n\n[session.delta]\nsession = \"molyuuctl-test-missing\"\n";
+        let _fixture = crate::testing::load_config(contents.as_str());
+
+        let stats = Session::stats().unwrap();
+        assert_eq!(stats, SessionStats {
+            total: 4,
+            x11: 1,
+            wayland: 2,
+            unknown: 1,
+            default: Some(String::from("alpha")),
+            oneshot: Some(String::from("gamma")),
+            autologin: true,
+        });
+        assert_eq!(stats.to_string(), "sessions: 4\nx11: 1\nwayland: 2\nunknown: 1\ndefault: alpha\noneshot: gamma\nautologin: enabled\n");
+        assert_eq!(stats.to_json()["oneshot"], "gamma");
+    }
+
+    #[test]
+    fn started_oneshot_is_not_counted() {
+        let _fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG
+            .replace("default = \"alpha\"\n", "oneshot_session = \"beta\"\noneshot_started = true\n").as_str());
+
+        let stats = Session::stats().unwrap();
+        assert_eq!((stats.total, stats.wayland), (2, 2));
+        assert_eq!((stats.default, stats.oneshot, stats.autologin), (None, None, false));
+    }
+}
@@ -1,6 +1,12 @@
 pub use protocol::Protocol;
-pub use session::Session;
+pub use launcher::ResourceLimits;
+pub use session::{Session, StartOptions};
 
 mod session;
 mod protocol;
+mod launcher;
+pub mod list;
+pub mod keys;
+pub mod export;
+pub mod watch;
 
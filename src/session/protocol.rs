@@ -1,5 +1,65 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::errors::session::SessionInstanceError;
+
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
 pub enum Protocol {
     X11,
     Wayland,
-}
\ No newline at end of file
+}
+
+impl Protocol {
+    /// The name of the protocol as stored in the configuration.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::X11 => "x11",
+            Protocol::Wayland => "wayland",
+        }
+    }
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = SessionInstanceError;
+
+    /// Parse `x11` or `wayland`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "x11" => Ok(Protocol::X11),
+            "wayland" => Ok(Protocol::Wayland),
+            _ => Err(SessionInstanceError::UnknownProtocol),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_round_trips_through_its_name() {
+        for protocol in [Protocol::X11, Protocol::Wayland] {
+            assert_eq!(protocol.to_string().parse::<Protocol>(), Ok(protocol));
+            assert_eq!(protocol.to_string(), protocol.as_str());
+        }
+    }
+
+    #[test]
+    fn protocol_is_parsed_ignoring_case() {
+        assert_eq!("Wayland".parse::<Protocol>(), Ok(Protocol::Wayland));
+        assert_eq!("X11".parse::<Protocol>(), Ok(Protocol::X11));
+    }
+
+    #[test]
+    fn invalid_protocol_is_unknown() {
+        for name in ["", "xorg", "wayland ", "auto"] {
+            assert_eq!(name.parse::<Protocol>(), Err(SessionInstanceError::UnknownProtocol), "{name:?}");
+        }
+    }
+}
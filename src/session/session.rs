@@ -1,53 +1,109 @@
+use std::env;
 use std::error::Error;
-use std::fs;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::string::String;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use ini::Ini;
+use ini::{Ini, Properties};
+use libc::pid_t;
 use log::{info, warn};
 use toml::{Table, Value};
 
 use crate::common::macros::toml_macros;
-use crate::config::GLOBAL_CONFIG;
+use crate::common::duration::parse_duration;
+use crate::common::shell_words;
+use crate::config::{Configuration, GLOBAL_CONFIG};
 use crate::errors::session::SessionInstanceError;
-use crate::login::manager::get_current_manager;
+use crate::explain::{self, Stage};
+use crate::login::manager::{consume_autologin_once, get_current_manager_if_set, is_autologin_enabled_in_config, is_autologin_once};
+use crate::session::launcher::{Launcher, ResourceLimits};
+use crate::session::list::SessionStats;
 use crate::session::protocol::Protocol;
-use crate::system::lock::Lock;
+use crate::session::watch::{self, RestartTracker, WatchPolicy};
+use crate::system::lock::{self, Lock, LockState, LOCK_RUN_DIR};
+use crate::system::notify;
+use crate::system::privilege;
+use crate::system::user::UserInfo;
+use crate::system::SYSTEMCTL;
+use crate::system::process::{self, Termination};
 
 static SYSTEM_XSESSIONS_PATH: &'static str = "/usr/share/xsessions";
 static SYSTEM_WAYLAND_SESSIONS_PATH: &'static str = "/usr/share/wayland-sessions";
 static MOLYUUCTL_SESSION_STARTUP_LOCK: &'static str = "molyuuctl-session-startup-lock";
+static SEAT_ENV: &str = "XDG_SEAT";
+static SESSION_NAME_ENV: &str = "MOLYUUCTL_SESSION";
+static CONFIRM_KEY: &str = "X-Molyuu-Confirm";
+static DEFAULT_SEAT: &str = "seat0";
+static SESSION_TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(5);
+static SESSION_SIGNAL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+static SESSION_PREREQUISITE_TIMEOUT: Duration = Duration::from_secs(30);
+static SESSION_LOG_ROTATED_SUFFIX: &str = ".old";
+static DEFAULT_KEYS: [&str; 3] = ["default", "default_x11", "default_wayland"];
+// The default session replaced by `swap_default`, restored by `revert_default`
+static PREVIOUS_DEFAULT_KEY: &str = "previous_default";
+static STRUCTURAL_KEYS: [&str; 2] = ["session", "protocol"];
+static MAX_REG_NAME_LENGTH: usize = 64;
+// Keys of the `[session]` section that are settings rather than registered sessions
+static RESERVED_REG_NAMES: [&str; 11] = ["default", "default_x11", "default_wayland", "oneshot_session", "oneshot_started",
+    "previous_default", "launcher", "log_output", "xsessions_dirs", "wayland_sessions_dirs", "logout_grace_period"];
+
+pub type ValidationResult = (String, Result<(), Box<dyn Error>>);
+pub type DryRunStep = (&'static str, Result<String, Box<dyn Error>>);
+
+/// Options that control how a session is started.
+#[derive(Debug, Default, Clone)]
+pub struct StartOptions {
+    /// Terminate the running session, if any, before starting the new one.
+    pub replace: bool,
+    /// Run the session as this user instead of the current one.
+    pub user: Option<String>,
+    /// Return as soon as the session is launched instead of waiting for it to exit.
+    pub detach: bool,
+    /// Start the session on this seat instead of the current one, see `Session::current_seat`.
+    pub seat: Option<String>,
+    /// Start a session marked with `X-Molyuu-Confirm=true` without asking for confirmation.
+    pub assume_yes: bool,
+    /// Redirect the output of the session to this file instead of the directory in
+    /// `[session].log_output`, see `Session::log_output_path`.
+    pub log_output: Option<PathBuf>,
+    /// Do not log which session is launched and how, for callers that feed into a login flow.
+    /// Warnings and errors are still logged.
+    pub quiet_session: bool,
+    /// Start the default session of this protocol when no session is named, see
+    /// `Session::get_default_name_for`.
+    pub protocol: Option<Protocol>,
+}
 
 pub struct Session {
     reg_name: String,
     real_name: String,
     logout_command: Option<String>,
     protocol: Protocol,
+    requires: Vec<String>,
+    action: Option<String>,
+    fallback: Option<String>,
+    vt: Option<u32>,
+    display_server_args: Vec<String>,
+    limits: ResourceLimits,
 }
 
 impl Session {
     pub fn new(reg_name: String, real_name: String, logout_command: Option<String>, protocol: Option<Protocol>) -> Result<Self, Box<dyn Error>> {
-        let detected_protocol = match protocol {
-            Some(Protocol::X11) => {
-                if !Path::new(format!("{SYSTEM_XSESSIONS_PATH}/{reg_name}.desktop").as_str()).exists() {
-                    return Err(Box::from(SessionInstanceError::SessionNotFoundInSystem));
-                }
-                Protocol::X11
-            }
-            Some(Protocol::Wayland) => {
-                if !Path::new(format!("{SYSTEM_WAYLAND_SESSIONS_PATH}/{reg_name}.desktop").as_str()).exists() {
-                    return Err(Box::from(SessionInstanceError::SessionNotFoundInSystem));
-                }
-                Protocol::Wayland
-            }
-            _ => {
-                let detected_protocol = Self::find_session_in_system(real_name.as_str());
-                if detected_protocol.is_err() {
-                    return Err(Box::from(SessionInstanceError::SessionNotFoundInSystem));
+        Self::validate_real_name(real_name.as_str())?;
+        let (real_name, detected_protocol) = match protocol {
+            Some(protocol) => {
+                match Self::resolve_session_name(real_name.as_str(), protocol) {
+                    Some(canonical_name) => (canonical_name, protocol),
+                    None => return Err(Box::from(SessionInstanceError::SessionNotFoundInSystem(real_name.clone()))),
                 }
-                detected_protocol?
             }
+            None => Self::find_session_in_system(real_name.as_str())?,
         };
 
         Ok(Self {
@@ -55,6 +111,12 @@ impl Session {
             real_name,
             logout_command,
             protocol: detected_protocol,
+            requires: Vec::new(),
+            action: None,
+            fallback: None,
+            vt: None,
+            display_server_args: Vec::new(),
+            limits: ResourceLimits::default(),
         })
     }
 
@@ -90,464 +152,3358 @@ impl Session {
             return Err(Box::from(SessionInstanceError::SessionNotFoundInConfig));
         }
 
-        let mut session_real_name = String::new();
-        let mut session_logout_command = None;
-        let mut session_protocol = None;
-        for session in session_info {
-            if session.0 == session_reg_name.as_str() {
-                session_real_name = String::from(session.1["session"].as_str().unwrap());
-                let try_get_protocol = session.1.get("protocol");
-                let try_get_logout_command = session.1.get("logout_command");
-                if try_get_protocol.is_none() {
-                    session_protocol = Some(Self::find_session_in_system(session_real_name.as_str())?)
-                } else {
-                    session_protocol = match try_get_protocol.unwrap().as_str() {
-                        Some("x11") => Some(Protocol::X11),
-                        Some("wayland") => Some(Protocol::Wayland),
-                        _ => return Err(Box::from(SessionInstanceError::UnknownProtocol))
-                    }
-                }
-                if try_get_logout_command.is_some() {
-                    session_logout_command = Some(String::from(try_get_logout_command.unwrap().as_str().unwrap()));
-                }
-                break;
+        let session_table = Self::config_table(session_reg_name.as_str())?;
+        Self::validate_table(session_reg_name.as_str(), &session_table)?;
+        let session_real_name = String::from(session_table["session"].as_str().unwrap());
+        let mut session_protocol = match session_table.get("protocol") {
+            None => Some(Self::find_session_in_system(session_real_name.as_str())?.1),
+            Some(protocol) => {
+                explain::record(Stage::Protocol, || format!("Session {session_reg_name} has the stored protocol {}", protocol.as_str().unwrap_or_default()));
+                Some(protocol.as_str().unwrap_or_default().parse::<Protocol>()?)
             }
         };
+        let session_logout_command = session_table.get("logout_command")
+            .map(|logout_command| String::from(logout_command.as_str().unwrap()));
+        let session_action = session_table.get("action")
+            .and_then(|action| action.as_str())
+            .map(String::from);
+        let session_fallback = session_table.get("fallback")
+            .and_then(|fallback| fallback.as_str())
+            .map(String::from);
+        let session_vt = session_table.get("vt").map(Self::parse_vt).transpose()?;
+        let session_limits = ResourceLimits::from_table(&session_table)?;
+        let session_requires = session_table.get("requires")
+            .and_then(|requires| requires.as_array())
+            .map(|requires| requires.iter()
+                .filter_map(|unit| unit.as_str())
+                .map(String::from)
+                .collect())
+            .unwrap_or_default();
+        let session_display_server_args = session_table.get("display_server_args")
+            .and_then(|args| args.as_array())
+            .map(|args| args.iter()
+                .filter_map(|arg| arg.as_str())
+                .map(String::from)
+                .collect())
+            .unwrap_or_default();
+
+        // The stored protocol may be outdated if the session only ships a desktop file for the
+        // other protocol after an upgrade, fall back to detection. The correction is only stored
+        // once the session is started, see `store_detected_protocol`
+        if let Some(stored_protocol) = session_protocol {
+            if !Self::exists_in_system(session_real_name.as_str(), stored_protocol) {
+                if let Ok((_, detected_protocol)) = Self::find_session_in_system(session_real_name.as_str()) {
+                    warn!("Session {} is no longer available under its stored protocol, switching to {}", session_reg_name, detected_protocol);
+                    explain::record(Stage::Protocol, || format!("Session {session_reg_name} is not installed as {stored_protocol}, switching to {detected_protocol}"));
+                    session_protocol = Some(detected_protocol);
+                }
+            }
+        }
 
         Ok(Self {
             reg_name: String::from(session_reg_name),
             real_name: String::from(session_real_name),
             logout_command: session_logout_command,
             protocol: session_protocol.unwrap(),
+            requires: session_requires,
+            action: session_action,
+            fallback: session_fallback,
+            vt: session_vt,
+            display_server_args: session_display_server_args,
+            limits: session_limits,
         })
     }
 
-    /// Search session in the system.
+    /// Check the structure of the configuration table of a registered session, the same rules
+    /// `register` enforces for the values it writes.
     ///
-    /// This function searches for the specified session in the system by looking in the following directories:
-    /// 1. /usr/share/xsessions
-    /// 2. /usr/share/wayland-sessions.
-    ///
-    /// # Parameters
-    /// - `real_session_name`: The real session name of the session to search for.
-    ///
-    /// # Returns
-    /// A Result containing the Protocol of the session if found, or an error if the session is not found or
-    /// if there is an issue with accessing the system directories.
+    /// The register name and the session name must be valid, see `validate_reg_name` and
+    /// `validate_real_name`, and the protocol, VT and resource limits must parse if they are set.
+    /// Whether the desktop file exists is not checked, see `validate`.
     ///
     /// # Errors
-    /// Returns an Error if session is not found in searching paths.
-    pub fn find_session_in_system(real_session_name: &str) -> Result<Protocol, Box<dyn Error>> {
-        let protocol = if Path::new(format!("{SYSTEM_XSESSIONS_PATH}/{real_session_name}.desktop").as_str()).exists() {
-            Protocol::X11
-        } else if Path::new(format!("{SYSTEM_WAYLAND_SESSIONS_PATH}/{real_session_name}.desktop").as_str()).exists() {
-            Protocol::Wayland
-        } else {
-            return Err(Box::from(SessionInstanceError::SessionNotFoundInSystem));
-        };
-
-        Ok(protocol)
+    ///
+    /// Returns an error describing the first problem found.
+    pub fn validate_table(reg_name: &str, session_table: &Table) -> Result<(), Box<dyn Error>> {
+        Self::validate_reg_name(reg_name)?;
+        let real_name = session_table.get("session")
+            .and_then(|session| session.as_str())
+            .ok_or(SessionInstanceError::KeyNotSet(String::from("session")))?;
+        Self::validate_real_name(real_name)?;
+        if let Some(protocol) = session_table.get("protocol") {
+            if protocol.as_str().and_then(|protocol| protocol.parse::<Protocol>().ok()).is_none() {
+                return Err(Box::from(format!("Unsupported protocol: {protocol}")));
+            }
+        }
+        if let Some(vt) = session_table.get("vt") {
+            Self::parse_vt(vt)?;
+        }
+        ResourceLimits::from_table(session_table)?;
+        Ok(())
     }
 
-    /// Start the session as specified by the desktop file, executing the appropriate command.
+    /// Retrieve a copy of the complete configuration table of a registered session.
     ///
-    /// This function loads the session desktop file, extracts the necessary information, and executes
-    /// the specified session command using a child process.
+    /// Unlike `from_config`, which only keeps the fields a `Session` needs, the returned table
+    /// contains every key stored for the session, including ones this version does not know about.
     ///
-    /// # Returns
+    /// # Parameters
     ///
-    /// Returns a `Result` indicating the success or failure of starting the session. If the session
-    /// is successfully started, it returns `Ok(())`. If an error occurs during the process, it
-    /// returns an error message wrapped in a `Box<dyn Error>`.
+    /// * `reg_name`: The register name of the session.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are issues encountered during the process of starting the session,
-    /// such as failure to load the session configuration file, inability to retrieve necessary
-    /// information from the desktop file, or failure to execute the session command.
-    pub fn start(&self) -> Result<(), Box<dyn Error>> {
-        // Create Lock
-        let mut molyuuctl_lock = Lock::new(MOLYUUCTL_SESSION_STARTUP_LOCK, Some(self.reg_name.clone()));
-        molyuuctl_lock.lock()?;
-
-        // Load the session desktop file
-        let session_file = Ini::load_from_file(format!(
-            "{}/{}.desktop",
-            if self.protocol == Protocol::X11 { SYSTEM_XSESSIONS_PATH } else { SYSTEM_WAYLAND_SESSIONS_PATH },
-            self.real_name
-        ))?;
-
-        // Extract the necessary information from the desktop file
-        let desktop_section = session_file.section(Some("Desktop Entry")).unwrap();
-        let command = desktop_section.get("Exec").unwrap();
-        info!("Target Session: {}", desktop_section.get("Name").unwrap());
-        info!("Executing Session Command: {}", command);
-
-        // Execute the session command
-        Command::new("/bin/bash")
-            .arg("-c")
-            .arg(command)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .output()
-            .expect("Failed to launch session");
-
-        // Unlock and detroy the lock.
-        // If fails to unlock, this is an unexpected exception 
-        // that cannot be handled, and panic should occur at this point.
-        drop(molyuuctl_lock);
-        Ok(())
+    /// Returns `SessionInstanceError::SessionNotFoundInConfig` if no session is registered under
+    /// `reg_name`.
+    pub fn config_table(reg_name: &str) -> Result<Table, Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+        match session_info.get(reg_name).and_then(|session| session.as_table()) {
+            Some(session_table) => Ok(session_table.clone()),
+            None => Err(Box::from(SessionInstanceError::SessionNotFoundInConfig)),
+        }
     }
 
-
-    /// Start either a one-shot session or the default session as specified in the global configuration.
+    /// Retrieve a key from the table of a registered session.
     ///
-    /// This function retrieves session information from the global configuration, checks if a
-    /// one-shot session is configured and not already started. If so, it starts the specified
-    /// one-shot session; otherwise, it starts the default session. After starting the session, it
-    /// updates the login manager configuration accordingly.
+    /// Unlike the dedicated getters, this reads any key, including ones this version does not
+    /// know about.
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// Returns a `Result` indicating the success or failure of starting the session. If the session
-    /// is successfully started, it returns `Ok(())`. If an error occurs during the process, it
-    /// returns an error message wrapped in a `Box<dyn Error>`.
+    /// Returns `SessionInstanceError::SessionNotFoundInConfig` if no session is registered under
+    /// `reg_name`, or `SessionInstanceError::KeyNotSet` if the key is not set.
+    pub fn get_key(reg_name: &str, key: &str) -> Result<Value, Box<dyn Error>> {
+        Self::config_table(reg_name)?.remove(key)
+            .ok_or_else(|| Box::from(SessionInstanceError::KeyNotSet(String::from(key))))
+    }
+
+    /// Set a key in the table of a registered session, as an escape hatch for keys without a
+    /// dedicated command.
+    ///
+    /// # Parameters
+    ///
+    /// * `reg_name`: The register name of the session.
+    /// * `key`: The key to set.
+    /// * `value`: The value to store.
+    /// * `force`: Allow changing the keys the session is started from, `session` and `protocol`.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are issues encountered during the process of starting the session,
-    /// such as failure to retrieve session information from the global configuration, failure to
-    /// update the configuration, or errors encountered while starting the session itself.
-    pub fn start_oneshot_or_default_session() -> Result<(), Box<dyn Error>> {
-        // Retrieve session information from the global configuration
+    /// Returns `SessionInstanceError::SessionNotFoundInConfig` if no session is registered under
+    /// `reg_name`, `SessionInstanceError::StructuralKey` if `key` is `session` or `protocol` and
+    /// `force` is not set, or an error if the global configuration cannot be saved.
+    pub fn set_key(reg_name: &str, key: &str, value: Value, force: bool) -> Result<(), Box<dyn Error>> {
+        Self::check_structural_key(key, force)?;
         let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
-        let oneshot_session = session_info.get("oneshot_session");
-        let oneshot_started = session_info.get("oneshot_started");
-
-        // Check if a one-shot session is configured and not already started, if so,
-        // start the configured one-shot session, else start the default session.
-        match (oneshot_session, oneshot_started) {
-            (Some(session), Some(started)) if !started.as_bool().unwrap() => {
-                let session_to_start = session.as_str().unwrap().to_string();
-                session_info["oneshot_started"] = Value::Boolean(true);
-                GLOBAL_CONFIG.get_mut().unwrap().save_config();
-                get_current_manager()?.save_config()?;
-
-                Self::from_config(Some(session_to_start.as_str()))?.start()?
-            }
-            _ => Self::from_config(None)?.start()?,
-        }
+        let Some(session_table) = session_info.get_mut(reg_name).and_then(|session| session.as_table_mut()) else {
+            return Err(Box::from(SessionInstanceError::SessionNotFoundInConfig));
+        };
+        toml_macros::change_or_insert!(session_table, key, value);
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
         Ok(())
     }
 
-    /// Execute the logout command to end the current user session.
-    ///
-    /// This function executes the logout command, if set, to end the current user session. If no
-    /// logout command is configured, it returns an error indicating that the logout command is not
-    /// set, and the logout operation cannot be performed.
-    ///
-    /// # Returns
-    ///
-    /// Returns a `Result` indicating the success or failure of the logout operation. If the logout
-    /// operation is successfully executed, it returns `Ok(())`. If an error occurs during the process,
-    /// it returns an error message wrapped in a `Box<dyn Error>`.
+    /// Remove a key from the table of a registered session.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are issues encountered during the process of executing the logout
-    /// command, such as failure to retrieve the logout command or errors encountered while executing
-    /// the command itself.
-    pub fn logout(&self) -> Result<(), Box<dyn Error>> {
-        // Check if a logout command is set
-        if self.logout_command.is_none() {
-            return Err(Box::from(SessionInstanceError::LogoutCommandNotSet));
+    /// Returns the same errors as `set_key`, and `SessionInstanceError::KeyNotSet` if the key is
+    /// not set.
+    pub fn unset_key(reg_name: &str, key: &str, force: bool) -> Result<(), Box<dyn Error>> {
+        Self::check_structural_key(key, force)?;
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let Some(session_table) = session_info.get_mut(reg_name).and_then(|session| session.as_table_mut()) else {
+            return Err(Box::from(SessionInstanceError::SessionNotFoundInConfig));
+        };
+        if session_table.remove(key).is_none() {
+            return Err(Box::from(SessionInstanceError::KeyNotSet(String::from(key))));
         }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        Ok(())
+    }
 
-        // Execute the logout command
-        Command::new("/bin/bash")
-            .arg("-c")
-            .arg(self.logout_command.as_ref().unwrap().as_str())
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .output()
-            .expect("Failed to logout session");
-
+    fn check_structural_key(key: &str, force: bool) -> Result<(), Box<dyn Error>> {
+        if !force && STRUCTURAL_KEYS.contains(&key) {
+            return Err(Box::from(SessionInstanceError::StructuralKey(String::from(key))));
+        }
         Ok(())
     }
 
-    /// Rename the session with a new name.
+    /// Search session in the system.
     ///
-    /// This function renames the session by updating its registered name in the global configuration.
-    /// It first checks if a session with the new name already exists; if so, it returns an error.
-    /// Otherwise, it updates the session's name in the configuration, updates the default session
-    /// if necessary, and saves the configuration.
+    /// This function searches for the specified session in the system by looking in the following directories:
+    /// 1. /usr/share/xsessions and the directories in `[session].xsessions_dirs`
+    /// 2. /usr/share/wayland-sessions and the directories in `[session].wayland_sessions_dirs`.
     ///
-    /// # Parameters
+    /// A trailing `.desktop` in the name is ignored, and the name is matched case-insensitively if
+    /// there is no exact match.
     ///
-    /// * `new_name`: The new name to assign to the session.
+    /// # Parameters
+    /// - `real_session_name`: The real session name of the session to search for.
     ///
     /// # Returns
-    ///
-    /// Returns a `Result` indicating the success or failure of renaming the session. If the session
-    /// is successfully renamed, it returns `Ok(())`. If an error occurs during the process, it
-    /// returns an error message wrapped in a `Box<dyn Error>`.
+    /// A Result containing the canonical session name, i.e. the name of the desktop file without
+    /// extension, and the Protocol of the session if found, or an error if the session is not found.
     ///
     /// # Errors
-    ///
-    /// Returns an error if the new name conflicts with an existing session name or if there are
-    /// issues encountered during the process of renaming the session or saving the configuration.
-    pub fn rename(&mut self, new_name: &str) -> Result<(), Box<dyn Error>> {
-        // Retrieve session information from the global configuration
-        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
-
-        // Check if a session with the new name already exists
-        if session_info.get(new_name).is_some() {
-            return Err(Box::from(SessionInstanceError::SessionExists));
-        }
-
-        // Store the current name of the session
-        let old_name = self.reg_name.clone();
-
-        // Retrieve information about the current session
-        let current_session_info = session_info.get(self.reg_name.as_str()).unwrap();
-
-        // Update session name in the configuration
-        session_info.insert(String::from(new_name), current_session_info.clone());
-        session_info.remove(&self.reg_name);
-        self.reg_name = String::from(new_name);
-
-        // Update default session if necessary
-        if let Some(default_session) = session_info.get("default") {
-            if default_session.as_str() == Some(old_name.as_str()) {
-                session_info["default"] = Value::String(self.reg_name.clone());
+    /// Returns an Error if session is not found in searching paths.
+    pub fn find_session_in_system(real_session_name: &str) -> Result<(String, Protocol), Box<dyn Error>> {
+        Self::validate_real_name(real_session_name)?;
+        for protocol in [Protocol::X11, Protocol::Wayland] {
+            if let Some(canonical_name) = Self::resolve_session_name(real_session_name, protocol) {
+                explain::record(Stage::Protocol, || {
+                    let directory = Self::find_desktop_file(canonical_name.as_str(), protocol)
+                        .and_then(|path| path.parent().map(|directory| directory.display().to_string()))
+                        .unwrap_or_default();
+                    format!("Session {real_session_name} is detected as {protocol}, found {canonical_name}.desktop in {directory}")
+                });
+                return Ok((canonical_name, protocol));
             }
         }
+        Err(Box::from(SessionInstanceError::SessionNotFoundInSystem(String::from(real_session_name))))
+    }
 
-        // Save the updated configuration
-        GLOBAL_CONFIG.get_mut().unwrap().save_config();
+    /// Resolve a session name to the name of its desktop file for the specified protocol.
+    ///
+    /// An exact match is preferred, otherwise the session directory is scanned for a desktop file
+    /// whose name matches case-insensitively.
+    ///
+    /// # Returns
+    /// Returns the name of the desktop file without extension, or `None` if there is no match.
+    fn resolve_session_name(real_session_name: &str, protocol: Protocol) -> Option<String> {
+        let name = real_session_name.strip_suffix(".desktop").unwrap_or(real_session_name);
+        if Self::exists_in_system(name, protocol) {
+            return Some(String::from(name));
+        }
 
-        Ok(())
+        Self::get_sessions_dirs(protocol).iter()
+            .filter_map(|directory| fs::read_dir(directory).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "desktop"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(String::from))
+            .find(|stem| stem.eq_ignore_ascii_case(name))
     }
 
-    /// Remove the session configuration from the global configuration.
+    /// Check whether a session desktop file exists in the system for the specified protocol.
     ///
-    /// This function removes the session configuration identified by its registered name from the
-    /// global configuration. It first checks if the session to be removed is the default session;
-    /// if so, it returns an error indicating that the default session cannot be removed. Otherwise,
-    /// it removes the session from the configuration, saves the updated configuration, and returns
-    /// successfully.
+    /// # Parameters
+    /// - `real_session_name`: The real session name of the session to check.
+    /// - `protocol`: The protocol whose session directories are searched.
     ///
     /// # Returns
+    /// Returns `true` if the desktop file of the session exists, `false` otherwise.
+    pub fn exists_in_system(real_session_name: &str, protocol: Protocol) -> bool {
+        Self::find_desktop_file(real_session_name, protocol).is_some()
+    }
+
+    /// Find the desktop file of a session in the session directories of a protocol.
     ///
-    /// Returns a `Result` indicating the success or failure of removing the session configuration.
-    /// If the session configuration is successfully removed, it returns `Ok(())`. If an error occurs
-    /// during the process, it returns an error message wrapped in a `Box<dyn Error>`.
-    ///
-    /// # Errors
+    /// # Returns
+    /// Returns the path of the desktop file in the first directory that has one, in the order of
+    /// `get_sessions_dirs`.
+    fn find_desktop_file(real_session_name: &str, protocol: Protocol) -> Option<PathBuf> {
+        // Never look outside of the session directories
+        Self::validate_real_name(real_session_name).ok()?;
+        Self::get_sessions_dirs(protocol).iter()
+            .map(|directory| Path::new(directory).join(format!("{real_session_name}.desktop")))
+            .find(|path| path.exists())
+    }
+
+    /// Retrieve the directories that hold the desktop files of a protocol.
     ///
-    /// Returns an error if there are issues encountered during the process of removing the session
-    /// configuration, such as attempting to remove the default session or failure to save the updated
+    /// The compiled-in system directory comes first, followed by the directories listed in the
+    /// `xsessions_dirs` or `wayland_sessions_dirs` key of the `[session]` section in the global
     /// configuration.
-    pub fn remove(&self) -> Result<(), Box<dyn Error>> {
-        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
-        if session_info.get("default").unwrap().as_str() == Some(self.reg_name.as_str()) {
-            warn!("You are removing default session, you need to set a default session to make molyuu-redirect session working.");
-            warn!("Auto Login is forced disabled");
-            session_info.remove("default");
-            get_current_manager()?.set_auto_login(false, None)?;
+    fn get_sessions_dirs(protocol: Protocol) -> Vec<String> {
+        let (system_directory, config_key) = match protocol {
+            Protocol::X11 => (SYSTEM_XSESSIONS_PATH, "xsessions_dirs"),
+            Protocol::Wayland => (SYSTEM_WAYLAND_SESSIONS_PATH, "wayland_sessions_dirs"),
+        };
+        let mut directories = vec![String::from(system_directory)];
+        if let Some(extra_directories) = GLOBAL_CONFIG.get_mut()
+            .and_then(|config| config.try_get("session"))
+            .and_then(|session_info| session_info.get(config_key))
+            .and_then(|extra_directories| extra_directories.as_array()) {
+            directories.extend(extra_directories.iter()
+                .filter_map(|directory| directory.as_str())
+                .map(String::from));
         }
-        session_info.remove(&self.reg_name);
-        GLOBAL_CONFIG.get_mut().unwrap().save_config();
-        Ok(())
+        directories
     }
 
-    /// Register a new session configuration in the global configuration.
+    /// Retrieve the register names of all registered sessions, sorted by name.
+    ///
+    /// Special keys in the session section (like `default`) are not included.
+    pub fn list_registered() -> Vec<String> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+        let mut names: Vec<String> = session_info.iter()
+            .filter(|(_, value)| value.is_table())
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Ask the user to pick one of the registered sessions.
     ///
-    /// This function registers a new session configuration in the global configuration. It first
-    /// checks if a session with the same registered name already exists; if so, it returns an error.
-    /// Otherwise, it constructs a new session table with the necessary information such as the
-    /// session name, protocol, and logout command (if provided). It then inserts this new session
-    /// into the session information in the global configuration, saves the updated configuration,
-    /// and returns successfully.
+    /// The sessions from `list_registered` are written to `output` as a numbered list, then a line
+    /// is read from `input`. The answer can be the number or the register name of a session; the
+    /// question is repeated until a valid answer is given.
+    ///
+    /// # Parameters
+    ///
+    /// * `input`: Where the answer is read from, usually the terminal.
+    /// * `output`: Where the list and the prompt are written to.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` indicating the success or failure of registering the session configuration.
-    /// If the session configuration is successfully registered, it returns `Ok(())`. If an error occurs
-    /// during the process, it returns an error message wrapped in a `Box<dyn Error>`.
+    /// Returns the register name of the selected session.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are issues encountered during the process of registering the session
-    /// configuration, such as attempting to register a session with a duplicate name or an unknown
-    /// protocol, or failure to save the updated configuration.
-    pub fn register(&mut self) -> Result<(), Box<dyn Error>> {
-        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
-        if session_info.get(self.reg_name.as_str()).is_some() {
-            return Err(Box::from(SessionInstanceError::SessionExists));
+    /// Returns `SessionInstanceError::DefaultSessionNotSet` if no session is registered or `input`
+    /// ends before a session is selected, or an error if reading or writing fails.
+    pub fn select_registered(input: &mut impl BufRead, output: &mut impl Write) -> Result<String, Box<dyn Error>> {
+        let names = Self::list_registered();
+        if names.is_empty() {
+            return Err(Box::from(SessionInstanceError::DefaultSessionNotSet));
         }
 
-        let protocol_str = if self.protocol == Protocol::X11 {
-            "x11"
-        } else if self.protocol == Protocol::Wayland {
-            "wayland"
-        } else {
-            return Err(Box::from("Unknown protocol"));
-        };
+        writeln!(output, "Default session is not set, registered sessions:")?;
+        for (index, name) in names.iter().enumerate() {
+            writeln!(output, "  {}) {}", index + 1, name)?;
+        }
+        loop {
+            write!(output, "Select a session [1-{}]: ", names.len())?;
+            output.flush()?;
 
-        let mut new_table = Table::new();
-        new_table.insert(String::from("session"), Value::String(self.real_name.clone()));
-        new_table.insert(String::from("protocol"), Value::String(String::from(protocol_str)));
-        if let Some(logout_command) = &self.logout_command {
-            new_table.insert(String::from("logout_command"), Value::String(logout_command.clone()));
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                writeln!(output)?;
+                return Err(Box::from(SessionInstanceError::DefaultSessionNotSet));
+            }
+            let answer = answer.trim();
+            let selected = match answer.parse::<usize>() {
+                Ok(number) => number.checked_sub(1).and_then(|index| names.get(index)),
+                Err(_) => names.iter().find(|name| name.as_str() == answer),
+            };
+            match selected {
+                Some(name) => return Ok(name.clone()),
+                None => writeln!(output, "Invalid selection: {answer}")?,
+            }
         }
-        session_info.insert(String::from(&self.reg_name), Value::Table(new_table));
-        GLOBAL_CONFIG.get_mut().unwrap().save_config();
-        Ok(())
     }
 
-    /// Set the logout command for the current session.
+    /// Retrieve the protocol of a registered session without loading the whole session.
     ///
-    /// # Parameters
-    ///
-    /// * `command`: A string representing the command to be executed when logging out of the session.
+    /// Unlike `from_config`, only the stored `protocol` key is read, so this still works while
+    /// the desktop file of the session is missing. The protocol is only detected from the system
+    /// if none is stored.
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// Returns a `Result` indicating the success or failure of setting the logout command. If the
-    /// command is successfully set, it returns `Ok(())`. If an error occurs during the process, it
-    /// returns an error message wrapped in a `Box<dyn Error>`.
+    /// Returns `SessionInstanceError::SessionNotFoundInConfig` if no session is registered under
+    /// `reg_name`, an error if the stored protocol is not supported, or
+    /// `SessionInstanceError::SessionNotFoundInSystem` if no protocol is stored and the session
+    /// is not installed.
+    pub fn protocol_of(reg_name: &str) -> Result<Protocol, Box<dyn Error>> {
+        let session_table = Self::config_table(reg_name)?;
+        match session_table.get("protocol") {
+            Some(protocol) => Ok(protocol.as_str().unwrap_or_default().parse::<Protocol>()?),
+            None => {
+                let real_name = session_table.get("session").and_then(|session| session.as_str())
+                    .ok_or_else(|| format!("Session {reg_name} has no session name"))?;
+                Self::validate_real_name(real_name)?;
+                Ok(Self::find_session_in_system(real_name)?.1)
+            }
+        }
+    }
+
+    /// Retrieve the protocol of a registered session, see `protocol_of`.
     ///
-    /// # Errors
+    /// # Returns
     ///
-    /// Returns an error if there are issues encountered during the process of setting the logout
-    /// command, such as failure to access or modify the global configuration or errors encountered
-    /// while saving the configuration.
-    pub fn set_logout_command(&mut self, command: &str) -> Result<(), Box<dyn Error>> {
-        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
-        let current_session_section = session_info.get_mut(self.reg_name.as_str()).unwrap().as_table_mut().unwrap();
-        toml_macros::change_or_insert!(current_session_section, "logout_command", Value::String(String::from(command)));
-        GLOBAL_CONFIG.get_mut().unwrap().save_config();
-        Ok(())
+    /// Returns `None` if the session is not registered or its protocol cannot be determined.
+    pub fn get_registered_protocol(reg_name: &str) -> Option<Protocol> {
+        Self::protocol_of(reg_name).ok()
     }
 
-    /// Set the current session as the default session in the global configuration.
+    /// Validate that the session is startable.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` indicating the success or failure of setting the session as default. If the
-    /// session is successfully set as default, it returns `Ok(())`. If an error occurs during the
-    /// process, it returns an error message wrapped in a `Box<dyn Error>`.
+    /// Returns `Ok(())` if the desktop file of the session exists for its protocol.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are issues encountered during the process of setting the session as
-    /// default, such as failure to access or modify the global configuration or errors encountered
-    /// while saving the configuration.
-    pub fn set_as_default(&self) -> Result<(), Box<dyn Error>> {
-        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
-        toml_macros::change_or_insert!(session_info, "default", Value::String(self.reg_name.clone()));
-        GLOBAL_CONFIG.get_mut().unwrap().save_config();
+    /// Returns `SessionInstanceError::SessionNotFoundInSystem` if the desktop file is missing.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !Self::exists_in_system(self.real_name.as_str(), self.protocol) {
+            return Err(Box::from(SessionInstanceError::SessionNotFoundInSystem(self.real_name.clone())));
+        }
         Ok(())
     }
 
-    /// Set the current session as a one-shot session in the global configuration.
+    /// Validate all registered sessions.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` indicating the success or failure of setting the session as a one-shot
-    /// session. If the session is successfully set as a one-shot session, it returns `Ok(())`. If an
-    /// error occurs during the process, it returns an error message wrapped in a `Box<dyn Error>`.
+    /// Returns a list of register names paired with the validation result of each session.
+    pub fn validate_all() -> Vec<ValidationResult> {
+        Self::list_registered()
+            .into_iter()
+            .map(|name| {
+                let result = Self::from_config(Some(name.as_str())).and_then(|session| session.validate());
+                (name, result)
+            })
+            .collect()
+    }
+
+    /// Start the session as specified by the desktop file, executing the appropriate command.
     ///
-    /// # Errors
+    /// This function loads the session desktop file, extracts the necessary information, and executes
+    /// the specified session command using a child process.
     ///
-    /// Returns an error if there are issues encountered during the process of setting the session as
-    /// a one-shot session, such as failure to access or modify the global configuration, errors
-    /// encountered while saving the configuration, or errors while updating the login manager
-    /// configuration for session changes.
-    pub fn set_start_oneshot(&self) -> Result<(), Box<dyn Error>> {
-        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
-        toml_macros::change_or_insert!(session_info, "oneshot_session", Value::String(self.reg_name.clone()));
-        toml_macros::change_or_insert!(session_info, "oneshot_started", Value::Boolean(false));
-        GLOBAL_CONFIG.get_mut().unwrap().save_config();
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of starting the session. If the session
+    /// is successfully started, it returns `Ok(())`. If an error occurs during the process, it
+    /// returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Parameters
+    ///
+    /// * `options`: Options that control how the session is started.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of starting the session,
+    /// such as failure to load the session configuration file, inability to retrieve necessary
+    /// information from the desktop file, or failure to execute the session command.
+    pub fn start(&self, options: &StartOptions) -> Result<(), Box<dyn Error>> {
+        // A session that ended because molyuuctl was asked to terminate is not failing
+        let run = |session: &Session| -> Result<Option<ExitStatus>, Box<dyn Error>> {
+            Ok(session.run(options)?.filter(|_| lock::termination_signal().is_none()))
+        };
+        let status = run(self)?;
+        self.follow_fallbacks(status, run)
+    }
 
-        // Update Login Manager config to reflect the session change
-        get_current_manager()?.save_config()?;
+    /// Walk the fallback chain while sessions exit with a non-zero exit code, starting each
+    /// session at most once so that a cycle in the chain ends instead of restarting sessions
+    /// forever.
+    ///
+    /// A session terminated by a signal, e.g. on logout or shutdown, was not failing to start and
+    /// is not followed by its fallback. A detached session has no exit status to act on.
+    ///
+    /// # Parameters
+    ///
+    /// * `status`: The exit status of this session.
+    /// * `run`: Starts a fallback session and returns its exit status, see `run`.
+    fn follow_fallbacks<F>(&self, mut status: Option<ExitStatus>, mut run: F) -> Result<(), Box<dyn Error>>
+        where F: FnMut(&Session) -> Result<Option<ExitStatus>, Box<dyn Error>>
+    {
+        let mut visited = vec![self.reg_name.clone()];
+        let mut failed_name = self.reg_name.clone();
+        let mut fallback = self.fallback.clone();
+        while let Some(failed_status) = status.filter(|status| status.code().is_some_and(|code| code != 0)) {
+            let Some(fallback_name) = fallback else {
+                break;
+            };
+            if visited.contains(&fallback_name) {
+                warn!("Fallback of session {} leads back to session {}, not starting it again", failed_name, fallback_name);
+                break;
+            }
+
+            warn!("Session {} exited with {}, falling back to session {}", failed_name, failed_status, fallback_name);
+            let fallback_session = Self::from_config(Some(fallback_name.as_str()))?;
+            visited.push(fallback_name);
+            status = run(&fallback_session)?;
+            failed_name = fallback_session.reg_name;
+            fallback = fallback_session.fallback;
+        }
         Ok(())
     }
 
-    /// Retrieve the protocol associated with the session.
+    /// Start the session and restart it whenever it exits unexpectedly, see `WatchPolicy`.
+    ///
+    /// The startup lock is held across restarts, so no other session starts in between. The
+    /// fallback of the session is not used, the session itself is restarted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `options.detach` is set, the session cannot be started, or it exits
+    /// with an error after it was restarted `max_retries` times within the window.
+    pub fn watch(&self, options: &StartOptions, policy: WatchPolicy) -> Result<(), Box<dyn Error>> {
+        if options.detach {
+            return Err(Box::from("A watched session cannot be detached"));
+        }
+
+        let mut tracker = RestartTracker::new(policy);
+        self.run_with(options, |launch| loop {
+            let status = launch()?.expect("a watched session is not detached");
+            if lock::termination_signal().is_some() {
+                info!("Session {} exited with {} as molyuuctl is terminating, not restarting it", self.reg_name, status);
+                return Ok(());
+            }
+            let normal_exit = watch::is_normal_exit(&status);
+            match tracker.next_restart(normal_exit, Instant::now()) {
+                Some(delay) => {
+                    warn!("Session {} exited with {}, restarting it in {:?} (restart {} of {} within {:?})",
+                        self.reg_name, status, delay, tracker.restarts_in_window(), policy.max_retries, policy.window);
+                    thread::sleep(delay);
+                }
+                None if normal_exit && !policy.restart_always => {
+                    info!("Session {} exited normally, not restarting it", self.reg_name);
+                    return Ok(());
+                }
+                None => {
+                    return Err(Box::from(format!("Session {} exited with {} after {} restarts within {:?}, giving up",
+                        self.reg_name, status, tracker.restarts_in_window(), policy.window)));
+                }
+            }
+        })
+    }
+
+    /// Launch the session command and wait for it to exit, unless `options.detach` is set.
     ///
     /// # Returns
     ///
-    /// Returns the protocol (`Protocol`) associated with the session.
-    pub fn get_protocol(&self) -> Protocol {
-        self.protocol
+    /// Returns the exit status of the session command, or `None` if the session is detached.
+    fn run(&self, options: &StartOptions) -> Result<Option<ExitStatus>, Box<dyn Error>> {
+        self.run_with(options, |launch| launch())
     }
 
-    /// Retrieve the default session configuration.
+    /// Check that the session can start and hold the startup lock of the seat while `supervise`
+    /// runs.
+    ///
+    /// `supervise` is given a function that launches the session once, see `launch`. It can call
+    /// it repeatedly, e.g. to restart a session that exited, and the lock is held across those
+    /// launches until `supervise` returns.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing either the default session configuration (`Self`) or an error
-    /// message wrapped in a `Box<dyn Error>`. If the default session configuration is successfully
-    /// retrieved, it returns `Ok(Self)`.
+    /// Returns the result of `supervise`.
+    fn run_with<T, F>(&self, options: &StartOptions, supervise: F) -> Result<T, Box<dyn Error>>
+        where F: FnOnce(&mut dyn FnMut() -> Result<Option<ExitStatus>, Box<dyn Error>>) -> Result<T, Box<dyn Error>>
+    {
+        self.store_detected_protocol()?;
+
+        // Fail on a misconfigured launcher or unknown user before touching a running session
+        let launcher = Launcher::from_config()?;
+        launcher.check_limits(&self.limits, self.reg_name.as_str())?;
+        let user = options.user.as_deref().map(UserInfo::lookup).transpose()?;
+        if let Some(user) = &user {
+            user.check_switch_allowed(privilege::real_uid())?;
+        }
+
+        // Load the session desktop file and extract the necessary information from it
+        let session_file = self.load_desktop_file()?;
+        let desktop_section = session_file.section(Some("Desktop Entry")).unwrap();
+        let command = self.append_display_server_args(self.get_command(&session_file)?);
+        self.check_start_confirmed(desktop_section, options.assume_yes, io::stdin().is_terminal(), &mut io::stdin().lock(), &mut io::stderr())?;
+
+        // Terminate the running session before taking over its lock
+        let seat = options.seat.clone().unwrap_or_else(Self::current_seat);
+        let running_lock = Self::startup_lock(seat.as_str(), None)?;
+        if options.replace && running_lock.is_locked()? {
+            Self::terminate_running_session(&running_lock)?;
+        }
+
+        // The startup lock is held until the session exits, or handed over to a detached session
+        lock::create_run_dir()?;
+        let mut molyuuctl_lock = Self::startup_lock(seat.as_str(), Some(self.reg_name.clone()))?;
+        molyuuctl_lock.lock()?;
+        // Do not leave the lock behind if molyuuctl is terminated while the session runs
+        molyuuctl_lock.register_release_on_signal()?;
+
+        let mut launch = || self.launch(options, &launcher, user.as_ref(), desktop_section, command.as_str(), &mut molyuuctl_lock);
+        supervise(&mut launch)
+    }
+
+    /// Store the protocol `from_config` switched the session to because the stored one is
+    /// outdated, so the next start does not need to detect it again.
+    ///
+    /// Starting runs without the configuration lock, so it is taken for the write.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are issues encountered during the process of retrieving the default
-    /// session configuration, such as failure to load the configuration from the file.
-    pub fn get_default_session() -> Result<Self, Box<dyn Error>> {
-        Ok(Self::from_config(None)?)
+    /// Returns an error if the configuration lock cannot be acquired or the configuration cannot
+    /// be saved.
+    fn store_detected_protocol(&self) -> Result<(), Box<dyn Error>> {
+        let stored_protocol = Self::config_table(self.reg_name.as_str())?.get("protocol")
+            .and_then(|protocol| protocol.as_str())
+            .map(String::from);
+        if stored_protocol.is_none_or(|protocol| protocol == self.protocol.to_string()) {
+            return Ok(());
+        }
+        Configuration::with_lock(|| {
+            let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+            if let Some(session_table) = session_info.get_mut(self.reg_name.as_str()).and_then(|session| session.as_table_mut()) {
+                toml_macros::change_or_insert!(session_table, "protocol", Value::String(self.protocol.to_string()));
+            }
+            GLOBAL_CONFIG.get_mut().unwrap().save_config()
+        })
     }
 
-    /// Retrieve the one-shot session configuration if it exists and is not already started.
+    /// Launch the session command once while holding the startup lock, and wait for it to exit
+    /// unless `options.detach` is set.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing either an optional one-shot session configuration (`Option<Self>`)
-    /// or an error message wrapped in a `Box<dyn Error>`. If a one-shot session configuration exists
-    /// and is not already started, it returns `Ok(Some(Self))`. If there is no one-shot session
-    /// configuration or it's already started, it returns `Ok(None)`.
+    /// Returns the exit status of the session command, or `None` if the session is detached.
+    fn launch(&self, options: &StartOptions, launcher: &Launcher, user: Option<&UserInfo>, desktop_section: &Properties, command: &str, molyuuctl_lock: &mut Lock) -> Result<Option<ExitStatus>, Box<dyn Error>> {
+        // Bring up the prerequisites before the compositor needs them
+        self.ensure_requirements()?;
+
+        self.announce_launch(desktop_section, command, options.quiet_session);
+
+        // Execute the session command
+        let mut session_command = launcher.build_command(command, self.reg_name.as_str(), &self.limits)?;
+        // Lets another instance confirm the PID in the startup lock, see `is_session_process`
+        session_command.env(SESSION_NAME_ENV, self.reg_name.as_str());
+        match self.log_output_path(options) {
+            Some(log_path) => {
+                if !options.quiet_session {
+                    info!("Writing session output to {}", log_path.display());
+                }
+                let log_file = Self::open_log_output(log_path.as_path())
+                    .map_err(|err| format!("Failed to open session log {}: {err}", log_path.display()))?;
+                session_command.stdout(log_file.try_clone()?).stderr(log_file);
+            }
+            None => {
+                session_command.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+            }
+        }
+        self.apply_vt(&mut session_command, options.quiet_session);
+        // Lead a process group of its own, so a forced logout can signal everything the session spawned
+        session_command.process_group(0);
+        // A detached session holds the startup lock on its own once molyuuctl exits
+        if options.detach {
+            molyuuctl_lock.set_inheritable(true)?;
+        }
+        let mut child = match user {
+            Some(user) => {
+                if !options.quiet_session {
+                    info!("Running session as user {}", user.name);
+                }
+                user.apply_to(&mut session_command)?;
+                // Switching to another user needs root permissions in the forked child
+                let mut child = None;
+                unsafe {
+                    privilege::exec(|| {
+                        child = Some(session_command.spawn()?);
+                        Ok(())
+                    })
+                }.map_err(|err| format!("Failed to launch session: {err}"))?;
+                child.unwrap()
+            }
+            None => session_command.spawn().map_err(|err| format!("Failed to launch session: {err}"))?,
+        };
+
+        // Record the PID of the session, so it can be terminated by another instance
+        molyuuctl_lock.set_content(format!("{}\n{}", self.reg_name, child.id()))?;
+        // Terminate the session along with molyuuctl, the seat is only free once it exited
+        if !options.detach {
+            molyuuctl_lock.set_signal_process_group(Some(child.id() as pid_t))?;
+        }
+
+        // Let a Type=notify unit know that the session is up
+        let status = format!("Running session {}", self.reg_name);
+        if let Err(_err) = notify::notify(&[("READY", "1"), ("STATUS", status.as_str())]) {
+            warn!("Failed to notify service manager: {}", _err);
+        }
+
+        if options.detach {
+            info!("Session {} is running detached (PID {})", self.reg_name, child.id());
+            molyuuctl_lock.set_inheritable(false)?;
+            // Releasing the lock here would release it for the session as well
+            molyuuctl_lock.hand_over();
+            return Ok(None);
+        }
+        let status = Self::wait_for_session(&mut child)?;
+        molyuuctl_lock.set_signal_process_group(None)?;
+        Ok(Some(status))
+    }
+
+    /// Wait for the session to exit.
+    ///
+    /// Once molyuuctl is asked to terminate by a signal, the session process group is sent SIGTERM
+    /// by the signal handler, see `lock::release_on_signal`. It is killed if its leader does not
+    /// exit within `SESSION_TERMINATE_GRACE_PERIOD` after that.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are issues encountered during the process of retrieving the
-    /// one-shot session configuration, such as failure to load the configuration from the file.
-    pub fn get_oneshot_session() -> Result<Option<Self>, Box<dyn Error>> {
-        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table().unwrap();
-        let oneshot_session = session_info.get("oneshot_session");
-        let oneshot_started = session_info.get("oneshot_started");
+    /// Returns an error if waiting for the session fails.
+    fn wait_for_session(child: &mut Child) -> Result<ExitStatus, Box<dyn Error>> {
+        loop {
+            if let Some(status) = process::wait_timeout(child, SESSION_SIGNAL_POLL_INTERVAL)? {
+                return Ok(status);
+            }
+            if lock::termination_signal().is_some() {
+                if let Some(status) = process::wait_timeout(child, SESSION_TERMINATE_GRACE_PERIOD)? {
+                    return Ok(status);
+                }
+                warn!("Session did not exit within {:?}, killing it", SESSION_TERMINATE_GRACE_PERIOD);
+                unsafe {
+                    libc::killpg(child.id() as pid_t, libc::SIGKILL);
+                }
+                return Ok(child.wait()?);
+            }
+        }
+    }
 
-        if let (Some(oneshot_session), Some(oneshot_started)) = (oneshot_session, oneshot_started) {
-            if !oneshot_started.as_bool().unwrap() {
-                return Ok(Some(Self::from_config(Some(oneshot_session.as_str().unwrap()))?));
+    /// Retrieve the file the output of the session is written to.
+    ///
+    /// `options.log_output` takes precedence over `<reg_name>.log` in the directory set by the
+    /// `log_output` key of the `[session]` section in the global configuration.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if neither is set, in which case the session inherits stdout and stderr.
+    fn log_output_path(&self, options: &StartOptions) -> Option<PathBuf> {
+        options.log_output.clone().or_else(|| {
+            Self::get_log_output_directory().map(|directory| directory.join(format!("{}.log", self.reg_name)))
+        })
+    }
+
+    /// Retrieve the directory set by the `log_output` key of the `[session]` section in the global
+    /// configuration.
+    fn get_log_output_directory() -> Option<PathBuf> {
+        GLOBAL_CONFIG.get_mut()
+            .and_then(|config| config.try_get("session"))
+            .and_then(|session_info| session_info.get("log_output"))
+            .and_then(|log_output| log_output.as_str())
+            .map(PathBuf::from)
+    }
+
+    /// Open the log file of a session for writing, keeping the log of the previous start with an
+    /// `.old` suffix like Xorg does.
+    ///
+    /// The directory of the file is created if needed. Only a file directly in the configured
+    /// log directory is opened with root permissions if the current user is not allowed to, since
+    /// the session only inherits the descriptor. Any other file, like one given with `--log`, is
+    /// opened as the invoking user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the previous log cannot be rotated or the file cannot be opened.
+    fn open_log_output(path: &Path) -> Result<File, Box<dyn Error>> {
+        let open = |follow_symlinks: bool| -> io::Result<File> {
+            if let Some(directory) = path.parent() {
+                fs::create_dir_all(directory)?;
+            }
+            if path.exists() {
+                let mut rotated_path = path.as_os_str().to_os_string();
+                rotated_path.push(SESSION_LOG_ROTATED_SUFFIX);
+                fs::rename(path, rotated_path)?;
+            }
+            let mut options = OpenOptions::new();
+            options.create(true).write(true).truncate(true);
+            if !follow_symlinks {
+                options.custom_flags(libc::O_NOFOLLOW);
+            }
+            options.open(path)
+        };
+
+        match open(true) {
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                let Some(directory) = Self::get_log_output_directory().filter(|directory| Self::is_log_file_in(path, directory)) else {
+                    return Err(Box::from(err));
+                };
+                let mut file = None;
+                unsafe {
+                    privilege::exec(|| {
+                        fs::create_dir_all(&directory)?;
+                        // The directory may be a symlink created as the user in the meantime
+                        if !Self::is_log_file_in(path, directory.as_path()) {
+                            return Err(Box::from(format!("{} is not in the log directory {}", path.display(), directory.display())));
+                        }
+                        file = Some(open(false)?);
+                        Ok(())
+                    })?;
+                }
+                Ok(file.unwrap())
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Whether `path` names a file directly in `directory`, after resolving symlinks and `..` in
+    /// both, so that a path merely spelled like the log directory does not match.
+    ///
+    /// The file itself is not resolved, it is opened without following symlinks instead.
+    fn is_log_file_in(path: &Path, directory: &Path) -> bool {
+        let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        if file_name == ".." {
+            return false;
+        }
+        match (fs::canonicalize(parent), fs::canonicalize(directory)) {
+            (Ok(parent), Ok(directory)) => parent == directory,
+            // The directory does not exist yet, so compare it as spelled
+            (Err(_), Err(_)) => parent == directory && !directory.components().any(|component| component == Component::ParentDir),
+            _ => false,
+        }
+    }
+
+    /// Retrieve the path of the desktop file of the session.
+    fn get_desktop_file_path(&self) -> String {
+        match Self::find_desktop_file(self.real_name.as_str(), self.protocol) {
+            Some(path) => path.to_string_lossy().into_owned(),
+            // Report the missing file under the system directory
+            None => format!("{}/{}.desktop", Self::get_sessions_dirs(self.protocol)[0], self.real_name),
+        }
+    }
+
+    /// Load the desktop file of the session.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::MalformedDesktopFile` if the file cannot be parsed or has no
+    /// `[Desktop Entry]` section, `SessionInstanceError::SessionNotFoundInSystem` if it is missing,
+    /// or `SessionInstanceError::InvalidSessionName` if the session name is not a file name.
+    fn load_desktop_file(&self) -> Result<Ini, Box<dyn Error>> {
+        Self::validate_real_name(self.real_name.as_str())?;
+        let desktop_file_path = self.get_desktop_file_path();
+        let session_file = Ini::load_from_file(desktop_file_path.as_str()).map_err(|err| -> Box<dyn Error> {
+            match err {
+                ini::Error::Io(err) => Box::from(SessionInstanceError::from_io(err, self.real_name.as_str())),
+                ini::Error::Parse(err) => Box::from(SessionInstanceError::MalformedDesktopFile(format!("{desktop_file_path}: {err}"))),
+            }
+        })?;
+        if session_file.section(Some("Desktop Entry")).is_none() {
+            return Err(Box::from(SessionInstanceError::MalformedDesktopFile(format!("{desktop_file_path}: missing [Desktop Entry] section"))));
+        }
+        Ok(session_file)
+    }
+
+    /// Retrieve the command the session launches, the `Exec` of the configured desktop action or
+    /// of the desktop entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::MalformedDesktopFile` if the desktop entry has no `Exec` key.
+    fn get_command<'a>(&self, session_file: &'a Ini) -> Result<&'a str, Box<dyn Error>> {
+        match self.get_action_command(session_file) {
+            Some(command) => Ok(command),
+            None => session_file.section(Some("Desktop Entry")).and_then(|section| section.get("Exec"))
+                .ok_or_else(|| Box::from(SessionInstanceError::MalformedDesktopFile(format!("{}: missing Exec key in [Desktop Entry]", self.get_desktop_file_path())))),
+        }
+    }
+
+    /// Log which session is launched with which command, unless `quiet` is set.
+    fn announce_launch(&self, desktop_section: &Properties, command: &str, quiet: bool) {
+        if quiet {
+            return;
+        }
+        info!("Target Session: {}", desktop_section.get("Name").unwrap_or(self.real_name.as_str()));
+        info!("Executing Session Command: {}", command);
+    }
+
+    /// Export the VT of the session to its command as `XDG_VTNR`.
+    ///
+    /// The VT is only used for X11 sessions, it is ignored with a warning for Wayland sessions.
+    fn apply_vt(&self, session_command: &mut Command, quiet: bool) {
+        let Some(vt) = self.vt else {
+            return;
+        };
+        match self.protocol {
+            // startx and the X server wrapper pick the VT to run on from XDG_VTNR
+            Protocol::X11 => {
+                if !quiet {
+                    info!("Running session on VT {}", vt);
+                }
+                session_command.env("XDG_VTNR", vt.to_string());
             }
+            Protocol::Wayland => warn!("Session {} is a Wayland session, ignoring VT {}", self.reg_name, vt),
         }
-        Ok(None)
     }
 
-    /// Retrieve the currently running session if it exists.
+    /// Append the display server arguments of the session to its command.
+    ///
+    /// The arguments are only used for Wayland sessions, where they reach the compositor, e.g. to
+    /// enable Xwayland. They are ignored with a warning for X11 sessions.
+    fn append_display_server_args(&self, command: &str) -> String {
+        if self.display_server_args.is_empty() {
+            return String::from(command);
+        }
+        match self.protocol {
+            Protocol::Wayland => {
+                let args: Vec<String> = self.display_server_args.iter().map(|arg| shell_words::quote(arg)).collect();
+                format!("{} {}", command, args.join(" "))
+            }
+            Protocol::X11 => {
+                warn!("Session {} is an X11 session, ignoring display server arguments {:?}", self.reg_name, self.display_server_args);
+                String::from(command)
+            }
+        }
+    }
+
+    /// Go through the steps of starting the session without launching it.
+    ///
+    /// The desktop file is located and loaded, the command is resolved and checked with
+    /// `validate_command`, the launcher composes the process it would spawn, and the units the
+    /// session requires are checked to be loadable. Later steps are skipped once a step fails.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing either an optional running session configuration
-    /// (`Option<Self>`) or an error message wrapped in a `Box<dyn Error>`. If a running
-    /// session exists, it returns `Ok(Some(Self))`. If there is no running session, it
-    /// returns `Ok(None)`.
+    /// Returns the name of each step that ran paired with its result, which describes what the
+    /// step found on success.
+    pub fn dry_run(&self) -> Vec<DryRunStep> {
+        let mut steps: Vec<DryRunStep> = Vec::new();
+
+        let session_file = self.load_desktop_file();
+        steps.push(("Desktop file loads", session_file.as_ref().map(|_| self.get_desktop_file_path()).map_err(|err| Box::from(err.to_string()))));
+        let Ok(session_file) = session_file else {
+            return steps;
+        };
+
+        let command = self.get_command(&session_file).map(|command| self.append_display_server_args(command));
+        steps.push(("Command resolves", command.as_ref().map(String::clone).map_err(|err| Box::from(err.to_string()))));
+        let Ok(command) = command else {
+            return steps;
+        };
+
+        steps.push(("Command is installed", self.validate_command().map(|_| String::from("found"))));
+        steps.push(("Launcher composes the command", Launcher::from_config()
+            .and_then(|launcher| launcher.build_command(command.as_str(), self.reg_name.as_str(), &self.limits))
+            .map(|process| format!("{:?}", process))));
+        steps.push(("Prerequisites are loadable", self.check_requirements_loadable()));
+        steps
+    }
+
+    /// Check that every unit the session requires is known to systemd, without starting it.
+    fn check_requirements_loadable(&self) -> Result<String, Box<dyn Error>> {
+        if self.requires.is_empty() {
+            return Ok(String::from("none"));
+        }
+        let mut systemctl = SYSTEMCTL.lock().unwrap();
+        for unit in &self.requires {
+            let state = systemctl.get_unit_load_state(unit.as_str())?;
+            if state != "loaded" {
+                return Err(Box::from(SessionInstanceError::PrerequisiteFailed(format!("{unit} is {state}"))));
+            }
+        }
+        Ok(self.requires.join(", "))
+    }
+
+    /// Whether the desktop entry of a session asks to confirm its start with
+    /// `X-Molyuu-Confirm=true`, e.g. for a recovery session that must not be started by accident.
+    pub fn requires_confirmation(desktop_section: &Properties) -> bool {
+        desktop_section.get(CONFIRM_KEY).is_some_and(|confirm| confirm.trim().eq_ignore_ascii_case("true"))
+    }
+
+    /// Gate the start of a session that `requires_confirmation` on the answer of the user.
+    ///
+    /// # Parameters
+    ///
+    /// * `desktop_section`: The `[Desktop Entry]` section of the desktop file of the session.
+    /// * `assume_yes`: Whether the start is confirmed up front, e.g. with `--yes`.
+    /// * `interactive`: Whether `input` is a terminal the user can answer on.
+    /// * `input`: Where the answer is read from.
+    /// * `output`: Where the comment and the question are written to.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are issues encountered during the process of retrieving the
-    /// running session configuration, such as failure to read the lock file.
-    pub fn get_running_session() -> Result<Option<Self>, Box<dyn Error>> {
-        let molyuuctl_lock = Lock::new(MOLYUUCTL_SESSION_STARTUP_LOCK, None);
-        if molyuuctl_lock.is_locked()? {
-            // Read running session name
-            let session_name = fs::read_to_string(format!("/tmp/{MOLYUUCTL_SESSION_STARTUP_LOCK}.lock"))?;
-            Ok(Some(Self::from_config(Some(session_name.as_str()))?))
-        } else {
-            Ok(None)
+    /// Returns `SessionInstanceError::StartNotConfirmed` if the start is declined or needs a
+    /// confirmation that cannot be asked for, or an error if reading or writing fails.
+    fn check_start_confirmed(&self, desktop_section: &Properties, assume_yes: bool, interactive: bool, input: &mut impl BufRead, output: &mut impl Write) -> Result<(), Box<dyn Error>> {
+        if assume_yes || !Self::requires_confirmation(desktop_section) {
+            return Ok(());
+        }
+        if !interactive {
+            return Err(Box::from(SessionInstanceError::StartNotConfirmed(format!("{} requires confirmation, pass --yes to start it without a terminal", self.reg_name))));
+        }
+        if !self.confirm_start(desktop_section, input, output)? {
+            return Err(Box::from(SessionInstanceError::StartNotConfirmed(self.reg_name.clone())));
+        }
+        Ok(())
+    }
+
+    /// Ask the user to confirm the start of the session.
+    ///
+    /// The `Comment` of the desktop entry, if any, is shown before the question. Only an answer of
+    /// `y` or `yes` confirms the start.
+    ///
+    /// # Parameters
+    ///
+    /// * `desktop_section`: The `[Desktop Entry]` section of the desktop file of the session.
+    /// * `input`: Where the answer is read from, usually the terminal.
+    /// * `output`: Where the comment and the question are written to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading or writing fails.
+    fn confirm_start(&self, desktop_section: &Properties, input: &mut impl BufRead, output: &mut impl Write) -> Result<bool, Box<dyn Error>> {
+        if let Some(comment) = desktop_section.get("Comment") {
+            writeln!(output, "{comment}")?;
+        }
+        write!(output, "Start session {}? [y/N]: ", self.reg_name)?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+    }
+
+    /// Check that the program the session launches is installed.
+    ///
+    /// The program is taken from the `TryExec` key of the desktop entry if present, otherwise from
+    /// the first word of the command the session launches. It is resolved against `PATH` unless
+    /// it contains a `/`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::CommandNotFound` if the program is not found or not
+    /// executable, or an error if the desktop file cannot be loaded or its command is empty.
+    pub fn validate_command(&self) -> Result<(), Box<dyn Error>> {
+        let session_file = self.load_desktop_file()?;
+        let program = match session_file.section(Some("Desktop Entry")).and_then(|section| section.get("TryExec")) {
+            Some(try_exec) => String::from(try_exec),
+            None => shell_words::split(self.get_command(&session_file)?)?
+                .into_iter()
+                .next()
+                .ok_or("Session command is empty")?,
+        };
+        if Self::resolve_executable(program.as_str()).is_none() {
+            return Err(Box::from(SessionInstanceError::CommandNotFound(program)));
+        }
+        Ok(())
+    }
+
+    /// Find an executable the way a shell would, looking it up in `PATH` unless it contains a `/`.
+    fn resolve_executable(program: &str) -> Option<PathBuf> {
+        let is_executable = |path: &Path| path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0);
+        if program.contains('/') {
+            let path = PathBuf::from(program);
+            return is_executable(path.as_path()).then_some(path);
+        }
+        env::split_paths(&env::var_os("PATH")?)
+            .map(|directory| directory.join(program))
+            .find(|path| is_executable(path.as_path()))
+    }
+
+    /// Retrieve the `Exec` command of the desktop action the session is configured to launch.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if no action is configured, or if the `[Desktop Action <action>]` section or
+    /// its `Exec` key is missing, in which case the main `Exec` of the desktop file should be used.
+    fn get_action_command<'a>(&self, session_file: &'a Ini) -> Option<&'a str> {
+        let action = self.action.as_ref()?;
+        let command = session_file.section(Some(format!("Desktop Action {action}")))
+            .and_then(|action_section| action_section.get("Exec"));
+        if command.is_none() {
+            warn!("Desktop action {} of session {} has no Exec, falling back to the main Exec", action, self.reg_name);
+        }
+        command
+    }
+
+    /// Start the systemd units the session requires and wait for them to become active.
+    ///
+    /// The units are started in the order they are listed in the `requires` key of the session.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::PrerequisiteFailed` naming the first unit that could not be
+    /// started or did not become active within `SESSION_PREREQUISITE_TIMEOUT`.
+    fn ensure_requirements(&self) -> Result<(), Box<dyn Error>> {
+        if self.requires.is_empty() {
+            return Ok(());
+        }
+
+        let mut systemctl = SYSTEMCTL.lock().unwrap();
+        self.ensure_requirements_with(|unit| {
+            systemctl.start_unit(unit)?;
+            systemctl.wait_unit_active(unit, SESSION_PREREQUISITE_TIMEOUT)
+        })
+    }
+
+    /// Start the units the session requires with `start_unit`, which waits for a unit to become
+    /// active, see `ensure_requirements`.
+    fn ensure_requirements_with<S>(&self, mut start_unit: S) -> Result<(), Box<dyn Error>>
+        where S: FnMut(&str) -> Result<(), Box<dyn Error>>
+    {
+        for unit in &self.requires {
+            info!("Starting session prerequisite: {}", unit);
+            start_unit(unit.as_str())
+                .map_err(|err| SessionInstanceError::PrerequisiteFailed(format!("{unit}: {err}")))?;
+        }
+        Ok(())
+    }
+
+    /// Terminate the session that holds the startup lock and wait for the lock to be released.
+    ///
+    /// The running session gets SIGTERM first and SIGKILL if it does not exit within the grace period.
+    fn terminate_running_session(molyuuctl_lock: &Lock) -> Result<(), Box<dyn Error>> {
+        let (session_name, pid) = Self::running_session_pid(molyuuctl_lock)?;
+        warn!("Replacing running session {} (PID {})", session_name, pid);
+        if process::terminate(pid, SESSION_TERMINATE_GRACE_PERIOD)? == Termination::Killed {
+            warn!("Session {} did not exit in time and was killed", session_name);
         }
+
+        // The instance that started the session releases the lock once the session exits
+        molyuuctl_lock.wait_unlocked(SESSION_TERMINATE_GRACE_PERIOD)?;
+        Ok(())
+    }
+
+    /// Parse the content of the startup lock into the register name and the PID of the session.
+    ///
+    /// A PID of 1 or less is never a session and would signal init, a process group or every
+    /// process, so it is treated as missing.
+    fn parse_startup_lock(content: &str) -> (String, Option<pid_t>) {
+        let mut lines = content.lines();
+        let session_name = lines.next().unwrap_or_default().to_string();
+        let pid = lines.next()
+            .and_then(|pid| pid.trim().parse::<pid_t>().ok())
+            .filter(|pid| *pid > 1);
+        (session_name, pid)
     }
-}
\ No newline at end of file
+
+    /// Retrieve the register name and the PID of the session holding the startup lock, making
+    /// sure the PID is safe to signal.
+    ///
+    /// The lock file must be writable by root only, see `Lock::read_trusted_content`, and the PID
+    /// must still be that session, see `is_session_process`, since it may have been reused after
+    /// the session exited.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file is not trusted, the session did not record a valid PID or
+    /// the PID is not the session.
+    fn running_session_pid(molyuuctl_lock: &Lock) -> Result<(String, pid_t), Box<dyn Error>> {
+        let (session_name, pid) = Self::parse_startup_lock(molyuuctl_lock.read_trusted_content()?.as_str());
+        let pid = pid.ok_or("Running session did not record a valid PID, cannot signal it")?;
+        if !Self::is_session_process(pid, session_name.as_str()) {
+            return Err(Box::from(format!("Process {pid} is not session {session_name}, not signaling it")));
+        }
+        Ok((session_name, pid))
+    }
+
+    /// Check whether a process is a session started by molyuuctl, i.e. it leads its own process
+    /// group and its environment names the session in `SESSION_NAME_ENV`, see `launch`.
+    fn is_session_process(pid: pid_t, session_name: &str) -> bool {
+        if unsafe { libc::getpgid(pid) } != pid {
+            return false;
+        }
+        let marker = format!("{SESSION_NAME_ENV}={session_name}");
+        fs::read(format!("/proc/{pid}/environ"))
+            .is_ok_and(|environ| environ.split(|byte| *byte == 0).any(|variable| variable == marker.as_bytes()))
+    }
+
+    /// Start either a one-shot session or the default session as specified in the global configuration.
+    ///
+    /// This function retrieves session information from the global configuration, checks if a
+    /// one-shot session is configured and not already started. If so, it starts the specified
+    /// one-shot session; otherwise, it starts the default session. After starting the session, it
+    /// updates the login manager configuration accordingly.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of starting the session. If the session
+    /// is successfully started, it returns `Ok(())`. If an error occurs during the process, it
+    /// returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Parameters
+    ///
+    /// * `options`: Options that control how the session is started.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of starting the session,
+    /// such as failure to retrieve session information from the global configuration, failure to
+    /// update the configuration, or errors encountered while starting the session itself.
+    pub fn start_oneshot_or_default_session(options: &StartOptions) -> Result<(), Box<dyn Error>> {
+        Self::select_oneshot_or_default_session(options.protocol)?.start(options)
+    }
+
+    /// Select the session `start_oneshot_or_default_session` starts, marking the one-shot
+    /// session as started if it is selected.
+    ///
+    /// # Parameters
+    ///
+    /// * `protocol`: The protocol to read the default session of, see `get_default_name_for`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be updated or the selected session cannot be
+    /// read from it.
+    fn select_oneshot_or_default_session(protocol: Option<Protocol>) -> Result<Session, Box<dyn Error>> {
+        // `session start` runs without the configuration lock, so it is only taken if there is a
+        // change to write, and released before the session starts
+        let oneshot_session = if is_autologin_once() || Self::get_oneshot_name().is_some() {
+            Configuration::with_lock(|| {
+                // A single-boot Auto Login logged in to this session, do not log in again on the next boot
+                consume_autologin_once()?;
+                Self::mark_oneshot_started()
+            })?
+        } else {
+            None
+        };
+
+        // Start the one-shot session if it was not started yet, else start the default session
+        match oneshot_session {
+            Some(session_to_start) => {
+                explain::record(Stage::Oneshot, || format!("Oneshot session {session_to_start} is not started yet, starting it"));
+                Self::from_config(Some(session_to_start.as_str()))
+            }
+            None => {
+                let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+                explain::record(Stage::Oneshot, || match session_info.get("oneshot_session").and_then(|session| session.as_str()) {
+                    Some(session) => format!("Oneshot session {session} is started already, starting the default session"),
+                    None => String::from("No oneshot session is set, starting the default session"),
+                });
+                Self::get_default_session_for(protocol)
+            }
+        }
+    }
+
+    /// Mark the one-shot session as started, if one is set and was not started yet.
+    ///
+    /// # Returns
+    ///
+    /// Returns the name of the one-shot session to start, or `None` if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be saved.
+    fn mark_oneshot_started() -> Result<Option<String>, Box<dyn Error>> {
+        let Some(session_to_start) = Self::get_oneshot_name() else {
+            return Ok(None);
+        };
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        session_info["oneshot_started"] = Value::Boolean(true);
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        if let Some(manager) = get_current_manager_if_set()? {
+            manager.save_config()?;
+        }
+        Ok(Some(session_to_start))
+    }
+
+    /// Execute the logout command to end the current user session.
+    ///
+    /// This function executes the logout command, if set, to end the current user session. If no
+    /// logout command is configured, it returns an error indicating that the logout command is not
+    /// set, and the logout operation cannot be performed.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of the logout operation. If the logout
+    /// operation is successfully executed, it returns `Ok(())`. If an error occurs during the process,
+    /// it returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of executing the logout
+    /// command, such as failure to retrieve the logout command, failure to spawn the shell or the
+    /// logout command exiting with a non-zero status.
+    pub fn logout(&self) -> Result<(), Box<dyn Error>> {
+        // Check if a logout command is set
+        if self.logout_command.is_none() {
+            return Err(Box::from(SessionInstanceError::LogoutCommandNotSet));
+        }
+
+        // Execute the logout command
+        let status = Command::new("/bin/bash")
+            .arg("-c")
+            .arg(self.logout_command.as_ref().unwrap().as_str())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .map_err(|err| format!("Failed to execute logout command: {err}"))?;
+
+        // Report the failure if the logout command exits with non-zero status
+        if !status.success() {
+            return Err(Box::from(SessionInstanceError::LogoutCommandFailed));
+        }
+
+        Ok(())
+    }
+
+    /// End the session even if its logout command hangs or fails.
+    ///
+    /// The logout command, if set, gets the logout grace period to finish. If it is not set, does
+    /// not finish in time or exits with non-zero status, the process group of the running session
+    /// receives SIGTERM, followed by SIGKILL if it is still alive after the grace period.
+    ///
+    /// Unless given, the grace period is read from the `logout_grace_period` key of the
+    /// `[session]` section in the global configuration and defaults to
+    /// `SESSION_TERMINATE_GRACE_PERIOD`.
+    ///
+    /// # Parameters
+    ///
+    /// * `seat`: The seat the session is running on.
+    /// * `grace_period`: The grace period, overriding the configured one.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::SessionNotRunning` if the logout command did not end the
+    /// session and the session is not the running one, or an error if it cannot be signaled.
+    pub fn force_logout(&self, seat: &str, grace_period: Option<Duration>) -> Result<(), Box<dyn Error>> {
+        let grace_period = grace_period.unwrap_or_else(Self::get_logout_grace_period);
+
+        if let Some(logout_command) = &self.logout_command {
+            let mut child = Command::new("/bin/bash")
+                .arg("-c")
+                .arg(logout_command.as_str())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .spawn()
+                .map_err(|err| format!("Failed to execute logout command: {err}"))?;
+            match process::wait_timeout(&mut child, grace_period)? {
+                Some(status) if status.success() => return Ok(()),
+                Some(status) => warn!("Logout command exited with {}, killing session {}", status, self.reg_name),
+                None => {
+                    warn!("Logout command did not finish within {:?}, killing session {}", grace_period, self.reg_name);
+                    child.kill()?;
+                    child.wait()?;
+                }
+            }
+        }
+
+        let molyuuctl_lock = Self::startup_lock(seat, None)?;
+        if !molyuuctl_lock.is_locked_exclusively()? {
+            return Err(Box::from(SessionInstanceError::SessionNotRunning(self.reg_name.clone())));
+        }
+        let (session_name, pid) = Self::running_session_pid(&molyuuctl_lock)?;
+        if session_name != self.reg_name {
+            return Err(Box::from(SessionInstanceError::SessionNotRunning(self.reg_name.clone())));
+        }
+
+        // The session leads its own process group, see `run`
+        warn!("Terminating process group of session {} (PID {})", self.reg_name, pid);
+        if process::terminate_group(pid, grace_period)? == Termination::Killed {
+            warn!("Session {} did not exit in time and was killed", self.reg_name);
+        }
+        Ok(())
+    }
+
+    /// Retrieve the grace period of a forced logout from the `logout_grace_period` key of the
+    /// `[session]` section, either a number of seconds or a duration like `10s`, see
+    /// `parse_duration`.
+    fn get_logout_grace_period() -> Duration {
+        let grace_period = GLOBAL_CONFIG.get_mut()
+            .and_then(|config| config.try_get("session"))
+            .and_then(|session| session.get("logout_grace_period"));
+        match grace_period {
+            Some(Value::Integer(seconds)) => u64::try_from(*seconds).ok().map(Duration::from_secs),
+            Some(Value::String(duration)) => parse_duration(duration.as_str())
+                .inspect_err(|err| warn!("Ignoring logout_grace_period: {}", err))
+                .ok(),
+            _ => None,
+        }.unwrap_or(SESSION_TERMINATE_GRACE_PERIOD)
+    }
+
+    /// Rename the session with a new name.
+    ///
+    /// This function renames the session by updating its registered name in the global configuration.
+    /// It first checks if a session with the new name already exists; if so, it returns an error.
+    /// Otherwise, it updates the session's name in the configuration, updates the default, one-shot
+    /// and fallback sessions that name it, and saves the configuration.
+    ///
+    /// # Parameters
+    ///
+    /// * `new_name`: The new name to assign to the session.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of renaming the session. If the session
+    /// is successfully renamed, it returns `Ok(())`. If an error occurs during the process, it
+    /// returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the new name conflicts with an existing session name or if there are
+    /// issues encountered during the process of renaming the session or saving the configuration.
+    pub fn rename(&mut self, new_name: &str) -> Result<(), Box<dyn Error>> {
+        Self::validate_reg_name(new_name)?;
+
+        // Retrieve session information from the global configuration
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+
+        // Check if a session with the new name already exists
+        if session_info.get(new_name).is_some() {
+            return Err(Box::from(SessionInstanceError::SessionExists));
+        }
+
+        // Store the current name of the session
+        let old_name = self.reg_name.clone();
+
+        // Retrieve information about the current session
+        let current_session_info = session_info.get(self.reg_name.as_str()).unwrap();
+
+        // Update session name in the configuration
+        session_info.insert(String::from(new_name), current_session_info.clone());
+        session_info.remove(&self.reg_name);
+        self.reg_name = String::from(new_name);
+
+        // Update default, one-shot and fallback sessions if necessary
+        for default_key in DEFAULT_KEYS.into_iter().chain([PREVIOUS_DEFAULT_KEY, "oneshot_session"]) {
+            if let Some(default_session) = session_info.get(default_key) {
+                if default_session.as_str() == Some(old_name.as_str()) {
+                    session_info[default_key] = Value::String(self.reg_name.clone());
+                }
+            }
+        }
+        Self::replace_fallbacks(session_info, old_name.as_str(), Some(new_name));
+
+        // Save the updated configuration
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+
+        Ok(())
+    }
+
+    /// Remove the session configuration from the global configuration.
+    ///
+    /// This function removes the session configuration identified by its registered name from the
+    /// global configuration. It first checks if the session to be removed is the default session;
+    /// if so, it returns an error indicating that the default session cannot be removed. Otherwise,
+    /// it removes the session from the configuration, saves the updated configuration, and returns
+    /// successfully. A one-shot session or fallbacks of other sessions that name it are cleared.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of removing the session configuration.
+    /// If the session configuration is successfully removed, it returns `Ok(())`. If an error occurs
+    /// during the process, it returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of removing the session
+    /// configuration, such as attempting to remove the default session or failure to save the updated
+    /// configuration.
+    pub fn remove(&self) -> Result<(), Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        if session_info.get("default").and_then(|default| default.as_str()) == Some(self.reg_name.as_str()) {
+            warn!("You are removing default session, you need to set a default session to make molyuu-redirect session working.");
+            warn!("Auto Login is forced disabled");
+            session_info.remove("default");
+            if let Some(mut manager) = get_current_manager_if_set()? {
+                manager.set_auto_login(false, None, false)?;
+            }
+        }
+        for default_key in DEFAULT_KEYS[1..].iter().chain(&[PREVIOUS_DEFAULT_KEY]) {
+            if session_info.get(*default_key).and_then(|default| default.as_str()) == Some(self.reg_name.as_str()) {
+                session_info.remove(*default_key);
+            }
+        }
+        if session_info.get("oneshot_session").and_then(|oneshot| oneshot.as_str()) == Some(self.reg_name.as_str()) {
+            session_info.remove("oneshot_session");
+            session_info.remove("oneshot_started");
+        }
+        Self::replace_fallbacks(session_info, self.reg_name.as_str(), None);
+        session_info.remove(&self.reg_name);
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        Ok(())
+    }
+
+    /// Point the `fallback` keys of the registered sessions that name `old_name` to `new_name`, or
+    /// remove them if `new_name` is `None`.
+    ///
+    /// # Parameters
+    ///
+    /// * `session_info`: The `[session]` section of the global configuration.
+    /// * `old_name`: The register name of the renamed or removed session.
+    /// * `new_name`: The new register name of the session, `None` if it is removed.
+    fn replace_fallbacks(session_info: &mut Table, old_name: &str, new_name: Option<&str>) {
+        for (_, session) in session_info.iter_mut() {
+            if let Some(session_table) = session.as_table_mut() {
+                if session_table.get("fallback").and_then(|fallback| fallback.as_str()) == Some(old_name) {
+                    match new_name {
+                        Some(new_name) => session_table["fallback"] = Value::String(String::from(new_name)),
+                        None => {
+                            session_table.remove("fallback");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove every registered session whose desktop file can no longer be found in the system.
+    ///
+    /// The `default` and one-shot keys are only cleared if the session they name is pruned, in
+    /// which case Auto Login is disabled like in `remove`. Fallbacks that name a pruned session
+    /// are removed as well.
+    ///
+    /// # Parameters
+    ///
+    /// * `dry_run`: Only report the sessions that would be removed, without changing anything.
+    ///
+    /// # Returns
+    ///
+    /// Returns the register names of the pruned sessions, sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Auto Login cannot be disabled or the global configuration cannot be saved.
+    pub fn prune(dry_run: bool) -> Result<Vec<String>, Box<dyn Error>> {
+        let pruned: Vec<String> = Self::list_registered().into_iter()
+            .filter(|name| {
+                let real_name = Self::config_table(name.as_str()).ok()
+                    .and_then(|table| table.get("session").and_then(|session| session.as_str()).map(String::from));
+                real_name.is_none_or(|real_name| Self::find_session_in_system(real_name.as_str()).is_err())
+            })
+            .collect();
+        if dry_run || pruned.is_empty() {
+            return Ok(pruned);
+        }
+
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        for name in &pruned {
+            session_info.remove(name);
+            Self::replace_fallbacks(session_info, name.as_str(), None);
+        }
+        if session_info.get("oneshot_session").and_then(|oneshot| oneshot.as_str()).is_some_and(|oneshot| pruned.iter().any(|name| name == oneshot)) {
+            session_info.remove("oneshot_session");
+            session_info.remove("oneshot_started");
+        }
+        if session_info.get("default").and_then(|default| default.as_str()).is_some_and(|default| pruned.iter().any(|name| name == default)) {
+            warn!("Default session is pruned, you need to set a default session to make molyuu-redirect session working.");
+            warn!("Auto Login is forced disabled");
+            session_info.remove("default");
+            if let Some(mut manager) = get_current_manager_if_set()? {
+                manager.set_auto_login(false, None, false)?;
+            }
+        }
+        for default_key in DEFAULT_KEYS[1..].iter().chain(&[PREVIOUS_DEFAULT_KEY]) {
+            if session_info.get(*default_key).and_then(|default| default.as_str()).is_some_and(|default| pruned.iter().any(|name| name == default)) {
+                session_info.remove(*default_key);
+            }
+        }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        Ok(pruned)
+    }
+
+    /// Check that a session name is the name of a desktop file in the session directories.
+    ///
+    /// Session names are joined to the session directories to find desktop files, so they must
+    /// not be able to point outside of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidSessionName` if the name is not a file name, see
+    /// `is_file_name`.
+    pub fn validate_real_name(real_name: &str) -> Result<(), SessionInstanceError> {
+        if !Self::is_file_name(real_name) {
+            return Err(SessionInstanceError::InvalidSessionName(real_name.escape_debug().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check that a name is a single file name: not empty, `.` or `..`, and without a path
+    /// separator or a control character.
+    fn is_file_name(name: &str) -> bool {
+        !name.is_empty()
+            && name != "."
+            && name != ".."
+            && !name.contains(['/', '\\'])
+            && !name.chars().any(char::is_control)
+    }
+
+    /// Check that a name can be used to register a session.
+    ///
+    /// Register names become keys of the global configuration and parts of file names, so they
+    /// must not be able to point outside of a directory, break a line-based format or replace a
+    /// setting of the `[session]` section.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidRegisterName` if the name is not a file name, see
+    /// `is_file_name`, or longer than `MAX_REG_NAME_LENGTH` characters, and
+    /// `SessionInstanceError::ReservedRegisterName` if it is the key of a `[session]` setting.
+    pub fn validate_reg_name(reg_name: &str) -> Result<(), SessionInstanceError> {
+        if !Self::is_file_name(reg_name) || reg_name.chars().count() > MAX_REG_NAME_LENGTH {
+            return Err(SessionInstanceError::InvalidRegisterName(reg_name.escape_debug().to_string()));
+        }
+        if RESERVED_REG_NAMES.contains(&reg_name) {
+            return Err(SessionInstanceError::ReservedRegisterName(String::from(reg_name)));
+        }
+        Ok(())
+    }
+
+    /// Register a new session configuration in the global configuration.
+    ///
+    /// This function registers a new session configuration in the global configuration. It first
+    /// checks if a session with the same registered name already exists; if so, it returns an error.
+    /// Otherwise, it constructs a new session table with the necessary information such as the
+    /// session name, protocol, and logout command (if provided). It then inserts this new session
+    /// into the session information in the global configuration, saves the updated configuration,
+    /// and returns successfully.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of registering the session configuration.
+    /// If the session configuration is successfully registered, it returns `Ok(())`. If an error occurs
+    /// during the process, it returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of registering the session
+    /// configuration, such as attempting to register a session with a duplicate name or an unknown
+    /// protocol, or failure to save the updated configuration.
+    pub fn register(&mut self) -> Result<(), Box<dyn Error>> {
+        Self::validate_reg_name(self.reg_name.as_str())?;
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        if session_info.get(self.reg_name.as_str()).is_some() {
+            return Err(Box::from(SessionInstanceError::SessionExists));
+        }
+
+        let mut new_table = Table::new();
+        new_table.insert(String::from("session"), Value::String(self.real_name.clone()));
+        new_table.insert(String::from("protocol"), Value::String(self.protocol.to_string()));
+        if let Some(logout_command) = &self.logout_command {
+            new_table.insert(String::from("logout_command"), Value::String(logout_command.clone()));
+        }
+        if !self.requires.is_empty() {
+            new_table.insert(String::from("requires"), Self::strings_to_value(&self.requires));
+        }
+        session_info.insert(String::from(&self.reg_name), Value::Table(new_table));
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        Ok(())
+    }
+
+    /// Make a session that was just registered the default session, the oneshot session, or both.
+    ///
+    /// The registration stands even if one of the steps fails, so it is not rolled back.
+    ///
+    /// # Parameters
+    ///
+    /// * `default`: Whether to set the session as default, see `set_as_default`.
+    /// * `oneshot`: Whether to start the session once on next start, see `set_start_oneshot`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the step that failed.
+    pub fn promote(&self, default: bool, oneshot: bool) -> Result<(), Box<dyn Error>> {
+        if default {
+            self.set_as_default()
+                .map_err(|err| format!("Session {} is registered, but failed to set it as default: {err}", self.reg_name))?;
+        }
+        if oneshot {
+            self.set_start_oneshot(true)
+                .map_err(|err| format!("Session {} is registered, but failed to set it as oneshot: {err}", self.reg_name))?;
+        }
+        Ok(())
+    }
+
+    /// Register the session, or update it in place if it is already registered.
+    ///
+    /// An existing session gets the session name and protocol of this instance, and its logout
+    /// command if one is set. Its other keys, such as prerequisites or a fallback, are kept.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the session was created, or `false` if an existing session was updated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the updated configuration cannot be saved.
+    pub fn register_or_update(&mut self) -> Result<bool, Box<dyn Error>> {
+        Self::validate_reg_name(self.reg_name.as_str())?;
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let Some(current_session_section) = session_info.get_mut(self.reg_name.as_str()).and_then(|session| session.as_table_mut()) else {
+            self.register()?;
+            return Ok(true);
+        };
+
+        toml_macros::change_or_insert!(current_session_section, "session", Value::String(self.real_name.clone()));
+        toml_macros::change_or_insert!(current_session_section, "protocol", Value::String(self.protocol.to_string()));
+        if let Some(logout_command) = &self.logout_command {
+            toml_macros::change_or_insert!(current_session_section, "logout_command", Value::String(logout_command.clone()));
+        }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        Ok(false)
+    }
+
+    /// Set the logout command for the current session.
+    ///
+    /// # Parameters
+    ///
+    /// * `command`: A string representing the command to be executed when logging out of the session.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of setting the logout command. If the
+    /// command is successfully set, it returns `Ok(())`. If an error occurs during the process, it
+    /// returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of setting the logout
+    /// command, such as failure to access or modify the global configuration or errors encountered
+    /// while saving the configuration.
+    pub fn set_logout_command(&mut self, command: &str) -> Result<(), Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let current_session_section = session_info.get_mut(self.reg_name.as_str()).unwrap().as_table_mut().unwrap();
+        toml_macros::change_or_insert!(current_session_section, "logout_command", Value::String(String::from(command)));
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        Ok(())
+    }
+
+    /// Set the systemd units that must be active before the session is started.
+    ///
+    /// # Parameters
+    ///
+    /// * `units`: The units to start, in order. An empty list removes all prerequisites.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the global configuration cannot be modified or saved.
+    pub fn set_requires(&mut self, units: Vec<String>) -> Result<(), Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let current_session_section = session_info.get_mut(self.reg_name.as_str()).unwrap().as_table_mut().unwrap();
+        if units.is_empty() {
+            current_session_section.remove("requires");
+        } else {
+            toml_macros::change_or_insert!(current_session_section, "requires", Self::strings_to_value(&units));
+        }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        self.requires = units;
+        Ok(())
+    }
+
+    /// Set the desktop action whose `Exec` is launched instead of the main `Exec` of the session.
+    ///
+    /// # Parameters
+    ///
+    /// * `action`: The identifier of the `[Desktop Action <action>]` section, or `None` to launch
+    ///   the main `Exec` again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the global configuration cannot be modified or saved.
+    pub fn set_action(&mut self, action: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let current_session_section = session_info.get_mut(self.reg_name.as_str()).unwrap().as_table_mut().unwrap();
+        match action {
+            Some(action) => toml_macros::change_or_insert!(current_session_section, "action", Value::String(String::from(action))),
+            None => {
+                current_session_section.remove("action");
+            }
+        }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        self.action = action.map(String::from);
+        Ok(())
+    }
+
+    /// Set the session that is started when this session exits with a non-zero status.
+    ///
+    /// # Parameters
+    ///
+    /// * `fallback`: The register name of the fallback session, or `None` to remove the fallback.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::SessionNotFoundInConfig` if the fallback session is not
+    /// registered, `SessionInstanceError::InvalidFallback` if it is the session itself, or an error
+    /// if the global configuration cannot be modified or saved.
+    pub fn set_fallback(&mut self, fallback: Option<&str>) -> Result<(), Box<dyn Error>> {
+        if let Some(fallback) = fallback {
+            if fallback == self.reg_name {
+                return Err(Box::from(SessionInstanceError::InvalidFallback(String::from(fallback))));
+            }
+            Self::config_table(fallback)?;
+        }
+
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let current_session_section = session_info.get_mut(self.reg_name.as_str()).unwrap().as_table_mut().unwrap();
+        match fallback {
+            Some(fallback) => toml_macros::change_or_insert!(current_session_section, "fallback", Value::String(String::from(fallback))),
+            None => {
+                current_session_section.remove("fallback");
+            }
+        }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        self.fallback = fallback.map(String::from);
+        Ok(())
+    }
+
+    /// Set the virtual terminal an X11 session runs on.
+    ///
+    /// The VT is exported to the session command as `XDG_VTNR`. It is ignored for Wayland
+    /// sessions.
+    ///
+    /// # Parameters
+    ///
+    /// * `vt`: The number of the VT, or `None` to let the session pick one.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidVt` if `vt` is not a positive number, or an error if
+    /// the global configuration cannot be modified or saved.
+    pub fn set_vt(&mut self, vt: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let vt = vt.map(|vt| Self::parse_vt(&Value::String(String::from(vt)))).transpose()?;
+        if vt.is_some() && self.protocol != Protocol::X11 {
+            warn!("Session {} is a {} session, the VT is only used for X11 sessions", self.reg_name, self.protocol);
+        }
+
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let current_session_section = session_info.get_mut(self.reg_name.as_str()).unwrap().as_table_mut().unwrap();
+        match vt {
+            Some(vt) => toml_macros::change_or_insert!(current_session_section, "vt", Value::Integer(i64::from(vt))),
+            None => {
+                current_session_section.remove("vt");
+            }
+        }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        self.vt = vt;
+        Ok(())
+    }
+
+    /// Set the arguments appended to the command of a Wayland session, e.g. a flag of the
+    /// compositor that enables Xwayland. They are ignored for X11 sessions.
+    ///
+    /// # Parameters
+    ///
+    /// * `args`: The arguments in order, or an empty list to remove them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the global configuration cannot be modified or saved.
+    pub fn set_display_server_args(&mut self, args: Vec<String>) -> Result<(), Box<dyn Error>> {
+        if !args.is_empty() && self.protocol != Protocol::Wayland {
+            warn!("Session {} is a {} session, display server arguments are only used for Wayland sessions", self.reg_name, self.protocol);
+        }
+
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let current_session_section = session_info.get_mut(self.reg_name.as_str()).unwrap().as_table_mut().unwrap();
+        if args.is_empty() {
+            current_session_section.remove("display_server_args");
+        } else {
+            toml_macros::change_or_insert!(current_session_section, "display_server_args", Self::strings_to_value(&args));
+        }
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        self.display_server_args = args;
+        Ok(())
+    }
+
+    /// Retrieve the resource limits of the session.
+    pub fn get_resource_limits(&self) -> &ResourceLimits {
+        &self.limits
+    }
+
+    /// Set the resource limits of the session, applied by the systemd-run launcher.
+    ///
+    /// # Parameters
+    ///
+    /// * `limits`: The limits to set. Unset limits are removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidResourceLimit` if a limit is malformed, or an error if
+    /// the global configuration cannot be modified or saved.
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) -> Result<(), Box<dyn Error>> {
+        if let Some(memory_max) = &limits.memory_max {
+            ResourceLimits::validate("memory_max", memory_max.as_str())?;
+        }
+        if let Some(cpu_quota) = &limits.cpu_quota {
+            ResourceLimits::validate("cpu_quota", cpu_quota.as_str())?;
+        }
+        if !limits.is_empty() && Launcher::from_config()? != Launcher::SystemdRun {
+            warn!("Resource limits are only applied with the systemd-run launcher, session {} will fail to start until [session].launcher is set to systemd-run", self.reg_name);
+        }
+
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        let current_session_section = session_info.get_mut(self.reg_name.as_str()).unwrap().as_table_mut().unwrap();
+        limits.write_to(current_session_section);
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        self.limits = limits;
+        Ok(())
+    }
+
+    /// Parse the `vt` key of a session, either an integer or a numeric string.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidVt` if the value is not a positive number.
+    pub fn parse_vt(value: &Value) -> Result<u32, SessionInstanceError> {
+        let vt = match value {
+            Value::Integer(vt) => u32::try_from(*vt).ok(),
+            Value::String(vt) => vt.trim().parse::<u32>().ok(),
+            _ => None,
+        };
+        vt.filter(|vt| *vt > 0)
+            .ok_or_else(|| SessionInstanceError::InvalidVt(value.to_string()))
+    }
+
+    fn strings_to_value(values: &[String]) -> Value {
+        Value::Array(values.iter().map(|value| Value::String(value.clone())).collect())
+    }
+
+    /// Set the current session as the default session in the global configuration.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of setting the session as default. If the
+    /// session is successfully set as default, it returns `Ok(())`. If an error occurs during the
+    /// process, it returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of setting the session as
+    /// default, such as failure to access or modify the global configuration or errors encountered
+    /// while saving the configuration.
+    pub fn set_as_default(&self) -> Result<(), Box<dyn Error>> {
+        self.set_as_default_for(None)
+    }
+
+    /// Set the current session as the default session of a protocol in the global configuration.
+    ///
+    /// The session is stored in the `default_x11` or `default_wayland` key of the `[session]`
+    /// section, which takes precedence over `default` when a session of that protocol is started,
+    /// see `get_default_name_for`.
+    ///
+    /// # Parameters
+    ///
+    /// * `protocol`: The protocol to set the default session of, or `None` to set the `default`
+    ///   key like `set_as_default`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session does not use `protocol`, or the configuration cannot be
+    /// saved.
+    pub fn set_as_default_for(&self, protocol: Option<Protocol>) -> Result<(), Box<dyn Error>> {
+        if let Some(protocol) = protocol {
+            if protocol != self.protocol {
+                return Err(Box::from(format!("Session {} is a {} session, it cannot be the default {} session", self.reg_name, self.protocol, protocol)));
+            }
+        }
+
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        toml_macros::change_or_insert!(session_info, Self::default_key(protocol).as_str(), Value::String(self.reg_name.clone()));
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+        Ok(())
+    }
+
+    /// Make the current session the default session, remembering the one it replaces so that
+    /// `revert_default` can restore it.
+    ///
+    /// The global configuration is saved once, after the config of the login manager is updated
+    /// for the protocol of the new default session. Nothing is saved if that fails.
+    ///
+    /// # Returns
+    ///
+    /// Returns the register name of the replaced default session, or `None` if none was set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session is the default session already, or either config cannot
+    /// be saved.
+    pub fn swap_default(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let previous_default = Self::get_default_name()?;
+        if previous_default.as_deref() == Some(self.reg_name.as_str()) {
+            return Err(Box::from(format!("Session {} is the default session already", self.reg_name)));
+        }
+
+        GLOBAL_CONFIG.get_mut().unwrap().begin_batch();
+        match self.replace_default(previous_default.as_deref()) {
+            Ok(()) => GLOBAL_CONFIG.get_mut().unwrap().end_batch()?,
+            Err(err) => {
+                GLOBAL_CONFIG.get_mut().unwrap().abort_batch()?;
+                return Err(err);
+            }
+        }
+        Ok(previous_default)
+    }
+
+    /// Restore the default session replaced by the last `swap_default`.
+    ///
+    /// The current default session becomes the previous one, so reverting twice swaps back.
+    ///
+    /// # Returns
+    ///
+    /// Returns the register name of the restored default session.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::PreviousDefaultNotSet` if no previous default session is
+    /// recorded, or an error if it is no longer registered or the configuration cannot be saved.
+    pub fn revert_default() -> Result<String, Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+        let previous_default = session_info.get(PREVIOUS_DEFAULT_KEY)
+            .and_then(|previous_default| previous_default.as_str())
+            .ok_or(SessionInstanceError::PreviousDefaultNotSet)?;
+        Self::from_config(Some(previous_default))?.swap_default()?;
+        Ok(String::from(previous_default))
+    }
+
+    fn replace_default(&self, previous_default: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        match previous_default {
+            Some(previous_default) => toml_macros::change_or_insert!(session_info, PREVIOUS_DEFAULT_KEY, Value::String(String::from(previous_default))),
+            None => {
+                session_info.remove(PREVIOUS_DEFAULT_KEY);
+            }
+        }
+        self.set_as_default()?;
+
+        // Update Login Manager config to reflect the protocol of the new default session
+        if let Some(manager) = get_current_manager_if_set()? {
+            manager.save_config()?;
+        }
+        Ok(())
+    }
+
+    fn default_key(protocol: Option<Protocol>) -> String {
+        match protocol {
+            Some(protocol) => format!("default_{protocol}"),
+            None => String::from("default"),
+        }
+    }
+
+    /// Set the current session as a one-shot session in the global configuration.
+    ///
+    /// # Parameters
+    ///
+    /// * `save_manager`: Whether to update the login manager config right away. If `false`, the
+    ///   config of the login manager is left untouched until it is saved again, e.g. by `login now`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the success or failure of setting the session as a one-shot
+    /// session. If the session is successfully set as a one-shot session, it returns `Ok(())`. If an
+    /// error occurs during the process, it returns an error message wrapped in a `Box<dyn Error>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of setting the session as
+    /// a one-shot session, such as failure to access or modify the global configuration, errors
+    /// encountered while saving the configuration, or errors while updating the login manager
+    /// configuration for session changes.
+    pub fn set_start_oneshot(&self, save_manager: bool) -> Result<(), Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session").as_table_mut().unwrap();
+        toml_macros::change_or_insert!(session_info, "oneshot_session", Value::String(self.reg_name.clone()));
+        toml_macros::change_or_insert!(session_info, "oneshot_started", Value::Boolean(false));
+        GLOBAL_CONFIG.get_mut().unwrap().save_config()?;
+
+        if !save_manager {
+            info!("Login Manager config is not updated, it will be on the next login now");
+            return Ok(());
+        }
+
+        // Update Login Manager config to reflect the session change
+        if let Some(manager) = get_current_manager_if_set()? {
+            manager.save_config()?;
+        }
+        Ok(())
+    }
+
+    /// Retrieve the protocol associated with the session.
+    ///
+    /// # Returns
+    ///
+    /// Returns the protocol (`Protocol`) associated with the session.
+    pub fn get_protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Retrieve the name the session is registered under.
+    pub fn get_reg_name(&self) -> &str {
+        self.reg_name.as_str()
+    }
+
+    /// Retrieve the name of the desktop file the session starts, without the `.desktop` extension.
+    pub fn get_real_name(&self) -> &str {
+        self.real_name.as_str()
+    }
+
+    /// Retrieve the default session configuration.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either the default session configuration (`Self`) or an error
+    /// message wrapped in a `Box<dyn Error>`. If the default session configuration is successfully
+    /// retrieved, it returns `Ok(Self)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of retrieving the default
+    /// session configuration, such as failure to load the configuration from the file.
+    pub fn get_default_session() -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_config(None)?)
+    }
+
+    /// Retrieve the default session of a protocol, see `get_default_name_for`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::DefaultSessionNotSet` if neither the default session of
+    /// `protocol` nor the `default` session is set, or an error if the session cannot be loaded.
+    pub fn get_default_session_for(protocol: Option<Protocol>) -> Result<Self, Box<dyn Error>> {
+        match Self::get_default_name_for(protocol)? {
+            Some(name) => Self::from_config(Some(name.as_str())),
+            None => Err(Box::from(SessionInstanceError::DefaultSessionNotSet)),
+        }
+    }
+
+    /// Retrieve the register name of the default session.
+    ///
+    /// Unlike `get_default_session`, this function only reads the `default` key from the global
+    /// configuration and does not resolve the session, so it still works when the desktop file of
+    /// the default session is missing from the system.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either the register name of the default session wrapped in
+    /// `Some`, or `None` if no default session is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `default` key exists but is not a string.
+    pub fn get_default_name() -> Result<Option<String>, Box<dyn Error>> {
+        Self::get_default_name_for(None)
+    }
+
+    /// Retrieve the register name of the default session of a protocol.
+    ///
+    /// The `default_x11` or `default_wayland` key of the `[session]` section is read first, with
+    /// the `default` key as the fallback, so a single default still serves both protocols.
+    ///
+    /// # Parameters
+    ///
+    /// * `protocol`: The protocol of the session to start, or `None` to only read `default`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the register name wrapped in `Some`, or `None` if neither key is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the keys exists but is not a string.
+    pub fn get_default_name_for(protocol: Option<Protocol>) -> Result<Option<String>, Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+        if protocol.is_some() {
+            let default_key = Self::default_key(protocol);
+            if let Some(default_session) = session_info.get(default_key.as_str()) {
+                let name = default_session.as_str().ok_or("Default session in config is not a string")?;
+                explain::record(Stage::Default, || format!("Using {default_key} = {name}"));
+                return Ok(Some(String::from(name)));
+            }
+            explain::record(Stage::Default, || format!("{default_key} is not set, falling back to default"));
+        }
+
+        match session_info.get("default") {
+            Some(default_session) => {
+                let name = default_session.as_str().ok_or("Default session in config is not a string")?;
+                explain::record(Stage::Default, || format!("Using default = {name}"));
+                Ok(Some(String::from(name)))
+            }
+            None => {
+                explain::record(Stage::Default, || String::from("default is not set"));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Summarize the registered sessions and the sessions and Auto Login settings that pick one.
+    ///
+    /// Sessions are read from the configuration as stored, like in `SessionSummary::collect`, so
+    /// a session whose desktop file is missing is still counted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the default session cannot be read from the global configuration.
+    pub fn stats() -> Result<SessionStats, Box<dyn Error>> {
+        let names = Self::list_registered();
+        let protocols: Vec<Option<Protocol>> = names.iter()
+            .map(|name| Self::get_registered_protocol(name.as_str()))
+            .collect();
+        let count = |protocol: Option<Protocol>| protocols.iter().filter(|&&other| other == protocol).count();
+
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+        let oneshot_started = session_info.get("oneshot_started").and_then(|started| started.as_bool()).unwrap_or(true);
+        let oneshot = session_info.get("oneshot_session")
+            .and_then(|oneshot| oneshot.as_str())
+            .filter(|_| !oneshot_started)
+            .map(String::from);
+
+        Ok(SessionStats {
+            total: names.len(),
+            x11: count(Some(Protocol::X11)),
+            wayland: count(Some(Protocol::Wayland)),
+            unknown: count(None),
+            default: Self::get_default_name()?,
+            oneshot,
+            autologin: is_autologin_enabled_in_config(),
+        })
+    }
+
+    /// Retrieve the one-shot session configuration, whether or not it was started already.
+    ///
+    /// Unlike `get_oneshot_session`, this still finds the one-shot session while it is running.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::OneshotSessionNotSet` if no one-shot session is set, or an
+    /// error if the session cannot be loaded.
+    pub fn get_last_oneshot_session() -> Result<Self, Box<dyn Error>> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+        let oneshot_session = session_info.get("oneshot_session")
+            .and_then(|oneshot_session| oneshot_session.as_str())
+            .map(String::from)
+            .ok_or(SessionInstanceError::OneshotSessionNotSet)?;
+        Self::from_config(Some(oneshot_session.as_str()))
+    }
+
+    /// Retrieve the one-shot session configuration if it exists and is not already started.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either an optional one-shot session configuration (`Option<Self>`)
+    /// or an error message wrapped in a `Box<dyn Error>`. If a one-shot session configuration exists
+    /// and is not already started, it returns `Ok(Some(Self))`. If there is no one-shot session
+    /// configuration or it's already started, it returns `Ok(None)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of retrieving the
+    /// one-shot session configuration, such as failure to load the configuration from the file.
+    pub fn get_oneshot_session() -> Result<Option<Self>, Box<dyn Error>> {
+        match Self::get_oneshot_name() {
+            Some(oneshot_session) => Ok(Some(Self::from_config(Some(oneshot_session.as_str()))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve the register name of the one-shot session if it is set and not already started,
+    /// without loading the session.
+    pub fn get_oneshot_name() -> Option<String> {
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get_section_snapshot("session").unwrap_or_default();
+        let oneshot_session = session_info.get("oneshot_session");
+        let oneshot_started = session_info.get("oneshot_started");
+
+        if let (Some(oneshot_session), Some(oneshot_started)) = (oneshot_session, oneshot_started) {
+            if !oneshot_started.as_bool().unwrap() {
+                return Some(String::from(oneshot_session.as_str().unwrap()));
+            }
+        }
+        None
+    }
+
+    /// Retrieve the register name of the session `start_oneshot_or_default_session` starts next,
+    /// without marking the one-shot session as started.
+    ///
+    /// # Parameters
+    ///
+    /// * `protocol`: The protocol to read the default session of, see `get_default_name_for`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the one-shot session if it was not started yet, else the default session, or
+    /// `None` if neither is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_default_name_for`.
+    pub fn get_next_start_name(protocol: Option<Protocol>) -> Result<Option<String>, Box<dyn Error>> {
+        match Self::get_oneshot_name() {
+            Some(oneshot_session) => Ok(Some(oneshot_session)),
+            None => Self::get_default_name_for(protocol),
+        }
+    }
+
+    /// Retrieve the seat molyuuctl runs on from `XDG_SEAT`, defaulting to `seat0`.
+    pub fn current_seat() -> String {
+        env::var(SEAT_ENV).ok()
+            .filter(|seat| !seat.is_empty())
+            .unwrap_or_else(|| String::from(DEFAULT_SEAT))
+    }
+
+    /// Compose the name of the startup lock of a seat, so sessions on different seats do not
+    /// contend for the same lock.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidSeat` if the seat name is not usable in a file name.
+    fn startup_lock_name(seat: &str) -> Result<String, SessionInstanceError> {
+        if seat.is_empty() || !seat.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(SessionInstanceError::InvalidSeat(String::from(seat)));
+        }
+        Ok(format!("{MOLYUUCTL_SESSION_STARTUP_LOCK}-{seat}"))
+    }
+
+    /// Create the startup lock of a seat in `LOCK_RUN_DIR`, which only root can write to, so that
+    /// other users can neither hold it nor plant its content.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SessionInstanceError::InvalidSeat` if the seat name is not usable in a file name.
+    fn startup_lock(seat: &str, content: Option<String>) -> Result<Lock, SessionInstanceError> {
+        Ok(Lock::new_in(LOCK_RUN_DIR, Self::startup_lock_name(seat)?.as_str(), content))
+    }
+
+    /// Check whether the session startup lock of a seat is left behind by a session that is no
+    /// longer running.
+    ///
+    /// # Parameters
+    ///
+    /// * `seat`: The seat whose startup lock is checked.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if the lock file exists but nobody holds the lock, `Ok(false)` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file cannot be probed.
+    pub fn has_stale_startup_lock(seat: &str) -> Result<bool, Box<dyn Error>> {
+        let molyuuctl_lock = Self::startup_lock(seat, None)?;
+        Ok(molyuuctl_lock.state()? == LockState::Stale)
+    }
+
+    /// Retrieve the session currently running on a seat if it exists.
+    ///
+    /// # Parameters
+    ///
+    /// * `seat`: The seat whose running session is retrieved.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either an optional running session configuration
+    /// (`Option<Self>`) or an error message wrapped in a `Box<dyn Error>`. If a running
+    /// session exists, it returns `Ok(Some(Self))`. If there is no running session, it
+    /// returns `Ok(None)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are issues encountered during the process of retrieving the
+    /// running session configuration, such as failure to read the lock file.
+    pub fn get_running_session(seat: &str) -> Result<Option<Self>, Box<dyn Error>> {
+        let molyuuctl_lock = Self::startup_lock(seat, None)?;
+        match molyuuctl_lock.exclusive_state()? {
+            LockState::Held => {
+                // Read running session name
+                let (session_name, _) = Self::parse_startup_lock(molyuuctl_lock.read_content()?.as_str());
+                Ok(Some(Self::from_config(Some(session_name.as_str()))?))
+            }
+            LockState::Stale => {
+                warn!("Session startup lock is left behind by a session that is no longer running");
+                Ok(None)
+            }
+            LockState::Free => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn test_session(reg_name: &str, logout_command: Option<&str>) -> Session {
+        Session {
+            reg_name: String::from(reg_name),
+            real_name: String::from("molyuuctl-test-alpha"),
+            logout_command: logout_command.map(String::from),
+            protocol: Protocol::Wayland,
+            requires: Vec::new(),
+            action: None,
+            fallback: None,
+            vt: None,
+            display_server_args: Vec::new(),
+            limits: ResourceLimits::default(),
+        }
+    }
+
+    #[test]
+    fn logout_reports_non_zero_exit() {
+        let err = test_session("alpha", Some("exit 3")).logout().unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::LogoutCommandFailed));
+    }
+
+    #[test]
+    fn logout_without_command_is_an_error() {
+        let err = test_session("alpha", None).logout().unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::LogoutCommandNotSet));
+    }
+
+    #[test]
+    fn logout_succeeds_with_zero_exit() {
+        test_session("alpha", Some("true")).logout().unwrap();
+    }
+
+    #[test]
+    fn default_name_is_read_without_resolving_the_session() {
+        // Neither session is installed
+        let _fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG);
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("alpha"));
+    }
+
+    #[test]
+    fn default_name_is_none_if_not_set() {
+        let _fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG.replace("default = \"alpha\"\n", "").as_str());
+        assert_eq!(Session::get_default_name().unwrap(), None);
+    }
+
+    #[test]
+    fn registered_session_is_promoted_to_default() {
+        let _fixture = crate::testing::load_sessions_config();
+        let mut session = test_session("gamma", None);
+
+        session.register().unwrap();
+        session.promote(true, false).unwrap();
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("gamma"));
+    }
+
+    #[test]
+    fn registration_stands_if_promoting_fails() {
+        let _fixture = crate::testing::load_config(format!("[login]\nmanager = \"unknown\"\n\n{}", crate::testing::SESSIONS_CONFIG).as_str());
+        let mut session = test_session("gamma", None);
+
+        session.register().unwrap();
+        let err = session.promote(true, true).unwrap_err();
+        assert!(err.to_string().starts_with("Session gamma is registered, but failed to set it as oneshot: "), "{err}");
+        assert!(Session::list_registered().contains(&String::from("gamma")));
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("gamma"));
+    }
+
+    #[test]
+    fn registered_session_is_not_promoted_without_flags() {
+        let fixture = crate::testing::load_sessions_config();
+        let mut session = test_session("gamma", None);
+
+        session.register().unwrap();
+        session.promote(false, false).unwrap();
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("alpha"));
+        assert!(!fixture.read().contains("oneshot_session"));
+    }
+
+    #[test]
+    fn registered_oneshot_session_is_started_once() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+        let mut session = test_session("gamma", None);
+
+        session.register().unwrap();
+        session.promote(false, true).unwrap();
+        assert_eq!(Session::select_oneshot_or_default_session(None).unwrap().get_reg_name(), "gamma");
+        assert!(fixture.read().contains("oneshot_started = true"));
+        assert_eq!(Session::get_oneshot_name(), None);
+        assert_eq!(Session::select_oneshot_or_default_session(None).unwrap().get_reg_name(), "alpha");
+    }
+
+    #[test]
+    fn oneshot_session_is_marked_started_once() {
+        let fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG
+            .replace("[session]\n", "[session]\noneshot_session = \"beta\"\noneshot_started = false\n").as_str());
+
+        assert_eq!(Configuration::with_lock(Session::mark_oneshot_started).unwrap().as_deref(), Some("beta"));
+        assert!(fixture.read().contains("oneshot_started = true"));
+        assert_eq!(Configuration::with_lock(Session::mark_oneshot_started).unwrap(), None);
+    }
+
+    #[test]
+    fn outdated_protocol_is_detected_with_a_warning_and_stored_on_start() {
+        let fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG);
+        fixture.install_session("xsessions", "molyuuctl-test-alpha", "/bin/true");
+        let before = fixture.read();
+
+        let mut session = None;
+        let logs = crate::testing::capture_logs(|| session = Some(Session::from_config(Some("alpha")).unwrap()));
+        let session = session.unwrap();
+        assert_eq!(session.protocol, Protocol::X11);
+        assert!(logs.contains(&String::from("warn: Session alpha is no longer available under its stored protocol, switching to x11")));
+        // Looking the session up does not write the configuration
+        assert_eq!(fixture.read(), before);
+
+        session.store_detected_protocol().unwrap();
+        assert!(fixture.read().contains("protocol = \"x11\""));
+    }
+
+    #[test]
+    fn prerequisites_are_started_in_order() {
+        let mut session = test_session("alpha", None);
+        session.requires = vec![String::from("pipewire.service"), String::from("seatd.service")];
+        let mut started = Vec::new();
+
+        session.ensure_requirements_with(|unit| {
+            started.push(String::from(unit));
+            Ok(())
+        }).unwrap();
+        assert_eq!(started, ["pipewire.service", "seatd.service"]);
+    }
+
+    #[test]
+    fn failed_prerequisite_is_reported_and_stops_the_start() {
+        let mut session = test_session("alpha", None);
+        session.requires = vec![String::from("pipewire.service"), String::from("seatd.service"), String::from("polkit.service")];
+        let mut started = Vec::new();
+
+        let err = session.ensure_requirements_with(|unit| {
+            started.push(String::from(unit));
+            match unit {
+                "seatd.service" => Err(Box::from("Unit seatd.service failed")),
+                _ => Ok(()),
+            }
+        }).unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(),
+            Some(&SessionInstanceError::PrerequisiteFailed(String::from("seatd.service: Unit seatd.service failed"))));
+        assert_eq!(started, ["pipewire.service", "seatd.service"]);
+    }
+
+    #[test]
+    fn prerequisites_are_stored_as_an_array() {
+        let fixture = crate::testing::load_sessions_config();
+        let mut session = Session::from_config(Some("alpha")).unwrap();
+
+        session.set_requires(vec![String::from("pipewire.service"), String::from("seatd.service")]).unwrap();
+        assert!(fixture.read().contains("requires = [\"pipewire.service\", \"seatd.service\"]"));
+        assert_eq!(Session::from_config(Some("alpha")).unwrap().requires, ["pipewire.service", "seatd.service"]);
+
+        session.set_requires(Vec::new()).unwrap();
+        assert!(!fixture.read().contains("requires"));
+    }
+
+    #[test]
+    fn config_table_keeps_unknown_keys() {
+        let _fixture = crate::testing::load_config(format!("{}future_key = \"kept\"\n\n[session.beta.extra]\nnested = 1\n", crate::testing::SESSIONS_CONFIG).as_str());
+
+        let mut table = Session::config_table("beta").unwrap();
+        assert_eq!(table["session"].as_str(), Some("molyuuctl-test-beta"));
+        assert_eq!(table["future_key"].as_str(), Some("kept"));
+        assert_eq!(table["extra"]["nested"].as_integer(), Some(1));
+
+        // The copy is detached from the global configuration
+        table.remove("future_key");
+        assert_eq!(Session::config_table("beta").unwrap()["future_key"].as_str(), Some("kept"));
+        assert!(Session::config_table("gamma").is_err());
+    }
+
+    #[test]
+    fn registered_names_skip_special_keys_and_are_sorted() {
+        let _fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG
+            .replace("[session]\n", "[session]\noneshot_session = \"beta\"\noneshot_started = false\nlauncher = \"shell\"\n")
+            .replace("[session.alpha]\n", "[session.zeta]\nsession = \"zeta\"\nprotocol = \"x11\"\n\n[session.alpha]\n").as_str());
+
+        assert_eq!(Session::list_registered(), ["alpha", "beta", "zeta"]);
+        assert_eq!(Session::get_registered_protocol("zeta"), Some(Protocol::X11));
+    }
+
+    #[test]
+    fn protocol_is_read_without_the_desktop_file() {
+        let _fixture = crate::testing::load_config(format!("{}\n[session.gamma]\nsession = \"molyuuctl-test-gamma\"\n", crate::testing::SESSIONS_CONFIG).as_str());
+        assert_eq!(Session::protocol_of("alpha").unwrap(), Protocol::Wayland);
+
+        // Without a stored protocol it can only be detected from the desktop file
+        let err = Session::protocol_of("gamma").unwrap_err();
+        assert!(matches!(err.downcast_ref::<SessionInstanceError>(), Some(SessionInstanceError::SessionNotFoundInSystem(_))));
+        let err = Session::protocol_of("missing").unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::SessionNotFoundInConfig));
+    }
+
+    #[test]
+    fn explicit_protocol_looks_up_the_session_name_not_the_register_name() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuuctl-test-plasma", "/bin/true");
+        fixture.install_session("xsessions", "gamma", "/bin/true");
+
+        let session = Session::new(String::from("gamma"), String::from("molyuuctl-test-plasma"), None, Some(Protocol::Wayland)).unwrap();
+        assert_eq!(session.real_name, "molyuuctl-test-plasma");
+        // A desktop file named like the register name does not stand in for the session
+        let err = Session::new(String::from("gamma"), String::from("molyuuctl-test-plasma"), None, Some(Protocol::X11)).err().unwrap();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::SessionNotFoundInSystem(String::from("molyuuctl-test-plasma"))));
+    }
+
+    #[test]
+    fn session_name_is_found_with_a_desktop_suffix() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("xsessions", "molyuuctl-test-plasma", "/bin/true");
+
+        assert_eq!(Session::find_session_in_system("molyuuctl-test-plasma.desktop").unwrap(), (String::from("molyuuctl-test-plasma"), Protocol::X11));
+        assert_eq!(Session::find_session_in_system("molyuuctl-test-alpha").unwrap(), (String::from("molyuuctl-test-alpha"), Protocol::Wayland));
+    }
+
+    #[test]
+    fn session_name_is_matched_case_insensitively() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("xsessions", "molyuuctl-test-plasma", "/bin/true");
+
+        assert_eq!(Session::find_session_in_system("Molyuuctl-Test-Plasma").unwrap().0, "molyuuctl-test-plasma");
+        assert_eq!(Session::find_session_in_system("MOLYUUCTL-TEST-PLASMA.desktop").unwrap().0, "molyuuctl-test-plasma");
+        let err = Session::find_session_in_system("molyuuctl-test-missing").unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::SessionNotFoundInSystem(String::from("molyuuctl-test-missing"))));
+    }
+
+    #[test]
+    fn exact_session_name_is_preferred() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("xsessions", "molyuuctl-test-plasma", "/bin/true");
+        fixture.install_session("xsessions", "molyuuctl-test-Plasma", "/bin/true");
+
+        assert_eq!(Session::find_session_in_system("molyuuctl-test-Plasma").unwrap().0, "molyuuctl-test-Plasma");
+        assert_eq!(Session::find_session_in_system("molyuuctl-test-plasma").unwrap().0, "molyuuctl-test-plasma");
+    }
+
+    static DESKTOP_FILE_WITH_ACTION: &str = "[Desktop Entry]\nName=Alpha\nExec=alpha-session\nActions=safe;\n\n[Desktop Action safe]\nName=Safe Mode\nExec=alpha-session --safe-mode\n";
+
+    #[test]
+    fn command_is_read_from_the_configured_action() {
+        let fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG
+            .replace("[session.alpha]\n", "[session.alpha]\naction = \"safe\"\n").as_str());
+        fixture.dir.write("wayland-sessions/molyuuctl-test-alpha.desktop", DESKTOP_FILE_WITH_ACTION);
+
+        let session = Session::from_config(Some("alpha")).unwrap();
+        let session_file = session.load_desktop_file().unwrap();
+        assert_eq!(session.get_command(&session_file).unwrap(), "alpha-session --safe-mode");
+    }
+
+    #[test]
+    fn command_falls_back_to_the_desktop_entry() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.dir.write("wayland-sessions/molyuuctl-test-alpha.desktop", DESKTOP_FILE_WITH_ACTION);
+
+        let mut session = Session::from_config(Some("alpha")).unwrap();
+        let session_file = session.load_desktop_file().unwrap();
+        assert_eq!(session.get_command(&session_file).unwrap(), "alpha-session");
+
+        // An action that the desktop file does not have
+        session.action = Some(String::from("missing"));
+        assert_eq!(session.get_command(&session_file).unwrap(), "alpha-session");
+    }
+
+    fn load_fallback_config() -> crate::testing::ConfigFixture {
+        let fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG
+            .replace("[session]\n", "[session]\noneshot_session = \"beta\"\noneshot_started = false\n")
+            .replace("[session.alpha]\n", "[session.alpha]\nfallback = \"beta\"\n").as_str());
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+        fixture.install_session("wayland-sessions", "molyuuctl-test-beta", "/bin/true");
+        fixture
+    }
+
+    #[test]
+    fn rename_updates_oneshot_and_fallbacks() {
+        let _fixture = load_fallback_config();
+
+        Session::from_config(Some("beta")).unwrap().rename("gamma").unwrap();
+        assert_eq!(Session::get_oneshot_name().as_deref(), Some("gamma"));
+        assert_eq!(Session::config_table("alpha").unwrap()["fallback"].as_str(), Some("gamma"));
+    }
+
+    #[test]
+    fn remove_clears_oneshot_and_fallbacks() {
+        let _fixture = load_fallback_config();
+
+        Session::from_config(Some("beta")).unwrap().remove().unwrap();
+        assert_eq!(Session::get_oneshot_name(), None);
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session");
+        assert!(session_info.get("oneshot_started").is_none());
+        assert!(Session::config_table("alpha").unwrap().get("fallback").is_none());
+    }
+
+    /// Load `SESSIONS_CONFIG` with an additional session gamma whose desktop file is not
+    /// installed, named by the one-shot, Wayland default and fallback keys.
+    fn load_dangling_config() -> crate::testing::ConfigFixture {
+        let fixture = crate::testing::load_config(format!("{}\n[session.gamma]\nsession = \"molyuuctl-test-gamma\"\nprotocol = \"wayland\"\n", crate::testing::SESSIONS_CONFIG)
+            .replace("[session]\n", "[session]\noneshot_session = \"gamma\"\noneshot_started = false\ndefault_wayland = \"gamma\"\n")
+            .replace("[session.alpha]\n", "[session.alpha]\nfallback = \"gamma\"\n").as_str());
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+        fixture.install_session("wayland-sessions", "molyuuctl-test-beta", "/bin/true");
+        fixture
+    }
+
+    #[test]
+    fn prune_removes_only_sessions_without_a_desktop_file() {
+        let fixture = load_dangling_config();
+
+        assert_eq!(Session::prune(false).unwrap(), ["gamma"]);
+        assert_eq!(Session::list_registered(), ["alpha", "beta"]);
+        let saved = fixture.read().parse::<Table>().unwrap();
+        assert!(saved["session"].get("gamma").is_none());
+        assert!(saved["session"]["alpha"].get("fallback").is_none());
+    }
+
+    #[test]
+    fn prune_clears_only_the_keys_naming_a_pruned_session() {
+        let _fixture = load_dangling_config();
+
+        Session::prune(false).unwrap();
+        let session_info = GLOBAL_CONFIG.get_mut().unwrap().get("session");
+        assert_eq!(session_info["default"].as_str(), Some("alpha"));
+        assert!(session_info.get("default_wayland").is_none());
+        assert!(session_info.get("oneshot_session").is_none());
+        assert!(session_info.get("oneshot_started").is_none());
+    }
+
+    #[test]
+    fn prune_clears_a_pruned_default_session() {
+        let _fixture = load_dangling_config();
+        GLOBAL_CONFIG.get_mut().unwrap().get("session")["default"] = Value::String(String::from("gamma"));
+
+        Session::prune(false).unwrap();
+        assert!(GLOBAL_CONFIG.get_mut().unwrap().get("session").get("default").is_none());
+    }
+
+    #[test]
+    fn prune_dry_run_leaves_the_configuration_unchanged() {
+        let fixture = load_dangling_config();
+        let before = fixture.read();
+
+        assert_eq!(Session::prune(true).unwrap(), ["gamma"]);
+        assert_eq!(fixture.read(), before);
+        assert_eq!(Session::list_registered(), ["alpha", "beta", "gamma"]);
+        assert_eq!(Session::get_oneshot_name().as_deref(), Some("gamma"));
+    }
+
+    /// Follow the fallbacks of session alpha, whose sessions exit with `statuses` in order, and
+    /// return the sessions that were started.
+    fn started_fallbacks(status: ExitStatus, statuses: &[ExitStatus]) -> Vec<String> {
+        let mut started = Vec::new();
+        let mut statuses = statuses.iter();
+        Session::from_config(Some("alpha")).unwrap().follow_fallbacks(Some(status), |session| {
+            started.push(session.reg_name.clone());
+            Ok(statuses.next().copied())
+        }).unwrap();
+        started
+    }
+
+    #[test]
+    fn fallback_is_started_when_a_session_exits_with_an_error() {
+        let _fixture = load_fallback_config();
+
+        assert_eq!(started_fallbacks(ExitStatus::from_raw(1 << 8), &[ExitStatus::from_raw(0)]), ["beta"]);
+        assert!(started_fallbacks(ExitStatus::from_raw(0), &[]).is_empty());
+    }
+
+    #[test]
+    fn fallback_is_not_started_when_a_session_is_terminated_by_a_signal() {
+        let _fixture = load_fallback_config();
+
+        assert!(started_fallbacks(ExitStatus::from_raw(libc::SIGTERM), &[]).is_empty());
+    }
+
+    #[test]
+    fn fallback_cycle_is_broken() {
+        let _fixture = load_fallback_config();
+        Session::from_config(Some("beta")).unwrap().set_fallback(Some("alpha")).unwrap();
+
+        let failed = ExitStatus::from_raw(1 << 8);
+        assert_eq!(started_fallbacks(failed, &[failed]), ["beta"]);
+    }
+
+    #[test]
+    fn desktop_file_without_desktop_entry_is_malformed() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.dir.write("wayland-sessions/molyuuctl-test-alpha.desktop", "[Desktop Action safe]\nExec=alpha-session\n");
+
+        let err = Session::from_config(Some("alpha")).unwrap().load_desktop_file().unwrap_err();
+        assert!(matches!(err.downcast_ref::<SessionInstanceError>(), Some(SessionInstanceError::MalformedDesktopFile(_))));
+    }
+
+    #[test]
+    fn desktop_file_that_does_not_parse_is_malformed() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.dir.write("wayland-sessions/molyuuctl-test-alpha.desktop", "[Desktop Entry\nExec=alpha-session\n");
+
+        let err = Session::from_config(Some("alpha")).unwrap().load_desktop_file().unwrap_err();
+        assert!(matches!(err.downcast_ref::<SessionInstanceError>(), Some(SessionInstanceError::MalformedDesktopFile(_))));
+    }
+
+    #[test]
+    fn desktop_entry_without_exec_is_malformed() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.dir.write("wayland-sessions/molyuuctl-test-alpha.desktop", "[Desktop Entry]\nName=Alpha\n");
+
+        let session = Session::from_config(Some("alpha")).unwrap();
+        let session_file = session.load_desktop_file().unwrap();
+        let err = session.get_command(&session_file).unwrap_err();
+        assert!(matches!(err.downcast_ref::<SessionInstanceError>(), Some(SessionInstanceError::MalformedDesktopFile(_))));
+    }
+
+    #[test]
+    fn seats_have_independent_startup_locks() {
+        let _fixture = crate::testing::load_sessions_config();
+        let first_seat = format!("molyuuctl-test-{}-seat0", std::process::id());
+        let second_seat = format!("molyuuctl-test-{}-seat1", std::process::id());
+        assert_ne!(Session::startup_lock_name(first_seat.as_str()).unwrap(), Session::startup_lock_name(second_seat.as_str()).unwrap());
+
+        lock::create_run_dir().unwrap();
+        let mut first_lock = Session::startup_lock(first_seat.as_str(), Some(String::from("alpha\n"))).unwrap();
+        first_lock.lock().unwrap();
+        let mut second_lock = Session::startup_lock(second_seat.as_str(), Some(String::from("beta\n"))).unwrap();
+        second_lock.lock().unwrap();
+
+        assert_eq!(Session::get_running_session(first_seat.as_str()).unwrap().unwrap().reg_name, "alpha");
+        assert_eq!(Session::get_running_session(second_seat.as_str()).unwrap().unwrap().reg_name, "beta");
+        drop(first_lock);
+        assert!(Session::get_running_session(first_seat.as_str()).unwrap().is_none());
+        assert_eq!(Session::get_running_session(second_seat.as_str()).unwrap().unwrap().reg_name, "beta");
+    }
+
+    #[test]
+    fn startup_lock_pid_of_init_or_a_process_group_is_rejected() {
+        assert_eq!(Session::parse_startup_lock("alpha\n4242\n"), (String::from("alpha"), Some(4242)));
+        for pid in ["1", "0", "-1", "-4242", "alpha"] {
+            assert_eq!(Session::parse_startup_lock(format!("alpha\n{pid}\n").as_str()), (String::from("alpha"), None));
+        }
+    }
+
+    /// Hold a startup lock in `dir` whose content names session alpha and `pid`.
+    fn forged_startup_lock(dir: &TempDir, pid: pid_t) -> Lock {
+        let mut lock = Lock::new_in(dir.path().to_str().unwrap(), "startup", Some(format!("alpha\n{pid}")));
+        lock.lock().unwrap();
+        lock
+    }
+
+    #[test]
+    fn startup_lock_pid_of_a_process_that_is_not_the_session_is_rejected() {
+        let dir = TempDir::new();
+
+        // Not a process group leader
+        let err = Session::running_session_pid(&forged_startup_lock(&dir, std::process::id() as pid_t)).unwrap_err();
+        assert!(err.to_string().contains("is not session alpha"));
+
+        // A process group leader that molyuuctl did not start as session alpha
+        let mut other = Command::new("sleep").arg("30").process_group(0).env(SESSION_NAME_ENV, "beta").spawn().unwrap();
+        // Give the process time to finish exec, its environment is empty until then
+        thread::sleep(Duration::from_millis(200));
+        let err = Session::running_session_pid(&forged_startup_lock(&dir, other.id() as pid_t)).unwrap_err();
+        assert!(err.to_string().contains("is not session alpha"));
+        other.kill().unwrap();
+        other.wait().unwrap();
+
+        let err = Session::running_session_pid(&forged_startup_lock(&dir, -1)).unwrap_err();
+        assert!(err.to_string().contains("did not record a valid PID"));
+    }
+
+    #[test]
+    fn startup_lock_pid_of_the_session_is_accepted() {
+        let dir = TempDir::new();
+        let mut session = Command::new("sleep").arg("30").process_group(0).env(SESSION_NAME_ENV, "alpha").spawn().unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        let lock = forged_startup_lock(&dir, session.id() as pid_t);
+        assert_eq!(Session::running_session_pid(&lock).unwrap(), (String::from("alpha"), session.id() as pid_t));
+
+        // Other users may not plant the content
+        fs::set_permissions(dir.path().join("startup.lock"), fs::Permissions::from_mode(0o666)).unwrap();
+        assert!(Session::running_session_pid(&lock).is_err());
+        session.kill().unwrap();
+        session.wait().unwrap();
+    }
+
+    #[test]
+    fn forced_logout_does_not_kill_a_forged_startup_lock_pid() {
+        let _fixture = crate::testing::load_sessions_config();
+        let seat = format!("molyuuctl-test-{}-forged", std::process::id());
+        let mut victim = Command::new("sleep").arg("30").process_group(0).spawn().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        lock::create_run_dir().unwrap();
+        let mut forged_lock = Session::startup_lock(seat.as_str(), Some(format!("alpha\n{}", victim.id()))).unwrap();
+        forged_lock.lock().unwrap();
+
+        let err = test_session("alpha", None).force_logout(seat.as_str(), Some(Duration::from_millis(100))).unwrap_err();
+        assert!(err.to_string().contains("is not session alpha"));
+        assert!(victim.try_wait().unwrap().is_none());
+        victim.kill().unwrap();
+        victim.wait().unwrap();
+    }
+
+    #[test]
+    fn seat_names_that_are_not_file_names_are_rejected() {
+        assert_eq!(Session::startup_lock_name("seat0").unwrap(), format!("{MOLYUUCTL_SESSION_STARTUP_LOCK}-seat0"));
+        for seat in ["", "../seat0", "seat 0"] {
+            assert_eq!(Session::startup_lock_name(seat), Err(SessionInstanceError::InvalidSeat(String::from(seat))));
+        }
+    }
+
+    #[test]
+    fn session_is_selected_by_number_or_name() {
+        let _fixture = crate::testing::load_sessions_config();
+        let mut output = Vec::new();
+        assert_eq!(Session::select_registered(&mut "2\n".as_bytes(), &mut output).unwrap(), "beta");
+        assert_eq!(String::from_utf8(output).unwrap(), "Default session is not set, registered sessions:\n  1) alpha\n  2) beta\nSelect a session [1-2]: ");
+
+        assert_eq!(Session::select_registered(&mut "alpha\n".as_bytes(), &mut Vec::new()).unwrap(), "alpha");
+    }
+
+    #[test]
+    fn invalid_selection_is_asked_again() {
+        let _fixture = crate::testing::load_sessions_config();
+        let mut output = Vec::new();
+        assert_eq!(Session::select_registered(&mut "0\n3\ngamma\n1\n".as_bytes(), &mut output).unwrap(), "alpha");
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("Invalid selection").count(), 3);
+        assert!(output.contains("Invalid selection: gamma\n"));
+    }
+
+    #[test]
+    fn selection_ends_with_the_input() {
+        let _fixture = crate::testing::load_sessions_config();
+        let err = Session::select_registered(&mut "".as_bytes(), &mut Vec::new()).unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::DefaultSessionNotSet));
+    }
+
+    #[test]
+    fn vt_is_exported_to_x11_sessions() {
+        let mut session = test_session("alpha", None);
+        session.protocol = Protocol::X11;
+        session.vt = Some(7);
+        let mut command = Command::new("startx");
+        session.apply_vt(&mut command, true);
+        assert_eq!(command.get_envs().collect::<Vec<_>>(), [(std::ffi::OsStr::new("XDG_VTNR"), Some(std::ffi::OsStr::new("7")))]);
+    }
+
+    #[test]
+    fn vt_is_ignored_for_wayland_sessions() {
+        let mut session = test_session("alpha", None);
+        session.vt = Some(7);
+        let mut command = Command::new("alpha-session");
+        session.apply_vt(&mut command, true);
+        assert_eq!(command.get_envs().count(), 0);
+    }
+
+    #[test]
+    fn display_server_args_are_appended_to_wayland_sessions() {
+        let mut session = test_session("alpha", None);
+        session.display_server_args = vec![String::from("--xwayland"), String::from("--config=/etc/a b")];
+        assert_eq!(session.append_display_server_args("sway -d"), "sway -d --xwayland '--config=/etc/a b'");
+        assert_eq!(shell_words::split(session.append_display_server_args("sway").as_str()).unwrap(), ["sway", "--xwayland", "--config=/etc/a b"]);
+    }
+
+    #[test]
+    fn display_server_args_are_ignored_for_x11_sessions() {
+        let mut session = test_session("alpha", None);
+        session.protocol = Protocol::X11;
+        session.display_server_args = vec![String::from("--xwayland")];
+        let logs = crate::testing::capture_logs(|| assert_eq!(session.append_display_server_args("startplasma-x11"), "startplasma-x11"));
+        assert!(logs.iter().any(|line| line.starts_with("warn: ") && line.contains("ignoring display server arguments")));
+    }
+
+    #[test]
+    fn display_server_args_round_trip_through_the_config_file() {
+        let fixture = crate::testing::load_sessions_config();
+        let mut session = Session::from_config(Some("alpha")).unwrap();
+        session.set_display_server_args(vec![String::from("--xwayland")]).unwrap();
+        assert!(fixture.read().contains("display_server_args = [\"--xwayland\"]"));
+        assert_eq!(Session::from_config(Some("alpha")).unwrap().display_server_args, ["--xwayland"]);
+
+        session.set_display_server_args(Vec::new()).unwrap();
+        assert!(!fixture.read().contains("display_server_args"));
+    }
+
+    #[test]
+    fn vt_must_be_a_positive_number() {
+        assert_eq!(Session::parse_vt(&Value::Integer(2)), Ok(2));
+        assert_eq!(Session::parse_vt(&Value::String(String::from(" 3 "))), Ok(3));
+        for value in [Value::Integer(0), Value::Integer(-1), Value::String(String::from("tty2")), Value::Boolean(true)] {
+            assert!(matches!(Session::parse_vt(&value), Err(SessionInstanceError::InvalidVt(_))));
+        }
+    }
+
+    #[test]
+    fn session_whose_program_is_absent_fails_validation() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "molyuuctl-test-missing-program --flag");
+        let err = Session::from_config(Some("alpha")).unwrap().validate_command().unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::CommandNotFound(String::from("molyuuctl-test-missing-program"))));
+
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/nonexistent/alpha-session");
+        let err = Session::from_config(Some("alpha")).unwrap().validate_command().unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::CommandNotFound(String::from("/nonexistent/alpha-session"))));
+    }
+
+    #[test]
+    fn installed_program_passes_validation() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "true --flag");
+        Session::from_config(Some("alpha")).unwrap().validate_command().unwrap();
+
+        // TryExec is checked instead of the command when present
+        fixture.dir.write("wayland-sessions/molyuuctl-test-alpha.desktop", "[Desktop Entry]\nTryExec=molyuuctl-test-missing-program\nExec=true\n");
+        let err = Session::from_config(Some("alpha")).unwrap().validate_command().unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::CommandNotFound(String::from("molyuuctl-test-missing-program"))));
+    }
+
+    fn desktop_entry(contents: &str) -> Ini {
+        Ini::load_from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn session_marked_for_confirmation_asks_first() {
+        let session_file = desktop_entry("[Desktop Entry]\nComment=Starts with a clean profile\nExec=alpha-session\nX-Molyuu-Confirm=true\n");
+        let desktop_section = session_file.section(Some("Desktop Entry")).unwrap();
+        let session = test_session("recovery", None);
+
+        let mut output = Vec::new();
+        session.check_start_confirmed(desktop_section, false, true, &mut "yes\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "Starts with a clean profile\nStart session recovery? [y/N]: ");
+
+        let err = session.check_start_confirmed(desktop_section, false, true, &mut "\n".as_bytes(), &mut Vec::new()).unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::StartNotConfirmed(String::from("recovery"))));
+    }
+
+    #[test]
+    fn confirmation_needs_a_terminal_unless_assumed() {
+        let session_file = desktop_entry("[Desktop Entry]\nExec=alpha-session\nX-Molyuu-Confirm=True\n");
+        let desktop_section = session_file.section(Some("Desktop Entry")).unwrap();
+        let session = test_session("recovery", None);
+
+        let err = session.check_start_confirmed(desktop_section, false, false, &mut "y\n".as_bytes(), &mut Vec::new()).unwrap_err();
+        assert!(matches!(err.downcast_ref::<SessionInstanceError>(), Some(SessionInstanceError::StartNotConfirmed(_))));
+
+        let mut output = Vec::new();
+        session.check_start_confirmed(desktop_section, true, false, &mut "".as_bytes(), &mut output).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn session_without_the_key_starts_without_asking() {
+        let session_file = desktop_entry("[Desktop Entry]\nExec=alpha-session\nX-Molyuu-Confirm=false\n");
+        let desktop_section = session_file.section(Some("Desktop Entry")).unwrap();
+        let mut output = Vec::new();
+        test_session("alpha", None).check_start_confirmed(desktop_section, false, false, &mut "".as_bytes(), &mut output).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn oneshot_session_is_resolved_for_logout_even_once_started() {
+        let _fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG
+            .replace("default = \"alpha\"\n", "default = \"alpha\"\noneshot_session = \"beta\"\noneshot_started = true\n").as_str());
+        assert_eq!(Session::get_last_oneshot_session().unwrap().reg_name, "beta");
+        assert!(Session::get_oneshot_session().unwrap().is_none());
+    }
+
+    #[test]
+    fn logout_of_an_unset_oneshot_session_is_an_error() {
+        let _fixture = crate::testing::load_sessions_config();
+        let err = Session::get_last_oneshot_session().err().unwrap();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::OneshotSessionNotSet));
+    }
+
+    #[test]
+    fn sessions_are_resolved_from_extra_directories() {
+        let fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG
+            .replace("[session]\n", "[session]\nxsessions_dirs = [\"{dir}/xsessions\", \"{dir}/more-xsessions\"]\n").as_str());
+        fixture.install_session("more-xsessions", "molyuuctl-test-gamma", "/bin/true");
+        fixture.install_session("wayland-sessions", "molyuuctl-test-delta", "/bin/true");
+
+        let dirs = Session::get_sessions_dirs(Protocol::X11);
+        assert_eq!(dirs[0], SYSTEM_XSESSIONS_PATH);
+        assert!(dirs[2].ends_with("/more-xsessions"));
+        assert_eq!(Session::find_session_in_system("molyuuctl-test-gamma").unwrap(), (String::from("molyuuctl-test-gamma"), Protocol::X11));
+        assert_eq!(Session::find_session_in_system("molyuuctl-test-delta").unwrap(), (String::from("molyuuctl-test-delta"), Protocol::Wayland));
+        assert!(Session::exists_in_system("molyuuctl-test-gamma", Protocol::X11));
+        assert!(!Session::exists_in_system("molyuuctl-test-gamma", Protocol::Wayland));
+    }
+
+    #[test]
+    fn first_directory_with_the_desktop_file_wins() {
+        let fixture = crate::testing::load_config(crate::testing::SESSIONS_CONFIG
+            .replace("[session]\n", "[session]\nxsessions_dirs = [\"{dir}/xsessions\", \"{dir}/more-xsessions\"]\n").as_str());
+        fixture.install_session("xsessions", "molyuuctl-test-gamma", "/bin/true");
+        fixture.install_session("more-xsessions", "molyuuctl-test-gamma", "/bin/false");
+
+        let path = Session::find_desktop_file("molyuuctl-test-gamma", Protocol::X11).unwrap();
+        assert_eq!(path, fixture.dir.path().join("xsessions/molyuuctl-test-gamma.desktop"));
+    }
+
+    #[test]
+    fn dry_run_of_a_valid_session_passes_every_step() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "true --flag");
+
+        let steps = Session::from_config(Some("alpha")).unwrap().dry_run();
+        assert_eq!(steps.len(), 5);
+        assert!(steps.iter().all(|(_, result)| result.is_ok()));
+        assert_eq!(steps[1].1.as_ref().unwrap(), "true --flag");
+        assert_eq!(steps[4].1.as_ref().unwrap(), "none");
+    }
+
+    #[test]
+    fn dry_run_of_a_broken_session_reports_the_failing_step() {
+        let fixture = crate::testing::load_sessions_config();
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "molyuuctl-test-missing-program");
+        let steps = Session::from_config(Some("alpha")).unwrap().dry_run();
+        let failed: Vec<&str> = steps.iter().filter(|(_, result)| result.is_err()).map(|(name, _)| *name).collect();
+        assert_eq!(failed, ["Command is installed"]);
+
+        // Nothing can be checked without the desktop file
+        fs::remove_file(fixture.dir.path().join("wayland-sessions/molyuuctl-test-alpha.desktop")).unwrap();
+        let steps = Session::from_config(Some("alpha")).unwrap().dry_run();
+        assert_eq!(steps.len(), 1);
+        assert!(steps[0].1.is_err());
+    }
+
+    #[test]
+    fn log_file_must_be_directly_in_the_log_directory() {
+        let dir = TempDir::new();
+        let log_directory = dir.path().join("logs");
+        fs::create_dir_all(&log_directory).unwrap();
+        fs::create_dir_all(log_directory.join("nested")).unwrap();
+
+        assert!(Session::is_log_file_in(log_directory.join("alpha.log").as_path(), log_directory.as_path()));
+        assert!(!Session::is_log_file_in(log_directory.join("nested/alpha.log").as_path(), log_directory.as_path()));
+        assert!(!Session::is_log_file_in(log_directory.join("../shadow").as_path(), log_directory.as_path()));
+        assert!(!Session::is_log_file_in(log_directory.join("..").as_path(), log_directory.as_path()));
+        assert!(!Session::is_log_file_in(Path::new("/etc/shadow"), log_directory.as_path()));
+    }
+
+    #[test]
+    fn log_directory_reached_through_a_symlink_elsewhere_does_not_match() {
+        let dir = TempDir::new();
+        let log_directory = dir.path().join("logs");
+        fs::create_dir_all(&log_directory).unwrap();
+        std::os::unix::fs::symlink("/etc", dir.path().join("link")).unwrap();
+
+        assert!(!Session::is_log_file_in(dir.path().join("link/shadow").as_path(), log_directory.as_path()));
+    }
+
+    #[test]
+    fn missing_log_directory_is_compared_as_spelled() {
+        let dir = TempDir::new();
+        let log_directory = dir.path().join("missing");
+
+        assert!(Session::is_log_file_in(log_directory.join("alpha.log").as_path(), log_directory.as_path()));
+        assert!(!Session::is_log_file_in(log_directory.join("nested/alpha.log").as_path(), log_directory.as_path()));
+    }
+
+    #[test]
+    fn open_log_output_keeps_the_previous_log() {
+        let dir = TempDir::new();
+        let path = dir.write("logs/alpha.log", "previous start\n");
+        let mut file = Session::open_log_output(path.as_path()).unwrap();
+        file.write_all(b"this start\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "this start\n");
+        assert_eq!(fs::read_to_string(dir.path().join("logs/alpha.log.old")).unwrap(), "previous start\n");
+    }
+
+    #[test]
+    fn quiet_session_omits_the_launch_lines() {
+        let session_file = desktop_entry("[Desktop Entry]\nName=Alpha\nExec=alpha-session\n");
+        let desktop_section = session_file.section(Some("Desktop Entry")).unwrap();
+        let mut session = test_session("alpha", None);
+        session.protocol = Protocol::X11;
+        session.vt = Some(2);
+
+        let logs = crate::testing::capture_logs(|| {
+            session.announce_launch(desktop_section, "alpha-session", false);
+            session.apply_vt(&mut Command::new("alpha-session"), false);
+        });
+        assert_eq!(logs, ["info: Target Session: Alpha", "info: Executing Session Command: alpha-session", "info: Running session on VT 2"]);
+
+        let logs = crate::testing::capture_logs(|| {
+            session.announce_launch(desktop_section, "alpha-session", true);
+            session.apply_vt(&mut Command::new("alpha-session"), true);
+        });
+        assert!(logs.is_empty());
+    }
+
+    #[test]
+    fn quiet_session_keeps_warnings() {
+        let mut session = test_session("alpha", None);
+        session.vt = Some(2);
+        let logs = crate::testing::capture_logs(|| session.apply_vt(&mut Command::new("alpha-session"), true));
+        assert_eq!(logs, ["warn: Session alpha is a Wayland session, ignoring VT 2"]);
+    }
+
+    static PER_PROTOCOL_CONFIG: &str = "\n[session.gamma]\nsession = \"molyuuctl-test-gamma\"\nprotocol = \"x11\"\n";
+
+    #[test]
+    fn protocol_default_takes_precedence_over_default() {
+        let contents = crate::testing::SESSIONS_CONFIG.replace("default = \"alpha\"\n", "default = \"alpha\"\ndefault_wayland = \"beta\"\n") + PER_PROTOCOL_CONFIG;
+        let _fixture = crate::testing::load_config(contents.as_str());
+
+        assert_eq!(Session::get_default_name_for(Some(Protocol::Wayland)).unwrap().as_deref(), Some("beta"));
+        // Without a default of its own a protocol falls back to default
+        assert_eq!(Session::get_default_name_for(Some(Protocol::X11)).unwrap().as_deref(), Some("alpha"));
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("alpha"));
+    }
+
+    #[test]
+    fn protocol_default_is_set_for_a_session_of_that_protocol() {
+        let contents = crate::testing::SESSIONS_CONFIG.replace("default = \"alpha\"\n", "") + PER_PROTOCOL_CONFIG;
+        let fixture = crate::testing::load_config(contents.as_str());
+        fixture.install_session("xsessions", "molyuuctl-test-gamma", "/bin/true");
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+
+        let gamma = Session::from_config(Some("gamma")).unwrap();
+        gamma.set_as_default_for(Some(Protocol::X11)).unwrap();
+        assert!(fixture.read().contains("default_x11 = \"gamma\""));
+        assert_eq!(Session::get_default_name_for(Some(Protocol::X11)).unwrap().as_deref(), Some("gamma"));
+        assert_eq!(Session::get_default_name_for(Some(Protocol::Wayland)).unwrap(), None);
+        assert_eq!(Session::get_default_name().unwrap(), None);
+
+        assert!(Session::from_config(Some("alpha")).unwrap().set_as_default_for(Some(Protocol::X11)).is_err());
+        assert!(gamma.set_as_default_for(Some(Protocol::Wayland)).is_err());
+    }
+
+    #[test]
+    fn custom_key_round_trips_through_the_config_file() {
+        let fixture = crate::testing::load_sessions_config();
+        Session::set_key("alpha", "greeting", Value::Integer(42), false).unwrap();
+        assert_eq!(Session::get_key("alpha", "greeting").unwrap(), Value::Integer(42));
+        assert!(fixture.read().contains("greeting = 42"));
+
+        Session::unset_key("alpha", "greeting", false).unwrap();
+        let err = Session::get_key("alpha", "greeting").unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::KeyNotSet(String::from("greeting"))));
+        let err = Session::unset_key("alpha", "greeting", false).unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::KeyNotSet(String::from("greeting"))));
+    }
+
+    #[test]
+    fn custom_key_on_unknown_session_is_an_error() {
+        let _fixture = crate::testing::load_sessions_config();
+        let err = Session::set_key("gamma", "greeting", Value::Boolean(true), false).unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::SessionNotFoundInConfig));
+    }
+
+    #[test]
+    fn structural_keys_need_force() {
+        let _fixture = crate::testing::load_sessions_config();
+        for key in ["session", "protocol"] {
+            let err = Session::set_key("alpha", key, Value::String(String::from("x11")), false).unwrap_err();
+            assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::StructuralKey(String::from(key))));
+            let err = Session::unset_key("alpha", key, false).unwrap_err();
+            assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::StructuralKey(String::from(key))));
+        }
+        assert_eq!(Session::get_key("alpha", "protocol").unwrap(), Value::String(String::from("wayland")));
+
+        Session::set_key("alpha", "protocol", Value::String(String::from("x11")), true).unwrap();
+        assert_eq!(Session::get_key("alpha", "protocol").unwrap(), Value::String(String::from("x11")));
+    }
+
+    #[test]
+    fn register_or_update_creates_a_missing_session() {
+        let fixture = crate::testing::load_sessions_config();
+        let mut session = Session::new(String::from("gamma"), String::from("molyuuctl-test-beta"), Some(String::from("true")), Some(Protocol::Wayland)).unwrap();
+        assert!(session.register_or_update().unwrap());
+        assert_eq!(Session::get_key("gamma", "session").unwrap(), Value::String(String::from("molyuuctl-test-beta")));
+        assert!(fixture.read().contains("[session.gamma]"));
+    }
+
+    #[test]
+    fn register_or_update_updates_an_existing_session_in_place() {
+        let _fixture = crate::testing::load_sessions_config();
+        Session::set_key("alpha", "greeting", Value::Boolean(true), false).unwrap();
+        let mut session = Session::new(String::from("alpha"), String::from("molyuuctl-test-beta"), Some(String::from("true")), Some(Protocol::Wayland)).unwrap();
+        assert!(!session.register_or_update().unwrap());
+        assert_eq!(Session::get_key("alpha", "session").unwrap(), Value::String(String::from("molyuuctl-test-beta")));
+        assert_eq!(Session::get_key("alpha", "logout_command").unwrap(), Value::String(String::from("true")));
+        // Keys the update does not manage are left alone
+        assert_eq!(Session::get_key("alpha", "greeting").unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn explain_traces_oneshot_then_default() {
+        let contents = crate::testing::SESSIONS_CONFIG.replace("[session]\n", "[session]\noneshot_session = \"beta\"\noneshot_started = false\n");
+        let fixture = crate::testing::load_config(contents.as_str());
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+        fixture.install_session("wayland-sessions", "molyuuctl-test-beta", "/bin/true");
+        explain::enable();
+        explain::take();
+
+        assert_eq!(Session::select_oneshot_or_default_session(None).unwrap().get_reg_name(), "beta");
+        assert_eq!(Session::select_oneshot_or_default_session(None).unwrap().get_reg_name(), "alpha");
+
+        let trace: Vec<String> = explain::take().into_iter()
+            .filter(|record| record.stage != Stage::Protocol)
+            .map(|record| record.to_string())
+            .collect();
+        assert_eq!(trace, [
+            "explain[oneshot]: Oneshot session beta is not started yet, starting it",
+            "explain[oneshot]: Oneshot session beta is started already, starting the default session",
+            "explain[default]: Using default = alpha",
+        ]);
+    }
+
+    #[test]
+    fn deferred_oneshot_leaves_the_manager_config_untouched() {
+        let contents = crate::testing::SESSIONS_CONFIG.replace("[login.autologin]\n", "[login]\nmanager = \"sddm\"\n\n[login.autologin]\n");
+        let fixture = crate::testing::load_config(contents.as_str());
+        fixture.install_session("wayland-sessions", "molyuuctl-test-beta", "/bin/true");
+        let manager_config = crate::login::manager::ManagerMetadata::build_for_supported_manager(crate::login::manager::SupportedManager::SDDM).config_path;
+        let before = fs::read(&manager_config).ok();
+
+        let logs = crate::testing::capture_logs(|| Session::from_config(Some("beta")).unwrap().set_start_oneshot(false).unwrap());
+        assert_eq!(fs::read(&manager_config).ok(), before);
+        assert!(logs.contains(&String::from("info: Login Manager config is not updated, it will be on the next login now")));
+        assert_eq!(Session::get_oneshot_name().as_deref(), Some("beta"));
+        assert!(fixture.read().contains("oneshot_session = \"beta\""));
+    }
+
+    #[test]
+    fn set_start_oneshot_without_manager_warns_and_succeeds() {
+        let _fixture = crate::testing::load_sessions_config();
+        let logs = crate::testing::capture_logs(|| Session::from_config(Some("beta")).unwrap().set_start_oneshot(true).unwrap());
+        assert!(logs.contains(&String::from("warn: Login Manager is not set, skipped updating its config")));
+        assert_eq!(Session::get_oneshot_name().as_deref(), Some("beta"));
+    }
+
+    #[test]
+    fn swap_default_records_the_previous_default_and_revert_restores_it() {
+        let fixture = crate::testing::load_sessions_config();
+        let previous = Session::from_config(Some("beta")).unwrap().swap_default().unwrap();
+        assert_eq!(previous.as_deref(), Some("alpha"));
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("beta"));
+        assert!(fixture.read().contains("previous_default = \"alpha\""));
+
+        assert_eq!(Session::revert_default().unwrap(), "alpha");
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("alpha"));
+        // Reverting twice swaps back
+        assert_eq!(Session::revert_default().unwrap(), "beta");
+    }
+
+    #[test]
+    fn swap_default_to_the_current_default_is_an_error() {
+        let fixture = crate::testing::load_sessions_config();
+        let before = fixture.read();
+        assert!(Session::from_config(Some("alpha")).unwrap().swap_default().is_err());
+        assert_eq!(fixture.read(), before);
+    }
+
+    #[test]
+    fn revert_default_without_a_swap_is_an_error() {
+        let _fixture = crate::testing::load_sessions_config();
+        let err = Session::revert_default().unwrap_err();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::PreviousDefaultNotSet));
+    }
+
+    #[test]
+    fn reg_names_that_leave_a_directory_are_rejected() {
+        for reg_name in ["", "../alpha", "alpha/beta", "alpha\\beta", ".", "..", "alpha\nbeta", &"a".repeat(MAX_REG_NAME_LENGTH + 1)] {
+            assert_eq!(Session::validate_reg_name(reg_name), Err(SessionInstanceError::InvalidRegisterName(reg_name.escape_debug().to_string())));
+        }
+        assert_eq!(Session::validate_reg_name("a".repeat(MAX_REG_NAME_LENGTH).as_str()), Ok(()));
+        assert_eq!(Session::validate_reg_name("alpha.beta-2"), Ok(()));
+        assert_eq!(Session::validate_reg_name("alpha..beta"), Ok(()));
+    }
+
+    #[test]
+    fn reg_names_of_session_settings_are_rejected() {
+        for reg_name in RESERVED_REG_NAMES {
+            assert_eq!(Session::validate_reg_name(reg_name), Err(SessionInstanceError::ReservedRegisterName(String::from(reg_name))));
+        }
+        assert_eq!(Session::validate_reg_name("default-plasma"), Ok(()));
+    }
+
+    #[test]
+    fn session_names_cannot_traverse_out_of_the_session_dirs() {
+        let contents = format!("{}\n[session.evil]\nsession = \"../evil\"\nprotocol = \"wayland\"\n", crate::testing::SESSIONS_CONFIG);
+        let fixture = crate::testing::load_config(contents.as_str());
+        fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+        // A desktop file next to the session directory, reachable as `wayland-sessions/../evil`
+        fs::write(fixture.dir.path().join("evil.desktop"), "[Desktop Entry]\nName=Evil\nExec=/bin/true\n").unwrap();
+
+        let err = Session::new(String::from("evil2"), String::from("../evil"), None, Some(Protocol::Wayland)).err().unwrap();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::InvalidSessionName(String::from("../evil"))));
+        assert!(Session::find_session_in_system("../evil").is_err());
+        assert!(Session::find_desktop_file("../evil", Protocol::Wayland).is_none());
+        // A traversal written to the config by hand is refused before it is used
+        let err = Session::from_config(Some("evil")).err().unwrap();
+        assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::InvalidSessionName(String::from("../evil"))));
+        assert!(Session::validate_real_name("molyuuctl-test-alpha").is_ok());
+        assert!(Session::validate_real_name("sway\n").is_err());
+        assert!(Session::validate_real_name("..").is_err());
+        assert!(Session::validate_real_name(".").is_err());
+        assert!(Session::validate_real_name("foo..bar").is_ok());
+    }
+}
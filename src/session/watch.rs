@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
+
+/// When `Session::watch` restarts a session that exited.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WatchPolicy {
+    /// How many restarts are allowed within `window` before giving up.
+    pub max_retries: u32,
+    /// The period restarts are counted in, older restarts are forgotten.
+    pub window: Duration,
+    /// The delay before the first restart within the window, doubled for every further one.
+    pub backoff: Duration,
+    /// The longest delay before a restart.
+    pub max_backoff: Duration,
+    /// Restart the session after it exited normally as well, e.g. for kiosks that should never
+    /// leave the session.
+    pub restart_always: bool,
+}
+
+impl Default for WatchPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            window: Duration::from_secs(60),
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            restart_always: false,
+        }
+    }
+}
+
+/// Whether a session exited normally, i.e. successfully or terminated by SIGTERM or SIGHUP as on
+/// logout or shutdown, so `Session::watch` does not treat it as a crash.
+pub fn is_normal_exit(status: &ExitStatus) -> bool {
+    status.success() || matches!(status.signal(), Some(libc::SIGTERM | libc::SIGHUP))
+}
+
+/// Tracks the restarts of a watched session to decide whether and when to restart it again.
+#[derive(Debug, Clone)]
+pub struct RestartTracker {
+    policy: WatchPolicy,
+    /// When the session was restarted within the window, oldest first.
+    restarts: VecDeque<Instant>,
+}
+
+impl RestartTracker {
+    pub fn new(policy: WatchPolicy) -> Self {
+        Self { policy, restarts: VecDeque::new() }
+    }
+
+    /// Record that the session exited and decide whether to restart it.
+    ///
+    /// # Parameters
+    ///
+    /// * `normal_exit`: Whether the session exited normally, see `is_normal_exit`.
+    /// * `now`: When the session exited.
+    ///
+    /// # Returns
+    ///
+    /// Returns the delay before restarting the session, or `None` if it is not restarted because
+    /// it exited normally or it was restarted `max_retries` times within the window already.
+    pub fn next_restart(&mut self, normal_exit: bool, now: Instant) -> Option<Duration> {
+        if normal_exit && !self.policy.restart_always {
+            return None;
+        }
+
+        while self.restarts.front().is_some_and(|restart| now.duration_since(*restart) >= self.policy.window) {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() >= self.policy.max_retries as usize {
+            return None;
+        }
+
+        let delay = self.policy.backoff
+            .checked_mul(2u32.saturating_pow(self.restarts.len() as u32))
+            .map_or(self.policy.max_backoff, |delay| delay.min(self.policy.max_backoff));
+        self.restarts.push_back(now + delay);
+        Some(delay)
+    }
+
+    /// The number of restarts within the window as of the last `next_restart`.
+    pub fn restarts_in_window(&self) -> usize {
+        self.restarts.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(restart_always: bool) -> WatchPolicy {
+        WatchPolicy {
+            max_retries: 3,
+            window: Duration::from_secs(60),
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(3),
+            restart_always,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit_and_max_retries_gives_up() {
+        let mut tracker = RestartTracker::new(policy(false));
+        let now = Instant::now();
+        assert_eq!(tracker.next_restart(false, now), Some(Duration::from_secs(1)));
+        assert_eq!(tracker.next_restart(false, now), Some(Duration::from_secs(2)));
+        assert_eq!(tracker.next_restart(false, now), Some(Duration::from_secs(3)));
+        assert_eq!(tracker.next_restart(false, now), None);
+        assert_eq!(tracker.restarts_in_window(), 3);
+    }
+
+    #[test]
+    fn restarts_outside_the_window_are_forgotten() {
+        let mut tracker = RestartTracker::new(policy(false));
+        let now = Instant::now();
+        for _ in 0..3 {
+            tracker.next_restart(false, now);
+        }
+        // The first two restarts happened at least a window ago
+        assert_eq!(tracker.next_restart(false, now + Duration::from_secs(62)), Some(Duration::from_secs(2)));
+        assert_eq!(tracker.restarts_in_window(), 2);
+    }
+
+    #[test]
+    fn normal_exit_stops_unless_restart_always() {
+        let now = Instant::now();
+        assert_eq!(RestartTracker::new(policy(false)).next_restart(true, now), None);
+        assert_eq!(RestartTracker::new(policy(true)).next_restart(true, now), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn logout_and_shutdown_signals_are_normal_exits() {
+        assert!(is_normal_exit(&ExitStatus::from_raw(0)));
+        assert!(is_normal_exit(&ExitStatus::from_raw(libc::SIGTERM)));
+        assert!(is_normal_exit(&ExitStatus::from_raw(libc::SIGHUP)));
+        assert!(!is_normal_exit(&ExitStatus::from_raw(1 << 8)));
+        assert!(!is_normal_exit(&ExitStatus::from_raw(libc::SIGSEGV)));
+        assert!(!is_normal_exit(&ExitStatus::from_raw(libc::SIGKILL)));
+    }
+}
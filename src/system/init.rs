@@ -0,0 +1,247 @@
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::GLOBAL_CONFIG;
+use crate::system::SYSTEMCTL;
+
+static SYSTEMD_RUNTIME_DIRECTORY: &str = "/run/systemd/system";
+static OPENRC_RUNTIME_DIRECTORY: &str = "/run/openrc";
+static INIT_SCRIPTS_DIRECTORY: &str = "/etc/init.d";
+
+/// The init system that manages the service of the login manager.
+pub trait InitSystem {
+    /// Restart a service so that it picks up its configuration.
+    fn restart_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Clear the failed state of a service, so that it can be restarted even if it failed too
+    /// often before.
+    fn reset_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Whether a service is running.
+    fn unit_active(&mut self, service: &str) -> Result<bool, Box<dyn Error>>;
+
+    /// Whether a service is installed, i.e. the init system knows how to start it.
+    fn unit_installed(&mut self, service: &str) -> Result<bool, Box<dyn Error>>;
+
+    /// Make a service the login manager the init system starts at boot.
+    fn set_boot_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// systemd, driven through `SYSTEMCTL`.
+pub struct SystemdInit;
+
+impl SystemdInit {
+    fn unit_name(service: &str) -> String {
+        format!("{service}.service")
+    }
+}
+
+impl InitSystem for SystemdInit {
+    fn restart_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        SYSTEMCTL.lock().unwrap().restart_unit(Self::unit_name(service).as_str())?;
+        Ok(())
+    }
+
+    fn reset_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        SYSTEMCTL.lock().unwrap().reset_failed_unit(Self::unit_name(service).as_str())
+    }
+
+    fn unit_active(&mut self, service: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(SYSTEMCTL.lock().unwrap().get_unit_active_state(Self::unit_name(service).as_str())? == "active")
+    }
+
+    /// Units that are not installed have no unit file state.
+    fn unit_installed(&mut self, service: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(SYSTEMCTL.lock().unwrap().get_unit_file_state(Self::unit_name(service).as_str()).is_ok())
+    }
+
+    /// Enable the unit with force, which re-points the `display-manager.service` alias from the
+    /// unit of another login manager to it.
+    fn set_boot_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        let mut systemctl = SYSTEMCTL.lock().unwrap();
+        systemctl.enable_unit_files(&[Self::unit_name(service).as_str()], true)?;
+        systemctl.reload()
+    }
+}
+
+/// SysV init scripts, run through `service`.
+pub struct SysVInit;
+
+impl SysVInit {
+    /// Compose `service <service> <action>`.
+    pub fn command(service: &str, action: &str) -> Command {
+        let mut command = Command::new("service");
+        command.arg(service).arg(action);
+        command
+    }
+}
+
+impl InitSystem for SysVInit {
+    fn restart_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        run(Self::command(service, "restart"))
+    }
+
+    /// SysV init has no failed state to clear, so this does nothing.
+    fn reset_manager(&mut self, _service: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn unit_active(&mut self, service: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(Self::command(service, "status").output()?.status.success())
+    }
+
+    fn unit_installed(&mut self, service: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(init_script_exists(service))
+    }
+
+    /// Init scripts have no alias for the login manager, so this does nothing.
+    fn set_boot_manager(&mut self, _service: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// OpenRC, run through `rc-service`.
+pub struct OpenRcInit;
+
+impl OpenRcInit {
+    /// Compose `rc-service <service> <action>`.
+    pub fn command(service: &str, action: &str) -> Command {
+        let mut command = Command::new("rc-service");
+        command.arg(service).arg(action);
+        command
+    }
+}
+
+impl InitSystem for OpenRcInit {
+    fn restart_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        run(Self::command(service, "restart"))
+    }
+
+    /// Reset a crashed service to stopped with `rc-service <service> zap`.
+    fn reset_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        run(Self::command(service, "zap"))
+    }
+
+    fn unit_active(&mut self, service: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(Self::command(service, "status").output()?.status.success())
+    }
+
+    fn unit_installed(&mut self, service: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(init_script_exists(service))
+    }
+
+    /// Init scripts have no alias for the login manager, so this does nothing.
+    fn set_boot_manager(&mut self, _service: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+fn init_script_exists(service: &str) -> bool {
+    Path::new(INIT_SCRIPTS_DIRECTORY).join(service).is_file()
+}
+
+fn run(mut command: Command) -> Result<(), Box<dyn Error>> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let status = command.status().map_err(|err| format!("Failed to run {program}: {err}"))?;
+    if !status.success() {
+        return Err(Box::from(format!("{program} exited with {status}")));
+    }
+    Ok(())
+}
+
+/// Create the init system named `name`.
+///
+/// # Returns
+///
+/// Returns `None` if `name` is not one of `systemd`, `sysvinit` or `openrc`.
+pub fn from_name(name: &str) -> Option<Box<dyn InitSystem>> {
+    match name {
+        "systemd" => Some(Box::new(SystemdInit)),
+        "sysvinit" => Some(Box::new(SysVInit)),
+        "openrc" => Some(Box::new(OpenRcInit)),
+        _ => None,
+    }
+}
+
+/// Detect the running init system from the directories it creates at boot.
+///
+/// Falls back to SysV init scripts if neither systemd nor OpenRC is running.
+pub fn detect() -> Box<dyn InitSystem> {
+    if Path::new(SYSTEMD_RUNTIME_DIRECTORY).is_dir() {
+        Box::new(SystemdInit)
+    } else if Path::new(OPENRC_RUNTIME_DIRECTORY).is_dir() {
+        Box::new(OpenRcInit)
+    } else {
+        Box::new(SysVInit)
+    }
+}
+
+/// Retrieve the init system from the `init` key of the `[system]` section in the global
+/// configuration, detecting it with `detect` if the key is not set.
+///
+/// # Errors
+///
+/// Returns an error if the configured init system is not supported.
+pub fn from_config() -> Result<Box<dyn InitSystem>, Box<dyn Error>> {
+    let name = GLOBAL_CONFIG.get_mut()
+        .and_then(|config| config.try_get("system"))
+        .and_then(|system| system.get("init"))
+        .and_then(|init| init.as_str())
+        .map(String::from);
+    match name {
+        None => Ok(detect()),
+        Some(name) => from_name(name.as_str())
+            .ok_or_else(|| Box::from(format!("Unknown init system: {name} (Supported: systemd, sysvinit, openrc)"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+    use crate::testing;
+
+    fn args(command: &Command) -> Vec<&OsStr> {
+        command.get_args().collect()
+    }
+
+    #[test]
+    fn sysv_command_runs_service() {
+        let command = SysVInit::command("sddm", "restart");
+        assert_eq!(command.get_program(), "service");
+        assert_eq!(args(&command), ["sddm", "restart"]);
+    }
+
+    #[test]
+    fn openrc_command_runs_rc_service() {
+        let command = OpenRcInit::command("sddm", "zap");
+        assert_eq!(command.get_program(), "rc-service");
+        assert_eq!(args(&command), ["sddm", "zap"]);
+    }
+
+    #[test]
+    fn init_systems_are_created_by_name() {
+        for name in ["systemd", "sysvinit", "openrc"] {
+            assert!(from_name(name).is_some(), "{name}");
+        }
+        assert!(from_name("runit").is_none());
+    }
+
+    #[test]
+    fn configured_init_system_must_be_supported() {
+        let _fixture = testing::load_config("[system]\ninit = \"runit\"\n");
+        let err = from_config().err().unwrap();
+        assert_eq!(err.to_string(), "Unknown init system: runit (Supported: systemd, sysvinit, openrc)");
+    }
+
+    #[test]
+    fn calls_are_dispatched_to_the_configured_init_system() {
+        let _fixture = testing::load_config("[system]\ninit = \"sysvinit\"\n");
+        // Only SysV init knows services from their init script and has nothing to reset
+        let mut init_system = from_config().unwrap();
+        assert!(!init_system.unit_installed("molyuuctl-test-missing").unwrap());
+        init_system.reset_manager("molyuuctl-test-missing").unwrap();
+    }
+}
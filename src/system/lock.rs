@@ -1,13 +1,35 @@
 use std::error::Error;
+use std::ffi::CString;
 use std::fs;
-use std::fs::File;
-use std::io::Write;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
 use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicI32, AtomicPtr, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use libc::c_int;
+use libc::{c_int, pid_t};
+use log::warn;
 
 use crate::errors::system::LockError;
+use crate::system::privilege;
+
+static LOCK_DEFAULT_DIR: &str = "/tmp";
+/// The directory for locks that only root may create, see `Lock::new_in` and `create_run_dir`.
+pub static LOCK_RUN_DIR: &str = "/run/molyuuctl";
+static LOCK_PROBE_DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The lock released by `release_on_signal`, only touched with async-signal-safe calls in there
+static SIGNAL_RELEASE_FD: AtomicI32 = AtomicI32::new(-1);
+static SIGNAL_RELEASE_PATH: AtomicPtr<libc::c_char> = AtomicPtr::new(ptr::null_mut());
+// The process group `release_on_signal` terminates instead of releasing the lock, 0 if there is none
+static SIGNAL_RELEASE_PROCESS_GROUP: AtomicI32 = AtomicI32::new(0);
+// The signal that asked to terminate the process group, 0 if there was none
+static SIGNAL_TERMINATION: AtomicI32 = AtomicI32::new(0);
 
 #[repr(i32)]
 #[allow(dead_code)]
@@ -25,21 +47,88 @@ impl Into<c_int> for FLockOperation {
     }
 }
 
+/// The state of a lock as seen by a probe.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LockState {
+    /// Somebody holds the lock.
+    Held,
+    /// The lock file does not exist.
+    Free,
+    /// The lock file exists but nobody holds the lock, e.g. because its holder was killed before
+    /// it could remove the file.
+    Stale,
+}
+
+/// A named lock backed by `flock(2)` on `/tmp/<name>.lock`, or `<dir>/<name>.lock` if created with
+/// `new_in`.
+///
+/// An exclusive lock (`lock`) is held by at most one process and is used to guard a session
+/// startup. A shared lock (`lock_shared`) can be held by multiple readers at the same time, so
+/// read-only queries do not contend with each other, but it cannot be acquired while an exclusive
+/// lock is held and vice versa.
 pub struct Lock {
     name: String,
+    dir: String,
+    /// Whether the lock file is left in place on release, see `set_keep_file`.
+    keep_file: bool,
     lock: Option<File>,
+    shared: bool,
     content: Option<String>,
+    probe_timeout: Duration,
+    /// The path of the lock file while the lock is registered with `release_on_signal`.
+    signal_path: Option<CString>,
 }
 
 impl Lock {
     pub fn new(name: &str, content: Option<String>) -> Self {
         Self {
             name: name.to_string(),
+            dir: String::from(LOCK_DEFAULT_DIR),
+            keep_file: false,
             lock: None,
+            shared: false,
             content,
+            probe_timeout: LOCK_PROBE_DEFAULT_TIMEOUT,
+            signal_path: None,
         }
     }
 
+    /// Creates a lock whose file is `<dir>/<name>.lock` instead of being in `/tmp`.
+    ///
+    /// This is meant for a directory only root can write to, like `LOCK_RUN_DIR`, so that other
+    /// users cannot create, replace or hold the lock file.
+    pub fn new_in(dir: &str, name: &str, content: Option<String>) -> Self {
+        let mut lock = Self::new(name, content);
+        lock.dir = String::from(dir);
+        lock
+    }
+
+    /// Sets whether the lock file is left in place when the lock is released.
+    ///
+    /// This is for a lock file the caller created with root permissions, since whoever takes the
+    /// lock next may not be allowed to create it again.
+    pub fn set_keep_file(&mut self, keep_file: bool) {
+        self.keep_file = keep_file;
+    }
+
+    /// The path of the lock file.
+    fn path(&self) -> String {
+        format!("{}/{}.lock", self.dir, self.name)
+    }
+
+    /// Sets how long a probe (`state`, `exclusive_state` and their shorthands) may take.
+    ///
+    /// `flock` can block even with `LOCK_NB` on some network filesystems, so probes give up after
+    /// this timeout, which defaults to `LOCK_PROBE_DEFAULT_TIMEOUT`.
+    pub fn set_probe_timeout(&mut self, timeout: Duration) {
+        self.probe_timeout = timeout;
+    }
+
+    /// Checks if the lock file exists, regardless of whether it is held.
+    pub fn exists(&self) -> bool {
+        Path::new(self.path().as_str()).exists()
+    }
+
     /// Attempts to perform a lock operation on a file descriptor.
     ///
     /// # Arguments
@@ -61,8 +150,38 @@ impl Lock {
         }
     }
 
+    /// Probes the state of the lock.
+    ///
+    /// This probes with an exclusive lock, so both exclusive and shared holders are reported as
+    /// `LockState::Held`.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error checking if the lock is held, this function will return an `Err`.
+    /// Returns `LockError::Timeout` if the probe hangs for longer than the probe timeout, see
+    /// `set_probe_timeout`.
+    pub fn state(&self) -> Result<LockState, Box<dyn Error>> {
+        self.probe(FLockOperation::LockExclusiveNonblock)
+    }
+
+    /// Probes the state of the lock, only reporting exclusive holders as `LockState::Held`.
+    ///
+    /// This probes with a shared lock, so concurrent probes do not make each other see the lock
+    /// as held.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error checking if the lock is held, this function will return an `Err`.
+    /// Returns `LockError::Timeout` if the probe hangs for longer than the probe timeout, see
+    /// `set_probe_timeout`.
+    pub fn exclusive_state(&self) -> Result<LockState, Box<dyn Error>> {
+        self.probe(FLockOperation::LockSharedNonblock)
+    }
+
     /// Checks if the lock file is currently locked.
     ///
+    /// This is a shorthand for `state` that does not tell a stale lock file from a missing one.
+    ///
     /// # Returns
     ///
     /// Returns `Ok(true)` if the lock is held, `Ok(false)` if it is not, or an `Err` if there was an error checking.
@@ -71,35 +190,72 @@ impl Lock {
     ///
     /// If there is an error checking if the lock is held, this function will return an `Err`.
     pub fn is_locked(&self) -> Result<bool, Box<dyn Error>> {
-        // If the lock is already held, return true
+        Ok(self.state()? == LockState::Held)
+    }
+
+    /// Checks if the lock file is currently locked exclusively.
+    ///
+    /// This is a shorthand for `exclusive_state` that does not tell a stale lock file from a
+    /// missing one.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if an exclusive lock is held, `Ok(false)` if it is not, or an `Err` if there was an error checking.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error checking if the lock is held, this function will return an `Err`.
+    pub fn is_locked_exclusively(&self) -> Result<bool, Box<dyn Error>> {
+        Ok(self.exclusive_state()? == LockState::Held)
+    }
+
+    /// Probes the lock with `operation`, giving up after the probe timeout.
+    ///
+    /// The probe runs in its own thread, which is left behind if it hangs.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LockError::Timeout` if the probe does not finish within the probe timeout.
+    fn probe(&self, operation: FLockOperation) -> Result<LockState, Box<dyn Error>> {
+        // If the lock is already held, it is held by us
         if self.lock.is_some() {
-            return Ok(true);
+            return Ok(LockState::Held);
         }
 
-        let name = &self.name;
-        let path = format!("/tmp/{name}.lock");
+        let path = self.path();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // The receiver is gone if the probe timed out, nobody is interested in the result then
+            let _ = sender.send(Self::probe_file(path.as_str(), operation));
+        });
+        match receiver.recv_timeout(self.probe_timeout) {
+            Ok(result) => result.map_err(|err| -> Box<dyn Error> { err }),
+            Err(_err) => Err(Box::from(LockError::Timeout(format!("{} after {:?}", self.name, self.probe_timeout)))),
+        }
+    }
 
-        if Path::new(path.as_str()).exists() {
+    fn probe_file(path: &str, operation: FLockOperation) -> Result<LockState, Box<dyn Error + Send + Sync>> {
+        if Path::new(path).exists() {
             let file = File::open(path)?;
 
-            // Attempt to perform a non-blocking exclusive lock on the file
-            let result = Self::try_flock(file.as_raw_fd(), FLockOperation::LockExclusiveNonblock);
+            // Attempt to perform a non-blocking lock on the file
+            let result = Self::try_flock(file.as_raw_fd(), operation);
 
             // Match the result of the lock attempt
-            // If the lock is held, return true
+            // If the lock is held, it is held by someone else
             // If there was an error, return the error
-            // If the lock was successfully acquired, release it and return false
+            // If the lock was successfully acquired, release it, the file is left behind
             match result {
-                Err(LockError::FileIsLocked) => Ok(true),
-                Err(_err) => Err(Box::try_from(_err).unwrap()),
+                Err(LockError::FileIsLocked) => Ok(LockState::Held),
+                Err(_err) => Err(Box::from(_err)),
                 Ok(_ok) => {
                     Self::try_flock(file.as_raw_fd(), FLockOperation::Unlock)?;
-                    Ok(false)
+                    Ok(LockState::Stale)
                 }
             }
         } else {
-            // If the lock file does not exist, return false
-            Ok(false)
+            // If the lock file does not exist, the lock is free
+            Ok(LockState::Free)
         }
     }
 
@@ -116,42 +272,319 @@ impl Lock {
     ///
     /// # Notes
     ///
-    /// This function attempts to acquire an exclusive lock on the lock file.
-    /// If the lock file already exists, it will be removed.
+    /// This function attempts to acquire an exclusive lock on the lock file, creating it if needed.
     /// If the lock acquisition is successful, the content of the lock file (if specified)
     /// will be written to it, and the lock will be held until it is explicitly released.
     pub fn lock(&mut self) -> Result<(), Box<dyn Error>> {
-        // Check if the lock is already held.
-        if self.is_locked()? {
-            // If the lock is already held, return an error.
-            return Err(Box::from(LockError::FileIsLocked));
-        }
+        let path = self.path();
 
-        let name = &self.name;
-        let path = format!("/tmp/{name}.lock");
+        // Never remove or truncate the file before holding the lock, another process may be
+        // acquiring it at the same time. Without content to write, the file is only read, so a
+        // lock file kept with `set_keep_file` can be locked by users that may not write to it
+        let mut file = Self::open_file(path.as_str(), self.content.is_some())?;
 
-        // Remove the lock file if it already exists.
-        let mut file = if Path::new(path.as_str()).exists() {
-            fs::remove_file(&path)?;
-            File::create(path.as_str())?
-        } else {
-            File::create(path.as_str())?
-        };
+        // Acquire the lock.
+        Self::try_flock(file.as_raw_fd(), FLockOperation::LockExclusiveNonblock)?;
+        Self::check_not_orphaned(path.as_str(), &file)?;
 
         // Write the content of the lock to the file, if it is specified.
         if let Some(content) = &self.content {
-            file.write(content.as_bytes())?;
+            file.set_len(0)?;
+            file.write_all(content.as_bytes())?;
         }
 
-        // Acquire the lock.
-        Self::try_flock(file.as_raw_fd(), FLockOperation::LockExclusiveNonblock)?;
         // Save the file handle to the lock.
         self.lock = Some(file);
         Ok(())
     }
 
+    /// Acquires an exclusive lock on the lock file, waiting for the current holder to release it.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum time to wait for the lock.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(LockError::FileIsLocked)` if the lock is still held after `timeout`.
+    /// If any other error occurs, the error will be returned.
+    pub fn lock_timeout(&mut self, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        Self::retry_until(timeout, || self.lock())
+    }
+
+    /// Acquires a shared lock on the lock file, waiting for the exclusive holder to release it.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum time to wait for the lock.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(LockError::FileIsLocked)` if an exclusive lock is still held after `timeout`.
+    /// If any other error occurs, the error will be returned.
+    pub fn lock_shared_timeout(&mut self, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        Self::retry_until(timeout, || self.lock_shared())
+    }
+
+    /// Runs `f` until it does not fail with `LockError::FileIsLocked` or `timeout` passed.
+    fn retry_until<F>(timeout: Duration, mut f: F) -> Result<(), Box<dyn Error>>
+        where F: FnMut() -> Result<(), Box<dyn Error>>
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match f() {
+                Err(err) if Instant::now() < deadline && err.downcast_ref::<LockError>() == Some(&LockError::FileIsLocked) => {
+                    thread::sleep(Duration::from_millis(100));
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Acquires an exclusive lock, runs `f` while holding it and releases the lock afterward.
+    ///
+    /// The lock is released when `f` returns, fails or panics, since it is dropped while the stack
+    /// unwinds. `f` can still keep the lock held past its return with `hand_over`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the lock, see `new`.
+    /// * `content` - The content written to the lock file, see `new`.
+    /// * `f` - The function to run while holding the lock.
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of `f`.
+    ///
+    /// # Errors
+    ///
+    /// If the lock is already held, this function will return `Err(LockError::FileIsLocked)`
+    /// without running `f`. Errors returned by `f` are passed through.
+    pub fn with<T, F>(name: &str, content: Option<String>, f: F) -> Result<T, Box<dyn Error>>
+        where F: FnOnce(&mut Lock) -> Result<T, Box<dyn Error>>
+    {
+        let mut lock = Self::new(name, content);
+        lock.lock()?;
+        f(&mut lock)
+    }
+
+    /// Attempts to acquire a shared lock on the lock file.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the lock operation was successful, or `Err(Box<dyn Error>)` if it was not.
+    ///
+    /// # Errors
+    ///
+    /// If an exclusive lock is held, this function will return `Err(LockError::FileIsLocked)`.
+    /// If any other error occurs, the error will be returned.
+    ///
+    /// # Notes
+    ///
+    /// Unlike `lock`, this function never removes an existing lock file, since other readers may
+    /// hold a shared lock on it. The lock file is only removed on drop if no other process holds it.
+    pub fn lock_shared(&mut self) -> Result<(), Box<dyn Error>> {
+        let path = self.path();
+        let file = Self::open_file(path.as_str(), false)?;
+
+        Self::try_flock(file.as_raw_fd(), FLockOperation::LockSharedNonblock)?;
+        Self::check_not_orphaned(path.as_str(), &file)?;
+        self.lock = Some(file);
+        self.shared = true;
+        Ok(())
+    }
+
+    /// Opens the lock file, creating it if it does not exist.
+    ///
+    /// An existing file is opened without `O_CREAT`, which `fs.protected_regular` refuses in `/tmp`
+    /// for files owned by another user, e.g. by a reader that is not root.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the lock file.
+    /// * `write` - Whether an existing file is opened for writing, a shared lock only needs to read.
+    fn open_file(path: &str, write: bool) -> std::io::Result<File> {
+        match OpenOptions::new().read(true).write(write).open(path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .mode(0o644)
+                .open(path),
+            result => result,
+        }
+    }
+
+    /// Checks that the locked file is still the lock file at `path`.
+    ///
+    /// The last holder removes the file on release, if that happened between opening and locking,
+    /// the lock is on an orphaned file and does not exclude anyone.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LockError::FileIsLocked` if the file was removed or replaced, so that callers
+    /// try again.
+    fn check_not_orphaned(path: &str, file: &File) -> Result<(), Box<dyn Error>> {
+        let path_metadata = fs::metadata(path);
+        let file_metadata = file.metadata()?;
+        if !path_metadata.is_ok_and(|metadata| metadata.dev() == file_metadata.dev() && metadata.ino() == file_metadata.ino()) {
+            return Err(Box::from(LockError::FileIsLocked));
+        }
+        Ok(())
+    }
+
+    /// Replaces the content of the held lock file.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The new content of the lock file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock is not held or the lock file cannot be written.
+    pub fn set_content(&mut self, content: String) -> Result<(), Box<dyn Error>> {
+        let file = self.lock.as_mut().ok_or("Lock is not held")?;
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(content.as_bytes())?;
+        self.content = Some(content);
+        Ok(())
+    }
+
+    /// Reads the content of the lock file, which may be written by another process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file cannot be read.
+    pub fn read_content(&self) -> Result<String, Box<dyn Error>> {
+        Ok(fs::read_to_string(self.path())?)
+    }
+
+    /// Reads the content of the lock file like `read_content`, but only if root owns the file and
+    /// nobody else may write to it, so that other users cannot forge its content.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file cannot be read, or may be written by users other than root.
+    pub fn read_trusted_content(&self) -> Result<String, Box<dyn Error>> {
+        let mut file = File::open(self.path())?;
+        let metadata = file.metadata()?;
+        if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+            return Err(Box::from(format!("Lock file {} may be written by users other than root, not trusting its content", self.path())));
+        }
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    /// Waits until nobody holds the lock.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum time to wait for the lock to be released.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(LockError::FileIsLocked)` if the lock is still held after `timeout`.
+    pub fn wait_unlocked(&self, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        while self.is_locked()? {
+            if Instant::now() >= deadline {
+                return Err(Box::from(LockError::FileIsLocked));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
+    }
+
+    /// Lets child processes spawned from now on inherit the held lock file.
+    ///
+    /// The lock is shared by every process that has the lock file open, so it stays held as long
+    /// as one of them is alive, see `hand_over`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock is not held or the close-on-exec flag cannot be changed.
+    pub fn set_inheritable(&self, inheritable: bool) -> Result<(), Box<dyn Error>> {
+        let fd = self.lock.as_ref().ok_or("Lock is not held")?.as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        if flags < 0 {
+            return Err(Box::from(std::io::Error::last_os_error()));
+        }
+        let flags = if inheritable { flags & !libc::FD_CLOEXEC } else { flags | libc::FD_CLOEXEC };
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, flags) } < 0 {
+            return Err(Box::from(std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    /// Closes the lock file without releasing the lock, leaving it to the child processes that
+    /// inherited it with `set_inheritable`.
+    ///
+    /// The lock is released once the last of them exits. The lock file is left behind then and
+    /// reported as `LockState::Stale` until the lock is acquired again. Dropping the `Lock`
+    /// afterward does nothing.
+    pub fn hand_over(&mut self) {
+        self.unregister_release_on_signal();
+        drop(self.lock.take());
+    }
 
-    /// Attempts to release the exclusive lock on the lock file.
+    /// Registers the held lock to be released by `release_on_signal`.
+    ///
+    /// A signal handler that exits the process skips `Drop`, which would leave the lock file
+    /// behind. Only one lock can be registered at a time, registering another one replaces it.
+    /// The lock is unregistered when it is dropped or handed over with `hand_over`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock is not held.
+    pub fn register_release_on_signal(&mut self) -> Result<(), Box<dyn Error>> {
+        let fd = self.lock.as_ref().ok_or("Lock is not held")?.as_raw_fd();
+        let path = CString::new(self.path())?;
+
+        // Unregister first, so that a signal never sees the new descriptor with the old path
+        SIGNAL_RELEASE_FD.store(-1, Ordering::SeqCst);
+        SIGNAL_RELEASE_PATH.store(path.as_ptr() as *mut libc::c_char, Ordering::SeqCst);
+        SIGNAL_RELEASE_FD.store(fd, Ordering::SeqCst);
+        self.signal_path = Some(path);
+        Ok(())
+    }
+
+    /// Makes `release_on_signal` terminate a process group instead of releasing the lock.
+    ///
+    /// A session runs in a process group of its own and keeps running when molyuuctl is
+    /// terminated, so releasing the lock right away would free the seat while the session still
+    /// runs on it. The group is sent SIGTERM instead, and the thread waiting for its leader
+    /// releases the lock as usual once the leader exited, see `termination_signal`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pgid` - The process group, led by a child of this process, or `None` once the leader
+    ///   exited.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock is not registered with `register_release_on_signal`.
+    pub fn set_signal_process_group(&mut self, pgid: Option<pid_t>) -> Result<(), Box<dyn Error>> {
+        if self.signal_path.is_none() {
+            return Err(Box::from("Lock is not registered to be released on signal"));
+        }
+        SIGNAL_RELEASE_PROCESS_GROUP.store(pgid.unwrap_or(0), Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn unregister_release_on_signal(&mut self) {
+        if let Some(path) = self.signal_path.take() {
+            if ptr::eq(SIGNAL_RELEASE_PATH.load(Ordering::SeqCst), path.as_ptr()) {
+                SIGNAL_RELEASE_FD.store(-1, Ordering::SeqCst);
+                SIGNAL_RELEASE_PATH.store(ptr::null_mut(), Ordering::SeqCst);
+                SIGNAL_RELEASE_PROCESS_GROUP.store(0, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Attempts to release the lock on the lock file.
     ///
     /// # Returns
     ///
@@ -159,7 +592,7 @@ impl Lock {
     ///
     /// # Notes
     ///
-    /// This function attempts to release the exclusive lock on the lock file. If the lock is not currently held,
+    /// This function attempts to release the lock on the lock file. If the lock is not currently held,
     /// it returns `Err(LockError::FileIsNotLocked)`.
     pub fn unlock(&mut self) -> Result<(), LockError> {
         // Attempt to release the exclusive lock on the lock file. If the lock is not currently held,
@@ -171,12 +604,357 @@ impl Lock {
     }
 }
 
+/// Creates `LOCK_RUN_DIR` with root permissions if it does not exist yet.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created.
+pub fn create_run_dir() -> Result<(), Box<dyn Error>> {
+    if Path::new(LOCK_RUN_DIR).is_dir() {
+        return Ok(());
+    }
+    unsafe {
+        privilege::exec(|| {
+            fs::DirBuilder::new().recursive(true).mode(0o755).create(LOCK_RUN_DIR)?;
+            Ok(())
+        })
+    }
+}
+
+/// Releases the lock registered with `Lock::register_release_on_signal` and removes its file.
+///
+/// This is meant to be called from a signal handler, so it only uses async-signal-safe calls and
+/// never blocks. If a process group is set with `Lock::set_signal_process_group`, the group is
+/// sent SIGTERM and `signal` is recorded for `termination_signal` instead, and the lock is left to
+/// the thread waiting for the group. It does nothing if no lock is registered.
+///
+/// # Returns
+///
+/// Returns `true` if the lock is left to the thread waiting for the process group, in which case
+/// the signal handler must return instead of exiting the process.
+pub fn release_on_signal(signal: c_int) -> bool {
+    let pgid = SIGNAL_RELEASE_PROCESS_GROUP.load(Ordering::SeqCst);
+    if pgid > 0 {
+        SIGNAL_TERMINATION.store(signal, Ordering::SeqCst);
+        unsafe {
+            libc::kill(-pgid, libc::SIGTERM);
+        }
+        return true;
+    }
+
+    let fd = SIGNAL_RELEASE_FD.swap(-1, Ordering::SeqCst);
+    if fd < 0 {
+        return false;
+    }
+    // Like on drop, the file is removed before the lock is released
+    let path = SIGNAL_RELEASE_PATH.swap(ptr::null_mut(), Ordering::SeqCst);
+    unsafe {
+        if !path.is_null() {
+            libc::unlink(path);
+        }
+        libc::flock(fd, libc::LOCK_UN);
+    }
+    false
+}
+
+/// The signal that asked to terminate the process group set with `Lock::set_signal_process_group`,
+/// see `release_on_signal`.
+///
+/// # Returns
+///
+/// Returns `None` if no such signal was received.
+pub fn termination_signal() -> Option<c_int> {
+    match SIGNAL_TERMINATION.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
 impl Drop for Lock {
     fn drop(&mut self) {
+        self.unregister_release_on_signal();
         if self.lock.is_some() {
-            self.unlock().unwrap();
+            // Other readers may still hold a shared lock on the file, only the last holder removes
+            // it. It is removed before the lock is released, so that a process that locks it in
+            // between notices it is orphaned
+            // Drop also runs while a panic unwinds, where panicking again would abort the process
+            if !self.keep_file && (!self.shared || Self::try_flock(self.lock.as_ref().unwrap().as_raw_fd(), FLockOperation::LockExclusiveNonblock).is_ok()) {
+                if let Err(_err) = fs::remove_file(self.path()) {
+                    warn!("Failed to remove lock file {}: {}", self.path(), _err);
+                }
+            }
+            if let Err(_err) = self.unlock() {
+                warn!("Failed to release lock {}: {}", self.name, _err);
+            }
             drop(self.lock.take());
-            fs::remove_file(format!("/tmp/{}.lock", self.name)).unwrap();
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+    use std::process;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Only one lock can be registered with `release_on_signal` in the process at a time
+    static SIGNAL_RELEASE_GUARD: Mutex<()> = Mutex::new(());
+
+    fn test_lock_name(test: &str) -> String {
+        format!("molyuuctl-test-{}-{test}", process::id())
+    }
+
+    #[test]
+    fn shared_locks_are_held_at_the_same_time() {
+        let name = test_lock_name("shared");
+        let mut first = Lock::new(name.as_str(), None);
+        let mut second = Lock::new(name.as_str(), None);
+        first.lock_shared().unwrap();
+        second.lock_shared().unwrap();
+
+        drop(first);
+        assert!(second.exists());
+        drop(second);
+        assert!(!Lock::new(name.as_str(), None).exists());
+    }
+
+    #[test]
+    fn shared_and_exclusive_locks_exclude_each_other() {
+        let name = test_lock_name("exclusive");
+        let mut reader = Lock::new(name.as_str(), None);
+        reader.lock_shared().unwrap();
+        let err = Lock::new(name.as_str(), None).lock().unwrap_err();
+        assert_eq!(err.downcast_ref::<LockError>(), Some(&LockError::FileIsLocked));
+        drop(reader);
+
+        let mut writer = Lock::new(name.as_str(), None);
+        writer.lock().unwrap();
+        let err = Lock::new(name.as_str(), None).lock_shared().unwrap_err();
+        assert_eq!(err.downcast_ref::<LockError>(), Some(&LockError::FileIsLocked));
+    }
+
+    #[test]
+    fn removed_lock_file_is_orphaned() {
+        let name = test_lock_name("orphaned");
+        let path = format!("/tmp/{name}.lock");
+        let file = Lock::open_file(path.as_str(), false).unwrap();
+        Lock::check_not_orphaned(path.as_str(), &file).unwrap();
+
+        fs::remove_file(path.as_str()).unwrap();
+        let err = Lock::check_not_orphaned(path.as_str(), &file).unwrap_err();
+        assert_eq!(err.downcast_ref::<LockError>(), Some(&LockError::FileIsLocked));
+    }
+
+    #[test]
+    fn lock_file_in_a_directory_is_kept_on_release() {
+        let dir = crate::testing::TempDir::new();
+        let dir_path = dir.path().to_str().unwrap();
+        let name = test_lock_name("kept");
+        let path = dir.path().join(format!("{name}.lock"));
+        // Created by root before anybody locks it, and only readable by others
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o444)).unwrap();
+
+        let mut holder = Lock::new_in(dir_path, name.as_str(), None);
+        holder.set_keep_file(true);
+        holder.lock().unwrap();
+        let err = Lock::new_in(dir_path, name.as_str(), None).lock().unwrap_err();
+        assert_eq!(err.downcast_ref::<LockError>(), Some(&LockError::FileIsLocked));
+        assert!(!Path::new(format!("/tmp/{name}.lock").as_str()).exists());
+
+        drop(holder);
+        assert!(path.exists());
+        Lock::new_in(dir_path, name.as_str(), None).lock().unwrap();
+    }
+
+    #[test]
+    fn lock_file_in_a_directory_is_removed_on_release_unless_kept() {
+        let dir = crate::testing::TempDir::new();
+        let name = test_lock_name("removed");
+        let mut lock = Lock::new_in(dir.path().to_str().unwrap(), name.as_str(), Some(String::from("alpha\n")));
+        lock.lock().unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join(format!("{name}.lock"))).unwrap(), "alpha\n");
+
+        drop(lock);
+        assert!(!dir.path().join(format!("{name}.lock")).exists());
+    }
+
+    #[test]
+    fn content_of_a_lock_file_others_may_write_is_not_trusted() {
+        let dir = crate::testing::TempDir::new();
+        let name = test_lock_name("trusted");
+        let mut lock = Lock::new_in(dir.path().to_str().unwrap(), name.as_str(), Some(String::from("alpha\n42")));
+        lock.lock().unwrap();
+        assert_eq!(lock.read_trusted_content().unwrap(), "alpha\n42");
+
+        fs::set_permissions(dir.path().join(format!("{name}.lock")), std::os::unix::fs::PermissionsExt::from_mode(0o666)).unwrap();
+        assert!(lock.read_trusted_content().is_err());
+    }
+
+    #[test]
+    fn lock_state_is_free_held_or_stale() {
+        let name = test_lock_name("state");
+        let probe = Lock::new(name.as_str(), None);
+        assert_eq!(probe.state().unwrap(), LockState::Free);
+        assert!(!probe.is_locked().unwrap());
+
+        let mut holder = Lock::new(name.as_str(), None);
+        holder.lock().unwrap();
+        assert_eq!(holder.state().unwrap(), LockState::Held);
+        assert_eq!(probe.state().unwrap(), LockState::Held);
+        assert_eq!(probe.exclusive_state().unwrap(), LockState::Held);
+        assert!(probe.is_locked().unwrap());
+
+        // A lock file that is left behind without a holder, e.g. by a crashed instance
+        drop(holder);
+        fs::write(format!("/tmp/{name}.lock"), "").unwrap();
+        assert_eq!(probe.state().unwrap(), LockState::Stale);
+        assert!(!probe.is_locked().unwrap());
+        fs::remove_file(format!("/tmp/{name}.lock")).unwrap();
+    }
+
+    #[test]
+    fn shared_holder_is_held_but_not_exclusively() {
+        let name = test_lock_name("shared-state");
+        let mut reader = Lock::new(name.as_str(), None);
+        reader.lock_shared().unwrap();
+        let probe = Lock::new(name.as_str(), None);
+        assert_eq!(probe.state().unwrap(), LockState::Held);
+        assert_ne!(probe.exclusive_state().unwrap(), LockState::Held);
+        assert!(!probe.is_locked_exclusively().unwrap());
+    }
+
+    #[test]
+    fn waited_for_session_releases_the_lock_when_dropped() {
+        let name = test_lock_name("wait");
+        let mut lock = Lock::new(name.as_str(), None);
+        lock.lock().unwrap();
+        let status = process::Command::new("true").status().unwrap();
+        assert!(status.success());
+
+        drop(lock);
+        assert_eq!(Lock::new(name.as_str(), None).state().unwrap(), LockState::Free);
+    }
+
+    #[test]
+    fn lock_is_released_when_a_panic_unwinds() {
+        let name = test_lock_name("panic");
+        let result = std::panic::catch_unwind(|| {
+            Lock::with(name.as_str(), None, |_lock| -> Result<(), Box<dyn Error>> {
+                panic!("session failed to start");
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(Lock::new(name.as_str(), None).state().unwrap(), LockState::Free);
+    }
+
+    #[test]
+    fn detached_session_holds_the_handed_over_lock_until_it_exits() {
+        let name = test_lock_name("detach");
+        let mut lock = Lock::new(name.as_str(), None);
+        lock.lock().unwrap();
+        lock.set_inheritable(true).unwrap();
+        let mut child = process::Command::new("sleep").arg("30").spawn().unwrap();
+        lock.set_inheritable(false).unwrap();
+        lock.hand_over();
+
+        let probe = Lock::new(name.as_str(), None);
+        assert_eq!(probe.state().unwrap(), LockState::Held);
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(probe.state().unwrap(), LockState::Stale);
+        fs::remove_file(format!("/tmp/{name}.lock")).unwrap();
+    }
+
+    #[test]
+    fn lock_is_not_inherited_unless_asked_for() {
+        let name = test_lock_name("not-inherited");
+        let mut lock = Lock::new(name.as_str(), None);
+        lock.lock().unwrap();
+        let mut child = process::Command::new("sleep").arg("30").spawn().unwrap();
+        lock.hand_over();
+
+        assert_eq!(Lock::new(name.as_str(), None).state().unwrap(), LockState::Stale);
+        child.kill().unwrap();
+        child.wait().unwrap();
+        fs::remove_file(format!("/tmp/{name}.lock")).unwrap();
+    }
+
+    #[test]
+    fn hanging_probe_times_out() {
+        let name = test_lock_name("probe-timeout");
+        let path = CString::new(format!("/tmp/{name}.lock")).unwrap();
+        // Opening a FIFO blocks until a writer shows up, like flock on a hung network filesystem
+        assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+        let mut lock = Lock::new(name.as_str(), None);
+        lock.set_probe_timeout(Duration::from_millis(200));
+
+        let start = Instant::now();
+        let err = lock.state().unwrap_err();
+        assert!(matches!(err.downcast_ref::<LockError>(), Some(LockError::Timeout(_))));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < LOCK_PROBE_DEFAULT_TIMEOUT);
+
+        // Let the probe left behind finish
+        let writer = OpenOptions::new().write(true).custom_flags(libc::O_NONBLOCK).open(format!("/tmp/{name}.lock"));
+        drop(writer);
+        fs::remove_file(format!("/tmp/{name}.lock")).unwrap();
+    }
+
+    #[test]
+    fn failing_release_does_not_abort_a_panic() {
+        let name = test_lock_name("panic-release");
+        let result = std::panic::catch_unwind(|| {
+            Lock::with(name.as_str(), None, |_lock| -> Result<(), Box<dyn Error>> {
+                // Make the removal on drop fail while unwinding
+                fs::remove_file(format!("/tmp/{name}.lock"))?;
+                panic!("session failed to start");
+            })
+        });
+        assert!(result.is_err());
+
+        let mut lock = Lock::new(name.as_str(), None);
+        lock.lock().unwrap();
+        assert_eq!(lock.state().unwrap(), LockState::Held);
+    }
+
+    #[test]
+    fn signal_leaves_the_lock_of_a_running_session_to_its_waiter() {
+        let _guard = SIGNAL_RELEASE_GUARD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let name = test_lock_name("signal");
+        let mut lock = Lock::new(name.as_str(), None);
+        lock.lock().unwrap();
+        lock.register_release_on_signal().unwrap();
+        let mut child = process::Command::new("sleep").arg("30").process_group(0).spawn().unwrap();
+        lock.set_signal_process_group(Some(child.id() as pid_t)).unwrap();
+
+        assert!(release_on_signal(libc::SIGTERM));
+        assert_eq!(termination_signal(), Some(libc::SIGTERM));
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+        assert!(lock.exists());
+        SIGNAL_TERMINATION.store(0, Ordering::SeqCst);
+
+        // Once the session exited, the lock is released right away
+        lock.set_signal_process_group(None).unwrap();
+        assert!(!release_on_signal(libc::SIGTERM));
+        assert!(!lock.exists());
+        lock.hand_over();
+    }
+
+    #[test]
+    fn signal_without_a_registered_lock_leaves_held_locks_alone() {
+        let _guard = SIGNAL_RELEASE_GUARD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let name = test_lock_name("unregistered");
+        let mut lock = Lock::new(name.as_str(), None);
+        lock.lock().unwrap();
+
+        assert!(!release_on_signal(libc::SIGTERM));
+        assert!(lock.exists());
+        let mut other = Lock::new(name.as_str(), None);
+        let err = other.lock().unwrap_err();
+        assert_eq!(err.downcast_ref::<LockError>(), Some(&LockError::FileIsLocked));
+    }
+}
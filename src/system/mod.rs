@@ -6,9 +6,13 @@ use crate::system::systemctl::SystemD;
 
 mod systemctl;
 
+pub mod init;
 pub mod privilege;
 pub mod lock;
+pub mod process;
+pub mod notify;
+pub mod user;
 
 lazy_static! {
-    pub static ref SYSTEMCTL: Mutex<SystemD> = Mutex::new(SystemD::new().unwrap());
+    pub static ref SYSTEMCTL: Mutex<SystemD> = Mutex::new(SystemD::new());
 }
\ No newline at end of file
@@ -0,0 +1,93 @@
+use std::env;
+use std::error::Error;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+static NOTIFY_SOCKET_ENV: &str = "NOTIFY_SOCKET";
+
+/// Format state assignments into a `sd_notify(3)` message.
+///
+/// # Arguments
+///
+/// * `states` - The variables to send, e.g. `("READY", "1")`.
+///
+/// # Returns
+///
+/// Returns one `KEY=value` assignment per line.
+pub fn format_message(states: &[(&str, &str)]) -> String {
+    states.iter()
+        .map(|(key, value)| format!("{key}={value}\n"))
+        .collect()
+}
+
+/// Send state changes to the service manager, like `sd_notify(3)`.
+///
+/// Nothing is sent if molyuuctl is not started by a `Type=notify` unit, i.e. `NOTIFY_SOCKET` is
+/// not set.
+///
+/// # Arguments
+///
+/// * `states` - The variables to send, e.g. `("READY", "1")`.
+///
+/// # Returns
+///
+/// Returns `Ok(true)` if the message was sent, or `Ok(false)` if there is no socket to notify.
+///
+/// # Errors
+///
+/// Returns an error if the notification socket cannot be reached.
+pub fn notify(states: &[(&str, &str)]) -> Result<bool, Box<dyn Error>> {
+    notify_socket(env::var(NOTIFY_SOCKET_ENV).ok().as_deref(), states)
+}
+
+/// Send state changes to the socket `socket_path` taken from `NOTIFY_SOCKET`, see `notify`.
+fn notify_socket(socket_path: Option<&str>, states: &[(&str, &str)]) -> Result<bool, Box<dyn Error>> {
+    let socket_path = match socket_path {
+        Some(socket_path) if !socket_path.is_empty() => socket_path,
+        _ => return Ok(false),
+    };
+
+    // A leading '@' denotes a socket in the abstract namespace
+    let address = match socket_path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes())?,
+        None => SocketAddr::from_pathname(socket_path)?,
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(format_message(states).as_bytes(), &address)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn message_has_one_assignment_per_line() {
+        assert_eq!(format_message(&[("READY", "1"), ("STATUS", "Started session alpha")]), "READY=1\nSTATUS=Started session alpha\n");
+        assert_eq!(format_message(&[]), "");
+    }
+
+    #[test]
+    fn payload_is_sent_only_if_the_socket_is_set() {
+        let dir = TempDir::new();
+        let socket_path = dir.path().join("notify.sock");
+        let receiver = UnixDatagram::bind(&socket_path).unwrap();
+
+        assert!(notify_socket(socket_path.to_str(), &[("READY", "1"), ("STATUS", "Started session alpha")]).unwrap());
+        let mut buf = [0u8; 64];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1\nSTATUS=Started session alpha\n");
+
+        // An abstract socket is addressed with a leading '@'
+        let name = format!("molyuuctl-test-notify-{}", std::process::id());
+        let abstract_receiver = UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(name.as_bytes()).unwrap()).unwrap();
+        assert!(notify_socket(Some(format!("@{name}").as_str()), &[("READY", "1")]).unwrap());
+        let len = abstract_receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1\n");
+
+        assert!(!notify_socket(Some(""), &[("READY", "1")]).unwrap());
+        assert!(!notify_socket(None, &[("READY", "1")]).unwrap());
+    }
+}
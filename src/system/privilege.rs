@@ -4,6 +4,8 @@ use std::sync::Mutex;
 use lazy_static::lazy_static;
 use libc::{gid_t, uid_t};
 
+use crate::errors::system::PrivilegeError;
+
 lazy_static! {
     static ref ROOT: Mutex<RootPermission> = unsafe { Mutex::new(RootPermission::new()) };
 }
@@ -53,11 +55,11 @@ impl RootPermission {
         if libc::geteuid() != 0 {
             // Get Root Permission
             if libc::setresuid(self.ruid, 0, 0) < 0 {
-                return Err(Box::from("Failed to reset uid"));
+                return Err(Box::from(PrivilegeError::GrantFailed(String::from("Failed to reset uid"))));
             }
 
             if libc::setresgid(self.rgid, 0, 0) < 0 {
-                return Err(Box::from("Failed to reset euid"));
+                return Err(Box::from(PrivilegeError::GrantFailed(String::from("Failed to reset euid"))));
             }
         }
 
@@ -88,11 +90,11 @@ impl RootPermission {
     /// user ID (euid) to its original value, such as failure to set the euid back to its original value.
     pub unsafe fn return_permission(&self) -> Result<(), Box<dyn Error>> {
         if libc::seteuid(self.euid) < 0 {
-            return Err(Box::from("Failed to reset euid"));
+            return Err(Box::from(PrivilegeError::ReturnFailed(String::from("Failed to reset euid"))));
         }
 
         if libc::setegid(self.egid) < 0 {
-            return Err(Box::from("Failed to reset egid"));
+            return Err(Box::from(PrivilegeError::ReturnFailed(String::from("Failed to reset egid"))));
         }
         Ok(())
     }
@@ -145,4 +147,9 @@ pub unsafe fn exec<F>(f: F) -> Result<(), Box<dyn Error>>
     f()?;
     root.return_permission()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// The real user ID of the process, i.e. the user that invoked molyuuctl even if it runs setuid.
+pub fn real_uid() -> uid_t {
+    ROOT.lock().unwrap().ruid
+}
@@ -0,0 +1,234 @@
+use std::error::Error;
+use std::process::{Child, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use libc::pid_t;
+
+static TERMINATE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Termination {
+    /// The process was not running anymore.
+    NotRunning,
+    /// The process exited after receiving SIGTERM within the grace period.
+    Terminated,
+    /// The process did not exit within the grace period and was killed with SIGKILL.
+    Killed,
+}
+
+/// Checks whether a process with the given PID is alive.
+///
+/// A process that exists but cannot be signaled by the current user is considered alive as well.
+pub fn is_alive(pid: pid_t) -> bool {
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Checks whether any process of the given process group is alive.
+///
+/// A group whose processes exist but cannot be signaled by the current user is considered alive.
+pub fn is_group_alive(pgid: pid_t) -> bool {
+    if unsafe { libc::killpg(pgid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Waits for a child process to exit, for at most `timeout`.
+///
+/// # Returns
+///
+/// Returns the exit status of the child, or `None` if it is still running after `timeout`.
+pub fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>, Box<dyn Error>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(TERMINATE_POLL_INTERVAL);
+    }
+}
+
+/// Terminates a process, escalating to SIGKILL if it does not exit within the grace period.
+///
+/// This function sends SIGTERM to the process and polls until it exits. If the process is still
+/// alive after `grace_period`, SIGKILL is sent.
+///
+/// # Arguments
+///
+/// * `pid` - The PID of the process to terminate.
+/// * `grace_period` - How long to wait for the process to exit after SIGTERM.
+///
+/// # Returns
+///
+/// Returns how the process ended, or an `Err` if a signal could not be delivered.
+pub fn terminate(pid: pid_t, grace_period: Duration) -> Result<Termination, Box<dyn Error>> {
+    escalate(|| is_alive(pid), |signal| send_signal(pid, signal), grace_period)
+}
+
+/// Terminates every process of a process group, escalating to SIGKILL like `terminate`.
+///
+/// The group counts as exited once none of its processes is alive anymore, so children the
+/// leader leaves behind are waited for as well.
+///
+/// # Arguments
+///
+/// * `pgid` - The ID of the process group to terminate.
+/// * `grace_period` - How long to wait for the group to exit after SIGTERM.
+///
+/// # Returns
+///
+/// Returns how the group ended, or an `Err` if a signal could not be delivered.
+pub fn terminate_group(pgid: pid_t, grace_period: Duration) -> Result<Termination, Box<dyn Error>> {
+    escalate(|| is_group_alive(pgid), |signal| send_group_signal(pgid, signal), grace_period)
+}
+
+fn escalate<A, S>(is_alive: A, send_signal: S, grace_period: Duration) -> Result<Termination, Box<dyn Error>>
+    where A: Fn() -> bool,
+          S: Fn(libc::c_int) -> Result<(), Box<dyn Error>>
+{
+    if !is_alive() {
+        return Ok(Termination::NotRunning);
+    }
+
+    send_signal(libc::SIGTERM)?;
+    let deadline = Instant::now() + grace_period;
+    while Instant::now() < deadline {
+        if !is_alive() {
+            return Ok(Termination::Terminated);
+        }
+        thread::sleep(TERMINATE_POLL_INTERVAL);
+    }
+
+    if !is_alive() {
+        return Ok(Termination::Terminated);
+    }
+    send_signal(libc::SIGKILL)?;
+    Ok(Termination::Killed)
+}
+
+fn send_signal(pid: pid_t, signal: libc::c_int) -> Result<(), Box<dyn Error>> {
+    if unsafe { libc::kill(pid, signal) } < 0 {
+        let err = std::io::Error::last_os_error();
+        // The process exited between the liveness check and the signal
+        if err.raw_os_error() != Some(libc::ESRCH) {
+            return Err(Box::from(format!("Failed to send signal {signal} to process {pid}: {err}")));
+        }
+    }
+    Ok(())
+}
+
+fn send_group_signal(pgid: pid_t, signal: libc::c_int) -> Result<(), Box<dyn Error>> {
+    if unsafe { libc::killpg(pgid, signal) } < 0 {
+        let err = std::io::Error::last_os_error();
+        // The group exited between the liveness check and the signal
+        if err.raw_os_error() != Some(libc::ESRCH) {
+            return Err(Box::from(format!("Failed to send signal {signal} to process group {pgid}: {err}")));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    use super::*;
+
+    /// Spawn `command` and reap it in the background, so it stops being alive once it exits.
+    fn spawn_reaped(command: &mut Command) -> pid_t {
+        let mut child = command.spawn().unwrap();
+        let pid = child.id() as pid_t;
+        thread::spawn(move || child.wait());
+        pid
+    }
+
+    #[test]
+    fn escalation_without_a_running_process_sends_no_signal() {
+        let signals = RefCell::new(Vec::new());
+        let termination = escalate(|| false, |signal| {
+            signals.borrow_mut().push(signal);
+            Ok(())
+        }, Duration::from_secs(1)).unwrap();
+        assert_eq!(termination, Termination::NotRunning);
+        assert!(signals.borrow().is_empty());
+    }
+
+    #[test]
+    fn escalation_stops_when_the_process_exits_on_sigterm() {
+        let alive = Cell::new(true);
+        let signals = RefCell::new(Vec::new());
+        let termination = escalate(|| alive.get(), |signal| {
+            signals.borrow_mut().push(signal);
+            alive.set(false);
+            Ok(())
+        }, Duration::from_secs(1)).unwrap();
+        assert_eq!(termination, Termination::Terminated);
+        assert_eq!(*signals.borrow(), [libc::SIGTERM]);
+    }
+
+    #[test]
+    fn escalation_kills_a_process_ignoring_sigterm_after_the_grace_period() {
+        let signals = RefCell::new(Vec::new());
+        let start = Instant::now();
+        let termination = escalate(|| true, |signal| {
+            signals.borrow_mut().push(signal);
+            Ok(())
+        }, Duration::from_millis(300)).unwrap();
+        assert_eq!(termination, Termination::Killed);
+        assert_eq!(*signals.borrow(), [libc::SIGTERM, libc::SIGKILL]);
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn dummy_child_is_terminated() {
+        let pid = spawn_reaped(Command::new("sleep").arg("30"));
+        assert_eq!(terminate(pid, Duration::from_secs(5)).unwrap(), Termination::Terminated);
+        assert!(!is_alive(pid));
+    }
+
+    #[test]
+    fn dummy_child_ignoring_sigterm_is_killed() {
+        let pid = spawn_reaped(Command::new("sh").args(["-c", "trap '' TERM; exec sleep 30"]));
+        // Give the shell time to ignore SIGTERM before it is sent
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(terminate(pid, Duration::from_millis(300)).unwrap(), Termination::Killed);
+        thread::sleep(Duration::from_millis(200));
+        assert!(!is_alive(pid));
+    }
+
+    #[test]
+    fn process_that_is_not_running_is_not_signaled() {
+        let pid = spawn_reaped(&mut Command::new("true"));
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(terminate(pid, Duration::from_secs(1)).unwrap(), Termination::NotRunning);
+    }
+
+    #[test]
+    fn dummy_process_group_ignoring_sigterm_is_killed_after_the_grace_period() {
+        let pgid = spawn_reaped(Command::new("sh").args(["-c", "trap '' TERM; sleep 30 & exec sleep 30"]).process_group(0));
+        thread::sleep(Duration::from_millis(200));
+        let start = Instant::now();
+        assert_eq!(terminate_group(pgid, Duration::from_millis(300)).unwrap(), Termination::Killed);
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        thread::sleep(Duration::from_millis(200));
+        assert!(!is_alive(pgid));
+    }
+
+    #[test]
+    fn dummy_process_group_is_terminated_within_the_grace_period() {
+        let pgid = spawn_reaped(Command::new("sleep").arg("30").process_group(0));
+        let start = Instant::now();
+        assert_eq!(terminate_group(pgid, Duration::from_secs(5)).unwrap(), Termination::Terminated);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(!is_group_alive(pgid));
+    }
+}
@@ -1,30 +1,699 @@
 use std::error::Error;
-use std::time::Duration;
+#[cfg(any(feature = "systemd", test))]
+use std::fmt::Display;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::FileTypeExt;
+#[cfg(not(feature = "systemd"))]
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "systemd")]
 use dbus::{blocking::{Connection, Proxy}, Path};
+#[cfg(feature = "systemd")]
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+#[cfg(any(feature = "systemd", test))]
+use log::warn;
 
+#[cfg(feature = "systemd")]
+use crate::config::GLOBAL_CONFIG;
+
+#[cfg(feature = "systemd")]
+static DBUS_CONNECT_DEFAULT_ATTEMPTS: u32 = 5;
+#[cfg(feature = "systemd")]
+static DBUS_CONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+static UNIT_STATE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+static BUS_SOCKET_POLL_INTERVAL: Duration = Duration::from_millis(100);
+static SYSTEM_BUS_DEFAULT_SOCKET: &str = "/run/dbus/system_bus_socket";
+static DISPLAY_MANAGER_ALIAS: &str = "/etc/systemd/system/display-manager.service";
+#[cfg(not(feature = "systemd"))]
+static SYSTEMCTL_PROGRAM: &str = "systemctl";
+
+/// A handle to the service manager.
+///
+/// With the `systemd` feature (the default), systemd is driven over D-Bus. Without it, every
+/// method runs the `systemctl` command instead, so molyuuctl builds without libdbus.
 pub struct SystemD {
-    conn: Connection,
+    #[cfg(feature = "systemd")]
+    conn: Option<Connection>,
+    /// How long to wait for the system bus socket before talking to systemd, see `set_bus_wait`.
+    bus_wait: Option<Duration>,
+}
+
+impl Default for SystemD {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SystemD {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        Ok(Self {
-            conn: Connection::new_system()?
-        })
+    /// Waits until a unit becomes active.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - The name of the unit to wait for.
+    /// * `timeout` - The maximum time to wait for the unit to become active.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the unit fails, does not become active within `timeout`, or its state
+    /// cannot be queried.
+    pub fn wait_unit_active(&mut self, unit: &str, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.get_unit_active_state(unit)?.as_str() {
+                "active" => return Ok(()),
+                "failed" => return Err(Box::from(format!("Unit {unit} failed"))),
+                state if Instant::now() >= deadline => {
+                    return Err(Box::from(format!("Unit {unit} is still {state} after {timeout:?}")));
+                }
+                _ => thread::sleep(UNIT_STATE_POLL_INTERVAL),
+            }
+        }
+    }
+
+    /// Lists the units loaded by systemd whose name matches a glob pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - A pattern like `*dm.service`, where `*` matches any sequence of characters
+    ///   and `?` matches a single character.
+    ///
+    /// # Returns
+    ///
+    /// Returns the name and the active state of every matching unit, sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the units cannot be listed.
+    pub fn list_units_by_pattern(&mut self, pattern: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        Ok(filter_units(self.list_units()?, pattern))
+    }
+
+    /// Returns the login manager unit systemd starts at boot, whatever molyuuctl is configured for.
+    ///
+    /// The unit is resolved from the target of the `display-manager.service` alias that enabling
+    /// a login manager links in `/etc/systemd/system`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the name of the unit, e.g. `sddm.service`, or `None` if no login manager is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the alias cannot be read or is not a symlink to a unit file.
+    pub fn get_default_display_manager(&self) -> Result<Option<String>, Box<dyn Error>> {
+        resolve_display_manager_alias(DISPLAY_MANAGER_ALIAS)
     }
 
-    fn get_proxy(&self) -> Result<Proxy<'_, &'_ Connection>, Box<dyn Error>> {
-        Ok(self.conn.with_proxy("org.freedesktop.systemd1", "/org/freedesktop/systemd1", Duration::from_millis(5000)))
+    /// Sets how long to wait for the system bus socket to appear before talking to systemd.
+    ///
+    /// This lets molyuuctl run early at boot, before `dbus.socket` is up. Without a timeout,
+    /// systemd is talked to right away.
+    pub fn set_bus_wait(&mut self, timeout: Option<Duration>) {
+        self.bus_wait = timeout;
     }
 
-    pub fn reset_failed_unit(&self, unit: &str) -> Result<(), Box<dyn Error>> {
-        self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "ResetFailedUnit", (unit, ))?;
+    /// Waits for the system bus socket if `set_bus_wait` was given a timeout.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket does not appear within the timeout.
+    fn wait_for_bus(&self) -> Result<(), Box<dyn Error>> {
+        match self.bus_wait {
+            Some(timeout) => wait_for_socket(get_system_bus_socket().as_str(), timeout, BUS_SOCKET_POLL_INTERVAL),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "systemd")]
+impl SystemD {
+    /// Creates a handle to systemd without connecting to the system bus.
+    ///
+    /// The connection is established on the first method call, so commands that never talk to
+    /// systemd keep working on a machine without a system bus.
+    pub fn new() -> Self {
+        Self {
+            conn: None,
+            bus_wait: None,
+        }
+    }
+
+    /// Returns the connection to the system bus, connecting first if needed.
+    ///
+    /// The bus is retried with exponential backoff if it is not available yet. The number of
+    /// attempts is read from the `dbus_connect_attempts` key of the `[system]` section in the
+    /// global configuration and defaults to `DBUS_CONNECT_DEFAULT_ATTEMPTS`. If a bus wait is
+    /// set, the system bus socket is waited for before the first attempt.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system bus socket does not appear in time, or the last connection
+    /// error if the system bus is still unavailable after all attempts.
+    fn get_connection(&mut self) -> Result<&Connection, Box<dyn Error>> {
+        if self.conn.is_none() {
+            self.wait_for_bus()?;
+            self.conn = Some(connect_with_retry(Connection::new_system, get_connect_attempts(), DBUS_CONNECT_INITIAL_BACKOFF)?);
+        }
+        Ok(self.conn.as_ref().unwrap())
+    }
+
+    fn get_proxy(&mut self) -> Result<Proxy<'_, &'_ Connection>, Box<dyn Error>> {
+        Ok(self.get_connection()?.with_proxy("org.freedesktop.systemd1", "/org/freedesktop/systemd1", Duration::from_millis(5000)))
+    }
+
+    fn get_unit_proxy<'a>(&'a mut self, path: Path<'a>) -> Result<Proxy<'a, &'a Connection>, Box<dyn Error>> {
+        Ok(self.get_connection()?.with_proxy("org.freedesktop.systemd1", path, Duration::from_millis(5000)))
+    }
+
+    pub fn reset_failed_unit(&mut self, unit: &str) -> Result<(), Box<dyn Error>> {
+        self.get_proxy()?.method_call::<(), _, _, _>("org.freedesktop.systemd1.Manager", "ResetFailedUnit", (unit, ))?;
         Ok(())
     }
 
-    pub fn restart_unit(&self, unit: &str) -> Result<String, Box<dyn Error>> {
+    pub fn restart_unit(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
         let (job, ): (Path, ) = self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "RestartUnit", (unit, "replace", ))?;
         Ok(job.to_string())
     }
-}
\ No newline at end of file
+
+    pub fn start_unit(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        let (job, ): (Path, ) = self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "StartUnit", (unit, "replace", ))?;
+        Ok(job.to_string())
+    }
+
+    pub fn get_unit_active_state(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        let (path, ): (Path, ) = self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "LoadUnit", (unit, ))?;
+        let state: String = self.get_unit_proxy(path)?.get("org.freedesktop.systemd1.Unit", "ActiveState")?;
+        Ok(state)
+    }
+
+    /// Returns the name and the active state of every unit loaded by systemd.
+    fn list_units(&mut self) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let (units, ): (Vec<UnitInfo>, ) = self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "ListUnits", ())?;
+        Ok(parse_unit_infos(units))
+    }
+
+    pub fn get_unit_file_state(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        let (state, ): (String, ) = self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "GetUnitFileState", (unit, ))?;
+        Ok(state)
+    }
+
+    pub fn get_unit_load_state(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        let (path, ): (Path, ) = self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "LoadUnit", (unit, ))?;
+        let state: String = self.get_unit_proxy(path)?.get("org.freedesktop.systemd1.Unit", "LoadState")?;
+        Ok(state)
+    }
+
+    pub fn enable_unit_files(&mut self, units: &[&str], force: bool) -> Result<(), Box<dyn Error>> {
+        let (_, _): (bool, Vec<(String, String, String)>) = self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "EnableUnitFiles", (units.to_vec(), false, force))?;
+        Ok(())
+    }
+
+    pub fn disable_unit_files(&mut self, units: &[&str]) -> Result<(), Box<dyn Error>> {
+        let (_, ): (Vec<(String, String, String)>, ) = self.get_proxy()?.method_call("org.freedesktop.systemd1.Manager", "DisableUnitFiles", (units.to_vec(), false))?;
+        Ok(())
+    }
+
+    pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        self.get_proxy()?.method_call::<(), _, _, _>("org.freedesktop.systemd1.Manager", "Reload", ())?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "systemd"))]
+impl SystemD {
+    pub fn new() -> Self {
+        Self {
+            bus_wait: None,
+        }
+    }
+
+    /// Runs `systemctl` with the given arguments.
+    ///
+    /// # Returns
+    ///
+    /// Returns the standard output of `systemctl` with the trailing newline removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `systemctl` cannot be run or exits with non-zero status, including its
+    /// standard error in the message.
+    fn systemctl(&self, args: &[&str]) -> Result<String, Box<dyn Error>> {
+        self.wait_for_bus()?;
+        let output = Command::new(SYSTEMCTL_PROGRAM).args(args).output()
+            .map_err(|err| format!("Failed to run {SYSTEMCTL_PROGRAM}: {err}"))?;
+        if !output.status.success() {
+            return Err(Box::from(format!("{SYSTEMCTL_PROGRAM} {} exited with {}: {}",
+                args.join(" "), output.status, String::from_utf8_lossy(&output.stderr).trim())));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+    }
+
+    fn get_unit_property(&self, unit: &str, property: &str) -> Result<String, Box<dyn Error>> {
+        self.systemctl(&["show", "--property", property, "--value", "--", unit])
+    }
+
+    pub fn reset_failed_unit(&mut self, unit: &str) -> Result<(), Box<dyn Error>> {
+        self.systemctl(&["reset-failed", "--", unit])?;
+        Ok(())
+    }
+
+    /// Restarts a unit without waiting for the job to finish.
+    ///
+    /// # Returns
+    ///
+    /// Returns an empty string, `systemctl` does not report the job.
+    pub fn restart_unit(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        self.systemctl(&["restart", "--no-block", "--", unit])?;
+        Ok(String::new())
+    }
+
+    /// Starts a unit without waiting for the job to finish.
+    ///
+    /// # Returns
+    ///
+    /// Returns an empty string, `systemctl` does not report the job.
+    pub fn start_unit(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        self.systemctl(&["start", "--no-block", "--", unit])?;
+        Ok(String::new())
+    }
+
+    pub fn get_unit_active_state(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        self.get_unit_property(unit, "ActiveState")
+    }
+
+    /// Returns the name and the active state of every unit loaded by systemd.
+    fn list_units(&mut self) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let output = self.systemctl(&["list-units", "--all", "--plain", "--no-legend", "--no-pager"])?;
+        Ok(parse_unit_list(output.as_str()))
+    }
+
+    /// Returns the unit file state of a unit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the unit has no unit file, like the D-Bus implementation does.
+    pub fn get_unit_file_state(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        let state = self.get_unit_property(unit, "UnitFileState")?;
+        if state.is_empty() {
+            return Err(Box::from(format!("Unit file {unit} does not exist")));
+        }
+        Ok(state)
+    }
+
+    pub fn get_unit_load_state(&mut self, unit: &str) -> Result<String, Box<dyn Error>> {
+        self.get_unit_property(unit, "LoadState")
+    }
+
+    pub fn enable_unit_files(&mut self, units: &[&str], force: bool) -> Result<(), Box<dyn Error>> {
+        let mut args = vec!["enable"];
+        if force {
+            args.push("--force");
+        }
+        args.push("--");
+        args.extend_from_slice(units);
+        self.systemctl(&args)?;
+        Ok(())
+    }
+
+    pub fn disable_unit_files(&mut self, units: &[&str]) -> Result<(), Box<dyn Error>> {
+        let mut args = vec!["disable", "--"];
+        args.extend_from_slice(units);
+        self.systemctl(&args)?;
+        Ok(())
+    }
+
+    pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        self.systemctl(&["daemon-reload"])?;
+        Ok(())
+    }
+}
+
+/// Polls until a Unix socket exists at `path`.
+///
+/// # Arguments
+///
+/// * `path` - The path of the socket, e.g. the system bus socket.
+/// * `timeout` - The maximum time to wait for the socket.
+/// * `poll_interval` - The delay between two checks.
+///
+/// # Errors
+///
+/// Returns an error if no socket exists at `path` after `timeout`.
+pub fn wait_for_socket(path: &str, timeout: Duration, poll_interval: Duration) -> Result<(), Box<dyn Error>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(Box::from(format!("System bus socket {path} did not appear within {timeout:?}")));
+        }
+        thread::sleep(poll_interval);
+    }
+}
+
+/// Returns the path of the system bus socket, read from a `unix:path=` address in
+/// `DBUS_SYSTEM_BUS_ADDRESS` if set, otherwise `SYSTEM_BUS_DEFAULT_SOCKET`.
+fn get_system_bus_socket() -> String {
+    std::env::var("DBUS_SYSTEM_BUS_ADDRESS").ok()
+        .and_then(|address| parse_unix_bus_path(address.as_str()))
+        .unwrap_or_else(|| SYSTEM_BUS_DEFAULT_SOCKET.to_string())
+}
+
+/// Returns the socket path of the first `unix:path=` address in a D-Bus address list.
+fn parse_unix_bus_path(address: &str) -> Option<String> {
+    address.split(';')
+        .filter_map(|entry| entry.strip_prefix("unix:"))
+        .flat_map(|params| params.split(','))
+        .find_map(|param| param.strip_prefix("path="))
+        .map(String::from)
+}
+
+/// Calls `connector` until it succeeds, doubling the delay between attempts.
+///
+/// # Arguments
+///
+/// * `connector` - The function establishing the connection.
+/// * `max_attempts` - The maximum number of times `connector` is called, at least once.
+/// * `initial_backoff` - The delay after the first failed attempt.
+///
+/// # Returns
+///
+/// Returns the first successful result of `connector`, or the error of the last attempt.
+#[cfg(any(feature = "systemd", test))]
+pub fn connect_with_retry<T, E: Display>(mut connector: impl FnMut() -> Result<T, E>, max_attempts: u32, initial_backoff: Duration) -> Result<T, E> {
+    let max_attempts = max_attempts.max(1);
+    let mut backoff = initial_backoff;
+    let mut attempt = 1;
+    loop {
+        match connector() {
+            Ok(conn) => return Ok(conn),
+            Err(err) if attempt < max_attempts => {
+                warn!("Failed to connect to system bus (attempt {attempt}/{max_attempts}): {err}, retrying in {backoff:?}");
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// An entry of the reply to `ListUnits`: the name, description, load state, active state, sub
+/// state, followed unit, object path, queued job ID, job type and job object path of a unit.
+#[cfg(feature = "systemd")]
+type UnitInfo = (String, String, String, String, String, String, Path<'static>, u32, String, Path<'static>);
+
+/// Reduces the reply to `ListUnits` to the name and the active state of every unit.
+#[cfg(feature = "systemd")]
+fn parse_unit_infos(units: Vec<UnitInfo>) -> Vec<(String, String)> {
+    units.into_iter().map(|unit| (unit.0, unit.3)).collect()
+}
+
+/// Parses the output of `systemctl list-units --plain --no-legend` into the name and the active
+/// state of every unit.
+#[cfg(not(feature = "systemd"))]
+fn parse_unit_list(output: &str) -> Vec<(String, String)> {
+    // Columns: UNIT LOAD ACTIVE SUB DESCRIPTION
+    output.lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let name = columns.next()?;
+            let active_state = columns.nth(1)?;
+            Some((String::from(name), String::from(active_state)))
+        })
+        .collect()
+    }
+
+/// Resolves the unit a `display-manager.service` alias links to, see
+/// `get_default_display_manager`.
+fn resolve_display_manager_alias(alias: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let target = match fs::read_link(alias) {
+        Ok(target) => target,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Box::from(format!("Failed to read {alias}: {err}"))),
+    };
+    match target.file_name().and_then(|name| name.to_str()) {
+        Some(unit) if unit.ends_with(".service") => Ok(Some(String::from(unit))),
+        _ => Err(Box::from(format!("{alias} does not point to a service: {}", target.display()))),
+    }
+}
+
+/// Keeps the units whose name matches a glob pattern, sorted by name.
+fn filter_units(units: Vec<(String, String)>, pattern: &str) -> Vec<(String, String)> {
+    let mut units: Vec<(String, String)> = units.into_iter()
+        .filter(|(name, _)| matches_pattern(pattern, name.as_str()))
+        .collect();
+    units.sort();
+    units
+}
+
+/// Matches a unit name against a glob pattern supporting `*` and `?`.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and of the name character it was matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(feature = "systemd")]
+fn get_connect_attempts() -> u32 {
+    GLOBAL_CONFIG.get_mut()
+        .and_then(|config| config.try_get("system"))
+        .and_then(|system| system.get("dbus_connect_attempts"))
+        .and_then(|attempts| attempts.as_integer())
+        .and_then(|attempts| u32::try_from(attempts).ok())
+        .unwrap_or(DBUS_CONNECT_DEFAULT_ATTEMPTS)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    #[cfg(feature = "systemd")]
+    use crate::session::Session;
+    #[cfg(feature = "systemd")]
+    use crate::system::SYSTEMCTL;
+    use crate::testing;
+
+    /// A connector that fails the first `failures` times it is called.
+    fn flaky_connector(failures: u32, calls: &mut u32) -> impl FnMut() -> Result<u32, String> + '_ {
+        move || {
+            *calls += 1;
+            if *calls <= failures {
+                Err(format!("attempt {calls} failed"))
+            } else {
+                Ok(*calls)
+            }
+        }
+    }
+
+    #[test]
+    fn connection_is_retried_until_it_succeeds() {
+        let mut calls = 0;
+        let conn = connect_with_retry(flaky_connector(3, &mut calls), 5, Duration::from_millis(1));
+        assert_eq!(conn, Ok(4));
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    fn last_error_is_returned_after_all_attempts() {
+        let mut calls = 0;
+        let conn = connect_with_retry(flaky_connector(10, &mut calls), 3, Duration::from_millis(1));
+        assert_eq!(conn, Err(String::from("attempt 3 failed")));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn connector_is_called_at_least_once() {
+        let mut calls = 0;
+        assert!(connect_with_retry(flaky_connector(0, &mut calls), 0, Duration::from_millis(1)).is_ok());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn backoff_doubles_between_attempts() {
+        let mut calls = 0;
+        let start = Instant::now();
+        let _ = connect_with_retry(flaky_connector(3, &mut calls), 4, Duration::from_millis(20));
+        // 20 + 40 + 80 ms
+        assert!(start.elapsed() >= Duration::from_millis(140));
+    }
+
+    #[cfg(feature = "systemd")]
+    #[test]
+    fn connect_attempts_are_configurable() {
+        let fixture = testing::load_config("[system]\ndbus_connect_attempts = 2\n");
+        assert_eq!(get_connect_attempts(), 2);
+        drop(fixture);
+
+        let _fixture = testing::load_config("[system]\n");
+        assert_eq!(get_connect_attempts(), DBUS_CONNECT_DEFAULT_ATTEMPTS);
+    }
+
+    #[cfg(feature = "systemd")]
+    #[test]
+    fn handle_does_not_connect_until_first_use() {
+        assert!(SystemD::new().conn.is_none());
+    }
+
+    #[cfg(feature = "systemd")]
+    #[test]
+    fn pure_config_command_does_not_need_the_bus() {
+        let fixture = testing::load_sessions_config();
+        Session::from_config(Some("beta")).unwrap().set_as_default().unwrap();
+        assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("beta"));
+        assert!(fixture.read().contains("default = \"beta\""));
+        assert!(SYSTEMCTL.lock().unwrap().conn.is_none());
+    }
+
+    #[test]
+    fn bus_socket_is_waited_for_until_it_appears() {
+        let dir = testing::TempDir::new();
+        let socket = dir.path().join("system_bus_socket");
+        let listener_path = socket.clone();
+        let start = Instant::now();
+        let listener = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(150));
+            std::os::unix::net::UnixListener::bind(listener_path).unwrap()
+        });
+
+        wait_for_socket(socket.to_str().unwrap(), Duration::from_secs(5), Duration::from_millis(10)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        drop(listener.join().unwrap());
+    }
+
+    #[test]
+    fn bus_wait_times_out_without_a_socket() {
+        let dir = testing::TempDir::new();
+        let missing = dir.path().join("system_bus_socket");
+        assert!(wait_for_socket(missing.to_str().unwrap(), Duration::from_millis(50), Duration::from_millis(10)).is_err());
+
+        // A regular file is not the bus
+        let file = dir.write("not_a_socket", "");
+        assert!(wait_for_socket(file.to_str().unwrap(), Duration::from_millis(50), Duration::from_millis(10)).is_err());
+    }
+
+    #[test]
+    fn unix_bus_path_is_read_from_the_address() {
+        assert_eq!(parse_unix_bus_path("unix:path=/run/dbus/system_bus_socket").as_deref(), Some("/run/dbus/system_bus_socket"));
+        assert_eq!(parse_unix_bus_path("tcp:host=localhost;unix:guid=1,path=/tmp/bus").as_deref(), Some("/tmp/bus"));
+        assert_eq!(parse_unix_bus_path("unix:abstract=/tmp/dbus-1"), None);
+    }
+
+    #[test]
+    fn glob_pattern_matches_unit_names() {
+        assert!(matches_pattern("*dm.service", "sddm.service"));
+        assert!(matches_pattern("*dm.service", "lightdm.service"));
+        assert!(matches_pattern("sddm.service", "sddm.service"));
+        assert!(matches_pattern("greetd.servic?", "greetd.service"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("*d*.service", "greetd.service"));
+        assert!(!matches_pattern("*dm.service", "greetd.service"));
+        assert!(!matches_pattern("sddm.service", "sddm.service.d"));
+        assert!(!matches_pattern("?sddm.service", "sddm.service"));
+    }
+
+    #[test]
+    fn listed_units_are_filtered_by_pattern_and_sorted() {
+        // What a ListUnits reply is reduced to, the name and active state of each unit
+        let units = vec![
+            (String::from("sddm.service"), String::from("active")),
+            (String::from("dbus.service"), String::from("active")),
+            (String::from("lightdm.service"), String::from("inactive")),
+            (String::from("greetd.service"), String::from("inactive")),
+            (String::from("gdm.socket"), String::from("active")),
+        ];
+        assert_eq!(filter_units(units.clone(), "*dm.service"), [
+            (String::from("lightdm.service"), String::from("inactive")),
+            (String::from("sddm.service"), String::from("active")),
+        ]);
+        assert_eq!(filter_units(units.clone(), "greetd.service"), [(String::from("greetd.service"), String::from("inactive"))]);
+        assert!(filter_units(units, "ly.service").is_empty());
+    }
+
+    #[cfg(feature = "systemd")]
+    #[test]
+    fn list_units_reply_is_parsed() {
+        let unit = |name: &str, load_state: &str, active_state: &str, sub_state: &str| -> UnitInfo {
+            (String::from(name), format!("{name} description"), String::from(load_state), String::from(active_state),
+             String::from(sub_state), String::new(), Path::from("/org/freedesktop/systemd1/unit/unit"), 0, String::new(), Path::from("/"))
+        };
+        let reply = vec![
+            unit("sddm.service", "loaded", "active", "running"),
+            unit("greetd.service", "not-found", "inactive", "dead"),
+            unit("dbus.service", "loaded", "active", "running"),
+        ];
+        assert_eq!(filter_units(parse_unit_infos(reply), "*dm.service"), [(String::from("sddm.service"), String::from("active"))]);
+    }
+
+    #[cfg(not(feature = "systemd"))]
+    #[test]
+    fn systemctl_unit_list_is_parsed() {
+        let output = "sddm.service loaded active running Simple Desktop Display Manager\ngreetd.service not-found inactive dead greetd.service\nmalformed\n";
+        assert_eq!(parse_unit_list(output), [
+            (String::from("sddm.service"), String::from("active")),
+            (String::from("greetd.service"), String::from("inactive")),
+        ]);
+        assert_eq!(filter_units(parse_unit_list(output), "*dm.service"), [(String::from("sddm.service"), String::from("active"))]);
+    }
+
+    #[cfg(not(feature = "systemd"))]
+    #[test]
+    fn missing_unit_file_is_an_error_without_the_systemd_feature() {
+        // The fallback keeps the D-Bus client's interface, so callers build the same either way
+        let mut systemd = SystemD::new();
+        let err = systemd.get_unit_file_state("molyuuctl-test-missing.service").unwrap_err();
+        assert!(!err.to_string().is_empty());
+    }
+
+    #[test]
+    fn display_manager_alias_resolves_to_the_linked_unit() {
+        let dir = testing::TempDir::new();
+        let alias = dir.path().join("display-manager.service");
+        let alias = alias.to_str().unwrap();
+        assert_eq!(resolve_display_manager_alias(alias).unwrap(), None);
+
+        std::os::unix::fs::symlink("/usr/lib/systemd/system/sddm.service", alias).unwrap();
+        assert_eq!(resolve_display_manager_alias(alias).unwrap().as_deref(), Some("sddm.service"));
+    }
+
+    #[test]
+    fn display_manager_alias_must_link_to_a_service() {
+        let dir = testing::TempDir::new();
+        let alias = dir.path().join("display-manager.service");
+        std::os::unix::fs::symlink("/usr/lib/systemd/system/graphical.target", &alias).unwrap();
+        assert!(resolve_display_manager_alias(alias.to_str().unwrap()).is_err());
+
+        // A regular file is not an alias
+        let file = dir.write("not-an-alias.service", "");
+        assert!(resolve_display_manager_alias(file.to_str().unwrap()).is_err());
+    }
+}
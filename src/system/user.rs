@@ -0,0 +1,257 @@
+use std::env;
+use std::error::Error;
+use std::ffi::{CStr, CString, OsString};
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+
+use libc::{gid_t, uid_t};
+
+use crate::errors::system::{PrivilegeError, UserError};
+
+static DEFAULT_PASSWD_BUFFER_SIZE: usize = 16384;
+static DEFAULT_GROUP_COUNT: usize = 32;
+// Like the `ENV_PATH` of login(1), the environment of the invoking user is not passed on
+static DEFAULT_USER_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+// The seat and session of the login, which the session needs to find its seat, and the terminal
+static INHERITED_VARIABLES: [&str; 5] = ["TERM", "LANG", "XDG_SEAT", "XDG_SESSION_ID", "XDG_VTNR"];
+
+/// An account from the user database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserInfo {
+    pub name: String,
+    pub uid: uid_t,
+    pub gid: gid_t,
+    pub home: String,
+    pub shell: String,
+}
+
+impl UserInfo {
+    /// Look up a user by name in the user database.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UserError::UserNotFound` if there is no such user, or `UserError::LookupFailed` if
+    /// the user database cannot be read.
+    pub fn lookup(name: &str) -> Result<Self, Box<dyn Error>> {
+        let c_name = CString::new(name).map_err(|_| UserError::InvalidUserName(String::from(name)))?;
+        let mut buffer_size = match unsafe { libc::sysconf(libc::_SC_GETPW_R_SIZE_MAX) } {
+            size if size > 0 => size as usize,
+            _ => DEFAULT_PASSWD_BUFFER_SIZE,
+        };
+
+        loop {
+            let mut passwd = MaybeUninit::<libc::passwd>::uninit();
+            let mut buffer = vec![0 as libc::c_char; buffer_size];
+            let mut result = ptr::null_mut();
+            let errno = unsafe {
+                libc::getpwnam_r(c_name.as_ptr(), passwd.as_mut_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut result)
+            };
+
+            match errno {
+                // The entry does not fit into the buffer, retry with a larger one
+                libc::ERANGE => buffer_size *= 2,
+                0 if result.is_null() => return Err(Box::from(UserError::UserNotFound(String::from(name)))),
+                0 => {
+                    let passwd = unsafe { passwd.assume_init() };
+                    let to_string = |field: *const libc::c_char| unsafe { CStr::from_ptr(field) }.to_string_lossy().into_owned();
+                    return Ok(Self {
+                        name: to_string(passwd.pw_name),
+                        uid: passwd.pw_uid,
+                        gid: passwd.pw_gid,
+                        home: to_string(passwd.pw_dir),
+                        shell: to_string(passwd.pw_shell),
+                    });
+                }
+                errno => return Err(Box::from(UserError::LookupFailed(io::Error::from_raw_os_error(errno).to_string()))),
+            }
+        }
+    }
+
+    /// Check that the invoking user may run commands as another user.
+    ///
+    /// Only root may, even if molyuuctl is installed setuid, so that a regular user cannot start a
+    /// session as anybody else.
+    ///
+    /// # Parameters
+    ///
+    /// * `real_uid`: The real user ID of the invoking user, see `privilege::real_uid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PrivilegeError::RootRequired` if `real_uid` is not 0.
+    pub fn check_switch_allowed(&self, real_uid: uid_t) -> Result<(), PrivilegeError> {
+        if real_uid != 0 {
+            return Err(PrivilegeError::RootRequired(format!("uid {real_uid} cannot run commands as user {}", self.name)));
+        }
+        Ok(())
+    }
+
+    /// The login environment of the user.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `HOME`, `USER`, `LOGNAME`, `SHELL`, `PATH` and `XDG_RUNTIME_DIR` variables of
+    /// the user.
+    pub fn environment(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("HOME", self.home.clone()),
+            ("USER", self.name.clone()),
+            ("LOGNAME", self.name.clone()),
+            ("SHELL", self.shell.clone()),
+            ("PATH", String::from(DEFAULT_USER_PATH)),
+            ("XDG_RUNTIME_DIR", format!("/run/user/{}", self.uid)),
+        ]
+    }
+
+    /// The groups of the user from the group database, including its primary group.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UserError::InvalidUserName` if the user name cannot be passed to the C library.
+    pub fn groups(&self) -> Result<Vec<gid_t>, Box<dyn Error>> {
+        let c_name = CString::new(self.name.as_str()).map_err(|_| UserError::InvalidUserName(self.name.clone()))?;
+        let mut capacity = DEFAULT_GROUP_COUNT;
+        loop {
+            let mut groups = vec![0 as gid_t; capacity];
+            let mut count = capacity as libc::c_int;
+            if unsafe { libc::getgrouplist(c_name.as_ptr(), self.gid, groups.as_mut_ptr(), &mut count) } >= 0 {
+                groups.truncate(count as usize);
+                return Ok(groups);
+            }
+            // The groups do not fit, `count` holds how many there are
+            capacity = (count as usize).max(capacity * 2);
+        }
+    }
+
+    /// Make a command run as the user, with the login environment of the user.
+    ///
+    /// The environment of molyuuctl is not inherited, only the variables set on `command` and
+    /// `INHERITED_VARIABLES` are kept. The supplementary groups, gid and uid are switched in the
+    /// child right before exec, so the command must be spawned with root permissions, e.g. inside
+    /// `privilege::exec`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the groups of the user cannot be looked up.
+    pub fn apply_to(&self, command: &mut Command) -> Result<(), Box<dyn Error>> {
+        // Looked up before fork, only async-signal-safe calls may run in the child
+        let groups = self.groups()?;
+        let (uid, gid) = (self.uid, self.gid);
+
+        let set_variables: Vec<(OsString, OsString)> = command.get_envs()
+            .filter_map(|(key, value)| value.map(|value| (key.to_os_string(), value.to_os_string())))
+            .collect();
+        command.env_clear();
+        command.envs(INHERITED_VARIABLES.iter().filter_map(|key| env::var_os(key).map(|value| (key, value))));
+        command.envs(set_variables);
+        command.envs(self.environment());
+        // Accounts like nobody have a home directory that does not exist
+        if Path::new(self.home.as_str()).is_dir() {
+            command.current_dir(self.home.as_str());
+        }
+        unsafe {
+            // Groups have to be set while still privileged, so uid is switched last
+            command.pre_exec(move || {
+                if libc::setgroups(groups.len(), groups.as_ptr()) < 0
+                    || libc::setresgid(gid, gid, gid) < 0
+                    || libc::setresuid(uid, uid, uid) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_user() -> UserInfo {
+        UserInfo {
+            name: String::from("molyuu"),
+            uid: 1000,
+            gid: 1000,
+            home: String::from("/home/molyuu"),
+            shell: String::from("/bin/zsh"),
+        }
+    }
+
+    #[test]
+    fn environment_is_derived_from_the_account() {
+        let environment = test_user().environment();
+        assert_eq!(environment, vec![
+            ("HOME", String::from("/home/molyuu")),
+            ("USER", String::from("molyuu")),
+            ("LOGNAME", String::from("molyuu")),
+            ("SHELL", String::from("/bin/zsh")),
+            ("PATH", String::from("/usr/local/bin:/usr/bin:/bin")),
+            ("XDG_RUNTIME_DIR", String::from("/run/user/1000")),
+        ]);
+    }
+
+    #[test]
+    fn only_root_may_switch_users() {
+        assert!(test_user().check_switch_allowed(0).is_ok());
+        assert!(matches!(test_user().check_switch_allowed(1000), Err(PrivilegeError::RootRequired(_))));
+        assert!(matches!(test_user().check_switch_allowed(1001), Err(PrivilegeError::RootRequired(_))));
+    }
+
+    #[test]
+    fn lookup_reads_the_user_database() {
+        let root = UserInfo::lookup("root").unwrap();
+        assert_eq!((root.uid, root.gid), (0, 0));
+    }
+
+    #[test]
+    fn lookup_of_a_missing_user_fails() {
+        let err = UserInfo::lookup("molyuuctl-no-such-user").unwrap_err();
+        assert_eq!(err.downcast_ref::<UserError>(), Some(&UserError::UserNotFound(String::from("molyuuctl-no-such-user"))));
+        let err = UserInfo::lookup("nul\0name").unwrap_err();
+        assert!(matches!(err.downcast_ref::<UserError>(), Some(UserError::InvalidUserName(_))));
+    }
+
+    #[test]
+    fn groups_include_the_primary_group() {
+        let root = UserInfo::lookup("root").unwrap();
+        assert!(root.groups().unwrap().contains(&0));
+    }
+
+    #[test]
+    fn apply_to_switches_uid_and_environment() {
+        // Switching users needs root, like starting a session as another user does
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+        let nobody = UserInfo::lookup("nobody").unwrap();
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg("echo $(id -u) $(id -g) $USER");
+        nobody.apply_to(&mut command).unwrap();
+        let output = command.output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), format!("{} {} nobody", nobody.uid, nobody.gid));
+    }
+
+    #[test]
+    fn apply_to_does_not_pass_on_the_environment_of_molyuuctl() {
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+        let nobody = UserInfo::lookup("nobody").unwrap();
+        let mut command = Command::new("/usr/bin/env");
+        command.env("XDG_VTNR", "7");
+        nobody.apply_to(&mut command).unwrap();
+        let output = String::from_utf8(command.output().unwrap().stdout).unwrap();
+        let variables: Vec<&str> = output.lines().filter_map(|line| line.split_once('=').map(|(key, _)| key)).collect();
+
+        // Set by cargo for the tests, but not for the session
+        assert!(!variables.iter().any(|key| key.starts_with("CARGO")), "{variables:?}");
+        assert!(output.lines().any(|line| line == "XDG_VTNR=7"));
+        assert!(output.lines().any(|line| line == "USER=nobody"));
+        assert!(variables.iter().all(|key| INHERITED_VARIABLES.contains(key) || nobody.environment().iter().any(|(name, _)| name == key)), "{variables:?}");
+    }
+}
@@ -0,0 +1,212 @@
+//! Fixtures shared by the unit tests.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, Once};
+
+use log::{LevelFilter, Metadata, Record};
+use toml::{Table, Value};
+
+use crate::config::Configuration;
+use crate::system::init::InitSystem;
+
+// The global configuration is process-wide, so tests that load one run one at a time
+static CONFIG_GUARD: Mutex<()> = Mutex::new(());
+static NEXT_TEMP_DIR: AtomicUsize = AtomicUsize::new(0);
+static CAPTURE_LOGGER: Once = Once::new();
+
+thread_local! {
+    // The records logged on this thread while `capture_logs` runs
+    static CAPTURED_LOGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// A directory under the system temp directory, removed with everything in it when dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("molyuuctl-test-{}-{}", process::id(), NEXT_TEMP_DIR.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Write a file relative to the directory, creating its parent directories.
+    pub fn write(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.path.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A global configuration loaded from a temp file, held exclusively until dropped.
+pub struct ConfigFixture {
+    pub dir: TempDir,
+    pub path: PathBuf,
+    // Declared last so it is dropped last, the next test only loads its configuration once this
+    // one is cleaned up
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl ConfigFixture {
+    /// Read the configuration file as it is on disk.
+    pub fn read(&self) -> String {
+        fs::read_to_string(&self.path).unwrap()
+    }
+
+    /// Install a session desktop file in the `xsessions` or `wayland-sessions` directory of the
+    /// fixture, which `load_config` adds to `xsessions_dirs` and `wayland_sessions_dirs`.
+    pub fn install_session(&self, directory: &str, name: &str, exec: &str) -> PathBuf {
+        self.dir.write(format!("{directory}/{name}.desktop").as_str(),
+            format!("[Desktop Entry]\nName={name}\nExec={exec}\n").as_str())
+    }
+}
+
+/// Load `contents` as the global configuration.
+///
+/// Every `{dir}` in `contents` is replaced with the temp directory of the fixture. If `contents`
+/// has a `[session]` section without `xsessions_dirs` or `wayland_sessions_dirs`, they are set to
+/// `{dir}/xsessions` and `{dir}/wayland-sessions`, where `ConfigFixture::install_session` puts
+/// desktop files.
+pub fn load_config(contents: &str) -> ConfigFixture {
+    let guard = CONFIG_GUARD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let dir = TempDir::new();
+    let mut value = contents.replace("{dir}", dir.path().to_str().unwrap()).parse::<Table>().unwrap();
+    if let Some(session_info) = value.get_mut("session").and_then(|session_info| session_info.as_table_mut()) {
+        for (key, directory) in [("xsessions_dirs", "xsessions"), ("wayland_sessions_dirs", "wayland-sessions")] {
+            let directory = dir.path().join(directory).to_str().unwrap().to_string();
+            session_info.entry(key).or_insert_with(|| Value::Array(vec![Value::String(directory)]));
+        }
+    }
+    let path = dir.write("config.toml", toml::to_string(&value).unwrap().as_str());
+    Configuration::init(Some(path.to_str().unwrap())).unwrap();
+    ConfigFixture { dir, path, _guard: guard }
+}
+
+/// A configuration with two Wayland sessions, `alpha` (the default) and `beta`, installed in the
+/// fixture directory, and no login manager.
+pub static SESSIONS_CONFIG: &str = r#"[login.autologin]
+
+[session]
+default = "alpha"
+
+[session.alpha]
+session = "molyuuctl-test-alpha"
+protocol = "wayland"
+
+[session.beta]
+session = "molyuuctl-test-beta"
+protocol = "wayland"
+"#;
+
+/// Load `SESSIONS_CONFIG` and install the desktop files of its sessions.
+pub fn load_sessions_config() -> ConfigFixture {
+    let fixture = load_config(SESSIONS_CONFIG);
+    fixture.install_session("wayland-sessions", "molyuuctl-test-alpha", "/bin/true");
+    fixture.install_session("wayland-sessions", "molyuuctl-test-beta", "/bin/true");
+    fixture
+}
+
+struct CaptureLogger;
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        CAPTURED_LOGS.with(|logs| {
+            if let Some(logs) = logs.borrow_mut().as_mut() {
+                logs.push(format!("{}: {}", record.level().as_str().to_lowercase(), record.args()));
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+/// Run `f` and collect what it logs on the current thread as `level: message` lines.
+pub fn capture_logs(f: impl FnOnce()) -> Vec<String> {
+    CAPTURE_LOGGER.call_once(|| {
+        log::set_logger(&CaptureLogger).map(|()| log::set_max_level(LevelFilter::Info)).unwrap();
+    });
+    CAPTURED_LOGS.with(|logs| *logs.borrow_mut() = Some(Vec::new()));
+    f();
+    CAPTURED_LOGS.with(|logs| logs.borrow_mut().take().unwrap_or_default())
+}
+
+/// An init system that records the calls made to it instead of managing services.
+#[derive(Default)]
+pub struct RecordingInit {
+    /// The calls made so far, e.g. `restart sddm`.
+    pub calls: Vec<String>,
+    /// What `unit_installed` and `unit_active` report for every service.
+    pub installed: bool,
+}
+
+impl InitSystem for RecordingInit {
+    fn restart_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        self.calls.push(format!("restart {service}"));
+        Ok(())
+    }
+
+    fn reset_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        self.calls.push(format!("reset {service}"));
+        Ok(())
+    }
+
+    fn unit_active(&mut self, service: &str) -> Result<bool, Box<dyn Error>> {
+        self.calls.push(format!("active {service}"));
+        Ok(self.installed)
+    }
+
+    fn unit_installed(&mut self, service: &str) -> Result<bool, Box<dyn Error>> {
+        self.calls.push(format!("installed {service}"));
+        Ok(self.installed)
+    }
+
+    fn set_boot_manager(&mut self, service: &str) -> Result<(), Box<dyn Error>> {
+        self.calls.push(format!("boot {service}"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Session;
+
+    #[test]
+    fn sessions_config_resolves_installed_sessions() {
+        let fixture = load_sessions_config();
+        assert!(fixture.read().contains(fixture.dir.path().to_str().unwrap()));
+        assert!(Session::from_config(None).is_ok());
+        assert!(Session::from_config(Some("beta")).is_ok());
+    }
+
+    #[test]
+    fn temp_dir_is_removed_on_drop() {
+        let dir = TempDir::new();
+        let path = dir.write("nested/file", "contents");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "contents");
+        let root = dir.path().to_path_buf();
+        drop(dir);
+        assert!(!root.exists());
+    }
+}
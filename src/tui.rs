@@ -0,0 +1,296 @@
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::mem::MaybeUninit;
+use std::time::Duration;
+
+use crate::session::{Session, StartOptions};
+
+static ESCAPE_SEQUENCE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// What the user asked for in the session picker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PickerAction {
+    Start(String),
+    SetDefault(String),
+    Quit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Char(char),
+    Escape,
+}
+
+/// The list/select model of the session picker, independent of the terminal.
+#[derive(Debug, Clone)]
+pub struct Picker {
+    names: Vec<String>,
+    default: Option<String>,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(names: Vec<String>, default: Option<String>) -> Self {
+        // Preselect the default session so Enter starts it right away
+        let selected = default.as_ref()
+            .and_then(|default| names.iter().position(|name| name == default))
+            .unwrap_or(0);
+        Self {
+            names,
+            default,
+            selected,
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.names.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn selected_name(&self) -> Option<&str> {
+        self.names.get(self.selected).map(|name| name.as_str())
+    }
+
+    /// Set the selected session as default in the model, so the marker follows it.
+    pub fn mark_default(&mut self) {
+        self.default = self.selected_name().map(String::from);
+    }
+
+    fn handle_key(&mut self, key: Key) -> Option<PickerAction> {
+        match key {
+            Key::Up | Key::Char('k') => self.move_up(),
+            Key::Down | Key::Char('j') => self.move_down(),
+            Key::Enter => return self.selected_name().map(|name| PickerAction::Start(String::from(name))),
+            Key::Char('d') => return self.selected_name().map(|name| PickerAction::SetDefault(String::from(name))),
+            Key::Char('q') | Key::Escape => return Some(PickerAction::Quit),
+            _ => {}
+        }
+        None
+    }
+
+    fn render(&self, out: &mut impl Write) -> io::Result<()> {
+        // Raw mode disables output post-processing, so lines need an explicit carriage return
+        write!(out, "\x1b[2J\x1b[H")?;
+        write!(out, "Select a session (Up/Down: move, Enter: start, d: set default, q: quit)\r\n\r\n")?;
+        for (index, name) in self.names.iter().enumerate() {
+            let marker = if index == self.selected { ">" } else { " " };
+            let default = if self.default.as_deref() == Some(name.as_str()) { " (default)" } else { "" };
+            write!(out, "{marker} {name}{default}\r\n")?;
+        }
+        out.flush()
+    }
+}
+
+/// Puts the terminal into raw mode and restores the previous mode when dropped.
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> Result<Self, Box<dyn Error>> {
+        let mut original = MaybeUninit::<libc::termios>::uninit();
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, original.as_mut_ptr()) } < 0 {
+            return Err(Box::from(format!("Failed to read terminal attributes: {}", io::Error::last_os_error())));
+        }
+        let original = unsafe { original.assume_init() };
+
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } < 0 {
+            return Err(Box::from(format!("Failed to enable raw mode: {}", io::Error::last_os_error())));
+        }
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original) };
+        print!("\x1b[2J\x1b[H");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Standard input without the buffer of `io::Stdin`, so that `stdin_has_input` sees every byte
+/// that was not read yet.
+struct RawStdin;
+
+impl Read for RawStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(read as usize)
+    }
+}
+
+/// Wait up to `timeout` for input on standard input.
+fn stdin_has_input(timeout: Duration) -> io::Result<bool> {
+    let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    let ready = unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) };
+    if ready < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ready > 0)
+}
+
+/// Read a key from `input`.
+///
+/// A terminal sends the bytes of an escape sequence at once, so if nothing follows ESC within
+/// `ESCAPE_SEQUENCE_TIMEOUT`, as told by `has_input`, it is a bare ESC and the next key is not
+/// waited for.
+fn read_key(input: &mut impl Read, mut has_input: impl FnMut(Duration) -> io::Result<bool>) -> io::Result<Key> {
+    let mut buf = [0u8; 1];
+    input.read_exact(&mut buf)?;
+    Ok(match buf[0] {
+        b'\r' | b'\n' => Key::Enter,
+        0x1b => {
+            if !has_input(ESCAPE_SEQUENCE_TIMEOUT)? {
+                return Ok(Key::Escape);
+            }
+            // Arrow keys are sent as ESC [ A / ESC [ B
+            let mut seq = [0u8; 2];
+            if input.read_exact(&mut seq).is_err() || seq[0] != b'[' {
+                return Ok(Key::Escape);
+            }
+            match seq[1] {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                _ => Key::Escape,
+            }
+        }
+        // Ctrl-C does not raise SIGINT in raw mode
+        0x03 => Key::Char('q'),
+        byte => Key::Char(byte as char),
+    })
+}
+
+/// Show the interactive session picker until a session is started or the user quits.
+///
+/// # Errors
+///
+/// Returns an error if no session is registered, the terminal cannot be switched to raw mode, or
+/// starting or setting the default session fails.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let names = Session::list_registered();
+    if names.is_empty() {
+        return Err(Box::from("No session is registered"));
+    }
+
+    let mut picker = Picker::new(names, Session::get_default_name()?);
+    let action = {
+        let _raw_mode = RawMode::enable()?;
+        let mut stdin = RawStdin;
+        let mut stdout = io::stdout().lock();
+        loop {
+            picker.render(&mut stdout)?;
+            match picker.handle_key(read_key(&mut stdin, stdin_has_input)?) {
+                Some(PickerAction::SetDefault(name)) => {
+                    Session::from_config(Some(name.as_str()))?.set_as_default()?;
+                    picker.mark_default();
+                }
+                Some(action) => break action,
+                None => {}
+            }
+        }
+    };
+
+    // The terminal is restored before the session takes it over
+    if let PickerAction::Start(name) = action {
+        Session::from_config(Some(name.as_str()))?.start(&StartOptions::default())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker() -> Picker {
+        Picker::new(vec![String::from("alpha"), String::from("beta"), String::from("gamma")], Some(String::from("beta")))
+    }
+
+    #[test]
+    fn default_session_is_preselected() {
+        assert_eq!(picker().selected_name(), Some("beta"));
+        let picker = Picker::new(vec![String::from("alpha")], Some(String::from("missing")));
+        assert_eq!(picker.selected_name(), Some("alpha"));
+    }
+
+    #[test]
+    fn selection_stays_within_the_list() {
+        let mut picker = picker();
+        picker.move_down();
+        picker.move_down();
+        assert_eq!(picker.selected_name(), Some("gamma"));
+        for _ in 0..5 {
+            picker.move_up();
+        }
+        assert_eq!(picker.selected_name(), Some("alpha"));
+    }
+
+    #[test]
+    fn keys_map_to_actions() {
+        let mut picker = picker();
+        assert_eq!(picker.handle_key(Key::Char('j')), None);
+        assert_eq!(picker.handle_key(Key::Enter), Some(PickerAction::Start(String::from("gamma"))));
+        assert_eq!(picker.handle_key(Key::Up), None);
+        assert_eq!(picker.handle_key(Key::Char('d')), Some(PickerAction::SetDefault(String::from("beta"))));
+        assert_eq!(picker.handle_key(Key::Escape), Some(PickerAction::Quit));
+        assert_eq!(picker.handle_key(Key::Char('q')), Some(PickerAction::Quit));
+        assert_eq!(picker.handle_key(Key::Char('x')), None);
+    }
+
+    #[test]
+    fn empty_picker_has_nothing_to_start() {
+        let mut picker = Picker::new(Vec::new(), None);
+        assert_eq!(picker.selected_name(), None);
+        assert_eq!(picker.handle_key(Key::Enter), None);
+    }
+
+    #[test]
+    fn default_marker_follows_the_selection() {
+        let mut picker = picker();
+        picker.move_up();
+        picker.mark_default();
+        let mut out = Vec::new();
+        picker.render(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("> alpha (default)\r\n"));
+        assert!(out.contains("  beta\r\n"));
+    }
+
+    #[test]
+    fn escape_sequences_are_read_as_keys() {
+        let keys = |bytes: &[u8]| {
+            let mut input = bytes;
+            let mut keys = Vec::new();
+            while !input.is_empty() {
+                keys.push(read_key(&mut input, |_| Ok(true)).unwrap());
+            }
+            keys
+        };
+        assert_eq!(keys(b"\x1b[A\x1b[B\rj\x03"), [Key::Up, Key::Down, Key::Enter, Key::Char('j'), Key::Char('q')]);
+        assert_eq!(keys(b"\x1b"), [Key::Escape]);
+    }
+
+    #[test]
+    fn bare_escape_does_not_wait_for_the_next_key() {
+        // The next key is typed only after the timeout
+        let mut input: &[u8] = b"\x1bj";
+        assert_eq!(read_key(&mut input, |timeout| {
+            assert_eq!(timeout, ESCAPE_SEQUENCE_TIMEOUT);
+            Ok(false)
+        }).unwrap(), Key::Escape);
+        assert_eq!(read_key(&mut input, |_| Ok(false)).unwrap(), Key::Char('j'));
+    }
+}
@@ -0,0 +1,35 @@
+//! Drives molyuuctl through its library API, the way an embedding program would.
+
+use std::fs;
+use std::process;
+
+use molyuuctl::config::Configuration;
+use molyuuctl::errors::session::SessionInstanceError;
+use molyuuctl::session::{Protocol, Session};
+
+#[test]
+fn register_set_default_and_validate() {
+    let dir = std::env::temp_dir().join(format!("molyuuctl-library-test-{}", process::id()));
+    fs::create_dir_all(dir.join("wayland-sessions")).unwrap();
+    fs::write(dir.join("wayland-sessions/molyuuctl-library-test.desktop"), "[Desktop Entry]\nName=Library Test\nExec=/bin/true\n").unwrap();
+    let config_path = dir.join("config.toml");
+    fs::write(&config_path, format!("[login.autologin]\n\n[session]\nwayland_sessions_dirs = [\"{}\"]\n", dir.join("wayland-sessions").display())).unwrap();
+
+    Configuration::init(Some(config_path.to_str().unwrap())).unwrap();
+
+    let mut session = Session::new(String::from("library"), String::from("molyuuctl-library-test"), None, Some(Protocol::Wayland)).unwrap();
+    session.register().unwrap();
+    let err = session.register().unwrap_err();
+    assert_eq!(err.downcast_ref::<SessionInstanceError>(), Some(&SessionInstanceError::SessionExists));
+
+    session.set_as_default().unwrap();
+    assert_eq!(Session::get_default_name().unwrap().as_deref(), Some("library"));
+    Session::get_default_session().unwrap().validate().unwrap();
+    assert!(fs::read_to_string(&config_path).unwrap().contains("default = \"library\""));
+
+    // A session whose desktop file is gone no longer validates
+    fs::remove_file(dir.join("wayland-sessions/molyuuctl-library-test.desktop")).unwrap();
+    assert!(Session::get_default_session().unwrap().validate().is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}